@@ -1,20 +1,54 @@
 use std::ops::Deref;
 use anyhow::{bail, Result};
 use getopts::Options;
+use batpu2::asm::Dialect;
+use batpu2::utils::{Cartridge, RomFormat};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Command {
 	Help,
 	Run{ filename: String },
 	Asm{ input: String, output: String },
+	Link{ inputs: Vec<String>, output: String },
+	Fmt{ files: Vec<String> },
+	Test{ files: Vec<String> },
+	Xref{ filename: String },
+	Check{ files: Vec<String> },
+	Cfg{ filename: String },
+	Decompile{ filename: String },
+	Compile{ input: String, output: String },
+	Sprite{ input: String, output: String },
+	Datapack{ input: String, output: String },
+	Cartridge{ input: String, output: String },
 }
 
 pub struct Arguments {
 	opts: Options,
 	pub command: Command,
 	pub help: bool,
-	pub tickrate: f32,
+	pub speed: Option<f32>,
 	pub kitty: bool,
+	pub object: bool,
+	pub map: Option<String>,
+	pub dialect: Dialect,
+	pub check: bool,
+	pub optimize: bool,
+	pub size_report: bool,
+	pub strip: bool,
+	pub checked: bool,
+	pub json: bool,
+	pub output: Option<String>,
+	pub position: (u8, u8),
+	pub invert: bool,
+	pub format: Option<RomFormat>,
+	pub origin: Option<[i32; 3]>,
+	pub title: Option<String>,
+	pub author: Option<String>,
+	pub release: Option<String>,
+	pub seed: Option<u64>,
+	pub memory: Option<String>,
+	pub clear_mask: Option<u8>,
+	pub keys: Vec<(String, u8)>,
 }
 
 impl Arguments {
@@ -24,13 +58,55 @@ impl Arguments {
 		opts.optflag("h", "help", "print this message");
 		opts.optopt("s", "speed", "number of instructions executed per second", "100.0");
 		opts.optflag("", "kitty", "enables precise input(requires kitty protocol support)");
+		opts.optflag("c", "object", "assemble into a relocatable object file instead of .mc");
+		opts.optopt("m", "map", "write a map of the linked program to a file", "FILE");
+		opts.optopt("", "dialect", "assembly dialect, upstream or extended", "extended");
+		opts.optflag("", "check", "do not write formatted files, fail if any needs formatting");
+		opts.optflag("O", "optimize", "apply peephole optimizations and print what was changed");
+		opts.optflag("", "size-report", "print unreachable code and ROM space used by every label");
+		opts.optflag("", "strip", "remove code unreachable from the start of the program");
+		opts.optflag("", "checked", "stop running when an ASSERT of the .asm file fails");
+		opts.optflag("", "json", "print the cross-reference as JSON");
+		opts.optopt("o", "output", "write the control-flow graph to a file instead of stdout", "FILE");
+		opts.optopt("", "at", "screen position of the top left corner of a sprite", "0,31");
+		opts.optflag("", "invert", "swap lit and unlit pixels of a sprite");
+		opts.optopt("f", "format", "machine code format: mc, bin, bin-le, ihex, memh, memb, logisim or schem", "FORMAT");
+		opts.optopt("", "origin", "world position the ROM of a datapack is relative to", "X,Y,Z");
+		opts.optopt("", "title", "title of a cartridge", "TITLE");
+		opts.optopt("", "author", "author of a cartridge", "NAME");
+		opts.optopt("", "release", "version of a cartridge", "VERSION");
+		opts.optopt("", "seed", "fixed seed of the random number generator", "N");
+		opts.optopt("", "memory", "binary file with the initial data memory of a cartridge", "FILE");
+		opts.optopt("", "clear-mask", "buttons a cartridge clears once read with --kitty", "A,B,START");
+		opts.optmulti("", "key", "key pressing buttons in a cartridge, repeatable", "space=A");
 		
 		Self {
 			opts,
 			command: Command::Help,
 			help: false,
-			tickrate: 100.0,
+			speed: None,
 			kitty: false,
+			object: false,
+			map: None,
+			dialect: Dialect::Extended,
+			check: false,
+			optimize: false,
+			size_report: false,
+			strip: false,
+			checked: false,
+			json: false,
+			output: None,
+			position: (0, 31),
+			invert: false,
+			format: None,
+			origin: None,
+			title: None,
+			author: None,
+			release: None,
+			seed: None,
+			memory: None,
+			clear_mask: None,
+			keys: Vec::new(),
 		}
 	}
 	
@@ -38,8 +114,48 @@ impl Arguments {
 		let matches = self.opts.parse(args)?;
 		
 		self.help = matches.opt_present("help");
-		self.tickrate = matches.opt_get("speed")?.unwrap_or(self.tickrate);
+		self.speed = matches.opt_get("speed")?;
 		self.kitty = matches.opt_present("kitty");
+		self.object = matches.opt_present("object");
+		self.map = matches.opt_str("map");
+		self.check = matches.opt_present("check");
+		self.optimize = matches.opt_present("optimize");
+		self.size_report = matches.opt_present("size-report");
+		self.strip = matches.opt_present("strip");
+		self.checked = matches.opt_present("checked");
+		self.json = matches.opt_present("json");
+		self.output = matches.opt_str("output");
+		self.invert = matches.opt_present("invert");
+		self.format = matches.opt_str("format").map(|format| format.parse()).transpose()?;
+		self.title = matches.opt_str("title");
+		self.author = matches.opt_str("author");
+		self.release = matches.opt_str("release");
+		self.seed = matches.opt_get("seed")?;
+		self.memory = matches.opt_str("memory");
+		
+		if let Some(position) = matches.opt_str("at") {
+			let Some((x, y)) = position.split_once(',') else { bail!("Expected a position like 0,31: {position}") };
+			self.position = (x.trim().parse()?, y.trim().parse()?);
+		}
+		if let Some(origin) = matches.opt_str("origin") {
+			let [x, y, z] = origin.split(',').collect::<Vec<_>>()[..] else { bail!("Expected a position like 0,-60,0: {origin}") };
+			self.origin = Some([x.trim().parse()?, y.trim().parse()?, z.trim().parse()?]);
+		}
+		if let Some(buttons) = matches.opt_str("clear-mask") {
+			let Some(mask) = Cartridge::parse_buttons(&buttons) else { bail!("Unknown buttons: {buttons}") };
+			self.clear_mask = Some(mask);
+		}
+		for key in matches.opt_strs("key") {
+			let Some((key, buttons)) = key.split_once('=') else { bail!("Expected a key binding like space=A: {key}") };
+			let Some(mask) = Cartridge::parse_buttons(buttons) else { bail!("Unknown buttons: {buttons}") };
+			self.keys.push((key.to_owned(), mask));
+		}
+		self.dialect = match matches.opt_str("dialect").as_deref() {
+			None => self.dialect,
+			Some("upstream") => Dialect::Upstream,
+			Some("extended") => Dialect::Extended,
+			Some(dialect) => bail!("Unknown dialect: {dialect}"),
+		};
 		
 		if !self.help {
 			self.command = match matches.free.first().map(Deref::deref) {
@@ -55,6 +171,66 @@ impl Arguments {
 					
 					Command::Asm{ input: input.clone(), output: output.clone() }
 				}
+				Some("link") => {
+					match &matches.free[1..] {
+						[] => bail!("Missing input"),
+						[_] => bail!("Missing output"),
+						[inputs @ .., output] => Command::Link{ inputs: inputs.to_vec(), output: output.clone() },
+					}
+				}
+				Some("fmt") => {
+					match &matches.free[1..] {
+						[] => bail!("Missing filename"),
+						files => Command::Fmt{ files: files.to_vec() },
+					}
+				}
+				Some("test") => {
+					match &matches.free[1..] {
+						[] => bail!("Missing filename"),
+						files => Command::Test{ files: files.to_vec() },
+					}
+				}
+				Some("check") => {
+					match &matches.free[1..] {
+						[] => bail!("Missing filename"),
+						files => Command::Check{ files: files.to_vec() },
+					}
+				}
+				Some("xref") => {
+					let [_, filename] = expect_free_args(&matches.free, ["", "filename"])?;
+					
+					Command::Xref{ filename: filename.clone() }
+				}
+				Some("cfg") => {
+					let [_, filename] = expect_free_args(&matches.free, ["", "filename"])?;
+					
+					Command::Cfg{ filename: filename.clone() }
+				}
+				Some("decompile") => {
+					let [_, filename] = expect_free_args(&matches.free, ["", "filename"])?;
+					
+					Command::Decompile{ filename: filename.clone() }
+				}
+				Some("compile") => {
+					let [_, input, output] = expect_free_args(&matches.free, ["", "input", "output"])?;
+					
+					Command::Compile{ input: input.clone(), output: output.clone() }
+				}
+				Some("sprite") => {
+					let [_, input, output] = expect_free_args(&matches.free, ["", "input", "output"])?;
+					
+					Command::Sprite{ input: input.clone(), output: output.clone() }
+				}
+				Some("datapack") => {
+					let [_, input, output] = expect_free_args(&matches.free, ["", "input", "output"])?;
+					
+					Command::Datapack{ input: input.clone(), output: output.clone() }
+				}
+				Some("cartridge") => {
+					let [_, input, output] = expect_free_args(&matches.free, ["", "input", "output"])?;
+					
+					Command::Cartridge{ input: input.clone(), output: output.clone() }
+				}
 				Some(cmd) => bail!("Unknown command: {cmd}"),
 			}
 		}
@@ -67,8 +243,24 @@ impl Arguments {
 Usage: {program} <Command> [options]
 
 Commands:
-    run <filename>        execute a file on the emulator
-    asm <input> <output>  compile .asm file to .mc\
+    run <filename>        execute a .asm file or machine code on the emulator
+    asm <input> <output>  compile .asm file to .mc, or the format of the output extension
+    link <inputs>... <output>
+                          link objects or .asm files into a single .mc
+    fmt <filenames>...    format .asm files in place
+    test <filenames>...   run test blocks of .asm files
+    xref <filename>       list definitions and references of every symbol
+    check <filenames>...  check subroutines against their @func annotations and the call depth
+    cfg <filename>        print the control-flow graph in Graphviz DOT format
+    decompile <filename>  print .mc or .asm file as C-like pseudo-code
+    compile <input> <output>
+                          compile a batpu2-lang source file to .mc, or to .asm if the output ends in .asm
+    sprite <input> <output>
+                          convert a PBM, PGM or text bitmap to a drawing subroutine in .asm, or a program in .mc
+    datapack <input> <output> --origin X,Y,Z
+                          write a datapack loading a program into the ROM of the BatPU-2 world, as a .zip or a directory
+    cartridge <input> <output>
+                          bundle a program with its memory and settings into a .bpc cartridge\
 ");
 		let controls = "\
 Controls:
@@ -78,7 +270,7 @@ Controls:
 	Select | ESC     T
 	Start  | Enter   Y
 ";
-		
+
 		let usage = self.opts.usage(&brief);
 		
 		if error {
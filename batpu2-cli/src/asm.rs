@@ -1,26 +1,116 @@
 use std::fs;
+use std::path::Path;
 use anyhow::{bail, Context, Result};
-use batpu2::{asm, isa, utils};
+use batpu2::{asm, isa, opt};
+use batpu2::asm::Dialect;
+use batpu2::utils::RomFormat;
+use batpu2::link::Object;
+use batpu2::vm::Assertions;
 
-pub fn cmd(input_path: &str, output_path: &str) -> Result<()> {
+use crate::arguments::Arguments;
+use crate::link;
+
+pub fn cmd(input_path: &str, output_path: &str, arguments: &Arguments) -> Result<()> {
 	let asm = fs::read_to_string(input_path).with_context(|| format!("Failed to open: \"{input_path}\""))?;
 	
-	let code = assemble(&asm, input_path)?;
-	let code = utils::into_mc(&code);
+	if arguments.size_report {
+		size_report(&asm, input_path, arguments.dialect)?;
+	}
+	
+	let code = if arguments.optimize || arguments.strip {
+		let mut object = assemble_object(&asm, input_path, arguments.dialect)?;
+		
+		if arguments.strip {
+			let removed: usize = opt::strip_unreachable(&mut object).iter().map(|range| range.len()).sum();
+			println!("removed {removed} unreachable instructions");
+		}
+		
+		if arguments.optimize {
+			println!("{}", opt::optimize(&mut object));
+		}
+		
+		if arguments.object {
+			object.to_string().into_bytes()
+		} else {
+			output_format(output_path, arguments).write(&link::link_objects(&[object])?.code)
+		}
+	} else if arguments.object {
+		assemble_object(&asm, input_path, arguments.dialect)?.to_string().into_bytes()
+	} else {
+		output_format(output_path, arguments).write(&assemble(&asm, input_path, arguments.dialect)?)
+	};
 	
 	fs::write(output_path, code).with_context(|| format!("Failed to create: \"{output_path}\""))?;
 	
 	Ok(())
 }
 
-pub fn assemble(input: &str, input_path: &str) -> Result<Vec<isa::Instruction>> {
-	let code = collect_asm(asm::parse_lines(&input), input_path, &input)?;
-	let code = collect_asm(asm::assemble(&code), input_path, &input)?;
+/// Format of written machine code, given by `--format` or by the extension of the output, .mc by default.
+pub fn output_format(output_path: &str, arguments: &Arguments) -> RomFormat {
+	arguments.format
+	         .or_else(|| Path::new(output_path).extension()?.to_str().and_then(RomFormat::from_extension))
+	         .unwrap_or(RomFormat::Mc)
+}
+
+pub fn assemble(input: &str, input_path: &str, dialect: Dialect) -> Result<Vec<isa::Instruction>> {
+	Ok(assemble_with_assertions(input, input_path, dialect)?.0)
+}
+
+/// Assembles a program along with its `ASSERT`s, which are used when running in checked mode.
+pub fn assemble_with_assertions(input: &str, input_path: &str, dialect: Dialect) -> Result<(Vec<isa::Instruction>, Assertions)> {
+	let lines = collect_asm(asm::parse_lines_with(input, dialect), input_path, input)?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+	let code = collect_asm(assembler.by_ref(), input_path, input)?;
 	
-	Ok(code)
+	Ok((code, assembler.assertions().clone()))
 }
 
-fn collect_asm<'a, T>(iter: impl Iterator<Item=std::result::Result<T, asm::AsmError<'a>>>,
+/// Prints unreachable instructions with their source lines, followed by the size of every label.
+fn size_report(input: &str, input_path: &str, dialect: Dialect) -> Result<()> {
+	let lines = collect_asm(asm::parse_lines_with(input, dialect), input_path, input)?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+	let mut line_numbers = Vec::new();
+	
+	let code = collect_asm(std::iter::from_fn(|| {
+		let result = assembler.next()?;
+		
+		if let (Ok(_), Some(line)) = (&result, assembler.current_line()) {
+			line_numbers.push(line.line_number);
+		}
+		
+		Some(result)
+	}), input_path, input)?;
+	
+	let reachable = opt::reachable(&code, [0]);
+	
+	for range in opt::unreachable_ranges(&reachable) {
+		let first = line_numbers[range.start as usize];
+		let last = line_numbers[range.end as usize - 1];
+		
+		println!("{input_path}:{first}-{last}: {} unreachable instructions at 0x{:03X}", range.len(), range.start);
+	}
+	
+	let labels = assembler.symbols()
+	                      .iter()
+	                      .filter(|(_, symbol)| symbol.label)
+	                      .map(|(&name, symbol)| (name, symbol.value as u16));
+	
+	println!("{}", opt::SizeReport::new(labels, &reachable));
+	
+	Ok(())
+}
+
+pub fn assemble_object(input: &str, input_path: &str, dialect: Dialect) -> Result<Object> {
+	let name = Path::new(input_path).file_stem().map_or("module".into(), |stem| stem.to_string_lossy());
+	
+	let lines = collect_asm(asm::parse_lines_with(input, dialect), input_path, input)?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(dialect).relocatable();
+	let code = collect_asm(assembler.by_ref(), input_path, input)?;
+	
+	Ok(Object::new(&name, code, &assembler))
+}
+
+pub fn collect_asm<'a, T>(iter: impl Iterator<Item=std::result::Result<T, asm::AsmError<'a>>>,
                       input_path: &str,
                       input: &str)
                       -> Result<Vec<T>> {
@@ -38,7 +128,7 @@ fn collect_asm<'a, T>(iter: impl Iterator<Item=std::result::Result<T, asm::AsmEr
 					eprintln!("error: {err}");
 					eprintln!("{:pad$}--> {input_path}:{}:{}", "", err.line_num(), err.col_num());
 					eprintln!("{:pad$} |", "");
-					eprintln!("{:pad$} | {}", err.line_num(), input.lines().skip(err.line_num().saturating_sub(1)).next().unwrap_or("<EOF>"));
+					eprintln!("{:pad$} | {}", err.line_num(), input.lines().nth(err.line_num().saturating_sub(1)).unwrap_or("<EOF>"));
 					
 					eprint!("{:pad$} | ", "");
 					let mut apos = 0;
@@ -56,6 +146,10 @@ fn collect_asm<'a, T>(iter: impl Iterator<Item=std::result::Result<T, asm::AsmEr
 					eprintln!("{:pad$} | {:tpad$}|", "", "");
 					eprintln!("{:pad$} | {:tpad$}{err}", "", "");
 					eprintln!("{:pad$} |", "");
+					
+					if let Some((line_number, previous)) = err.previous_definition() {
+						eprintln!("{:pad$} = note: previously defined at {input_path}:{line_number}:{}", "", previous.char_number);
+					}
 				} else if error_count < 5 {
 					eprintln!("{input_path}:{}:{} error: {err}", err.line_num(), err.col_num());
 				}
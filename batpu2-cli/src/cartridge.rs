@@ -0,0 +1,42 @@
+use std::fs;
+use anyhow::{bail, Context, Result};
+use batpu2::isa::MAX_CODE_LEN;
+use batpu2::utils::SeedPolicy;
+
+use crate::arguments::Arguments;
+use crate::run;
+
+/// Bundles a program with the options given, an input cartridge keeps the settings that are not overridden.
+pub fn cmd(input_path: &str, output_path: &str, arguments: &Arguments) -> Result<()> {
+	let (mut cartridge, _) = run::load_cartridge(input_path, arguments)?;
+	
+	if cartridge.code.len() > MAX_CODE_LEN {
+		bail!("Program has {} instructions (max {MAX_CODE_LEN})", cartridge.code.len());
+	}
+	
+	if let Some(memory_path) = &arguments.memory {
+		cartridge.memory = fs::read(memory_path).with_context(|| format!("Failed to open: \"{memory_path}\""))?;
+	}
+	
+	for (key, _) in &arguments.keys {
+		run::key_code(key)?;
+	}
+	
+	if !arguments.keys.is_empty() {
+		cartridge.keys = arguments.keys.clone();
+	}
+	
+	cartridge.title = arguments.title.clone().unwrap_or(cartridge.title);
+	cartridge.author = arguments.author.clone().unwrap_or(cartridge.author);
+	cartridge.version = arguments.release.clone().unwrap_or(cartridge.version);
+	cartridge.speed = arguments.speed.or(cartridge.speed);
+	cartridge.clear_mask = arguments.clear_mask.or(cartridge.clear_mask);
+	cartridge.seed = arguments.seed.map_or(cartridge.seed, SeedPolicy::Fixed);
+	cartridge.validate()?;
+	
+	fs::write(output_path, cartridge.to_string()).with_context(|| format!("Failed to create: \"{output_path}\""))?;
+	
+	println!("{} instructions, {} bytes of memory", cartridge.code.len(), cartridge.memory.len());
+	
+	Ok(())
+}
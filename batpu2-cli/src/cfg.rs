@@ -0,0 +1,27 @@
+use std::fs;
+use anyhow::{Context, Result};
+use batpu2::asm;
+
+use crate::arguments::Arguments;
+use crate::asm::collect_asm;
+
+pub fn cmd(path: &str, arguments: &Arguments) -> Result<()> {
+	let input = fs::read_to_string(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+	let lines = collect_asm(asm::parse_lines_with(&input, arguments.dialect), path, &input)?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(arguments.dialect);
+	let code = collect_asm(assembler.by_ref(), path, &input)?;
+	
+	let labels = assembler.symbols()
+	                      .iter()
+	                      .filter(|(_, symbol)| symbol.label)
+	                      .map(|(&name, symbol)| (name, symbol.value as u16));
+	
+	let dot = asm::Cfg::new(&code).to_dot(labels);
+	
+	match &arguments.output {
+		Some(output) => fs::write(output, dot).with_context(|| format!("Failed to create: \"{output}\""))?,
+		None => print!("{dot}"),
+	}
+	
+	Ok(())
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use anyhow::{bail, Context, Result};
+use batpu2::{asm, opt};
+use batpu2::asm::Dialect;
+
+use crate::arguments::Arguments;
+use crate::asm::collect_asm;
+
+pub fn cmd(paths: &[String], arguments: &Arguments) -> Result<()> {
+	let mut problems = 0;
+	
+	for path in paths {
+		let input = fs::read_to_string(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+		let errors = collect_asm(std::iter::once(opt::check_conventions(&input, arguments.dialect)), path, &input)?.pop().unwrap();
+		
+		for err in &errors {
+			println!("{path}:{}: {err}", err.line_num());
+		}
+		
+		problems += errors.len();
+		
+		let report = call_report(&input, path, arguments.dialect)?;
+		let start = report.start();
+		
+		match &start.depth {
+			opt::CallDepth::Bounded { depth, chain } if *depth > opt::CALL_STACK_LEN => {
+				println!("{path}: call depth {depth} exceeds the call stack of {}: {}", opt::CALL_STACK_LEN, chain.join(" -> "));
+				problems += 1;
+			}
+			opt::CallDepth::Recursive { chain } => {
+				println!("{path}: recursive calls may overflow the call stack: {}", chain.join(" -> "));
+				problems += 1;
+			}
+			opt::CallDepth::Bounded { .. } => {}
+		}
+	}
+	
+	if problems > 0 {
+		bail!("{problems} problems found.")
+	}
+	
+	Ok(())
+}
+
+/// Builds the call graph of a program, subroutines are named by their labels.
+fn call_report(input: &str, input_path: &str, dialect: Dialect) -> Result<opt::CallReport> {
+	let lines = collect_asm(asm::parse_lines_with(input, dialect), input_path, input)?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+	let code = collect_asm(assembler.by_ref(), input_path, input)?;
+	
+	let labels = assembler.symbols()
+	                      .iter()
+	                      .filter(|(_, symbol)| symbol.label)
+	                      .map(|(&name, symbol)| (name, symbol.value as u16));
+	
+	Ok(opt::CallReport::new(labels, &code))
+}
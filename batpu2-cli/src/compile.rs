@@ -0,0 +1,41 @@
+use std::fs;
+use anyhow::{bail, Context, Result};
+use batpu2::utils;
+use batpu2_lang::CompileError;
+
+pub fn cmd(input_path: &str, output_path: &str) -> Result<()> {
+	let source = fs::read_to_string(input_path).with_context(|| format!("Failed to open: \"{input_path}\""))?;
+	
+	let output = if output_path.ends_with(".asm") {
+		batpu2_lang::compile_to_asm(&source)
+	} else {
+		batpu2_lang::compile(&source).map(|code| utils::into_mc(&code))
+	};
+	
+	let output = match output {
+		Ok(output) => output,
+		Err(err) => {
+			print_error(&err, input_path, &source);
+			bail!("Compilation aborted due to an error.")
+		}
+	};
+	
+	fs::write(output_path, output).with_context(|| format!("Failed to create: \"{output_path}\""))?;
+	
+	Ok(())
+}
+
+fn print_error(err: &CompileError, input_path: &str, source: &str) {
+	let span = err.span();
+	let line = source.lines().nth(span.line_number.saturating_sub(1)).unwrap_or("<EOF>");
+	let pad = span.line_number.max(1).ilog10() as usize + 1;
+	let tpad = span.char_number.saturating_sub(1);
+	let len = span.len.clamp(1, line.chars().count().saturating_sub(tpad).max(1));
+	
+	eprintln!("error: {err}");
+	eprintln!("{:pad$}--> {input_path}:{}:{}", "", span.line_number, span.char_number);
+	eprintln!("{:pad$} |", "");
+	eprintln!("{:pad$} | {line}", span.line_number);
+	eprintln!("{:pad$} | {:tpad$}{}", "", "", "^".repeat(len));
+	eprintln!("{:pad$} |", "");
+}
@@ -0,0 +1,34 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use batpu2::isa::MAX_CODE_LEN;
+use batpu2::utils;
+
+use crate::arguments::Arguments;
+use crate::run;
+
+pub fn cmd(input_path: &str, output_path: &str, arguments: &Arguments) -> Result<()> {
+	let Some(origin) = arguments.origin else { bail!("Missing --origin, the world position schematics of the ROM are pasted from") };
+	let (code, _) = run::load(input_path, arguments)?;
+	
+	if code.len() > MAX_CODE_LEN {
+		bail!("Program has {} instructions (max {MAX_CODE_LEN})", code.len());
+	}
+	
+	let datapack = utils::into_datapack(&code, origin);
+	
+	if output_path.ends_with(".zip") {
+		fs::write(output_path, datapack.to_zip()).with_context(|| format!("Failed to create: \"{output_path}\""))?;
+	} else {
+		for (path, content) in &datapack.files {
+			let path = Path::new(output_path).join(path);
+			
+			fs::create_dir_all(path.parent().unwrap()).with_context(|| format!("Failed to create: \"{}\"", path.display()))?;
+			fs::write(&path, content).with_context(|| format!("Failed to create: \"{}\"", path.display()))?;
+		}
+	}
+	
+	println!("{} instructions, load them with /function batpu2:load", code.len());
+	
+	Ok(())
+}
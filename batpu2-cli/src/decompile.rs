@@ -0,0 +1,34 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use batpu2::{asm, decompile};
+use batpu2::utils::RomFormat;
+
+use crate::arguments::Arguments;
+use crate::asm::collect_asm;
+use crate::run;
+
+pub fn cmd(path: &str, arguments: &Arguments) -> Result<()> {
+	let input = fs::read(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+	
+	// Labels are only known when assembling, machine code goes through the same loader as `run`
+	let program = if arguments.format.or_else(|| RomFormat::guess(Path::new(path), &input)).is_some() {
+		decompile::decompile(&run::load(path, arguments)?.0, [])
+	} else {
+		let input = String::from_utf8(input)?;
+		let lines = collect_asm(asm::parse_lines_with(&input, arguments.dialect), path, &input)?;
+		let mut assembler = asm::Assembler::new(&lines).dialect(arguments.dialect);
+		let code = collect_asm(assembler.by_ref(), path, &input)?;
+		
+		let labels = assembler.symbols()
+		                      .iter()
+		                      .filter(|(_, symbol)| symbol.label)
+		                      .map(|(&name, symbol)| (name, symbol.value as u16));
+		
+		decompile::decompile(&code, labels)
+	};
+	
+	print!("{program}");
+	
+	Ok(())
+}
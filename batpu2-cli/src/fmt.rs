@@ -0,0 +1,40 @@
+use std::fs;
+use anyhow::{bail, Context, Result};
+use batpu2::asm::FormatOptions;
+
+use crate::arguments::Arguments;
+use crate::asm;
+
+pub fn cmd(paths: &[String], arguments: &Arguments) -> Result<()> {
+	let options = FormatOptions {
+		dialect: arguments.dialect,
+		..FormatOptions::default()
+	};
+	
+	let mut unformatted = 0;
+	
+	for path in paths {
+		let input = fs::read_to_string(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+		
+		let formatted = asm::collect_asm(std::iter::once(batpu2::asm::format(&input, &options)), path, &input)?
+			.pop()
+			.unwrap();
+		
+		if formatted == input {
+			continue;
+		}
+		
+		if arguments.check {
+			println!("{path} needs formatting");
+			unformatted += 1;
+		} else {
+			fs::write(path, formatted).with_context(|| format!("Failed to write: \"{path}\""))?;
+		}
+	}
+	
+	if unformatted > 0 {
+		bail!("{unformatted} files need formatting.")
+	}
+	
+	Ok(())
+}
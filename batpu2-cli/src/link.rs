@@ -0,0 +1,45 @@
+use std::fs;
+use anyhow::{bail, Context, Result};
+use batpu2::link;
+use batpu2::link::{Linked, Object};
+
+use crate::arguments::Arguments;
+use crate::asm;
+
+pub fn cmd(input_paths: &[String], output_path: &str, arguments: &Arguments) -> Result<()> {
+	let mut objects = Vec::with_capacity(input_paths.len());
+	
+	for input_path in input_paths {
+		let input = fs::read_to_string(input_path).with_context(|| format!("Failed to open: \"{input_path}\""))?;
+		
+		let object = if input.trim_start().starts_with("batpu2-object") {
+			input.parse::<Object>().with_context(|| format!("Failed to load object: \"{input_path}\""))?
+		} else {
+			asm::assemble_object(&input, input_path, arguments.dialect)?
+		};
+		
+		objects.push(object);
+	}
+	
+	let linked = link_objects(&objects)?;
+	
+	fs::write(output_path, asm::output_format(output_path, arguments).write(&linked.code)).with_context(|| format!("Failed to create: \"{output_path}\""))?;
+	
+	if let Some(map_path) = &arguments.map {
+		fs::write(map_path, linked.map()).with_context(|| format!("Failed to create: \"{map_path}\""))?;
+	}
+	
+	Ok(())
+}
+
+pub fn link_objects(objects: &[Object]) -> Result<Linked> {
+	match link::link(objects) {
+		Ok(linked) => Ok(linked),
+		Err(errors) => {
+			for err in &errors {
+				eprintln!("error: {err}");
+			}
+			bail!("Linking aborted due to {} errors.", errors.len())
+		}
+	}
+}
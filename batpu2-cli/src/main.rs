@@ -1,12 +1,19 @@
-#![feature(try_blocks)]
-#![feature(array_chunks)]
-#![feature(hash_extract_if)]
-
 use std::process::exit;
 
 mod arguments;
 mod run;
 mod asm;
+mod link;
+mod fmt;
+mod test;
+mod xref;
+mod check;
+mod cfg;
+mod decompile;
+mod compile;
+mod sprite;
+mod datapack;
+mod cartridge;
 
 use arguments::{Arguments, Command};
 
@@ -22,8 +29,22 @@ fn main() {
 	}
 	
 	let result = match &arguments.command {
-		Command::Help => Ok(arguments.print_usage(program, false)),
-		Command::Asm{ input, output } => asm::cmd(input, output),
+		Command::Help => {
+			arguments.print_usage(program, false);
+			Ok(())
+		},
+		Command::Asm{ input, output } => asm::cmd(input, output, &arguments),
+		Command::Link{ inputs, output } => link::cmd(inputs, output, &arguments),
+		Command::Fmt{ files } => fmt::cmd(files, &arguments),
+		Command::Test{ files } => test::cmd(files, &arguments),
+		Command::Xref{ filename } => xref::cmd(filename, &arguments),
+		Command::Check{ files } => check::cmd(files, &arguments),
+		Command::Cfg{ filename } => cfg::cmd(filename, &arguments),
+		Command::Decompile{ filename } => decompile::cmd(filename, &arguments),
+		Command::Compile{ input, output } => compile::cmd(input, output),
+		Command::Sprite{ input, output } => sprite::cmd(input, output, &arguments),
+		Command::Datapack{ input, output } => datapack::cmd(input, output, &arguments),
+		Command::Cartridge{ input, output } => cartridge::cmd(input, output, &arguments),
 		Command::Run{ filename } => run::cmd(filename, &arguments),
 	};
 	
@@ -1,32 +1,85 @@
 use std::fs;
 use std::io::Write;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::io;
 use crossterm::*;
 use crossterm::terminal::ClearType;
 use crossterm::style::Color;
 use crossterm::event::{ Event, KeyEvent, KeyCode, KeyEventKind, KeyModifiers };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use batpu2::{BatPU2, isa, utils};
+use batpu2::utils::{Cartridge, LoadError, SeedPolicy};
+use batpu2::vm::Assertions;
 use batpu2::vm::embedded::Controller;
 
 use crate::arguments::Arguments;
-use crate::asm;
 
-pub fn cmd(filename: &str, arguments: &Arguments) -> Result<()> {
-	let input = fs::read_to_string(filename).with_context(|| format!("Failed to open: \"{filename}\""))?;
+/// Loads machine code in any format or assembles the file with [`utils::load_with`], along with its `ASSERT`s.
+/// Corrupted cells of schematics are printed and read as 0 to help finding them in the world.
+pub fn load(filename: &str, arguments: &Arguments) -> Result<(Vec<isa::Instruction>, Assertions)> {
+	let program = match utils::load_with(filename, arguments.format, arguments.dialect) {
+		Ok(program) => program,
+		Err(LoadError::Asm { line_number, column, message }) => bail!("{filename}:{line_number}:{column}: {message}"),
+		Err(err) => return Err(err).with_context(|| format!("Failed to load: \"{filename}\"")),
+	};
 	
-	let is_mc = input.lines().all(|line| {
-		let trimmed = line.trim();
-		trimmed.is_empty() || (trimmed.len() == 16 && trimmed.chars().all(|c| c == '0' || c == '1'))
-	});
+	for cell in program.corrupted.iter().take(10) {
+		eprintln!("{filename}: warning: corrupted {cell}");
+	}
 	
-	let code = if is_mc {
-		utils::from_mc(&input)?
-	} else {
-		asm::assemble(&input, filename)?
-	};
+	if program.corrupted.len() > 10 {
+		eprintln!("({} corrupted cells skipped...)", program.corrupted.len() - 10);
+	}
+	
+	Ok((program.code, program.assertions))
+}
+
+/// Loads a `.bpc` cartridge, or a program in any other format with default settings.
+pub fn load_cartridge(filename: &str, arguments: &Arguments) -> Result<(Cartridge, Assertions)> {
+	if Path::new(filename).extension().is_some_and(|extension| extension == "bpc") {
+		let input = fs::read_to_string(filename).with_context(|| format!("Failed to open: \"{filename}\""))?;
+		let cartridge = input.parse().with_context(|| format!("Failed to load cartridge: \"{filename}\""))?;
+		return Ok((cartridge, Assertions::new()));
+	}
+	
+	let (code, assertions) = load(filename, arguments)?;
+	
+	Ok((Cartridge::new(code), assertions))
+}
+
+/// Parses the name of a key in a cartridge, a single character or one of the named keys.
+pub fn key_code(name: &str) -> Result<KeyCode> {
+	let mut chars = name.chars();
+	
+	if let (Some(char), None) = (chars.next(), chars.next()) {
+		return Ok(KeyCode::Char(char));
+	}
+	
+	Ok(match name.to_ascii_lowercase().as_str() {
+		"left" => KeyCode::Left,
+		"down" => KeyCode::Down,
+		"right" => KeyCode::Right,
+		"up" => KeyCode::Up,
+		"space" => KeyCode::Char(' '),
+		"enter" => KeyCode::Enter,
+		"esc" => KeyCode::Esc,
+		"tab" => KeyCode::Tab,
+		"backspace" => KeyCode::Backspace,
+		_ => bail!("Unknown key: {name}"),
+	})
+}
+
+pub fn cmd(filename: &str, arguments: &Arguments) -> Result<()> {
+	let (cartridge, assertions) = load_cartridge(filename, arguments)?;
+	let keys = cartridge.keys
+	                    .iter()
+	                    .map(|(key, buttons)| Ok((key_code(key)?, *buttons)))
+	                    .collect::<Result<Vec<_>>>()?;
+	
+	// Assertions are only evaluated in checked mode
+	let assertions = if arguments.checked { assertions } else { Assertions::new() };
 	
 	terminal::enable_raw_mode()?;
 	
@@ -46,7 +99,7 @@ pub fn cmd(filename: &str, arguments: &Arguments) -> Result<()> {
 		))?;
 	}
 	
-	let result = run(code, arguments);
+	let result = run(&cartridge, &keys, &assertions, arguments);
 	
 	execute!(io::stdout(),
 	         style::ResetColor,
@@ -75,12 +128,12 @@ where W: Fn(&T) -> R,
 		Self {
 			value: None,
 			func,
-			phantom_data: PhantomData::default(),
+			phantom_data: PhantomData,
 		}
 	}
 	
 	fn changed(&mut self, arg: &T) -> Option<&R> {
-		let old = std::mem::replace(&mut self.value, Some((self.func)(arg)));
+		let old = self.value.replace((self.func)(arg));
 		
 		if self.value != old {
 			self.value.as_ref()
@@ -94,23 +147,30 @@ where W: Fn(&T) -> R,
 	}
 }
 
-fn run(code: Vec<isa::Instruction>, arguments: &Arguments) -> Result<()> {
-	let mut vm = BatPU2::new(code);
-	
-	let mut seed = [0; 32];
-	seed[0..16].copy_from_slice(
-		&SystemTime::now().duration_since(UNIX_EPOCH)
-		                  .unwrap()
-		                  .as_nanos()
-		                  .to_ne_bytes()
-	);
+fn run(cartridge: &Cartridge, keys: &[(KeyCode, u8)], assertions: &Assertions, arguments: &Arguments) -> Result<()> {
+	let mut vm = cartridge.to_vm();
+	let tickrate = arguments.speed.or(cartridge.speed).unwrap_or(100.0);
 	
-	vm.io.set_seed(seed);
+	match arguments.seed.map_or(cartridge.seed, SeedPolicy::Fixed).to_seed() {
+		Some(seed) => vm.io.set_seed(seed),
+		None => {
+			let mut seed = [0; 32];
+			seed[0..16].copy_from_slice(
+				&SystemTime::now().duration_since(UNIX_EPOCH)
+				                  .unwrap()
+				                  .as_nanos()
+				                  .to_ne_bytes()
+			);
+			
+			vm.io.set_seed(seed);
+		}
+	}
 	
-	if arguments.kitty {
-		vm.io.controller.set_clear_mask(Controller::B_NONE);
-	} else {
+	// Without kitty releases are not reported, so every button is cleared once read
+	if !arguments.kitty {
 		vm.io.controller.set_clear_mask(Controller::B_ALL);
+	} else if cartridge.clear_mask.is_none() {
+		vm.io.controller.set_clear_mask(Controller::B_NONE);
 	}
 	
 	let mut last_sec = Instant::now();
@@ -161,6 +221,18 @@ fn run(code: Vec<isa::Instruction>, arguments: &Arguments) -> Result<()> {
 					
 					queue!(io::stdout(), terminal::Clear(ClearType::Purge))?;
 				},
+				// Keys of the cartridge replace the default bindings
+				Event::Key(KeyEvent { code, kind, .. }) if keys.iter().any(|&(key, _)| key == code) => {
+					let buttons = keys.iter()
+					                  .filter(|&&(key, _)| key == code)
+					                  .fold(0, |buttons, &(_, bits)| buttons | bits);
+					
+					match kind {
+						KeyEventKind::Press => vm.io.controller.set_button(buttons),
+						KeyEventKind::Release if arguments.kitty => vm.io.controller.clear_button(buttons),
+						_ => {},
+					}
+				},
 				event => {
 					binds!(event, vm;
 						KeyCode::Left => Controller::B_LEFT,
@@ -184,9 +256,9 @@ fn run(code: Vec<isa::Instruction>, arguments: &Arguments) -> Result<()> {
 			}
 		}
 		
-		let steps_target = (last_sec.elapsed().as_secs_f32() * arguments.tickrate) as usize;
+		let steps_target = (last_sec.elapsed().as_secs_f32() * tickrate) as usize;
 		if steps_target > steps {
-			steps += vm.step_multiple((steps_target - steps).min(arguments.tickrate.max(10.0) as usize));
+			steps += vm.try_step_multiple_checked((steps_target - steps).min(tickrate.max(10.0) as usize), assertions)?;
 		}
 		
 		if last_sec.elapsed().as_secs_f32() > 1.0 {
@@ -208,7 +280,7 @@ fn run(code: Vec<isa::Instruction>, arguments: &Arguments) -> Result<()> {
 		}
 		
 		if let Some(screen) = screen.changed(&vm) {
-			for (y, [lower, upper]) in screen.array_chunks().rev().enumerate() {
+			for (y, [lower, upper]) in screen.as_chunks::<2>().0.iter().rev().enumerate() {
 				let mut line = String::with_capacity(32 * 3); // 3 bytes per characters in utf-8
 				
 				for bit in (0..32).map(|b| 1 << b) {
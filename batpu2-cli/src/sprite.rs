@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use batpu2::sprite::Sprite;
+use batpu2::utils;
+
+use crate::arguments::Arguments;
+
+pub fn cmd(input_path: &str, output_path: &str, arguments: &Arguments) -> Result<()> {
+	let input = fs::read(input_path).with_context(|| format!("Failed to open: \"{input_path}\""))?;
+	let mut sprite = Sprite::parse(&input).with_context(|| format!("Failed to read image: \"{input_path}\""))?;
+	
+	if arguments.invert {
+		sprite.invert();
+	}
+	
+	// The subroutine is named after the file, e.g. `title-screen.pbm` becomes `.title_screen`
+	let stem = Path::new(input_path).file_stem().map_or("sprite".into(), |stem| stem.to_string_lossy());
+	let name: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+	
+	let (x, y) = arguments.position;
+	let code = sprite.to_asm(&name, x, y)?;
+	
+	println!("{name}: {}×{} pixels, {} strategy, {} instructions", sprite.width(), sprite.height(), code.strategy, code.instructions);
+	
+	let output = if output_path.ends_with(".asm") {
+		code.asm
+	} else {
+		let program = format!("\tCAL .{name}\n\tLDI r15 buffer_screen\n\tSTR r15 r0\n\tHLT\n\n{}", code.asm);
+		utils::into_mc(&utils::from_asm(&program).map_err(|err| anyhow::anyhow!("{err}"))?)
+	};
+	
+	fs::write(output_path, output).with_context(|| format!("Failed to create: \"{output_path}\""))?;
+	
+	Ok(())
+}
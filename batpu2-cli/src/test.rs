@@ -0,0 +1,37 @@
+use std::fs;
+use anyhow::{anyhow, bail, Context, Result};
+use batpu2::testing::{TestError, TestSuite};
+
+use crate::arguments::Arguments;
+
+pub fn cmd(paths: &[String], arguments: &Arguments) -> Result<()> {
+	let mut passed = 0;
+	let mut failed = 0;
+	
+	for path in paths {
+		let input = fs::read_to_string(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+		
+		let suite = TestSuite::from_asm(&input, arguments.dialect).map_err(|err| match err {
+			TestError::Asm(err) => anyhow!("{path}:{}:{}: {err}", err.line_num(), err.col_num()),
+			err => anyhow!("{path}: {err}"),
+		})?;
+		
+		for result in suite.run() {
+			println!("{path}: {result}");
+			
+			if result.passed() {
+				passed += 1;
+			} else {
+				failed += 1;
+			}
+		}
+	}
+	
+	println!("{passed} passed, {failed} failed");
+	
+	if failed > 0 {
+		bail!("{failed} tests failed.")
+	}
+	
+	Ok(())
+}
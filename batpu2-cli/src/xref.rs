@@ -0,0 +1,45 @@
+use std::fs;
+use anyhow::{Context, Result};
+use batpu2::asm::{self, Xref};
+use serde_json::{json, Value};
+
+use crate::arguments::Arguments;
+use crate::asm::collect_asm;
+
+pub fn cmd(path: &str, arguments: &Arguments) -> Result<()> {
+	let input = fs::read_to_string(path).with_context(|| format!("Failed to open: \"{path}\""))?;
+	let xref = collect_asm(std::iter::once(asm::xref(&input, arguments.dialect)), path, &input)?.pop().unwrap();
+	
+	if arguments.json {
+		println!("{}", serde_json::to_string_pretty(&to_json(&xref))?);
+	} else {
+		print!("{xref}");
+	}
+	
+	Ok(())
+}
+
+fn to_json(xref: &Xref) -> Value {
+	let symbols: Vec<_> = xref.symbols
+	                          .iter()
+	                          .map(|symbol| json!({
+		                          "name": symbol.name,
+		                          "kind": symbol.kind.name(),
+		                          "value": symbol.value,
+		                          "definitions": symbol.definitions,
+		                          "references": symbol.references
+		                                              .iter()
+		                                              .map(|reference| json!({
+			                                              "line": reference.line_number,
+			                                              "column": reference.char_number,
+			                                              "address": reference.address,
+			                                              "mnemonic": reference.mnemonic,
+			                                              "operand": reference.operand,
+			                                              "usage": reference.usage.name(),
+		                                              }))
+		                                              .collect::<Vec<_>>(),
+	                          }))
+	                          .collect();
+	
+	json!({ "symbols": symbols })
+}
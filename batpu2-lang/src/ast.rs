@@ -0,0 +1,138 @@
+use crate::Span;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum Item {
+	Const { name: Name, value: Expr },
+	Var(VarDecl),
+	Function(Function),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Name {
+	pub name: String,
+	pub span: Span,
+}
+
+/// `var x;`, `var x = 1;` or `var a[16];`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct VarDecl {
+	pub name: Name,
+	/// Length of an array
+	pub len: Option<Expr>,
+	pub value: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Function {
+	pub name: Name,
+	pub params: Vec<Name>,
+	pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum Stmt {
+	Var(VarDecl),
+	/// `x = e`, compound assignments like `x += e` carry their operator, `x++` is `x += 1`
+	Assign { place: Place, op: Option<BinaryOp>, value: Expr },
+	If { condition: Expr, then: Vec<Stmt>, otherwise: Vec<Stmt> },
+	While { condition: Expr, body: Vec<Stmt> },
+	Break(Span),
+	Continue(Span),
+	Return(Option<Expr>),
+	Expr(Expr),
+}
+
+/// Variable or array element which can be assigned to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Place {
+	pub name: Name,
+	pub index: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Expr {
+	pub kind: ExprKind,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum ExprKind {
+	Number(u32),
+	/// Encoded characters, only accepted by `print`
+	Str(Vec<u8>),
+	Name(String),
+	Index(Name, Box<Expr>),
+	Call(Name, Vec<Expr>),
+	Unary(UnaryOp, Box<Expr>),
+	Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum UnaryOp {
+	/// `-`
+	Neg,
+	/// `~`
+	Not,
+	/// `!`
+	LogicalNot,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum BinaryOp {
+	Add,
+	Sub,
+	And,
+	Or,
+	Xor,
+	Shl,
+	Shr,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+	LogicalAnd,
+	LogicalOr,
+}
+
+impl BinaryOp {
+	/// Binding strength, higher binds tighter.
+	pub fn precedence(self) -> u8 {
+		match self {
+			BinaryOp::LogicalOr => 1,
+			BinaryOp::LogicalAnd => 2,
+			BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 3,
+			BinaryOp::Or => 4,
+			BinaryOp::Xor => 5,
+			BinaryOp::And => 6,
+			BinaryOp::Shl | BinaryOp::Shr => 7,
+			BinaryOp::Add | BinaryOp::Sub => 8,
+		}
+	}
+	
+	pub fn from_symbol(symbol: &str) -> Option<Self> {
+		Some(match symbol {
+			"+" => BinaryOp::Add,
+			"-" => BinaryOp::Sub,
+			"&" => BinaryOp::And,
+			"|" => BinaryOp::Or,
+			"^" => BinaryOp::Xor,
+			"<<" => BinaryOp::Shl,
+			">>" => BinaryOp::Shr,
+			"==" => BinaryOp::Eq,
+			"!=" => BinaryOp::Ne,
+			"<" => BinaryOp::Lt,
+			"<=" => BinaryOp::Le,
+			">" => BinaryOp::Gt,
+			">=" => BinaryOp::Ge,
+			"&&" => BinaryOp::LogicalAnd,
+			"||" => BinaryOp::LogicalOr,
+			_ => return None,
+		})
+	}
+	
+	pub fn is_comparison(self) -> bool {
+		self.precedence() == 3
+	}
+}
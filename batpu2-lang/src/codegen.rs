@@ -0,0 +1,926 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use batpu2::isa::MAX_CODE_LEN;
+
+use crate::ast::*;
+use crate::{CompileError, Span};
+
+/// Registers holding temporaries, `r15` holds addresses and the result of a call is returned in `r1`.
+const TEMPS: std::ops::RangeInclusive<u8> = 1..=14;
+const ADDRESS: u8 = 15;
+const RAM_LEN: usize = 240;
+
+#[derive(Debug, Clone)]
+enum Symbol {
+	Const(u8),
+	/// Variable or array stored at the address of a `define`d name
+	Var { slot: String, len: Option<u8> },
+	Function(usize),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Intrinsic {
+	/// Stores the value to the IO port of the same name
+	Write,
+	/// Stores anything to the IO port of the same name
+	Signal,
+	/// Loads from the IO port of the same name
+	Read,
+	/// Sets `pixel_x` and `pixel_y`, then accesses the port at given offset from `pixel_x`
+	Pixel { offset: i8, read: bool },
+	/// Writes every character of a string literal
+	Print,
+}
+
+/// Built-in functions named after the IO ports of [`DEFAULT_SYMBOLS`](batpu2::asm::DEFAULT_SYMBOLS).
+const INTRINSICS: [(&str, Intrinsic); 15] = [
+	("draw_pixel", Intrinsic::Pixel { offset: 2, read: false }),
+	("clear_pixel", Intrinsic::Pixel { offset: 3, read: false }),
+	("load_pixel", Intrinsic::Pixel { offset: 4, read: true }),
+	("buffer_screen", Intrinsic::Signal),
+	("clear_screen_buffer", Intrinsic::Signal),
+	("write_char", Intrinsic::Write),
+	("buffer_chars", Intrinsic::Signal),
+	("clear_chars_buffer", Intrinsic::Signal),
+	("show_number", Intrinsic::Write),
+	("clear_number", Intrinsic::Signal),
+	("signed_mode", Intrinsic::Signal),
+	("unsigned_mode", Intrinsic::Signal),
+	("rng", Intrinsic::Read),
+	("controller_input", Intrinsic::Read),
+	("print", Intrinsic::Print),
+];
+
+impl Intrinsic {
+	fn find(name: &str) -> Option<Self> {
+		INTRINSICS.iter().find(|(intrinsic, _)| *intrinsic == name).map(|&(_, intrinsic)| intrinsic)
+	}
+	
+	fn arg_count(self) -> usize {
+		match self {
+			Intrinsic::Signal | Intrinsic::Read => 0,
+			Intrinsic::Write | Intrinsic::Print => 1,
+			Intrinsic::Pixel { .. } => 2,
+		}
+	}
+	
+	fn returns(self) -> bool {
+		matches!(self, Intrinsic::Read | Intrinsic::Pixel { read: true, .. })
+	}
+}
+
+/// Memory and calls of a generated function, used to lay out the frames.
+#[derive(Debug, Default)]
+struct Frame {
+	/// Parameters, locals and spill slots along with their sizes
+	slots: Vec<(String, u8)>,
+	/// Called functions along with the span of the first call
+	calls: Vec<(usize, Span)>,
+	/// Number of instructions before the function
+	start: usize,
+}
+
+/// Generates assembly for a whole program.
+///
+/// Every variable lives at a fixed address, so the frames of functions which are never active at the same
+/// time share memory. Temporaries are kept in registers and spilled to the frame of the caller around calls.
+pub(crate) struct Codegen<'a> {
+	globals: HashMap<String, Symbol>,
+	global_slots: Vec<(String, u8, Span)>,
+	functions: Vec<&'a Function>,
+	frames: Vec<Frame>,
+	/// Index of the function being generated
+	function: usize,
+	scopes: Vec<HashMap<String, Symbol>>,
+	/// Registers holding temporaries as a bit mask
+	used: u16,
+	/// `continue` and `break` targets of the enclosing loops
+	loops: Vec<(String, String)>,
+	labels: usize,
+	asm: String,
+	instructions: usize,
+}
+
+impl<'a> Codegen<'a> {
+	pub fn new() -> Self {
+		Self {
+			globals: HashMap::new(),
+			global_slots: Vec::new(),
+			functions: Vec::new(),
+			frames: Vec::new(),
+			function: 0,
+			scopes: Vec::new(),
+			used: 0,
+			loops: Vec::new(),
+			labels: 0,
+			asm: String::new(),
+			instructions: 0,
+		}
+	}
+	
+	/// Generates the program, `end` is the span of the end of file, where a missing `main` is reported.
+	pub fn program(mut self, items: &'a [Item], end: Span) -> Result<String, CompileError> {
+		let mut inits = Vec::new();
+		
+		for item in items {
+			match item {
+				Item::Const { name, value } => {
+					let value = self.constant(value)?.ok_or(CompileError::NotConstant { span: value.span })?;
+					self.declare_global(name, Symbol::Const(value))?;
+				}
+				Item::Var(decl) => {
+					let len = self.array_len(decl)?;
+					let slot = format!("var.{}", decl.name.name);
+					
+					if let Some(value) = &decl.value {
+						let init = self.constant(value)?.ok_or(CompileError::NotConstant { span: value.span })?;
+						inits.extend((init != 0).then(|| (slot.clone(), init)));
+					}
+					
+					self.declare_global(&decl.name, Symbol::Var { slot: slot.clone(), len })?;
+					self.global_slots.push((slot, len.unwrap_or(1), decl.name.span));
+				}
+				Item::Function(function) => {
+					self.declare_global(&function.name, Symbol::Function(self.functions.len()))?;
+					self.functions.push(function);
+				}
+			}
+		}
+		
+		let Some(main) = self.functions.iter().position(|function| function.name.name == "main") else {
+			return Err(CompileError::MissingMain { span: end });
+		};
+		
+		for (slot, value) in inits {
+			self.emit(format_args!("LDI r1 {value}"));
+			self.emit(format_args!("LDI r{ADDRESS} {slot}"));
+			self.emit(format_args!("STR r{ADDRESS} r1"));
+		}
+		
+		self.emit(format_args!("CAL .main"));
+		self.emit(format_args!("HLT"));
+		
+		for index in 0..self.functions.len() {
+			self.function(index)?;
+		}
+		
+		if self.instructions > MAX_CODE_LEN {
+			let function = self.frames.iter().rposition(|frame| frame.start < MAX_CODE_LEN).unwrap_or(main);
+			return Err(CompileError::TooManyInstructions { span: self.functions[function].name.span, len: self.instructions });
+		}
+		
+		self.layout()?;
+		
+		Ok(self.asm)
+	}
+	
+	fn declare_global(&mut self, name: &Name, symbol: Symbol) -> Result<(), CompileError> {
+		check_name(name)?;
+		
+		if self.globals.insert(name.name.clone(), symbol).is_some() {
+			return Err(CompileError::Duplicate { span: name.span, name: name.name.clone() });
+		}
+		
+		Ok(())
+	}
+	
+	fn function(&mut self, index: usize) -> Result<(), CompileError> {
+		let function = self.functions[index];
+		let mut params = HashMap::new();
+		let mut frame = Frame { start: self.instructions, ..Frame::default() };
+		
+		for param in &function.params {
+			check_name(param)?;
+			
+			let slot = format!("{}.{}", function.name.name, param.name);
+			
+			if params.insert(param.name.clone(), Symbol::Var { slot: slot.clone(), len: None }).is_some() {
+				return Err(CompileError::Duplicate { span: param.span, name: param.name.clone() });
+			}
+			
+			frame.slots.push((slot, 1));
+		}
+		
+		self.function = index;
+		self.frames.push(frame);
+		self.scopes = vec![params];
+		self.label(&format!(".{}", function.name.name));
+		self.block(&function.body)?;
+		
+		if !matches!(function.body.last(), Some(Stmt::Return(_))) {
+			self.emit(format_args!("RET"));
+		}
+		
+		Ok(())
+	}
+	
+	/// Places the frames after the globals, every callee after all of its callers, and `define`s the addresses.
+	fn layout(&mut self) -> Result<(), CompileError> {
+		let order = self.call_order()?;
+		let mut address = 0;
+		
+		for (slot, len, span) in &self.global_slots {
+			address += *len as usize;
+			
+			if address > RAM_LEN {
+				return Err(CompileError::OutOfMemory { span: *span, needed: address });
+			}
+			
+			writeln!(self.asm, "define {slot} {}", address - *len as usize).unwrap();
+		}
+		
+		let mut offsets = vec![address; self.frames.len()];
+		
+		for index in order {
+			let frame = &self.frames[index];
+			let mut address = offsets[index];
+			
+			for (slot, len) in &frame.slots {
+				writeln!(self.asm, "define {slot} {address}").unwrap();
+				address += *len as usize;
+			}
+			
+			if address > RAM_LEN {
+				return Err(CompileError::OutOfMemory { span: self.functions[index].name.span, needed: address });
+			}
+			
+			for &(callee, _) in &frame.calls {
+				offsets[callee] = offsets[callee].max(address);
+			}
+		}
+		
+		Ok(())
+	}
+	
+	/// Functions ordered so that callers come before their callees, fails if a function may call itself.
+	fn call_order(&self) -> Result<Vec<usize>, CompileError> {
+		#[derive(Copy, Clone, Eq, PartialEq)]
+		enum State { New, Active, Done }
+		
+		fn visit(frames: &[Frame], functions: &[&Function], index: usize, states: &mut [State], order: &mut Vec<usize>) -> Result<(), CompileError> {
+			states[index] = State::Active;
+			
+			for &(callee, span) in &frames[index].calls {
+				match states[callee] {
+					State::New => visit(frames, functions, callee, states, order)?,
+					State::Active => return Err(CompileError::Recursion { span, name: functions[callee].name.name.clone() }),
+					State::Done => {}
+				}
+			}
+			
+			states[index] = State::Done;
+			order.push(index);
+			
+			Ok(())
+		}
+		
+		let mut states = vec![State::New; self.frames.len()];
+		let mut order = Vec::new();
+		
+		for index in 0..self.frames.len() {
+			if states[index] == State::New {
+				visit(&self.frames, &self.functions, index, &mut states, &mut order)?;
+			}
+		}
+		
+		order.reverse();
+		
+		Ok(order)
+	}
+	
+	fn block(&mut self, statements: &[Stmt]) -> Result<(), CompileError> {
+		self.scopes.push(HashMap::new());
+		
+		for statement in statements {
+			self.stmt(statement)?;
+		}
+		
+		self.scopes.pop();
+		
+		Ok(())
+	}
+	
+	fn stmt(&mut self, statement: &Stmt) -> Result<(), CompileError> {
+		match statement {
+			Stmt::Var(decl) => self.local(decl)?,
+			Stmt::Assign { place, op, value } => self.assign(place, *op, value)?,
+			Stmt::If { condition, then, otherwise } => {
+				let end = self.fresh("end");
+				
+				if otherwise.is_empty() {
+					self.branch(condition, &end, false)?;
+					self.block(then)?;
+				} else {
+					let other = self.fresh("else");
+					
+					self.branch(condition, &other, false)?;
+					self.block(then)?;
+					self.emit(format_args!("JMP {end}"));
+					self.label(&other);
+					self.block(otherwise)?;
+				}
+				
+				self.label(&end);
+			}
+			Stmt::While { condition, body } => {
+				let top = self.fresh("while");
+				let done = self.fresh("done");
+				
+				self.label(&top);
+				self.branch(condition, &done, false)?;
+				self.loops.push((top.clone(), done.clone()));
+				self.block(body)?;
+				self.loops.pop();
+				self.emit(format_args!("JMP {top}"));
+				self.label(&done);
+			}
+			Stmt::Break(span) => {
+				let (_, done) = self.loops.last().ok_or(CompileError::OutsideLoop { span: *span, keyword: "break" })?;
+				let done = done.clone();
+				self.emit(format_args!("JMP {done}"));
+			}
+			Stmt::Continue(span) => {
+				let (top, _) = self.loops.last().ok_or(CompileError::OutsideLoop { span: *span, keyword: "continue" })?;
+				let top = top.clone();
+				self.emit(format_args!("JMP {top}"));
+			}
+			Stmt::Return(value) => {
+				if let Some(value) = value {
+					let reg = self.value(value)?;
+					
+					if reg != 1 {
+						self.emit(format_args!("MOV r{reg} r1"));
+					}
+					
+					self.free(reg);
+				}
+				
+				self.emit(format_args!("RET"));
+			}
+			Stmt::Expr(Expr { kind: ExprKind::Call(name, args), span }) => {
+				if let Some(reg) = self.call(name, args, *span, false)? {
+					self.free(reg);
+				}
+			}
+			Stmt::Expr(expr) => {
+				let reg = self.value(expr)?;
+				self.free(reg);
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn local(&mut self, decl: &VarDecl) -> Result<(), CompileError> {
+		check_name(&decl.name)?;
+		
+		if self.scopes.last().unwrap().contains_key(&decl.name.name) {
+			return Err(CompileError::Duplicate { span: decl.name.span, name: decl.name.name.clone() });
+		}
+		
+		let len = self.array_len(decl)?;
+		let function = &self.functions[self.function].name.name;
+		let frame = self.frames.last_mut().unwrap();
+		let mut slot = format!("{function}.{}", decl.name.name);
+		
+		// Shadowed variables get a slot of their own
+		if frame.slots.iter().any(|(existing, _)| *existing == slot) {
+			slot = format!("{slot}.{}", frame.slots.len());
+		}
+		
+		frame.slots.push((slot.clone(), len.unwrap_or(1)));
+		
+		// The variable is only visible after its initializer, so `var x = x + 1;` refers to an outer `x`
+		if let Some(value) = &decl.value {
+			let reg = self.value(value)?;
+			self.emit(format_args!("LDI r{ADDRESS} {slot}"));
+			self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+			self.free(reg);
+		}
+		
+		self.scopes.last_mut().unwrap().insert(decl.name.name.clone(), Symbol::Var { slot, len });
+		
+		Ok(())
+	}
+	
+	fn array_len(&self, decl: &VarDecl) -> Result<Option<u8>, CompileError> {
+		let Some(len) = &decl.len else { return Ok(None) };
+		let value = self.constant(len)?.ok_or(CompileError::NotConstant { span: len.span })?;
+		
+		if value == 0 || value as usize > RAM_LEN {
+			return Err(CompileError::OutOfRange { span: len.span, value: value as u32, min: 1, max: RAM_LEN as u32 });
+		}
+		
+		Ok(Some(value))
+	}
+	
+	fn assign(&mut self, place: &Place, op: Option<BinaryOp>, value: &Expr) -> Result<(), CompileError> {
+		let Some(index) = &place.index else {
+			let slot = self.scalar(&place.name)?;
+			let reg = match op {
+				Some(op) => {
+					let reg = self.load(&slot, place.name.span)?;
+					self.apply(op, reg, value)?;
+					reg
+				}
+				None => self.value(value)?,
+			};
+			
+			self.emit(format_args!("LDI r{ADDRESS} {slot}"));
+			self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+			self.free(reg);
+			
+			return Ok(());
+		};
+		
+		let (address, offset) = self.element(&place.name, index)?;
+		let reg = match op {
+			Some(op) => {
+				let reg = self.alloc(place.name.span)?;
+				self.emit(format_args!("LOD r{address} r{reg}{}", Offset(offset)));
+				self.apply(op, reg, value)?;
+				reg
+			}
+			None => self.value(value)?,
+		};
+		
+		self.emit(format_args!("STR r{address} r{reg}{}", Offset(offset)));
+		self.free(reg);
+		self.free(address);
+		
+		Ok(())
+	}
+	
+	/// Evaluates an expression into a newly allocated register.
+	fn value(&mut self, expr: &Expr) -> Result<u8, CompileError> {
+		if let Some(value) = self.constant(expr)? {
+			let reg = self.alloc(expr.span)?;
+			self.emit(format_args!("LDI r{reg} {value}"));
+			return Ok(reg);
+		}
+		
+		match &expr.kind {
+			ExprKind::Str(_) => Err(CompileError::StringLiteral { span: expr.span }),
+			ExprKind::Name(name) => {
+				let slot = self.scalar(&Name { name: name.clone(), span: expr.span })?;
+				self.load(&slot, expr.span)
+			}
+			ExprKind::Index(name, index) => {
+				let (reg, offset) = self.element(name, index)?;
+				self.emit(format_args!("LOD r{reg} r{reg}{}", Offset(offset)));
+				Ok(reg)
+			}
+			ExprKind::Call(name, args) => {
+				let reg = self.call(name, args, expr.span, true)?;
+				Ok(reg.unwrap())
+			}
+			ExprKind::Unary(UnaryOp::Neg, operand) => {
+				let reg = self.value(operand)?;
+				self.emit(format_args!("NEG r{reg} r{reg}"));
+				Ok(reg)
+			}
+			ExprKind::Unary(UnaryOp::Not, operand) => {
+				let reg = self.value(operand)?;
+				self.emit(format_args!("NOT r{reg} r{reg}"));
+				Ok(reg)
+			}
+			ExprKind::Binary(op, left, right) if !op.is_comparison() && !matches!(op, BinaryOp::LogicalAnd | BinaryOp::LogicalOr) => {
+				let reg = self.value(left)?;
+				self.apply(*op, reg, right)?;
+				Ok(reg)
+			}
+			// Comparisons and logical operators give 0 or 1
+			_ => {
+				let yes = self.fresh("true");
+				let end = self.fresh("end");
+				
+				self.branch(expr, &yes, true)?;
+				
+				let reg = self.alloc(expr.span)?;
+				self.emit(format_args!("LDI r{reg} 0"));
+				self.emit(format_args!("JMP {end}"));
+				self.label(&yes);
+				self.emit(format_args!("LDI r{reg} 1"));
+				self.label(&end);
+				
+				Ok(reg)
+			}
+		}
+	}
+	
+	/// Applies an arithmetic operator to the value in `reg` and the right operand, leaving the result in `reg`.
+	fn apply(&mut self, op: BinaryOp, reg: u8, right: &Expr) -> Result<(), CompileError> {
+		let constant = self.constant(right)?;
+		
+		match (op, constant) {
+			(BinaryOp::Add, Some(value)) => if value != 0 {
+				self.emit(format_args!("ADI r{reg} {value}"));
+			},
+			(BinaryOp::Sub, Some(value)) => if value != 0 {
+				self.emit(format_args!("ADI r{reg} {}", value.wrapping_neg()));
+			},
+			(BinaryOp::Shl | BinaryOp::Shr, Some(value)) => {
+				let mnemonic = if op == BinaryOp::Shl { "LSH" } else { "RSH" };
+				
+				for _ in 0..value.min(8) {
+					self.emit(format_args!("{mnemonic} r{reg} r{reg}"));
+				}
+			}
+			(BinaryOp::Shl | BinaryOp::Shr, None) => return Err(CompileError::NotConstant { span: right.span }),
+			_ => {
+				let other = self.value(right)?;
+				
+				match op {
+					BinaryOp::Add => self.emit(format_args!("ADD r{reg} r{other} r{reg}")),
+					BinaryOp::Sub => self.emit(format_args!("SUB r{reg} r{other} r{reg}")),
+					BinaryOp::And => self.emit(format_args!("AND r{reg} r{other} r{reg}")),
+					BinaryOp::Xor => self.emit(format_args!("XOR r{reg} r{other} r{reg}")),
+					BinaryOp::Or => {
+						self.emit(format_args!("NOR r{reg} r{other} r{reg}"));
+						self.emit(format_args!("NOT r{reg} r{reg}"));
+					}
+					_ => unreachable!("{op:?} is not arithmetic"),
+				}
+				
+				self.free(other);
+			}
+		}
+		
+		Ok(())
+	}
+	
+	/// Jumps to `label` if the truth of the condition equals `when`, comparisons are unsigned.
+	fn branch(&mut self, condition: &Expr, label: &str, when: bool) -> Result<(), CompileError> {
+		if let Some(value) = self.constant(condition)? {
+			if (value != 0) == when {
+				self.emit(format_args!("JMP {label}"));
+			}
+			
+			return Ok(());
+		}
+		
+		match &condition.kind {
+			ExprKind::Unary(UnaryOp::LogicalNot, operand) => self.branch(operand, label, !when)?,
+			// `&&` jumps when both are true or either is false, `||` when either is true or both are false
+			ExprKind::Binary(op @ (BinaryOp::LogicalAnd | BinaryOp::LogicalOr), left, right) => {
+				if (*op == BinaryOp::LogicalOr) == when {
+					self.branch(left, label, when)?;
+					self.branch(right, label, when)?;
+				} else {
+					let skip = self.fresh("skip");
+					
+					self.branch(left, &skip, !when)?;
+					self.branch(right, label, when)?;
+					self.label(&skip);
+				}
+			}
+			ExprKind::Binary(op, left, right) if op.is_comparison() => {
+				let (op, left, right) = match op {
+					BinaryOp::Gt => (BinaryOp::Lt, right, left),
+					BinaryOp::Le => (BinaryOp::Ge, right, left),
+					_ => (*op, left, right),
+				};
+				let op = match (op, when) {
+					(op, true) => op,
+					(BinaryOp::Eq, false) => BinaryOp::Ne,
+					(BinaryOp::Ne, false) => BinaryOp::Eq,
+					(BinaryOp::Lt, false) => BinaryOp::Ge,
+					(_, false) => BinaryOp::Lt,
+				};
+				let cond = match op {
+					BinaryOp::Eq => "eq",
+					BinaryOp::Ne => "ne",
+					BinaryOp::Lt => "lt",
+					_ => "ge",
+				};
+				
+				let a = self.value(left)?;
+				let b = if self.constant(right)? == Some(0) { 0 } else { self.value(right)? };
+				
+				self.emit(format_args!("CMP r{a} r{b}"));
+				self.emit(format_args!("BRH {cond} {label}"));
+				self.free(a);
+				self.free(b);
+			}
+			_ => {
+				let reg = self.value(condition)?;
+				
+				self.emit(format_args!("CMP r{reg} r0"));
+				self.emit(format_args!("BRH {} {label}", if when { "ne" } else { "eq" }));
+				self.free(reg);
+			}
+		}
+		
+		Ok(())
+	}
+	
+	/// Calls a function or an intrinsic, returning the register holding the result if `value` is set.
+	fn call(&mut self, name: &Name, args: &[Expr], span: Span, value: bool) -> Result<Option<u8>, CompileError> {
+		if let Some(intrinsic) = Intrinsic::find(&name.name) {
+			check_args(name, intrinsic.arg_count(), args)?;
+			
+			if value && !intrinsic.returns() {
+				return Err(CompileError::NoValue { span, name: name.name.clone() });
+			}
+			
+			return self.intrinsic(&name.name, intrinsic, args, span);
+		}
+		
+		let index = match self.lookup(name)? {
+			Symbol::Function(index) => index,
+			symbol => return Err(wrong_kind(name, &symbol, "a function")),
+		};
+		let callee = self.functions[index];
+		
+		check_args(name, callee.params.len(), args)?;
+		
+		if value && !returns_value(&callee.body) {
+			return Err(CompileError::NoValue { span, name: name.name.clone() });
+		}
+		
+		// Arguments are only stored once all of them are known, evaluating one may call the same function
+		let regs = args.iter().map(|arg| self.value(arg)).collect::<Result<Vec<_>, _>>()?;
+		
+		for (reg, param) in regs.into_iter().zip(&callee.params) {
+			self.emit(format_args!("LDI r{ADDRESS} {}.{}", callee.name.name, param.name));
+			self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+			self.free(reg);
+		}
+		
+		let live: Vec<u8> = TEMPS.filter(|&reg| self.used & 1 << reg != 0).collect();
+		let caller = &self.functions[self.function].name.name;
+		let spills: Vec<String> = (0..live.len()).map(|slot| format!("{caller}.{slot}")).collect();
+		let frame = self.frames.last_mut().unwrap();
+		
+		for spill in &spills {
+			if !frame.slots.iter().any(|(slot, _)| slot == spill) {
+				frame.slots.push((spill.clone(), 1));
+			}
+		}
+		
+		if !frame.calls.iter().any(|&(function, _)| function == index) {
+			frame.calls.push((index, name.span));
+		}
+		
+		for (reg, spill) in live.iter().zip(&spills) {
+			self.emit(format_args!("LDI r{ADDRESS} {spill}"));
+			self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+		}
+		
+		self.emit(format_args!("CAL .{}", callee.name.name));
+		
+		let result = if value {
+			let reg = self.alloc(span)?;
+			
+			if reg != 1 {
+				self.emit(format_args!("MOV r1 r{reg}"));
+			}
+			
+			Some(reg)
+		} else {
+			None
+		};
+		
+		for (reg, spill) in live.iter().zip(&spills) {
+			self.emit(format_args!("LDI r{ADDRESS} {spill}"));
+			self.emit(format_args!("LOD r{ADDRESS} r{reg}"));
+		}
+		
+		Ok(result)
+	}
+	
+	fn intrinsic(&mut self, port: &str, intrinsic: Intrinsic, args: &[Expr], span: Span) -> Result<Option<u8>, CompileError> {
+		let result = match intrinsic {
+			Intrinsic::Write => {
+				let reg = self.value(&args[0])?;
+				self.emit(format_args!("LDI r{ADDRESS} {port}"));
+				self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+				self.free(reg);
+				None
+			}
+			Intrinsic::Signal => {
+				self.emit(format_args!("LDI r{ADDRESS} {port}"));
+				self.emit(format_args!("STR r{ADDRESS} r0"));
+				None
+			}
+			Intrinsic::Read => {
+				let reg = self.alloc(span)?;
+				self.emit(format_args!("LDI r{ADDRESS} {port}"));
+				self.emit(format_args!("LOD r{ADDRESS} r{reg}"));
+				Some(reg)
+			}
+			Intrinsic::Pixel { offset, read } => {
+				let x = self.value(&args[0])?;
+				let y = self.value(&args[1])?;
+				
+				self.emit(format_args!("LDI r{ADDRESS} pixel_x"));
+				self.emit(format_args!("STR r{ADDRESS} r{x}"));
+				self.emit(format_args!("STR r{ADDRESS} r{y} 1"));
+				self.free(y);
+				
+				if read {
+					self.emit(format_args!("LOD r{ADDRESS} r{x} {offset}"));
+					Some(x)
+				} else {
+					self.emit(format_args!("STR r{ADDRESS} r0 {offset}"));
+					self.free(x);
+					None
+				}
+			}
+			Intrinsic::Print => {
+				let ExprKind::Str(codes) = &args[0].kind else {
+					return Err(CompileError::ExpectedString { span: args[0].span });
+				};
+				
+				let reg = self.alloc(span)?;
+				let mut previous = None;
+				
+				self.emit(format_args!("LDI r{ADDRESS} write_char"));
+				
+				for &code in codes {
+					if previous != Some(code) {
+						self.emit(format_args!("LDI r{reg} {code}"));
+						previous = Some(code);
+					}
+					
+					self.emit(format_args!("STR r{ADDRESS} r{reg}"));
+				}
+				
+				self.free(reg);
+				None
+			}
+		};
+		
+		Ok(result)
+	}
+	
+	/// Register holding the address of an array element, along with an offset to use with `LOD` or `STR`.
+	fn element(&mut self, name: &Name, index: &Expr) -> Result<(u8, i8), CompileError> {
+		let (slot, len) = match self.lookup(name)? {
+			Symbol::Var { slot, len: Some(len) } => (slot, len),
+			symbol => return Err(wrong_kind(name, &symbol, "an array")),
+		};
+		
+		let reg = match self.constant(index)? {
+			Some(value) if value >= len => {
+				return Err(CompileError::OutOfRange { span: index.span, value: value as u32, min: 0, max: len as u32 - 1 });
+			}
+			Some(value @ 0..=7) => {
+				let reg = self.alloc(index.span)?;
+				self.emit(format_args!("LDI r{reg} {slot}"));
+				return Ok((reg, value as i8));
+			}
+			_ => self.value(index)?,
+		};
+		
+		self.emit(format_args!("ADI r{reg} {slot}"));
+		
+		Ok((reg, 0))
+	}
+	
+	/// Slot of a variable which is not an array.
+	fn scalar(&self, name: &Name) -> Result<String, CompileError> {
+		match self.lookup(name)? {
+			Symbol::Var { slot, len: None } => Ok(slot),
+			symbol => Err(wrong_kind(name, &symbol, "a variable")),
+		}
+	}
+	
+	fn load(&mut self, slot: &str, span: Span) -> Result<u8, CompileError> {
+		let reg = self.alloc(span)?;
+		self.emit(format_args!("LDI r{reg} {slot}"));
+		self.emit(format_args!("LOD r{reg} r{reg}"));
+		Ok(reg)
+	}
+	
+	fn lookup(&self, name: &Name) -> Result<Symbol, CompileError> {
+		self.scopes.iter()
+		           .rev()
+		           .chain([&self.globals])
+		           .find_map(|scope| scope.get(&name.name))
+		           .cloned()
+		           .ok_or_else(|| CompileError::Undefined { span: name.span, name: name.name.clone() })
+	}
+	
+	/// Value of an expression made of literals and constants, arithmetic wraps around like it does at runtime.
+	fn constant(&self, expr: &Expr) -> Result<Option<u8>, CompileError> {
+		let value = match &expr.kind {
+			&ExprKind::Number(value) => match u8::try_from(value) {
+				Ok(value) => value,
+				Err(_) => return Err(CompileError::OutOfRange { span: expr.span, value, min: 0, max: u8::MAX as u32 }),
+			},
+			ExprKind::Name(name) => match self.lookup(&Name { name: name.clone(), span: expr.span })? {
+				Symbol::Const(value) => value,
+				_ => return Ok(None),
+			},
+			ExprKind::Unary(op, operand) => {
+				let Some(value) = self.constant(operand)? else { return Ok(None) };
+				
+				match op {
+					UnaryOp::Neg => value.wrapping_neg(),
+					UnaryOp::Not => !value,
+					UnaryOp::LogicalNot => (value == 0) as u8,
+				}
+			}
+			ExprKind::Binary(op, left, right) => {
+				let (Some(a), Some(b)) = (self.constant(left)?, self.constant(right)?) else { return Ok(None) };
+				
+				match op {
+					BinaryOp::Add => a.wrapping_add(b),
+					BinaryOp::Sub => a.wrapping_sub(b),
+					BinaryOp::And => a & b,
+					BinaryOp::Or => a | b,
+					BinaryOp::Xor => a ^ b,
+					BinaryOp::Shl => a.checked_shl(b as u32).unwrap_or(0),
+					BinaryOp::Shr => a.checked_shr(b as u32).unwrap_or(0),
+					BinaryOp::Eq => (a == b) as u8,
+					BinaryOp::Ne => (a != b) as u8,
+					BinaryOp::Lt => (a < b) as u8,
+					BinaryOp::Le => (a <= b) as u8,
+					BinaryOp::Gt => (a > b) as u8,
+					BinaryOp::Ge => (a >= b) as u8,
+					BinaryOp::LogicalAnd => (a != 0 && b != 0) as u8,
+					BinaryOp::LogicalOr => (a != 0 || b != 0) as u8,
+				}
+			}
+			ExprKind::Str(_) | ExprKind::Index(..) | ExprKind::Call(..) => return Ok(None),
+		};
+		
+		Ok(Some(value))
+	}
+	
+	fn alloc(&mut self, span: Span) -> Result<u8, CompileError> {
+		let reg = TEMPS.into_iter().find(|&reg| self.used & 1 << reg == 0).ok_or(CompileError::OutOfRegisters { span })?;
+		self.used |= 1 << reg;
+		Ok(reg)
+	}
+	
+	/// Releases a temporary, `r0` is ignored.
+	fn free(&mut self, reg: u8) {
+		self.used &= !(1 << reg) | 1;
+	}
+	
+	fn fresh(&mut self, kind: &str) -> String {
+		self.labels += 1;
+		format!(".{}.{kind}{}", self.functions[self.function].name.name, self.labels)
+	}
+	
+	fn emit(&mut self, instruction: fmt::Arguments) {
+		writeln!(self.asm, "  {instruction}").unwrap();
+		self.instructions += 1;
+	}
+	
+	fn label(&mut self, label: &str) {
+		writeln!(self.asm, "{label}").unwrap();
+	}
+}
+
+/// Offset operand of `LOD` and `STR`, left out when it is 0.
+struct Offset(i8);
+
+impl fmt::Display for Offset {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.0 {
+			0 => Ok(()),
+			offset => write!(f, " {offset}"),
+		}
+	}
+}
+
+fn check_name(name: &Name) -> Result<(), CompileError> {
+	if Intrinsic::find(&name.name).is_some() {
+		return Err(CompileError::Reserved { span: name.span, name: name.name.clone() });
+	}
+	
+	Ok(())
+}
+
+fn check_args(name: &Name, expected: usize, args: &[Expr]) -> Result<(), CompileError> {
+	if args.len() != expected {
+		return Err(CompileError::WrongArgumentCount { span: name.span, name: name.name.clone(), expected, got: args.len() });
+	}
+	
+	Ok(())
+}
+
+/// Whether any `return` of a function body carries a value.
+fn returns_value(body: &[Stmt]) -> bool {
+	body.iter().any(|stmt| match stmt {
+		Stmt::Return(value) => value.is_some(),
+		Stmt::If { then, otherwise, .. } => returns_value(then) || returns_value(otherwise),
+		Stmt::While { body, .. } => returns_value(body),
+		_ => false,
+	})
+}
+
+fn wrong_kind(name: &Name, symbol: &Symbol, expected: &'static str) -> CompileError {
+	let found = match symbol {
+		Symbol::Const(_) => "a constant",
+		Symbol::Var { len: None, .. } => "a variable",
+		Symbol::Var { len: Some(_), .. } => "an array",
+		Symbol::Function(_) => "a function",
+	};
+	
+	CompileError::WrongKind { span: name.span, name: name.name.clone(), found, expected }
+}
@@ -0,0 +1,134 @@
+use std::fmt::{self, Display, Formatter};
+
+use batpu2::utils::Char;
+
+use crate::{CompileError, Span};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Token {
+	pub kind: TokenKind,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum TokenKind {
+	/// Identifier or keyword
+	Ident(String),
+	/// Integer or character literal, characters are already encoded
+	Number(u32),
+	/// String literal encoded as characters
+	Str(Vec<u8>),
+	Symbol(&'static str),
+	Eof,
+}
+
+impl Display for TokenKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			TokenKind::Ident(name) => write!(f, "`{name}`"),
+			TokenKind::Number(value) => write!(f, "`{value}`"),
+			TokenKind::Str(_) => write!(f, "string literal"),
+			TokenKind::Symbol(symbol) => write!(f, "`{symbol}`"),
+			TokenKind::Eof => write!(f, "end of file"),
+		}
+	}
+}
+
+/// Longer symbols come first, so `<<=` is not read as `<` followed by `<=`.
+const SYMBOLS: [&str; 35] = [
+	"<<=", ">>=",
+	"==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "+=", "-=", "&=", "|=", "^=", "++", "--",
+	"+", "-", "&", "|", "^", "~", "!", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ",",
+];
+
+/// Splits source code into tokens, `//` starts a comment. The last token is always [`TokenKind::Eof`].
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, CompileError> {
+	let mut tokens = Vec::new();
+	let mut end = Span { line_number: 1, char_number: 1, len: 0 };
+	
+	for (index, line) in source.lines().enumerate() {
+		let line_number = index + 1;
+		let chars: Vec<char> = line.chars().collect();
+		let mut i = 0;
+		
+		end = Span { line_number, char_number: chars.len() + 1, len: 0 };
+		
+		while i < chars.len() {
+			let start = i;
+			let c = chars[i];
+			let span = |end: usize| Span { line_number, char_number: start + 1, len: end - start };
+			
+			if c.is_whitespace() {
+				i += 1;
+				continue;
+			}
+			
+			if c == '/' && chars.get(i + 1) == Some(&'/') {
+				break;
+			}
+			
+			let kind = if c.is_ascii_alphabetic() || c == '_' {
+				while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+					i += 1;
+				}
+				
+				TokenKind::Ident(chars[start..i].iter().collect())
+			} else if c.is_ascii_digit() {
+				while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+					i += 1;
+				}
+				
+				let text: String = chars[start..i].iter().collect();
+				
+				match parse_number(&text) {
+					Some(value) => TokenKind::Number(value),
+					None => return Err(CompileError::InvalidNumber { span: span(i), text }),
+				}
+			} else if c == '\'' || c == '"' {
+				let Some(len) = chars[i + 1..].iter().position(|&end| end == c) else {
+					return Err(CompileError::Unterminated { span: span(chars.len()) });
+				};
+				
+				i += len + 2;
+				
+				let codes = chars[start + 1..i - 1].iter()
+				                                   .map(|&char| encode(char).ok_or(CompileError::InvalidCharacter { span: span(i), char }))
+				                                   .collect::<Result<Vec<_>, _>>()?;
+				
+				match (c, &codes[..]) {
+					('"', _) => TokenKind::Str(codes),
+					(_, &[code]) => TokenKind::Number(code as u32),
+					_ => return Err(CompileError::InvalidCharLiteral { span: span(i) }),
+				}
+			} else if let Some(symbol) = SYMBOLS.into_iter().find(|symbol| symbol.chars().enumerate().all(|(k, s)| chars.get(i + k) == Some(&s))) {
+				i += symbol.len();
+				TokenKind::Symbol(symbol)
+			} else {
+				return Err(CompileError::UnexpectedCharacter { span: span(i + 1), char: c });
+			};
+			
+			tokens.push(Token { kind, span: span(i) });
+		}
+	}
+	
+	tokens.push(Token { kind: TokenKind::Eof, span: end });
+	
+	Ok(tokens)
+}
+
+/// Decimal, `0x` hexadecimal and `0b` binary literals, `_` separates digits.
+fn parse_number(text: &str) -> Option<u32> {
+	let text = text.replace('_', "");
+	let (digits, radix) = match text.get(..2) {
+		Some("0x" | "0X") => (&text[2..], 16),
+		Some("0b" | "0B") => (&text[2..], 2),
+		_ => (&text[..], 10),
+	};
+	
+	u32::from_str_radix(digits, radix).ok()
+}
+
+/// Code of a character on the char display, lowercase letters are shown as uppercase.
+fn encode(char: char) -> Option<u8> {
+	Char::try_from(char.to_ascii_uppercase()).ok().map(Char::as_u8)
+}
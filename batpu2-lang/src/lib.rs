@@ -0,0 +1,475 @@
+//! # batpu2-lang
+//! Compiler for a small structured language targeting the [BatPU-2](https://github.com/mattbatwings/BatPU-2).
+//!
+//! ### The language
+//! Every value is an unsigned byte, arithmetic wraps around and comparisons are unsigned.
+//!
+//! ```text
+//! const SIZE = 8;          // constants are folded at compile time
+//! var grid[SIZE];          // global arrays and variables live in RAM
+//! var count = 1;
+//!
+//! fn fill(value) {
+//!     var i = 0;
+//!     while (i < SIZE) {
+//!         grid[i] = value;
+//!         i++;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     fill('A');
+//!     if (grid[3] == 'A' && count != 0) {
+//!         print("OK");
+//!         buffer_chars();
+//!     } else {
+//!         show_number(grid[3]);
+//!     }
+//! }
+//! ```
+//!
+//! - Items are `const NAME = expr;`, `var x;`, `var x = expr;`, `var a[len];` and `fn name(params) { ... }`,
+//!   the program starts by calling `main`.
+//! - Statements are `var` declarations, assignments (`=`, `+=`, `-=`, `&=`, `|=`, `^=`, `<<=`, `>>=`, `++` and
+//!   `--`), `if`/`else if`/`else`, `while`, `break`, `continue`, `return` and calls.
+//! - Operators from loosest to tightest are `||`, `&&`, comparisons, `|`, `^`, `&`, `<<` and `>>` by a
+//!   constant, `+` and `-`, followed by unary `-`, `~` and `!`.
+//! - Literals are decimal, `0x` hexadecimal, `0b` binary and characters like `'A'`, which are encoded for the
+//!   char display.
+//! - Intrinsics are named after the IO ports of [`DEFAULT_SYMBOLS`](batpu2::asm::DEFAULT_SYMBOLS):
+//!   `draw_pixel(x, y)`, `clear_pixel(x, y)`, `load_pixel(x, y)`, `buffer_screen()`, `clear_screen_buffer()`,
+//!   `write_char(c)`, `buffer_chars()`, `clear_chars_buffer()`, `show_number(n)`, `clear_number()`,
+//!   `signed_mode()`, `unsigned_mode()`, `rng()` and `controller_input()`. `print("TEXT")` writes a string
+//!   literal to the char display.
+//!
+//! ### Code generation
+//! Variables, parameters included, are given fixed addresses in the 240 bytes of RAM. Functions which are
+//! never active at the same time share memory, which is why recursion is rejected. Locals declared without a
+//! value start out undefined. Temporaries are allocated to `r1`-`r14` and saved in memory around calls, `r15`
+//! holds addresses and results are returned in `r1`. Using the result of a function without a `return` of a
+//! value is rejected, a function which only returns a value on some paths leaves it undefined on the others.
+
+#![allow(clippy::result_large_err)]
+
+use std::fmt::{self, Display, Formatter};
+
+use batpu2::isa::{Instruction, MAX_CODE_LEN};
+use batpu2::utils;
+use thiserror::Error;
+
+mod ast;
+mod codegen;
+mod lexer;
+mod parser;
+
+/// Location of an error in the source, lines and characters are numbered from 1.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct Span {
+	pub line_number: usize,
+	pub char_number: usize,
+	/// Length in characters, spans over several lines end with the first one
+	pub len: usize,
+}
+
+impl Span {
+	/// Span from the start of this one to the end of `other`.
+	pub fn to(self, other: Span) -> Span {
+		if other.line_number != self.line_number {
+			return Span { len: usize::MAX, ..self };
+		}
+		
+		Span { len: (other.char_number + other.len).saturating_sub(self.char_number), ..self }
+	}
+}
+
+impl Display for Span {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.line_number, self.char_number)
+	}
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CompileError {
+	#[error("Unexpected character `{char}`")]
+	UnexpectedCharacter {
+		span: Span,
+		char: char,
+	},
+	#[error("Invalid number `{text}`")]
+	InvalidNumber {
+		span: Span,
+		text: String,
+	},
+	#[error("Unterminated literal")]
+	Unterminated {
+		span: Span,
+	},
+	#[error("Character `{char}` cannot be shown on the char display")]
+	InvalidCharacter {
+		span: Span,
+		char: char,
+	},
+	#[error("Character literals hold exactly one character, use double quotes for strings")]
+	InvalidCharLiteral {
+		span: Span,
+	},
+	#[error("Unexpected {found}, expected {expected}")]
+	UnexpectedToken {
+		span: Span,
+		found: String,
+		expected: &'static str,
+	},
+	#[error("Only variables and array elements can be assigned to")]
+	NotAssignable {
+		span: Span,
+	},
+	#[error("Value {value} out of range (min {min}, max {max})")]
+	OutOfRange {
+		span: Span,
+		value: u32,
+		min: u32,
+		max: u32,
+	},
+	#[error("Expected a constant expression")]
+	NotConstant {
+		span: Span,
+	},
+	#[error("`{name}` is not defined")]
+	Undefined {
+		span: Span,
+		name: String,
+	},
+	#[error("`{name}` is already defined")]
+	Duplicate {
+		span: Span,
+		name: String,
+	},
+	#[error("`{name}` is a built-in function")]
+	Reserved {
+		span: Span,
+		name: String,
+	},
+	#[error("`{name}` is {found}, expected {expected}")]
+	WrongKind {
+		span: Span,
+		name: String,
+		found: &'static str,
+		expected: &'static str,
+	},
+	#[error("`{name}` expects {expected} arguments (got {got})")]
+	WrongArgumentCount {
+		span: Span,
+		name: String,
+		expected: usize,
+		got: usize,
+	},
+	#[error("`{name}` does not return a value")]
+	NoValue {
+		span: Span,
+		name: String,
+	},
+	#[error("String literals can only be passed to `print`")]
+	StringLiteral {
+		span: Span,
+	},
+	#[error("`print` expects a string literal")]
+	ExpectedString {
+		span: Span,
+	},
+	#[error("`{keyword}` outside of a loop")]
+	OutsideLoop {
+		span: Span,
+		keyword: &'static str,
+	},
+	#[error("`{name}` may call itself, recursion is not supported")]
+	Recursion {
+		span: Span,
+		name: String,
+	},
+	#[error("Expression is too complex, ran out of registers")]
+	OutOfRegisters {
+		span: Span,
+	},
+	#[error("Variables need {needed} bytes of memory (max 240)")]
+	OutOfMemory {
+		span: Span,
+		needed: usize,
+	},
+	#[error("Missing `fn main()`")]
+	MissingMain {
+		span: Span,
+	},
+	#[error("Program has {len} instructions (max {MAX_CODE_LEN})")]
+	TooManyInstructions {
+		span: Span,
+		len: usize,
+	},
+}
+
+impl CompileError {
+	pub fn span(&self) -> Span {
+		match *self {
+			CompileError::UnexpectedCharacter { span, .. } => span,
+			CompileError::InvalidNumber { span, .. } => span,
+			CompileError::Unterminated { span } => span,
+			CompileError::InvalidCharacter { span, .. } => span,
+			CompileError::InvalidCharLiteral { span } => span,
+			CompileError::UnexpectedToken { span, .. } => span,
+			CompileError::NotAssignable { span } => span,
+			CompileError::OutOfRange { span, .. } => span,
+			CompileError::NotConstant { span } => span,
+			CompileError::Undefined { span, .. } => span,
+			CompileError::Duplicate { span, .. } => span,
+			CompileError::Reserved { span, .. } => span,
+			CompileError::WrongKind { span, .. } => span,
+			CompileError::WrongArgumentCount { span, .. } => span,
+			CompileError::NoValue { span, .. } => span,
+			CompileError::StringLiteral { span } => span,
+			CompileError::ExpectedString { span } => span,
+			CompileError::OutsideLoop { span, .. } => span,
+			CompileError::Recursion { span, .. } => span,
+			CompileError::OutOfRegisters { span } => span,
+			CompileError::OutOfMemory { span, .. } => span,
+			CompileError::MissingMain { span } => span,
+			CompileError::TooManyInstructions { span, .. } => span,
+		}
+	}
+	
+	pub fn line_num(&self) -> usize {
+		self.span().line_number
+	}
+	
+	pub fn col_num(&self) -> usize {
+		self.span().char_number
+	}
+}
+
+/// Compiles a program to assembly text, which can be assembled with [`utils::from_asm`].
+///
+/// Functions start at labels of the same name, variables are `define`d at the end, e.g. `var.score` for a
+/// global and `main.i` for a local of `main`.
+///
+/// # Example
+///
+/// ```
+/// let asm = batpu2_lang::compile_to_asm("
+///     fn main() {
+///         show_number(42);
+///     }
+/// ").unwrap();
+///
+/// assert_eq!(asm, "  CAL .main
+///   HLT
+/// .main
+///   LDI r1 42
+///   LDI r15 show_number
+///   STR r15 r1
+///   RET
+/// ");
+/// ```
+pub fn compile_to_asm(source: &str) -> Result<String, CompileError> {
+	let tokens = lexer::tokenize(source)?;
+	let end = tokens.last().unwrap().span;
+	let items = parser::Parser::new(tokens).program()?;
+	
+	codegen::Codegen::new().program(&items, end)
+}
+
+/// Compiles a program to machine code which runs on [`BatPU2`](batpu2::BatPU2).
+///
+/// # Example
+///
+/// ```
+/// use batpu2::BatPU2;
+///
+/// let code = batpu2_lang::compile("
+///     fn square(x) {
+///         var result = 0;
+///         var i = 0;
+///         while (i < x) {
+///             result += x;
+///             i++;
+///         }
+///         return result;
+///     }
+///
+///     fn main() {
+///         show_number(square(7));
+///     }
+/// ").unwrap();
+///
+/// let mut vm = BatPU2::new(code);
+/// vm.step_multiple(1000);
+///
+/// assert!(vm.halted);
+/// assert_eq!(vm.io.number_display.value, Some(49));
+/// ```
+pub fn compile(source: &str) -> Result<Vec<Instruction>, CompileError> {
+	let asm = compile_to_asm(source)?;
+	
+	Ok(utils::from_asm(&asm).expect("generated assembly is valid"))
+}
+
+#[cfg(test)]
+mod tests {
+	use batpu2::BatPU2;
+	use super::*;
+	
+	fn run(source: &str) -> BatPU2 {
+		let mut vm = BatPU2::new(compile(source).unwrap());
+		vm.step_multiple(100_000);
+		assert!(vm.halted);
+		vm
+	}
+	
+	fn error(source: &str) -> (CompileError, Span) {
+		let error = compile(source).unwrap_err();
+		let span = error.span();
+		(error, span)
+	}
+	
+	#[test]
+	fn arithmetic() {
+		let vm = run("
+			const K = 3;
+			var a = 200;
+			var b;
+			
+			fn main() {
+				b = a + 100;              // wraps to 44
+				b -= K << 2;              // 32
+				b = (b | 5) ^ ~0 & 0x0F;  // 37 ^ 15 = 42
+				b++;
+				b = b >> 1;               // 21
+				show_number(-b + 42);     // 21
+			}
+		");
+		
+		assert_eq!(vm.io.number_display.value, Some(21));
+		assert_eq!(vm.memory[1], 21);
+	}
+	
+	#[test]
+	fn control_flow() {
+		let vm = run("
+			var primes[10];
+			
+			fn is_prime(n) {
+				if (n < 2) {
+					return 0;
+				}
+				var d = 2;
+				while (d < n) {
+					var r = n;
+					while (r >= d) {
+						r -= d;
+					}
+					if (r == 0) {
+						return 0;
+					}
+					d++;
+				}
+				return 1;
+			}
+			
+			fn main() {
+				var n = 0;
+				var count = 0;
+				while (1) {
+					n++;
+					if (!is_prime(n)) {
+						continue;
+					} else if (count == 10) {
+						break;
+					}
+					primes[count] = n;
+					count++;
+				}
+				show_number(primes[9]);
+			}
+		");
+		
+		assert_eq!(&vm.memory[..10], [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+		assert_eq!(vm.io.number_display.value, Some(29));
+	}
+	
+	#[test]
+	fn calls_keep_temporaries() {
+		let vm = run("
+			fn add(a, b) {
+				return a + b;
+			}
+			
+			fn twice(x) {
+				return add(x, x);
+			}
+			
+			fn main() {
+				// Both operands are live while the other side calls
+				show_number(1 + add(twice(2), add(3, 4)) + twice(5) + (add(1, 1) == 2 && 3 > 2));
+			}
+		");
+		
+		assert_eq!(vm.io.number_display.value, Some(1 + 4 + 7 + 10 + 1));
+	}
+	
+	#[test]
+	fn intrinsics() {
+		let vm = run("
+			fn main() {
+				var x = 0;
+				while (x < 4) {
+					draw_pixel(x, x + 1);
+					x++;
+				}
+				clear_pixel(2, 3);
+				buffer_screen();
+				if (load_pixel(1, 2)) {
+					print(\"Hi!\");
+					write_char('?');
+				}
+				buffer_chars();
+			}
+		");
+		
+		let screen = &vm.io.screen;
+		assert!(screen.get_pixel(0, 1) && screen.get_pixel(1, 2) && screen.get_pixel(3, 4));
+		assert!(!screen.get_pixel(2, 3));
+		assert_eq!(vm.io.char_display.to_string().trim_end(), "HI!?");
+	}
+	
+	#[test]
+	fn errors() {
+		let span = |line_number, char_number, len| Span { line_number, char_number, len };
+		
+		assert_eq!(error("fn main() { x = 1; }"), (CompileError::Undefined { span: span(1, 13, 1), name: "x".to_owned() }, span(1, 13, 1)));
+		assert!(matches!(error("fn main() {\n  var x = 256;\n}").0, CompileError::OutOfRange { value: 256, .. }));
+		assert!(matches!(error("fn main() { var a[4]; a[4] = 1; }").0, CompileError::OutOfRange { max: 3, .. }));
+		assert!(matches!(error("fn main() { 1 = 2; }").0, CompileError::NotAssignable { .. }));
+		assert!(matches!(error("fn main() { break; }").0, CompileError::OutsideLoop { keyword: "break", .. }));
+		assert!(matches!(error("fn main() { show_number(clear_number()); }").0, CompileError::NoValue { .. }));
+		assert!(matches!(error("fn v() { } fn main() { var x = v(); }").0, CompileError::NoValue { .. }));
+		assert!(matches!(error("fn main() { var rng; }").0, CompileError::Reserved { .. }));
+		assert!(matches!(error("fn f() { g(); } fn g() { f(); } fn main() { f(); }").0, CompileError::Recursion { .. }));
+		assert!(matches!(error("var a[200]; fn main() { var b[41]; }").0, CompileError::OutOfMemory { needed: 241, .. }));
+		assert!(matches!(error("fn f() {}").0, CompileError::MissingMain { .. }));
+		assert!(matches!(error("fn main() { print('a'); }").0, CompileError::ExpectedString { .. }));
+		assert!(matches!(error("fn main() { var x = 1 << x; }").0, CompileError::Undefined { .. }));
+		assert_eq!(error("fn main() {\n  if (1 {}\n}").1, span(2, 9, 1));
+	}
+	
+	#[test]
+	fn frames_share_memory() {
+		let asm = compile_to_asm("
+			var g;
+			fn a() { var x = 1; }
+			fn b() { var y = 2; }
+			fn main() { var z; a(); b(); }
+		").unwrap();
+		
+		for define in ["define var.g 0", "define main.z 1", "define a.x 2", "define b.y 2"] {
+			assert!(asm.contains(define), "{define} missing from:\n{asm}");
+		}
+	}
+}
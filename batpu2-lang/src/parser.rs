@@ -0,0 +1,308 @@
+use crate::ast::*;
+use crate::lexer::{Token, TokenKind};
+use crate::{CompileError, Span};
+
+const KEYWORDS: [&str; 9] = ["const", "var", "fn", "if", "else", "while", "break", "continue", "return"];
+
+/// Recursive descent parser over the tokens of a whole program.
+pub(crate) struct Parser {
+	tokens: Vec<Token>,
+	position: usize,
+}
+
+impl Parser {
+	pub fn new(tokens: Vec<Token>) -> Self {
+		Self { tokens, position: 0 }
+	}
+	
+	pub fn program(mut self) -> Result<Vec<Item>, CompileError> {
+		let mut items = Vec::new();
+		
+		while self.peek().kind != TokenKind::Eof {
+			items.push(self.item()?);
+		}
+		
+		Ok(items)
+	}
+	
+	fn item(&mut self) -> Result<Item, CompileError> {
+		if self.eat_keyword("const") {
+			let name = self.name()?;
+			self.expect("=", "`=`")?;
+			let value = self.expr()?;
+			self.expect(";", "`;`")?;
+			
+			Ok(Item::Const { name, value })
+		} else if self.eat_keyword("var") {
+			Ok(Item::Var(self.var()?))
+		} else if self.eat_keyword("fn") {
+			let name = self.name()?;
+			let mut params = Vec::new();
+			
+			self.expect("(", "`(`")?;
+			
+			if !self.eat(")") {
+				loop {
+					params.push(self.name()?);
+					
+					if self.eat(")") {
+						break;
+					}
+					
+					self.expect(",", "`,` or `)`")?;
+				}
+			}
+			
+			let body = self.block()?;
+			
+			Ok(Item::Function(Function { name, params, body }))
+		} else {
+			Err(self.unexpected("`const`, `var` or `fn`"))
+		}
+	}
+	
+	/// Declaration after `var`, including the `;`.
+	fn var(&mut self) -> Result<VarDecl, CompileError> {
+		let name = self.name()?;
+		let len = if self.eat("[") {
+			let len = self.expr()?;
+			self.expect("]", "`]`")?;
+			Some(len)
+		} else {
+			None
+		};
+		let value = if len.is_none() && self.eat("=") { Some(self.expr()?) } else { None };
+		
+		self.expect(";", if len.is_some() { "`;`" } else { "`=` or `;`" })?;
+		
+		Ok(VarDecl { name, len, value })
+	}
+	
+	fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+		let mut statements = Vec::new();
+		
+		self.expect("{", "`{`")?;
+		
+		while !self.eat("}") {
+			statements.push(self.stmt()?);
+		}
+		
+		Ok(statements)
+	}
+	
+	fn stmt(&mut self) -> Result<Stmt, CompileError> {
+		let span = self.peek().span;
+		
+		if self.eat_keyword("var") {
+			Ok(Stmt::Var(self.var()?))
+		} else if self.eat_keyword("if") {
+			self.r#if()
+		} else if self.eat_keyword("while") {
+			let condition = self.condition()?;
+			let body = self.block()?;
+			
+			Ok(Stmt::While { condition, body })
+		} else if self.eat_keyword("break") {
+			self.expect(";", "`;`")?;
+			Ok(Stmt::Break(span))
+		} else if self.eat_keyword("continue") {
+			self.expect(";", "`;`")?;
+			Ok(Stmt::Continue(span))
+		} else if self.eat_keyword("return") {
+			let value = if self.eat(";") {
+				None
+			} else {
+				let value = self.expr()?;
+				self.expect(";", "`;`")?;
+				Some(value)
+			};
+			
+			Ok(Stmt::Return(value))
+		} else {
+			let expr = self.expr()?;
+			let statement = match self.peek().kind {
+				TokenKind::Symbol(symbol @ ("=" | "+=" | "-=" | "&=" | "|=" | "^=" | "<<=" | ">>=")) => {
+					self.position += 1;
+					
+					let op = BinaryOp::from_symbol(symbol.trim_end_matches('='));
+					Stmt::Assign { place: place(expr)?, op, value: self.expr()? }
+				}
+				TokenKind::Symbol(symbol @ ("++" | "--")) => {
+					let span = self.next().span;
+					let op = if symbol == "++" { BinaryOp::Add } else { BinaryOp::Sub };
+					
+					Stmt::Assign { place: place(expr)?, op: Some(op), value: Expr { kind: ExprKind::Number(1), span } }
+				}
+				_ => Stmt::Expr(expr),
+			};
+			
+			self.expect(";", "`;`")?;
+			
+			Ok(statement)
+		}
+	}
+	
+	/// `if` statement after the keyword, `else if` chains become nested statements.
+	fn r#if(&mut self) -> Result<Stmt, CompileError> {
+		let condition = self.condition()?;
+		let then = self.block()?;
+		let otherwise = if !self.eat_keyword("else") {
+			Vec::new()
+		} else if self.eat_keyword("if") {
+			vec![self.r#if()?]
+		} else {
+			self.block()?
+		};
+		
+		Ok(Stmt::If { condition, then, otherwise })
+	}
+	
+	/// Parenthesized condition of `if` and `while`.
+	fn condition(&mut self) -> Result<Expr, CompileError> {
+		self.expect("(", "`(`")?;
+		let condition = self.expr()?;
+		self.expect(")", "`)`")?;
+		
+		Ok(condition)
+	}
+	
+	fn expr(&mut self) -> Result<Expr, CompileError> {
+		self.binary(1)
+	}
+	
+	/// Binary operators of at least the given precedence, they are all left associative.
+	fn binary(&mut self, precedence: u8) -> Result<Expr, CompileError> {
+		let mut left = self.unary()?;
+		
+		while let TokenKind::Symbol(symbol) = self.peek().kind {
+			let Some(op) = BinaryOp::from_symbol(symbol).filter(|op| op.precedence() >= precedence) else { break };
+			
+			self.position += 1;
+			
+			let right = self.binary(op.precedence() + 1)?;
+			let span = left.span.to(right.span);
+			
+			left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), span };
+		}
+		
+		Ok(left)
+	}
+	
+	fn unary(&mut self) -> Result<Expr, CompileError> {
+		let op = match self.peek().kind {
+			TokenKind::Symbol("-") => UnaryOp::Neg,
+			TokenKind::Symbol("~") => UnaryOp::Not,
+			TokenKind::Symbol("!") => UnaryOp::LogicalNot,
+			_ => return self.primary(),
+		};
+		
+		let span = self.next().span;
+		let operand = self.unary()?;
+		
+		Ok(Expr { span: span.to(operand.span), kind: ExprKind::Unary(op, Box::new(operand)) })
+	}
+	
+	fn primary(&mut self) -> Result<Expr, CompileError> {
+		let token = self.next();
+		let span = token.span;
+		
+		let kind = match token.kind {
+			TokenKind::Number(value) => ExprKind::Number(value),
+			TokenKind::Str(codes) => ExprKind::Str(codes),
+			TokenKind::Symbol("(") => {
+				let expr = self.expr()?;
+				let end = self.expect(")", "`)`")?;
+				
+				return Ok(Expr { span: span.to(end), ..expr });
+			}
+			TokenKind::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+				let name = Name { name, span };
+				
+				if self.eat("(") {
+					let mut args = Vec::new();
+					
+					let end = loop {
+						if let Some(end) = self.eat_span(")") {
+							break end;
+						}
+						
+						if !args.is_empty() {
+							self.expect(",", "`,` or `)`")?;
+						}
+						
+						args.push(self.expr()?);
+					};
+					
+					return Ok(Expr { kind: ExprKind::Call(name, args), span: span.to(end) });
+				}
+				
+				if self.eat("[") {
+					let index = self.expr()?;
+					let end = self.expect("]", "`]`")?;
+					
+					return Ok(Expr { kind: ExprKind::Index(name, Box::new(index)), span: span.to(end) });
+				}
+				
+				ExprKind::Name(name.name)
+			}
+			kind => {
+				return Err(CompileError::UnexpectedToken { span, found: kind.to_string(), expected: "an expression" });
+			}
+		};
+		
+		Ok(Expr { kind, span })
+	}
+	
+	fn name(&mut self) -> Result<Name, CompileError> {
+		match self.peek().kind.clone() {
+			TokenKind::Ident(name) if !KEYWORDS.contains(&name.as_str()) => Ok(Name { name, span: self.next().span }),
+			_ => Err(self.unexpected("a name")),
+		}
+	}
+	
+	fn peek(&self) -> &Token {
+		&self.tokens[self.position]
+	}
+	
+	/// Returns the current token and advances, the end of file is never passed.
+	fn next(&mut self) -> Token {
+		let token = self.tokens[self.position].clone();
+		self.position = (self.position + 1).min(self.tokens.len() - 1);
+		token
+	}
+	
+	fn eat(&mut self, symbol: &str) -> bool {
+		self.eat_span(symbol).is_some()
+	}
+	
+	fn eat_span(&mut self, symbol: &str) -> Option<Span> {
+		matches!(self.peek().kind, TokenKind::Symbol(s) if s == symbol).then(|| self.next().span)
+	}
+	
+	fn eat_keyword(&mut self, keyword: &str) -> bool {
+		let found = matches!(&self.peek().kind, TokenKind::Ident(name) if name == keyword);
+		
+		if found {
+			self.position += 1;
+		}
+		
+		found
+	}
+	
+	fn expect(&mut self, symbol: &str, expected: &'static str) -> Result<Span, CompileError> {
+		self.eat_span(symbol).ok_or_else(|| self.unexpected(expected))
+	}
+	
+	fn unexpected(&self, expected: &'static str) -> CompileError {
+		let token = self.peek();
+		CompileError::UnexpectedToken { span: token.span, found: token.kind.to_string(), expected }
+	}
+}
+
+fn place(expr: Expr) -> Result<Place, CompileError> {
+	match expr.kind {
+		ExprKind::Name(name) => Ok(Place { name: Name { name, span: expr.span }, index: None }),
+		ExprKind::Index(name, index) => Ok(Place { name, index: Some(*index) }),
+		_ => Err(CompileError::NotAssignable { span: expr.span }),
+	}
+}
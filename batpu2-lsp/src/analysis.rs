@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use batpu2::asm::{self, AsmError, Assembler, Dialect, SyntaxLine, SyntaxTree, Token};
+use batpu2::isa::Instruction;
+
+/// Everything known about a single document, recomputed on every change.
+/// All ranges are byte offsets into the source.
+#[derive(Debug, Default)]
+pub struct Analysis {
+	pub problems: Vec<Problem>,
+	pub definitions: Vec<Definition>,
+	pub references: Vec<Reference>,
+	pub instructions: Vec<InstructionInfo>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Problem {
+	pub range: Range<usize>,
+	pub message: String,
+	/// Earlier definition this problem conflicts with
+	pub previous: Option<Range<usize>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DefinitionKind {
+	Label,
+	Define,
+	Redefine,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Definition {
+	pub name: String,
+	pub kind: DefinitionKind,
+	pub range: Range<usize>,
+	pub line_number: usize,
+	/// Address of a label or value of a constant, if it could be resolved
+	pub value: Option<i16>,
+}
+
+/// Operand which is not a literal, it refers to a user or a built-in symbol.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reference {
+	pub name: String,
+	pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InstructionInfo {
+	/// Range of the mnemonic
+	pub range: Range<usize>,
+	pub line_number: usize,
+	pub address: u16,
+	/// Source of the instruction, without the label and comment
+	pub code: String,
+	/// Assembled instruction, `None` if the line has errors
+	pub instruction: Option<Instruction>,
+}
+
+/// What is being typed at the cursor.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CompletionContext {
+	Mnemonic,
+	Operand,
+	None,
+}
+
+impl Analysis {
+	pub fn new(source: &str, dialect: Dialect) -> Self {
+		let tree = SyntaxTree::parse(source, dialect);
+		let mut analysis = Analysis::default();
+		let mut lines = Vec::new();
+		
+		for line in tree.lines().iter().filter(|line| !line.is_blank()) {
+			analysis.collect_symbols(line, dialect);
+		}
+		
+		// Parsed separately, so that `include`s are expanded
+		for result in asm::parse_lines_with(source, dialect) {
+			match result {
+				Ok(parsed) => lines.push(parsed),
+				Err(err) => analysis.add_problem(&tree, &err),
+			}
+		}
+		
+		let mut assembler = Assembler::new(&lines).dialect(dialect);
+		let mut instructions = HashMap::new();
+		
+		while let Some(result) = assembler.next() {
+			match result {
+				Ok(instruction) => if let Some(line) = assembler.current_line() {
+					instructions.insert(line.line_number, instruction);
+				},
+				Err(err) => analysis.add_problem(&tree, &err),
+			}
+		}
+		
+		for definition in &mut analysis.definitions {
+			if definition.kind != DefinitionKind::Redefine {
+				definition.value = assembler.symbols()
+				                            .get(definition.name.as_str())
+				                            .filter(|symbol| symbol.line_number == definition.line_number)
+				                            .map(|symbol| symbol.value);
+			}
+		}
+		
+		let mut addresses = HashMap::new();
+		let mut address = 0;
+		
+		for line in &lines {
+			if line.mnemonic.is_some_and(|mnemonic| !matches!(mnemonic.span, "define" | "redefine" | "undef" | "export" | "ASSERT")) {
+				addresses.entry(line.line_number).or_insert(address);
+				address += 1;
+			}
+		}
+		
+		for info in &mut analysis.instructions {
+			info.instruction = instructions.get(&info.line_number).copied();
+			info.address = addresses.get(&info.line_number).copied().unwrap_or_default();
+		}
+		
+		analysis
+	}
+	
+	fn add_problem(&mut self, tree: &SyntaxTree, err: &AsmError) {
+		let range = |line_number: usize, token: Token| {
+			let line = &tree.lines()[line_number - 1];
+			let start = line.offset + token.char_number - 1;
+			
+			// Tokens of included files are reported on the `include` line
+			match tree.source().get(start..start + token.len()) {
+				Some(text) if text == token.span => start..start + token.len(),
+				_ => line.range(),
+			}
+		};
+		
+		self.problems.push(Problem {
+			range: range(err.line_num(), err.token()),
+			message: err.to_string(),
+			previous: err.previous_definition().map(|(line_number, token)| range(line_number, token)),
+		});
+	}
+	
+	fn collect_symbols(&mut self, line: &SyntaxLine, dialect: Dialect) {
+		let mut definition = |kind, range: Range<usize>, name: &str, value| self.definitions.push(Definition {
+			name: name.to_owned(),
+			kind,
+			range,
+			line_number: line.line_number,
+			value,
+		});
+		
+		if let Some(label) = line.label() {
+			definition(DefinitionKind::Label, label.range(), label.text, None);
+		}
+		
+		let mut operands = line.operands();
+		
+		match line.mnemonic().map(|mnemonic| mnemonic.text) {
+			Some("define") => if let Some(key) = operands.next() {
+				definition(DefinitionKind::Define, key.range(), key.text, None);
+			},
+			Some("redefine") if dialect == Dialect::Extended => if let Some(key) = operands.next() {
+				let value = line.operands().nth(1).and_then(|value| asm::parse_literal(Token::new(0, value.text)));
+				definition(DefinitionKind::Redefine, key.range(), key.text, value);
+				operands.next();
+			},
+			Some("undef" | "export" | "ASSERT") if dialect == Dialect::Extended => {}
+			Some("include") if dialect == Dialect::Extended => {
+				operands.next();
+			}
+			Some(_) => {
+				let mnemonic = line.mnemonic().unwrap();
+				let end = line.operands().last().unwrap_or(mnemonic).range().end;
+				
+				self.instructions.push(InstructionInfo {
+					range: mnemonic.range(),
+					line_number: line.line_number,
+					address: 0,
+					code: line.tokens
+					          .iter()
+					          .filter(|token| token.offset >= mnemonic.offset && token.range().end <= end)
+					          .map(|token| token.text)
+					          .collect(),
+					instruction: None,
+				});
+			}
+			None => {}
+		}
+		
+		for operand in operands {
+			if asm::parse_literal(Token::new(0, operand.text)).is_none() {
+				self.references.push(Reference { name: operand.text.to_owned(), range: operand.range() });
+			}
+		}
+	}
+	
+	/// Name and range of the symbol at given offset, either a definition or a reference.
+	pub fn symbol_at(&self, offset: usize) -> Option<(&str, Range<usize>)> {
+		let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+		
+		self.definitions
+		    .iter()
+		    .map(|definition| (definition.name.as_str(), &definition.range))
+		    .chain(self.references.iter().map(|reference| (reference.name.as_str(), &reference.range)))
+		    .find(|(_, range)| contains(range))
+		    .map(|(name, range)| (name, range.clone()))
+	}
+	
+	/// Definition visible at given offset: the last one before it, or the first one for forward references.
+	pub fn definition(&self, name: &str, offset: usize) -> Option<&Definition> {
+		let mut definitions = self.definitions.iter().filter(|definition| definition.name == name);
+		let first = definitions.next()?;
+		
+		Some(definitions.rfind(|definition| definition.range.start <= offset).unwrap_or(first))
+	}
+	
+	/// Ranges of all definitions and references of a symbol.
+	pub fn occurrences(&self, name: &str, include_definitions: bool) -> Vec<Range<usize>> {
+		let mut ranges: Vec<_> = self.definitions
+		                             .iter()
+		                             .filter(|_| include_definitions)
+		                             .filter(|definition| definition.name == name)
+		                             .map(|definition| definition.range.clone())
+		                             .chain(self.references
+		                                        .iter()
+		                                        .filter(|reference| reference.name == name)
+		                                        .map(|reference| reference.range.clone()))
+		                             .collect();
+		
+		ranges.sort_by_key(|range| range.start);
+		ranges
+	}
+	
+	pub fn instruction_at(&self, offset: usize) -> Option<&InstructionInfo> {
+		self.instructions
+		    .iter()
+		    .find(|info| info.range.start <= offset && offset <= info.range.end)
+	}
+}
+
+/// Decides what can be completed at the end of `prefix`, which is the part of a line before the cursor.
+pub fn completion_context(prefix: &str, dialect: Dialect) -> CompletionContext {
+	let line = SyntaxLine::parse(0, 0, prefix, dialect);
+	let typing = !prefix.ends_with(char::is_whitespace);
+	let last = line.tokens.iter().rev().find(|token| !token.is_trivia());
+	
+	if line.comment().is_some() {
+		CompletionContext::None
+	} else if line.mnemonic().is_none() || typing && last == line.mnemonic() {
+		match line.label() {
+			Some(label) if typing && last == Some(label) => CompletionContext::None,
+			_ => CompletionContext::Mnemonic,
+		}
+	} else {
+		CompletionContext::Operand
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn analyzes_symbols() {
+		let code = "define limit 3\n.loop ADI r1 1\n  CMP r1 limit\n  BRH nz .loop\nredefine limit 0x10\n  LDI r2 limit\n  JMP .missing\n";
+		let analysis = Analysis::new(code, Dialect::Extended);
+		
+		let names: Vec<_> = analysis.definitions.iter().map(|definition| (definition.name.as_str(), definition.value)).collect();
+		assert_eq!(names, [("limit", Some(3)), (".loop", Some(0)), ("limit", Some(16))]);
+		
+		let loop_reference = code.find(".loop\n").unwrap();
+		let (name, range) = analysis.symbol_at(loop_reference + 2).unwrap();
+		assert_eq!(name, ".loop");
+		assert_eq!(analysis.definition(name, range.start).unwrap().range, 15..20);
+		
+		let limit = code.rfind("limit").unwrap();
+		assert_eq!(analysis.definition("limit", limit).unwrap().kind, DefinitionKind::Redefine);
+		assert_eq!(analysis.occurrences("limit", true).len(), 4);
+		assert_eq!(analysis.occurrences("limit", false).len(), 2);
+		
+		let instruction = analysis.instruction_at(code.find("BRH").unwrap()).unwrap();
+		assert_eq!(instruction.code, "BRH nz .loop");
+		assert_eq!(instruction.address, 2);
+		assert_eq!(instruction.instruction.unwrap().as_word(), 0xB400);
+		
+		assert_eq!(analysis.problems.len(), 1);
+		assert_eq!(&code[analysis.problems[0].range.clone()], ".missing");
+	}
+	
+	#[test]
+	fn expands_includes() {
+		let code = "  CAL .math_mul\n  HLT\ninclude <std/math.asm>\n  JMP .math_divmod\ninclude <std/missing.asm>\n";
+		let analysis = Analysis::new(code, Dialect::Extended);
+		
+		assert!(analysis.references.iter().all(|reference| !reference.name.starts_with('<')));
+		assert!(analysis.instruction_at(code.find("JMP").unwrap()).unwrap().address > 2);
+		
+		assert_eq!(analysis.problems.len(), 1);
+		assert_eq!(&code[analysis.problems[0].range.clone()], "<std/missing.asm>");
+	}
+	
+	#[test]
+	fn completion_contexts() {
+		assert_eq!(completion_context("", Dialect::Extended), CompletionContext::Mnemonic);
+		assert_eq!(completion_context(".loop ", Dialect::Extended), CompletionContext::Mnemonic);
+		assert_eq!(completion_context(".lo", Dialect::Extended), CompletionContext::None);
+		assert_eq!(completion_context("  LD", Dialect::Extended), CompletionContext::Mnemonic);
+		assert_eq!(completion_context("  LDI ", Dialect::Extended), CompletionContext::Operand);
+		assert_eq!(completion_context("  LDI r1 wri", Dialect::Extended), CompletionContext::Operand);
+		assert_eq!(completion_context("  LDI r1 ; wri", Dialect::Extended), CompletionContext::None);
+	}
+}
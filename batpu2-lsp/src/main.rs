@@ -0,0 +1,39 @@
+use anyhow::Result;
+use lsp_server::Connection;
+use lsp_types::{
+	CompletionOptions, HoverProviderCapability, InitializeParams, OneOf, ServerCapabilities, TextDocumentSyncCapability,
+	TextDocumentSyncKind,
+};
+
+mod analysis;
+mod position;
+mod server;
+
+use server::Server;
+
+fn main() -> Result<()> {
+	let (connection, io_threads) = Connection::stdio();
+	
+	let capabilities = ServerCapabilities {
+		text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+		definition_provider: Some(OneOf::Left(true)),
+		references_provider: Some(OneOf::Left(true)),
+		hover_provider: Some(HoverProviderCapability::Simple(true)),
+		completion_provider: Some(CompletionOptions {
+			trigger_characters: Some(vec![".".to_owned()]),
+			..CompletionOptions::default()
+		}),
+		rename_provider: Some(OneOf::Left(true)),
+		..ServerCapabilities::default()
+	};
+	
+	let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+	let params: InitializeParams = serde_json::from_value(params)?;
+	
+	Server::new(&params).run(&connection)?;
+	
+	drop(connection);
+	io_threads.join()?;
+	
+	Ok(())
+}
@@ -0,0 +1,54 @@
+use std::ops::Range;
+
+use lsp_types::Position;
+
+/// Converts between byte offsets and LSP positions, which count UTF-16 code units.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+	starts: Vec<usize>,
+}
+
+impl LineIndex {
+	pub fn new(text: &str) -> Self {
+		let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(pos, _)| pos + 1))
+		                               .collect();
+		
+		Self { starts }
+	}
+	
+	pub fn position(&self, text: &str, offset: usize) -> Position {
+		let line = self.starts.partition_point(|&start| start <= offset) - 1;
+		let character = text[self.starts[line]..offset].encode_utf16().count();
+		
+		Position::new(line as u32, character as u32)
+	}
+	
+	pub fn range(&self, text: &str, range: Range<usize>) -> lsp_types::Range {
+		lsp_types::Range::new(self.position(text, range.start), self.position(text, range.end))
+	}
+	
+	/// Returns the offset of a position, positions past the end of a line are clamped to it.
+	pub fn offset(&self, text: &str, position: Position) -> usize {
+		let Some(&start) = self.starts.get(position.line as usize) else { return text.len() };
+		let line = text[start..].split_inclusive('\n').next().unwrap_or("");
+		let line = line.trim_end_matches(['\n', '\r']);
+		let mut units = 0;
+		
+		for (pos, char) in line.char_indices() {
+			if units >= position.character as usize {
+				return start + pos;
+			}
+			
+			units += char.len_utf16();
+		}
+		
+		start + line.len()
+	}
+	
+	/// Part of the line before the offset.
+	pub fn line_prefix<'t>(&self, text: &'t str, offset: usize) -> &'t str {
+		let line = self.starts.partition_point(|&start| start <= offset) - 1;
+		
+		&text[self.starts[line]..offset]
+	}
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+	DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, References, Rename, Request as _};
+use lsp_types::{
+	CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, Diagnostic, DiagnosticRelatedInformation,
+	DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+	InitializeParams, Location, MarkupContent, MarkupKind, PublishDiagnosticsParams, ReferenceParams, RenameParams,
+	TextDocumentPositionParams, TextEdit, Uri, WorkspaceEdit,
+};
+use batpu2::asm::{self, Dialect, DEFAULT_SYMBOLS};
+use batpu2::isa::Mnemonic;
+
+use crate::analysis::{self, Analysis, CompletionContext, DefinitionKind};
+use crate::position::LineIndex;
+
+pub struct Server {
+	dialect: Dialect,
+	documents: HashMap<Uri, Document>,
+}
+
+struct Document {
+	text: String,
+	index: LineIndex,
+	analysis: Analysis,
+}
+
+impl Server {
+	/// Dialect can be selected using `{ "dialect": "upstream" }` initialization options.
+	pub fn new(params: &InitializeParams) -> Self {
+		let dialect = params.initialization_options
+		                    .as_ref()
+		                    .and_then(|options| options.get("dialect"))
+		                    .and_then(|dialect| dialect.as_str());
+		
+		Self {
+			dialect: match dialect {
+				Some("upstream") => Dialect::Upstream,
+				_ => Dialect::Extended,
+			},
+			documents: HashMap::new(),
+		}
+	}
+	
+	pub fn run(&mut self, connection: &Connection) -> Result<()> {
+		for message in &connection.receiver {
+			match message {
+				Message::Request(request) => {
+					if connection.handle_shutdown(&request)? {
+						return Ok(());
+					}
+					
+					connection.sender.send(self.handle_request(request).into())?;
+				}
+				Message::Notification(notification) => {
+					if let Some(diagnostics) = self.handle_notification(notification) {
+						connection.sender.send(diagnostics.into())?;
+					}
+				}
+				Message::Response(_) => {}
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn handle_request(&self, request: Request) -> Response {
+		match request.method.as_str() {
+			GotoDefinition::METHOD => self.respond(request, Self::goto_definition),
+			References::METHOD => self.respond(request, Self::references),
+			HoverRequest::METHOD => self.respond(request, Self::hover),
+			Completion::METHOD => self.respond(request, Self::completion),
+			Rename::METHOD => self.respond(request, Self::rename),
+			_ => Response::new_err(request.id, ErrorCode::MethodNotFound as i32, format!("Unknown method: {}", request.method)),
+		}
+	}
+	
+	fn respond<P, R>(&self, request: Request, handler: fn(&Self, P) -> Result<R>) -> Response
+	where P: serde::de::DeserializeOwned,
+	      R: serde::Serialize {
+		let id: RequestId = request.id;
+		
+		match serde_json::from_value(request.params) {
+			Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+			Ok(params) => match handler(self, params) {
+				Ok(result) => Response::new_ok(id, result),
+				Err(err) => Response::new_err(id, ErrorCode::RequestFailed as i32, err.to_string()),
+			},
+		}
+	}
+	
+	/// Updates stored documents, returns diagnostics to publish.
+	fn handle_notification(&mut self, notification: Notification) -> Option<Notification> {
+		let (uri, version) = match notification.method.as_str() {
+			DidOpenTextDocument::METHOD => {
+				let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+				self.update(params.text_document.uri.clone(), params.text_document.text);
+				(params.text_document.uri, Some(params.text_document.version))
+			}
+			DidChangeTextDocument::METHOD => {
+				let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+				let text = params.content_changes.into_iter().last()?.text;
+				self.update(params.text_document.uri.clone(), text);
+				(params.text_document.uri, Some(params.text_document.version))
+			}
+			DidCloseTextDocument::METHOD => {
+				let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+				self.documents.remove(&params.text_document.uri);
+				(params.text_document.uri, None)
+			}
+			_ => return None,
+		};
+		
+		let diagnostics = self.documents
+		                      .get(&uri)
+		                      .map_or_else(Vec::new, |document| document.diagnostics(&uri));
+		
+		Some(Notification::new(PublishDiagnostics::METHOD.to_owned(), PublishDiagnosticsParams { uri, diagnostics, version }))
+	}
+	
+	fn update(&mut self, uri: Uri, text: String) {
+		let document = Document {
+			index: LineIndex::new(&text),
+			analysis: Analysis::new(&text, self.dialect),
+			text,
+		};
+		
+		self.documents.insert(uri, document);
+	}
+	
+	fn document(&self, position: &TextDocumentPositionParams) -> Result<(&Document, usize)> {
+		let document = self.documents
+		                   .get(&position.text_document.uri)
+		                   .ok_or_else(|| anyhow!("Unknown document: {}", position.text_document.uri.as_str()))?;
+		
+		Ok((document, document.index.offset(&document.text, position.position)))
+	}
+	
+	fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+		let position = &params.text_document_position_params;
+		let (document, offset) = self.document(position)?;
+		
+		let definition = document.analysis
+		                         .symbol_at(offset)
+		                         .and_then(|(name, _)| document.analysis.definition(name, offset));
+		
+		Ok(definition.map(|definition| GotoDefinitionResponse::Scalar(document.location(&position.text_document.uri, definition.range.clone()))))
+	}
+	
+	fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+		let position = &params.text_document_position;
+		let (document, offset) = self.document(position)?;
+		
+		let Some((name, _)) = document.analysis.symbol_at(offset) else { return Ok(None) };
+		
+		Ok(Some(document.analysis
+		                .occurrences(name, params.context.include_declaration)
+		                .into_iter()
+		                .map(|range| document.location(&position.text_document.uri, range))
+		                .collect()))
+	}
+	
+	fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+		let (document, offset) = self.document(&params.text_document_position_params)?;
+		let analysis = &document.analysis;
+		
+		let (text, range) = if let Some((name, range)) = analysis.symbol_at(offset) {
+			let text = match analysis.definition(name, offset) {
+				Some(definition) => {
+					let line = definition.line_number;
+					match (definition.kind, definition.value) {
+						(DefinitionKind::Label, Some(address)) => format!("`{name}`: label at address {address} (`0x{address:03X}`), line {line}"),
+						(DefinitionKind::Label, None) => format!("`{name}`: label, line {line}"),
+						(_, Some(value)) => format!("`{name}` = {}, defined at line {line}", describe_value(value)),
+						(_, None) => format!("`{name}`: constant, line {line}"),
+					}
+				}
+				None => match asm::default_symbols(name) {
+					Some(value) => format!("`{name}` = {} (built-in)", describe_value(value)),
+					None => return Ok(None),
+				},
+			};
+			
+			(text, range)
+		} else if let Some(info) = analysis.instruction_at(offset) {
+			let text = match info.instruction {
+				Some(instruction) => {
+					let word = instruction.as_word();
+					format!("`{}`\n\naddress {} (`0x{:03X}`)\n\nencoding `{:04b} {:04b} {:04b} {:04b}` (`0x{word:04X}`)",
+					        info.code, info.address, info.address, word >> 12, word >> 8 & 0xF, word >> 4 & 0xF, word & 0xF)
+				}
+				None => format!("`{}`\n\naddress {} (`0x{:03X}`)", info.code, info.address, info.address),
+			};
+			
+			(text, info.range.clone())
+		} else {
+			return Ok(None);
+		};
+		
+		Ok(Some(Hover {
+			contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: text }),
+			range: Some(document.index.range(&document.text, range)),
+		}))
+	}
+	
+	fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+		let (document, offset) = self.document(&params.text_document_position)?;
+		let prefix = document.index.line_prefix(&document.text, offset);
+		
+		let item = |label: &str, kind, detail: Option<String>| CompletionItem {
+			label: label.to_owned(),
+			kind: Some(kind),
+			detail,
+			..CompletionItem::default()
+		};
+		
+		let items = match analysis::completion_context(prefix, self.dialect) {
+			CompletionContext::None => return Ok(None),
+			CompletionContext::Mnemonic => {
+				let directives: &[&str] = match self.dialect {
+					Dialect::Upstream => &["define"],
+					Dialect::Extended => &["define", "redefine", "undef", "export", "include", "ASSERT"],
+				};
+				
+				Mnemonic::ALL.iter()
+				             .map(|mnemonic| item(&mnemonic.to_string(), CompletionItemKind::KEYWORD, None))
+				             .chain(directives.iter().map(|directive| item(directive, CompletionItemKind::KEYWORD, Some("directive".to_owned()))))
+				             .collect()
+			}
+			CompletionContext::Operand => {
+				let mut items: Vec<CompletionItem> = Vec::new();
+				
+				for definition in &document.analysis.definitions {
+					if items.iter().any(|item| item.label == definition.name) {
+						continue;
+					}
+					
+					let kind = match definition.kind {
+						DefinitionKind::Label => CompletionItemKind::REFERENCE,
+						DefinitionKind::Define | DefinitionKind::Redefine => CompletionItemKind::CONSTANT,
+					};
+					
+					items.push(item(&definition.name, kind, definition.value.map(describe_value)));
+				}
+				
+				items.extend(DEFAULT_SYMBOLS.iter().map(|&(name, value)| {
+					let kind = match value {
+						_ if name.starts_with('r') && name[1..].parse::<u8>().is_ok() => CompletionItemKind::VARIABLE,
+						240.. => CompletionItemKind::CONSTANT,
+						_ => CompletionItemKind::ENUM_MEMBER,
+					};
+					
+					item(name, kind, Some(describe_value(value)))
+				}));
+				
+				items
+			}
+		};
+		
+		Ok(Some(CompletionResponse::Array(items)))
+	}
+	
+	fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+		let position = &params.text_document_position;
+		let (document, offset) = self.document(position)?;
+		let new_name = params.new_name.as_str();
+		
+		let Some((name, _)) = document.analysis.symbol_at(offset) else { return Ok(None) };
+		let Some(definition) = document.analysis.definition(name, offset) else { bail!("Built-in symbols cannot be renamed") };
+		
+		if new_name.is_empty() || new_name.contains(|char: char| char.is_whitespace() || matches!(char, ';' | '#' | '/')) {
+			bail!("`{new_name}` is not a valid symbol name");
+		}
+		
+		if (definition.kind == DefinitionKind::Label) != new_name.starts_with('.') {
+			bail!("Labels must start with `.`, other symbols must not");
+		}
+		
+		if asm::default_symbols(new_name).is_some() || asm::parse_literal(asm::Token::new(0, new_name)).is_some() {
+			bail!("`{new_name}` is reserved");
+		}
+		
+		if document.analysis.definitions.iter().any(|definition| definition.name == new_name) {
+			bail!("`{new_name}` is already defined");
+		}
+		
+		let edits = document.analysis
+		                    .occurrences(name, true)
+		                    .into_iter()
+		                    .map(|range| TextEdit::new(document.index.range(&document.text, range), new_name.to_owned()))
+		                    .collect();
+		
+		Ok(Some(WorkspaceEdit {
+			changes: Some(HashMap::from([(position.text_document.uri.clone(), edits)])),
+			..WorkspaceEdit::default()
+		}))
+	}
+}
+
+impl Document {
+	fn location(&self, uri: &Uri, range: std::ops::Range<usize>) -> Location {
+		Location::new(uri.clone(), self.index.range(&self.text, range))
+	}
+	
+	fn diagnostics(&self, uri: &Uri) -> Vec<Diagnostic> {
+		self.analysis
+		    .problems
+		    .iter()
+		    .map(|problem| Diagnostic {
+			    range: self.index.range(&self.text, problem.range.clone()),
+			    severity: Some(DiagnosticSeverity::ERROR),
+			    source: Some("batpu2".to_owned()),
+			    message: problem.message.clone(),
+			    related_information: problem.previous.clone().map(|previous| vec![DiagnosticRelatedInformation {
+				    location: self.location(uri, previous),
+				    message: "previously defined here".to_owned(),
+			    }]),
+			    ..Diagnostic::default()
+		    })
+		    .collect()
+	}
+}
+
+fn describe_value(value: i16) -> String {
+	if value < 0 {
+		value.to_string()
+	} else {
+		format!("{value} (0x{value:02X})")
+	}
+}
@@ -0,0 +1,201 @@
+//! # batpu2-macros
+//! Procedural macros assembling [batpu2](https://docs.rs/batpu2) programs at compile time.
+//!
+//! Both macros expand to an array of [`Instruction`](batpu2::isa::Instruction)s. Prefixing the input
+//! with `u16;` produces machine code words instead, and `upstream;` selects the
+//! [upstream dialect](batpu2::asm::Dialect::Upstream). Assembly errors are reported as compiler errors.
+
+#![allow(clippy::result_large_err)]
+
+use std::path::PathBuf;
+
+use batpu2::asm::{self, AsmError, Assembler, Dialect};
+use batpu2::isa::{Cond, Instruction};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use syn::LitStr;
+
+mod source;
+
+use source::Source;
+
+/// Assembles a program written directly in Rust source.
+///
+/// The program is recovered from the layout of the macro input, so every instruction must be placed on
+/// its own line. Use Rust `//` comments, since `;` comments still have to consist of valid Rust tokens.
+/// A single string literal is accepted as well.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::BatPU2;
+/// use batpu2::isa::Instruction;
+/// use batpu2_macros::batpu_asm;
+///
+/// const PROGRAM: [Instruction; 3] = batpu_asm! {
+///     .loop
+///         ADI r1 1    // count
+///         BRH nc .loop
+///         HLT
+/// };
+///
+/// assert_eq!(PROGRAM[1], Instruction::BRH { cond: batpu2::isa::Cond::NotCarry, addr: 0 });
+///
+/// let words: [u16; 2] = batpu_asm!(u16; "LDI r1 write_char\nHLT");
+/// assert_eq!(words, [0x81F7, 0x1000]);
+///
+/// let mut vm = BatPU2::new(PROGRAM);
+/// vm.step_multiple(1000);
+/// assert!(vm.halted);
+/// ```
+///
+/// Errors point into the macro input:
+///
+/// ```compile_fail
+/// batpu2_macros::batpu_asm! {
+///     LDI r1 .missing
+/// };
+/// ```
+#[proc_macro]
+pub fn batpu_asm(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let (options, input) = match Options::parse(input.into()) {
+		Ok(parsed) => parsed,
+		Err(err) => return err.to_compile_error().into(),
+	};
+	
+	let result = match syn::parse2::<LitStr>(input.clone()) {
+		Ok(literal) => {
+			let code = literal.value();
+			assemble(&code, options.dialect).map_err(|err| syn::Error::new(literal.span(), describe(&err)))
+		}
+		Err(_) => {
+			let source = Source::new(input);
+			assemble(&source.text, options.dialect).map_err(|err| source.error(err.line_num(), err.col_num(), err.token().len(), &err))
+		}
+	};
+	
+	match result {
+		Ok(code) => options.expand(&code).into(),
+		Err(err) => err.to_compile_error().into(),
+	}
+}
+
+/// Assembles a program from a file, the path is relative to `CARGO_MANIFEST_DIR`.
+///
+/// # Example
+///
+/// ```
+/// use batpu2_macros::include_asm;
+///
+/// let code = include_asm!(upstream; u16; "../batpu2/tests/dialect/hello_world.asm");
+///
+/// assert_eq!(code[0], 0x8FF9);
+/// ```
+#[proc_macro]
+pub fn include_asm(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let result = Options::parse(input.into()).and_then(|(options, input)| {
+		let literal: LitStr = syn::parse2(input)?;
+		let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(literal.value());
+		
+		let code = std::fs::read_to_string(&path)
+			.map_err(|err| syn::Error::new(literal.span(), format!("Failed to open \"{}\": {err}", path.display())))?;
+		
+		let instructions = assemble(&code, options.dialect)
+			.map_err(|err| syn::Error::new(literal.span(), format!("{}:{}", path.display(), describe(&err))))?;
+		
+		let path = path.to_string_lossy();
+		let array = options.expand(&instructions);
+		
+		// Makes the compiler rebuild when the file changes
+		Ok(quote! {{
+			const _: &str = include_str!(#path);
+			#array
+		}})
+	});
+	
+	match result {
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into(),
+	}
+}
+
+struct Options {
+	words: bool,
+	dialect: Dialect,
+}
+
+impl Options {
+	/// Parses leading `u16;` and `upstream;` flags, returns the remaining input.
+	fn parse(input: TokenStream) -> syn::Result<(Options, TokenStream)> {
+		let mut options = Options { words: false, dialect: Dialect::Extended };
+		let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+		
+		while let [TokenTree::Ident(flag), TokenTree::Punct(semicolon), ..] = tokens.as_slice() {
+			if semicolon.as_char() != ';' {
+				break;
+			}
+			
+			match flag.to_string().as_str() {
+				"u16" => options.words = true,
+				"upstream" => options.dialect = Dialect::Upstream,
+				_ => break,
+			}
+			
+			tokens.drain(..2);
+		}
+		
+		Ok((options, tokens.into_iter().collect()))
+	}
+	
+	fn expand(&self, code: &[Instruction]) -> TokenStream {
+		if self.words {
+			let words = code.iter().map(|instruction| instruction.as_word());
+			quote! { [#(#words),*] }
+		} else {
+			let instructions = code.iter().map(instruction_tokens);
+			quote! { [#(#instructions),*] }
+		}
+	}
+}
+
+fn assemble(code: &str, dialect: Dialect) -> Result<Vec<Instruction>, AsmError<'_>> {
+	let lines = asm::parse_lines_with(code, dialect).collect::<Result<Vec<_>, _>>()?;
+	
+	Assembler::new(&lines).dialect(dialect).collect()
+}
+
+fn describe(err: &AsmError) -> String {
+	format!("{}:{}: {err}", err.line_num(), err.col_num())
+}
+
+fn instruction_tokens(instruction: &Instruction) -> TokenStream {
+	let variant = match *instruction {
+		Instruction::NOP => quote! { NOP },
+		Instruction::HLT => quote! { HLT },
+		Instruction::ADD { a, b, c } => quote! { ADD { a: #a, b: #b, c: #c } },
+		Instruction::SUB { a, b, c } => quote! { SUB { a: #a, b: #b, c: #c } },
+		Instruction::NOR { a, b, c } => quote! { NOR { a: #a, b: #b, c: #c } },
+		Instruction::AND { a, b, c } => quote! { AND { a: #a, b: #b, c: #c } },
+		Instruction::XOR { a, b, c } => quote! { XOR { a: #a, b: #b, c: #c } },
+		Instruction::RSH { a, c } => quote! { RSH { a: #a, c: #c } },
+		Instruction::LDI { a, imm } => quote! { LDI { a: #a, imm: #imm } },
+		Instruction::ADI { a, imm } => quote! { ADI { a: #a, imm: #imm } },
+		Instruction::JMP { addr } => quote! { JMP { addr: #addr } },
+		Instruction::BRH { cond, addr } => {
+			let cond = match cond {
+				Cond::Zero => quote! { Zero },
+				Cond::NotZero => quote! { NotZero },
+				Cond::Carry => quote! { Carry },
+				Cond::NotCarry => quote! { NotCarry },
+			};
+			
+			quote! { BRH { cond: ::batpu2::isa::Cond::#cond, addr: #addr } }
+		}
+		Instruction::CAL { addr } => quote! { CAL { addr: #addr } },
+		Instruction::RET => quote! { RET },
+		Instruction::LOD { a, b, offset } => quote! { LOD { a: #a, b: #b, offset: #offset } },
+		Instruction::STR { a, b, offset } => quote! { STR { a: #a, b: #b, offset: #offset } },
+	};
+	
+	quote! { ::batpu2::isa::Instruction::#variant }
+}
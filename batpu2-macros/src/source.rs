@@ -0,0 +1,104 @@
+use std::fmt::Display;
+
+use proc_macro2::{Delimiter, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Assembly source recovered from macro input, tokens are placed at their original lines and columns.
+pub struct Source {
+	pub text: String,
+	/// Line number, char number and length of every token in `text`, along with its span
+	tokens: Vec<(usize, usize, usize, Span)>,
+	first_line: Option<usize>,
+	line_number: usize,
+	line_start: usize,
+	column: usize,
+}
+
+impl Source {
+	pub fn new(tokens: TokenStream) -> Self {
+		let mut source = Self {
+			text: String::new(),
+			tokens: Vec::new(),
+			first_line: None,
+			line_number: 1,
+			line_start: 0,
+			column: 1,
+		};
+		
+		source.extend(tokens);
+		source
+	}
+	
+	fn extend(&mut self, tokens: TokenStream) {
+		for token in tokens {
+			match token {
+				TokenTree::Group(group) => {
+					let (open, close) = match group.delimiter() {
+						Delimiter::Parenthesis => ("(", ")"),
+						Delimiter::Brace => ("{", "}"),
+						Delimiter::Bracket => ("[", "]"),
+						Delimiter::None => ("", ""),
+					};
+					
+					self.push(group.span_open(), open);
+					self.extend(group.stream());
+					self.push(group.span_close(), close);
+				}
+				token => self.push(token.span(), &token.to_string()),
+			}
+		}
+	}
+	
+	fn push(&mut self, span: Span, text: &str) {
+		if text.is_empty() {
+			return;
+		}
+		
+		let start = span.unwrap();
+		let first_line = *self.first_line.get_or_insert(start.line());
+		let line_number = start.line().saturating_sub(first_line) + 1;
+		
+		while self.line_number < line_number {
+			self.text.push('\n');
+			self.line_number += 1;
+			self.line_start = self.text.len();
+			self.column = 1;
+		}
+		
+		if start.column() > self.column {
+			let padding = start.column() - self.column;
+			self.text.extend(std::iter::repeat_n(' ', padding));
+			self.column += padding;
+		} else if start.column() < self.column {
+			// Tokens without a real location, e.g. passed through `macro_rules!`
+			self.text.push(' ');
+			self.column += 1;
+		}
+		
+		self.tokens.push((self.line_number, self.text.len() - self.line_start + 1, text.len(), span));
+		self.text.push_str(text);
+		self.column += text.chars().count();
+	}
+	
+	/// Creates an error spanning Rust tokens which make up given part of a line in `text`.
+	pub fn error(&self, line_number: usize, char_number: usize, len: usize, message: impl Display) -> syn::Error {
+		let range = char_number..char_number + len.max(1);
+		let mut spans = self.tokens
+		                    .iter()
+		                    .filter(|&&(line, start, len, _)| line == line_number && start < range.end && range.start < start + len)
+		                    .map(|&(.., span)| span);
+		
+		let Some(first) = spans.next() else { return syn::Error::new(Span::call_site(), message) };
+		let last = spans.next_back().unwrap_or(first);
+		
+		// `new_spanned` covers everything from the first to the last token
+		let tokens: TokenStream = [first, last].into_iter()
+		                                       .map(|span| {
+			                                       let mut punct = Punct::new('.', Spacing::Alone);
+			                                       punct.set_span(span);
+			                                       TokenTree::Punct(punct)
+		                                       })
+		                                       .collect();
+		
+		syn::Error::new_spanned(tokens, message)
+	}
+}
@@ -0,0 +1,55 @@
+use batpu2::{asm, utils, BatPU2};
+use batpu2::isa::Instruction;
+use batpu2_macros::{batpu_asm, include_asm};
+
+#[test]
+fn matches_runtime_assembler() {
+	let code = r"
+	define limit 10
+	.loop
+	  ADI r1 1
+	  CMP r1 limit
+	  BRH ne .loop
+	  LDI r2 show_number
+	  STR r2 r1
+	  HLT";
+	
+	let lines: Vec<_> = asm::parse_lines(code).collect::<Result<_, _>>().unwrap();
+	let expected: Vec<Instruction> = asm::assemble(&lines).collect::<Result<_, _>>().unwrap();
+	
+	let tokens = batpu_asm! {
+		define limit 10
+		.loop
+		  ADI r1 1
+		  CMP r1 limit    // compare
+		  BRH ne .loop
+		  LDI r2 show_number
+		  STR r2 r1
+		  HLT
+	};
+	
+	assert_eq!(tokens, *expected);
+}
+
+#[test]
+fn hello_world() {
+	const CODE: [u16; 26] = include_asm!(upstream; u16; "../batpu2/tests/dialect/hello_world.asm");
+	
+	let expected = std::fs::read_to_string("../batpu2/tests/dialect/hello_world.mc").unwrap();
+	assert_eq!(utils::into_mc(&CODE.map(Instruction::from)), expected);
+	
+	let mut vm = BatPU2::new(CODE);
+	vm.step_multiple(100);
+	assert_eq!(vm.io.char_display.to_string(), "HELLOWORLD");
+	
+	let mut vm = BatPU2::new(batpu_asm! {
+		LDI r1 'A'
+		LDI r2 write_char
+		STR r2 r1
+		LDI r2 buffer_chars
+		STR r2 r0
+		HLT
+	});
+	vm.step_multiple(100);
+	assert_eq!(vm.io.char_display.to_string().trim_end(), "A");
+}
@@ -1,28 +1,57 @@
 use std::collections::HashMap;
 use arrayvec::ArrayVec;
 
-use crate::asm::{AsmError, Token, Line};
+use crate::asm::{AsmError, Dialect, Token, Line};
 use crate::isa::{Instruction, InstructionError, MAX_CODE_LEN, MAX_ARGS, Mnemonic};
+use crate::link::{Relocation, RelocationKind, RelocationTarget};
+use crate::vm::{Assertion, Assertions, Comparison, Subject};
 use crate::utils::Char;
 
 const MAX_ERRORS: usize = 100;
 
+/// Assembles parsed lines into instructions.
+///
+/// Labels and `define`s share a single namespace and may only be defined once. Names of built-in
+/// symbols (registers, conditions, opcodes and IO ports) cannot be `define`d or used as labels. `redefine` and `undef`
+/// can be used to intentionally override or remove a definition for the lines that follow.
+/// `export` marks a label as visible to other modules when [linking](crate::link).
+/// `ASSERT`s are collected into [`Assertions`] keyed by the address of the following instruction.
 pub fn assemble<'l, 'c>(lines: &'l [Line<'c>]) -> impl 'l + Iterator<Item=Result<Instruction, AsmError<'c>>> {
 	Assembler::new(lines)
 }
 
-struct Assembler<'l, 'c> {
+/// Two pass assembler, yields assembled instructions or errors.
+///
+/// See [`assemble`] for the description of symbol handling. In [relocatable](Assembler::relocatable)
+/// mode, label references are recorded as [`Relocation`]s and unknown labels are treated as imports
+/// to be resolved by the [linker](crate::link).
+pub struct Assembler<'l, 'c> {
 	line: usize,
 	lines: &'l [Line<'c>],
 	pc: i16,
 	pc_overflow: bool,
 	errors: usize,
-	symbols: HashMap<&'c str, i16>,
+	symbols: HashMap<&'c str, Symbol<'c>>,
 	symbols_done: bool,
+	overrides: HashMap<&'c str, Option<i16>>,
+	dialect: Dialect,
+	relocatable: bool,
+	relocations: Vec<Relocation>,
+	exports: Vec<(&'c str, u16)>,
+	assertions: Assertions,
+}
+
+/// Label or `define`d constant collected in the first pass.
+#[derive(Debug, Copy, Clone)]
+pub struct Symbol<'c> {
+	pub value: i16,
+	pub label: bool,
+	pub line_number: usize,
+	pub token: Token<'c>,
 }
 
 impl<'l, 'c> Assembler<'l, 'c> {
-	fn new(lines: &'l [Line<'c>]) -> Self {
+	pub fn new(lines: &'l [Line<'c>]) -> Self {
 		Self {
 			line: 0,
 			lines,
@@ -31,43 +60,230 @@ impl<'l, 'c> Assembler<'l, 'c> {
 			errors: 0,
 			symbols: HashMap::new(),
 			symbols_done: false,
+			overrides: HashMap::new(),
+			dialect: Dialect::default(),
+			relocatable: false,
+			relocations: Vec::new(),
+			exports: Vec::new(),
+			assertions: Assertions::new(),
 		}
 	}
 	
+	/// Sets the assembly dialect, [`Dialect::Extended`] by default.
+	pub fn dialect(mut self, dialect: Dialect) -> Self {
+		self.dialect = dialect;
+		self
+	}
+	
+	/// Enables relocatable mode, used to assemble object files.
+	pub fn relocatable(mut self) -> Self {
+		self.relocatable = true;
+		self
+	}
+	
+	/// Relocations recorded so far, only populated in relocatable mode.
+	pub fn relocations(&self) -> &[Relocation] {
+		&self.relocations
+	}
+	
+	/// Labels exported using `export` directive, with their addresses.
+	pub fn exports(&self) -> &[(&'c str, u16)] {
+		&self.exports
+	}
+	
+	/// Assertions collected so far, complete once all instructions are assembled.
+	pub fn assertions(&self) -> &Assertions {
+		&self.assertions
+	}
+	
+	/// Labels and `define`s, complete once the first pass is done. `redefine`s are not included.
+	pub fn symbols(&self) -> &HashMap<&'c str, Symbol<'c>> {
+		&self.symbols
+	}
+	
+	/// Line which produced the most recently yielded instruction or error.
+	pub fn current_line(&self) -> Option<&'l Line<'c>> {
+		self.lines.get(self.line.checked_sub(1)?)
+	}
+	
 	fn define_symbols(&mut self, line: &'l Line<'c>) -> Result<(), AsmError<'c>> {
 		if let Some(label) = line.label {
 			self.check_pc_overflow(line.line_number, label)?;
 			
-			self.symbols.insert(label.span, self.pc);
+			self.insert_symbol(line, label, self.pc, true)?;
 		}
 		
-		if Some("define") == line.mnemonic.as_deref() {
-			let [key, value] = line.args.as_ref()
-			                       .try_into()
-			                       .map_err(|_| AsmError::WrongOperandCount {
-				                       line_number: line.line_number,
-				                       expected: 2..=2,
-				                       args: line.args.clone(),
-				                       mnemonic: line.mnemonic.unwrap()
-			                       })?;
-			
-			let value = value.parse()
-			                 .map_err(|source| AsmError::IntParseError {
-				                 line_number: line.line_number,
-				                 token: value,
-				                 source,
-			                 })?;
-			
-			self.symbols.insert(key.span, value);
-		} else if let Some(mnemonic) = line.mnemonic {
-			self.check_pc_overflow(line.line_number, mnemonic)?;
-			
-			self.pc += 1;
+		match self.directive(line) {
+			Some("define") => {
+				let [key, value] = expect_directive_args(line)?;
+				let value = self.parse_define_value(line, value)?;
+				
+				self.insert_symbol(line, key, value, false)?;
+			}
+			Some("redefine") => {
+				expect_directive_args::<2>(line)?;
+			}
+			Some("ASSERT") => {}
+			Some(_) => {
+				expect_directive_args::<1>(line)?;
+			}
+			None => if let Some(mnemonic) = line.mnemonic {
+				self.check_pc_overflow(line.line_number, mnemonic)?;
+				
+				self.pc += 1;
+			}
+		}
+		
+		Ok(())
+	}
+	
+	/// Returns the name of the directive on this line, if it is supported by the dialect.
+	fn directive(&self, line: &Line<'c>) -> Option<&'c str> {
+		let mnemonic = line.mnemonic?.span;
+		
+		match mnemonic {
+			"define" => Some(mnemonic),
+			"redefine" | "undef" | "export" | "ASSERT" if self.dialect == Dialect::Extended => Some(mnemonic),
+			_ => None,
+		}
+	}
+	
+	/// Upstream only accepts decimal integers, extended dialect accepts any literal valid as an operand.
+	fn parse_define_value(&self, line: &Line<'c>, value: Token<'c>) -> Result<i16, AsmError<'c>> {
+		if self.dialect == Dialect::Extended {
+			if let Some(value) = parse_literal(value) {
+				return Ok(value);
+			}
+		}
+		
+		value.parse()
+		     .map_err(|source| AsmError::IntParseError {
+			     line_number: line.line_number,
+			     token: value,
+			     source,
+		     })
+	}
+	
+	fn insert_symbol(&mut self, line: &Line<'c>, token: Token<'c>, value: i16, label: bool) -> Result<(), AsmError<'c>> {
+		if default_symbols(token.span).is_some() {
+			return Err(AsmError::ReservedSymbol { line_number: line.line_number, token });
 		}
 		
+		if let Some(previous) = self.symbols.get(token.span) {
+			return Err(AsmError::DuplicateSymbol {
+				line_number: line.line_number,
+				token,
+				previous_line_number: previous.line_number,
+				previous: previous.token,
+			});
+		}
+		
+		self.symbols.insert(token.span, Symbol { value, label, line_number: line.line_number, token });
+		
 		Ok(())
 	}
 	
+	/// Applies `redefine` and `undef` directives, which only affect the lines following them.
+	/// `undef` removes user definitions only, built-in symbols become visible again.
+	fn apply_directive(&mut self, line: &Line<'c>) -> Result<(), AsmError<'c>> {
+		let (key, value) = match (line.mnemonic.as_deref(), line.args.as_slice()) {
+			(Some("redefine"), &[key, value]) => (key, Some(self.parse_define_value(line, value)?)),
+			(Some("undef"), &[key]) => (key, None),
+			// Operand count was already reported while defining symbols
+			_ => return Ok(()),
+		};
+		
+		if let Some(label) = self.symbols.get(key.span).filter(|symbol| symbol.label) {
+			return Err(AsmError::RedefinedLabel {
+				line_number: line.line_number,
+				token: key,
+				label_line_number: label.line_number,
+				label: label.token,
+			});
+		}
+		
+		self.overrides.insert(key.span, value);
+		
+		Ok(())
+	}
+	
+	fn export(&mut self, line: &Line<'c>) -> Result<(), AsmError<'c>> {
+		let &[token] = line.args.as_slice() else { return Ok(()) };
+		
+		match self.symbols.get(token.span) {
+			Some(symbol) if symbol.label => {
+				self.exports.push((token.span, symbol.value as u16));
+				Ok(())
+			}
+			_ => Err(AsmError::UnknownSymbol { line_number: line.line_number, token, literal: false }),
+		}
+	}
+	
+	fn assertion(&self, line: &Line<'c>) -> Result<Assertion, AsmError<'c>> {
+		let invalid = |token| AsmError::InvalidAssertion { line_number: line.line_number, token };
+		
+		let (subject, check) = match *line.args.as_slice() {
+			[subject] => (subject, None),
+			[subject, comparison, value] => (subject, Some((comparison, value))),
+			_ => return Err(invalid(line.args.last().copied().unwrap_or(line.mnemonic.unwrap()))),
+		};
+		
+		let subject = match subject.span {
+			"flags.zero" => Subject::Zero,
+			"flags.carry" => Subject::Carry,
+			span => match span.strip_prefix("mem[").and_then(|span| span.strip_suffix(']')) {
+				Some(address) => {
+					let address = self.resolve_token(line, Token::new(subject.char_number + 4, address), true)?;
+					Subject::Mem(u8::try_from(address).ok().filter(|&address| address < 240).ok_or(invalid(subject))?)
+				}
+				None => {
+					let reg = span.strip_prefix('r').and_then(|_| default_symbols(span)).filter(|&reg| reg < 16).ok_or(invalid(subject))?;
+					Subject::Reg(reg as u8)
+				}
+			},
+		};
+		
+		let check = match check {
+			None => None,
+			Some((comparison, value)) => {
+				let comparison = Comparison::try_from(comparison.span).map_err(|_| invalid(comparison))?;
+				let number = self.resolve_token(line, value, true)?;
+				
+				// Comparisons are unsigned, a negative value would silently compare against its two's complement
+				if !(0..=255).contains(&number) {
+					return Err(invalid(value));
+				}
+				
+				Some((comparison, number as u8))
+			}
+		};
+		
+		Ok(Assertion { line_number: line.line_number, subject, check })
+	}
+	
+	fn resolve_operand(&mut self, line: &Line<'c>, mnemonic: Mnemonic, operand: usize, token: Token<'c>) -> Result<i16, AsmError<'c>> {
+		if !self.relocatable {
+			return self.resolve_token(line, token, true);
+		}
+		
+		let (value, target) = match self.resolve_token(line, token, true) {
+			Ok(value) if self.symbols.get(token.span).is_some_and(|symbol| symbol.label) => (value, RelocationTarget::Local),
+			Err(AsmError::UnknownSymbol { .. }) if token.starts_with('.') => (0, RelocationTarget::Symbol(token.span.to_owned())),
+			result => return result,
+		};
+		
+		let kind = match (mnemonic, operand) {
+			(Mnemonic::JMP | Mnemonic::CAL, 0) | (Mnemonic::BRH, 1) => RelocationKind::Addr,
+			(Mnemonic::LDI | Mnemonic::ADI, 1) => RelocationKind::Imm,
+			_ => return Err(AsmError::NotRelocatable { line_number: line.line_number, token }),
+		};
+		
+		// pc already points past the current instruction
+		self.relocations.push(Relocation { address: (self.pc - 1) as u16, kind, target });
+		
+		Ok(value)
+	}
+	
 	fn check_pc_overflow(&mut self, line_number: usize, token: Token<'c>) -> Result<(), AsmError<'c>> {
 		if self.pc as usize >= MAX_CODE_LEN {
 			self.pc_overflow = true;
@@ -79,41 +295,61 @@ impl<'l, 'c> Assembler<'l, 'c> {
 	
 	fn resolve_token(&self, line: &Line, token: Token<'c>, literal: bool) -> Result<i16, AsmError<'c>> {
 		if literal {
-			if let Some(char) = match token.as_bytes() {
-				&[b'"' | b'\'', ref inner @ .., b'"' | b'\''] if !inner.is_empty() && inner.trim_ascii().is_empty() => Char::try_from(' ').ok(),
-				&[b'\'', inner, b'\''] |
-				&[b'"', inner, b'"'] => Char::try_from((inner as char).to_ascii_uppercase()).ok(),
-				_ => None,
-			} {
-				return Ok(char.as_u8() as i16);
-			}
-			
-			if let Some(int) = parse_python_numeric(token.span) {
-				return Ok(int);
+			if let Some(value) = parse_literal(token) {
+				return Ok(value);
 			}
 		}
 		
-		self.symbols
-		    .get(token.span)
-		    .copied()
-		    .or_else(|| default_symbols(token.span))
-		    .ok_or(AsmError::UnknownSymbol {
-			    line_number: line.line_number,
-			    token,
-			    literal,
-		    })
+		let value = match self.overrides.get(token.span) {
+			Some(&value) => value,
+			None => self.symbols
+			            .get(token.span)
+			            .map(|symbol| symbol.value),
+		};
+		
+		value.or_else(|| default_symbols(token.span))
+		     .ok_or(AsmError::UnknownSymbol {
+			line_number: line.line_number,
+			token,
+			literal,
+		})
 	}
 }
 
+pub(super) fn expect_directive_args<'c, const N: usize>(line: &Line<'c>) -> Result<[Token<'c>; N], AsmError<'c>> {
+	line.args.as_slice()
+	    .try_into()
+	    .map_err(|_| AsmError::WrongOperandCount {
+		    line_number: line.line_number,
+		    expected: N..=N,
+		    args: line.args.clone(),
+		    mnemonic: line.mnemonic.unwrap(),
+	    })
+}
+
+/// Parses a character or a numeric literal, as accepted in operands.
+pub fn parse_literal(token: Token) -> Option<i16> {
+	if let Some(char) = match *token.as_bytes() {
+		[b'"' | b'\'', ref inner @ .., b'"' | b'\''] if !inner.is_empty() && inner.trim_ascii().is_empty() => Char::try_from(' ').ok(),
+		[b'\'', inner, b'\''] |
+		[b'"', inner, b'"'] => Char::try_from((inner as char).to_ascii_uppercase()).ok(),
+		_ => None,
+	} {
+		return Some(char.as_u8() as i16);
+	}
+	
+	parse_python_numeric(token.span)
+}
+
 fn parse_python_numeric(token: &str) -> Option<i16> {
 	let (is_negative, num_literal) = token.strip_prefix('-').map_or((false, token), |bytes| (true, bytes));
 	
-	let (num_literal, radix) = match num_literal.as_bytes() {
-		&[b'0', b'x' | b'X', ref rest @ ..] => (rest, 16),
-		&[b'0', b'o' | b'O', ref rest @ ..] => (rest, 8),
-		&[b'0', b'b' | b'B', ref rest @ ..] => (rest, 2),
-		&[b'1'..=b'9', ..] => (num_literal.as_bytes(), 10),
-		&[b'0',          ..] => (num_literal.as_bytes(), 1),
+	let (num_literal, radix) = match *num_literal.as_bytes() {
+		[b'0', b'x' | b'X', ref rest @ ..] => (rest, 16),
+		[b'0', b'o' | b'O', ref rest @ ..] => (rest, 8),
+		[b'0', b'b' | b'B', ref rest @ ..] => (rest, 2),
+		[b'1'..=b'9', ..] => (num_literal.as_bytes(), 10),
+		[b'0',          ..] => (num_literal.as_bytes(), 1),
 		_ => return None,
 	};
 	
@@ -169,6 +405,7 @@ impl<'l, 'c> Iterator for Assembler<'l, 'c> {
 			} else {
 				self.symbols_done = true;
 				self.line = 0;
+				self.pc = 0;
 			}
 		}
 		
@@ -176,17 +413,33 @@ impl<'l, 'c> Iterator for Assembler<'l, 'c> {
 			self.line += 1;
 			
 			if let Some(mnemonic_token) = line.mnemonic {
-				if &mnemonic_token == "define" {
-					continue;
+				if let Some(directive) = self.directive(line) {
+					let result = match directive {
+						"define" => Ok(()),
+						"redefine" | "undef" => self.apply_directive(line),
+						"ASSERT" => self.assertion(line).map(|assertion| self.assertions.insert(self.pc as u16, assertion)),
+						_ => self.export(line),
+					};
+					
+					match result {
+						Ok(()) => continue,
+						Err(err) => {
+							self.errors += 1;
+							return Some(Err(err));
+						}
+					}
 				}
 				
+				self.pc += 1;
+				
 				let mnemonic = match Mnemonic::try_from(&*mnemonic_token) {
 					Ok(mnemonic) => mnemonic,
 					_ => return Some(Err(AsmError::UnknownMnemonic { line_number: line.line_number, token: mnemonic_token })),
 				};
 				
 				let args = match line.args.iter()
-				                          .map(|&token| self.resolve_token(line, token, true))
+				                          .enumerate()
+				                          .map(|(operand, &token)| self.resolve_operand(line, mnemonic, operand, token))
 				                          .collect::<Result<ArrayVec<_, MAX_ARGS>, _>>() {
 					Ok(args) => args,
 					Err(err) => return Some(Err(err)),
@@ -225,7 +478,67 @@ impl<'l, 'c> Iterator for Assembler<'l, 'c> {
 }
 
 
-fn default_symbols(symbol: &str) -> Option<i16> {
+/// Built-in symbols: IO ports, opcodes, registers and branch conditions, as resolved by [`default_symbols`].
+pub const DEFAULT_SYMBOLS: &[(&str, i16)] = &[
+	("pixel_x",             240),
+	("pixel_y",             241),
+	("draw_pixel",          242),
+	("clear_pixel",         243),
+	("load_pixel",          244),
+	("buffer_screen",       245),
+	("clear_screen_buffer", 246),
+	("write_char",          247),
+	("buffer_chars",        248),
+	("clear_chars_buffer",  249),
+	("show_number",         250),
+	("clear_number",        251),
+	("signed_mode",         252),
+	("unsigned_mode",       253),
+	("rng",                 254),
+	("controller_input",    255),
+	
+	("NOP", 0x0),
+	("HLT", 0x1),
+	("ADD", 0x2),
+	("SUB", 0x3),
+	("NOR", 0x4),
+	("AND", 0x5),
+	("XOR", 0x6),
+	("RSH", 0x7),
+	("LDI", 0x8),
+	("ADI", 0x9),
+	("JMP", 0xA),
+	("BRH", 0xB),
+	("CAL", 0xC),
+	("RET", 0xD),
+	("LOD", 0xE),
+	("STR", 0xF),
+	
+	("r0",  0),
+	("r1",  1),
+	("r2",  2),
+	("r3",  3),
+	("r4",  4),
+	("r5",  5),
+	("r6",  6),
+	("r7",  7),
+	("r8",  8),
+	("r9",  9),
+	("r10", 10),
+	("r11", 11),
+	("r12", 12),
+	("r13", 13),
+	("r14", 14),
+	("r15", 15),
+	
+	("eq", 0), ("=",  0), ("z",  0), ("zero",     0),
+	("ne", 1), ("!=", 1), ("nz", 1), ("notzero",  1),
+	("ge", 2), (">=", 2), ("c",  2), ("carry",    2),
+	("lt", 3), ("<",  3), ("nc", 3), ("notcarry", 3),
+];
+
+/// Value of a built-in symbol, see [`DEFAULT_SYMBOLS`].
+pub fn default_symbols(symbol: &str) -> Option<i16> {
 	Some(match symbol {
 		"pixel_x"             => 240,
 		"pixel_y"             => 241,
@@ -286,4 +599,3 @@ fn default_symbols(symbol: &str) -> Option<i16> {
 		_ => return None,
 	})
 }
-
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry as MapEntry;
+use arrayvec::ArrayVec;
+
+use crate::asm::BuildError;
+use crate::isa::{Cond, Instruction, Mnemonic, Operand, Reg, MAX_ARGS, MAX_CODE_LEN};
+
+/// Builds programs from Rust without going through assembly text.
+///
+/// Labels can be referenced before they are defined, they are resolved by [`build`](ProgramBuilder::build).
+/// Operands are range checked the same way as in [`Instruction::new`], errors are reported along with the
+/// address of the offending instruction.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::ProgramBuilder;
+/// use batpu2::isa::{Cond, Instruction, R1, R2};
+///
+/// let mut b = ProgramBuilder::new();
+/// let done = b.fresh_label("done");
+///
+/// b.ldi(R1, 5)
+///  .label("loop")
+///  .dec(R1)
+///  .brh(Cond::Zero, &done)
+///  .jmp("loop")
+///  .label(&done)
+///  .ldi(R2, "loop")
+///  .raw(Instruction::HLT);
+///
+/// let program = b.build_program().unwrap();
+///
+/// assert_eq!(program.code[2], Instruction::BRH { cond: Cond::Zero, addr: 4 });
+/// assert_eq!(program.symbols["loop"], 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+	entries: Vec<Entry>,
+	labels: BTreeMap<String, u16>,
+	error: Option<BuildError>,
+	fresh_labels: usize,
+}
+
+/// Operand value, either a number or an address of a label.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Value {
+	Imm(Operand),
+	Label(String),
+}
+
+/// Program produced by [`ProgramBuilder`], along with addresses of its labels.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Program {
+	pub code: Vec<Instruction>,
+	pub symbols: BTreeMap<String, u16>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+	Raw(Instruction),
+	Op(Mnemonic, ArrayVec<Value, MAX_ARGS>),
+}
+
+impl ProgramBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	
+	/// Address of the next instruction.
+	pub fn address(&self) -> u16 {
+		self.entries.len() as u16
+	}
+	
+	/// Defines a label at the address of the next instruction.
+	pub fn label(&mut self, name: impl Into<String>) -> &mut Self {
+		let address = self.address();
+		
+		match self.labels.entry(name.into()) {
+			MapEntry::Vacant(entry) => {
+				entry.insert(address);
+			}
+			MapEntry::Occupied(entry) => {
+				self.error.get_or_insert(BuildError::DuplicateLabel { label: entry.key().clone() });
+			}
+		}
+		
+		self
+	}
+	
+	/// Returns a new label name which does not collide with other fresh labels.
+	/// The label still has to be defined using [`label`](ProgramBuilder::label).
+	pub fn fresh_label(&mut self, hint: &str) -> String {
+		self.fresh_labels += 1;
+		format!("{hint}@{}", self.fresh_labels - 1)
+	}
+	
+	/// Appends an already constructed instruction.
+	pub fn raw(&mut self, instruction: Instruction) -> &mut Self {
+		self.entries.push(Entry::Raw(instruction));
+		self
+	}
+	
+	/// Appends any instruction, operands are checked when the program is built.
+	pub fn op<V: Into<Value>>(&mut self, mnemonic: Mnemonic, operands: impl IntoIterator<Item=V>) -> &mut Self {
+		let mut values = ArrayVec::new();
+		
+		for operand in operands {
+			if values.try_push(operand.into()).is_err() {
+				let address = self.address();
+				self.error.get_or_insert(BuildError::TooManyOperands { address, mnemonic });
+				break;
+			}
+		}
+		
+		self.entries.push(Entry::Op(mnemonic, values));
+		self
+	}
+	
+	pub fn nop(&mut self) -> &mut Self { self.op(Mnemonic::NOP, [] as [Value; 0]) }
+	pub fn hlt(&mut self) -> &mut Self { self.op(Mnemonic::HLT, [] as [Value; 0]) }
+	pub fn add(&mut self, a: Reg, b: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::ADD, [a, b, c]) }
+	pub fn sub(&mut self, a: Reg, b: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::SUB, [a, b, c]) }
+	pub fn nor(&mut self, a: Reg, b: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::NOR, [a, b, c]) }
+	pub fn and(&mut self, a: Reg, b: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::AND, [a, b, c]) }
+	pub fn xor(&mut self, a: Reg, b: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::XOR, [a, b, c]) }
+	pub fn rsh(&mut self, a: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::RSH, [a, c]) }
+	pub fn ldi(&mut self, a: Reg, imm: impl Into<Value>) -> &mut Self { self.op(Mnemonic::LDI, [Value::from(a), imm.into()]) }
+	pub fn adi(&mut self, a: Reg, imm: impl Into<Value>) -> &mut Self { self.op(Mnemonic::ADI, [Value::from(a), imm.into()]) }
+	pub fn jmp(&mut self, addr: impl Into<Value>) -> &mut Self { self.op(Mnemonic::JMP, [addr]) }
+	pub fn brh(&mut self, cond: Cond, addr: impl Into<Value>) -> &mut Self { self.op(Mnemonic::BRH, [Value::from(cond), addr.into()]) }
+	pub fn cal(&mut self, addr: impl Into<Value>) -> &mut Self { self.op(Mnemonic::CAL, [addr]) }
+	pub fn ret(&mut self) -> &mut Self { self.op(Mnemonic::RET, [] as [Value; 0]) }
+	pub fn lod(&mut self, a: Reg, b: Reg, offset: Operand) -> &mut Self { self.op(Mnemonic::LOD, [Value::from(a), b.into(), offset.into()]) }
+	pub fn str(&mut self, a: Reg, b: Reg, offset: Operand) -> &mut Self { self.op(Mnemonic::STR, [Value::from(a), b.into(), offset.into()]) }
+	
+	pub fn cmp(&mut self, a: Reg, b: Reg) -> &mut Self { self.op(Mnemonic::CMP, [a, b]) }
+	pub fn mov(&mut self, a: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::MOV, [a, c]) }
+	pub fn lsh(&mut self, a: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::LSH, [a, c]) }
+	pub fn inc(&mut self, a: Reg) -> &mut Self { self.op(Mnemonic::INC, [a]) }
+	pub fn dec(&mut self, a: Reg) -> &mut Self { self.op(Mnemonic::DEC, [a]) }
+	pub fn not(&mut self, a: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::NOT, [a, c]) }
+	pub fn neg(&mut self, a: Reg, c: Reg) -> &mut Self { self.op(Mnemonic::NEG, [a, c]) }
+	
+	/// Resolves labels and checks operands, returns the first error found.
+	pub fn build(&self) -> Result<Vec<Instruction>, BuildError> {
+		self.build_program().map(|program| program.code)
+	}
+	
+	pub fn build_program(&self) -> Result<Program, BuildError> {
+		if let Some(err) = &self.error {
+			return Err(err.clone());
+		}
+		
+		if self.entries.len() > MAX_CODE_LEN {
+			return Err(BuildError::TooManyInstructions { len: self.entries.len() });
+		}
+		
+		let code = self.entries
+		               .iter()
+		               .enumerate()
+		               .map(|(address, entry)| self.resolve(address as u16, entry))
+		               .collect::<Result<_, _>>()?;
+		
+		Ok(Program { code, symbols: self.labels.clone() })
+	}
+	
+	fn resolve(&self, address: u16, entry: &Entry) -> Result<Instruction, BuildError> {
+		let (mnemonic, operands) = match entry {
+			Entry::Raw(instruction) => return Ok(*instruction),
+			Entry::Op(mnemonic, operands) => (*mnemonic, operands),
+		};
+		
+		let operands = operands.iter()
+		                       .map(|operand| match operand {
+			                       Value::Imm(value) => Ok(*value),
+			                       Value::Label(label) => self.labels
+			                                                  .get(label)
+			                                                  .map(|&address| address as Operand)
+			                                                  .ok_or_else(|| BuildError::UndefinedLabel { label: label.clone(), address }),
+		                       })
+		                       .collect::<Result<ArrayVec<_, MAX_ARGS>, _>>()?;
+		
+		Instruction::new(mnemonic, operands).map_err(|source| BuildError::InvalidInstruction { address, mnemonic, source })
+	}
+}
+
+impl From<Operand> for Value {
+	fn from(value: Operand) -> Self {
+		Value::Imm(value)
+	}
+}
+
+impl From<Reg> for Value {
+	fn from(value: Reg) -> Self {
+		Value::Imm(value.into())
+	}
+}
+
+impl From<Cond> for Value {
+	fn from(value: Cond) -> Self {
+		Value::Imm(value.into())
+	}
+}
+
+impl From<&str> for Value {
+	fn from(value: &str) -> Self {
+		Value::Label(value.to_owned())
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Self {
+		Value::Label(value)
+	}
+}
+
+impl From<&String> for Value {
+	fn from(value: &String) -> Self {
+		Value::Label(value.clone())
+	}
+}
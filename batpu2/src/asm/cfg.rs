@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::isa::Instruction;
+use crate::opt::walk_body;
+
+/// Control-flow graph of a program.
+///
+/// Blocks cover the whole program, including unreachable code. Every call is assumed to return to the
+/// instruction after it, so the block of a `CAL` also falls through to the next block.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cfg<'a> {
+	pub code: &'a [Instruction],
+	/// Basic blocks in order of address
+	pub blocks: Vec<BasicBlock>,
+	pub edges: Vec<Edge>,
+	/// The start of the program followed by every `CAL` target, in order of address
+	pub functions: Vec<Function>,
+}
+
+/// Instructions executed in sequence, only the first one is a jump target and only the last one jumps.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BasicBlock {
+	pub addresses: Range<u16>,
+}
+
+/// Edge between two blocks, given by their indices.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Edge {
+	pub from: usize,
+	pub to: usize,
+	pub kind: EdgeKind,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EdgeKind {
+	/// Taken `BRH` or `JMP`
+	Branch,
+	/// Execution continues with the next block
+	Fallthrough,
+	/// From a `CAL` to the start of the subroutine
+	Call,
+	/// From a `RET` of a subroutine to the blocks after its calls
+	Return,
+}
+
+/// Subroutine starting at a `CAL` target, or the program starting at address 0.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Function {
+	pub entry: u16,
+	/// Indices of blocks reachable from the entry without following calls, a block may belong to more than
+	/// one function
+	pub blocks: Vec<usize>,
+}
+
+impl<'a> Cfg<'a> {
+	/// Splits a program into basic blocks and connects them.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::asm::{Cfg, Edge, EdgeKind};
+	/// use batpu2::utils;
+	///
+	/// let code = utils::from_asm("
+	///   LDI r1 3
+	/// .loop
+	///   DEC r1
+	///   BRH nz .loop
+	///   HLT
+	/// ").unwrap();
+	///
+	/// let cfg = Cfg::new(&code);
+	///
+	/// assert_eq!(cfg.blocks.iter().map(|block| block.addresses.clone()).collect::<Vec<_>>(), [0..1, 1..3, 3..4]);
+	/// assert!(cfg.edges.contains(&Edge { from: 1, to: 1, kind: EdgeKind::Branch }));
+	/// assert!(cfg.edges.contains(&Edge { from: 1, to: 2, kind: EdgeKind::Fallthrough }));
+	/// ```
+	pub fn new(code: &'a [Instruction]) -> Self {
+		let len = code.len() as u16;
+		let mut leaders = BTreeSet::from([0]);
+		
+		for (address, &instruction) in code.iter().enumerate() {
+			let address = address as u16;
+			
+			match instruction {
+				Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr } => {
+					leaders.extend([addr, address + 1]);
+				}
+				Instruction::HLT | Instruction::RET => {
+					leaders.insert(address + 1);
+				}
+				_ => {}
+			}
+		}
+		
+		let leaders: Vec<u16> = leaders.into_iter().filter(|&address| address < len).collect();
+		let blocks: Vec<BasicBlock> = leaders.iter()
+		                                     .zip(leaders.iter().skip(1).copied().chain([len]))
+		                                     .map(|(&start, end)| BasicBlock { addresses: start..end })
+		                                     .collect();
+		
+		let mut cfg = Cfg { code, blocks, edges: Vec::new(), functions: Vec::new() };
+		let mut edges = Vec::new();
+		// Blocks after the calls of every subroutine
+		let mut calls: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+		
+		for (index, block) in cfg.blocks.iter().enumerate() {
+			let last = block.addresses.end - 1;
+			let next = cfg.block_at(block.addresses.end);
+			let mut edge = |to: Option<usize>, kind| edges.extend(to.map(|to| Edge { from: index, to, kind }));
+			
+			match code[last as usize] {
+				Instruction::HLT | Instruction::RET => {}
+				Instruction::JMP { addr } => edge(cfg.block_at(addr), EdgeKind::Branch),
+				Instruction::BRH { addr, .. } => {
+					edge(cfg.block_at(addr), EdgeKind::Branch);
+					edge(next, EdgeKind::Fallthrough);
+				}
+				Instruction::CAL { addr } => {
+					edge(cfg.block_at(addr), EdgeKind::Call);
+					edge(next, EdgeKind::Fallthrough);
+					calls.entry(addr).or_default().extend(next);
+				}
+				_ => edge(next, EdgeKind::Fallthrough),
+			}
+		}
+		
+		cfg.edges = edges;
+		
+		for entry in [0].into_iter().chain(calls.keys().copied()).collect::<BTreeSet<_>>() {
+			let Some(start) = cfg.block_at(entry) else { continue };
+			let blocks = cfg.body(start);
+			let returns: Vec<usize> = blocks.iter().copied().filter(|&block| cfg.ends_with_ret(block)).collect();
+			
+			for &after in calls.get(&entry).into_iter().flatten() {
+				cfg.edges.extend(returns.iter().map(|&block| Edge { from: block, to: after, kind: EdgeKind::Return }));
+			}
+			
+			cfg.functions.push(Function { entry, blocks });
+		}
+		
+		cfg
+	}
+	
+	/// Index of the block containing given address.
+	pub fn block_at(&self, address: u16) -> Option<usize> {
+		let index = self.blocks.partition_point(|block| block.addresses.end <= address);
+		self.blocks.get(index).filter(|block| block.addresses.contains(&address)).map(|_| index)
+	}
+	
+	/// Instructions of a block along with their addresses.
+	pub fn instructions(&self, block: usize) -> impl Iterator<Item=(u16, Instruction)> + '_ {
+		self.blocks[block].addresses.clone().map(|address| (address, self.code[address as usize]))
+	}
+	
+	/// Edges leaving a block.
+	pub fn successors(&self, block: usize) -> impl Iterator<Item=&Edge> {
+		self.edges.iter().filter(move |edge| edge.from == block)
+	}
+	
+	/// Edges entering a block.
+	pub fn predecessors(&self, block: usize) -> impl Iterator<Item=&Edge> {
+		self.edges.iter().filter(move |edge| edge.to == block)
+	}
+	
+	/// Renders the graph in Graphviz DOT format, blocks are grouped by function and show their
+	/// disassembly with label names.
+	pub fn to_dot<'b>(&self, labels: impl IntoIterator<Item=(&'b str, u16)>) -> String {
+		let mut names: BTreeMap<u16, Vec<&str>> = BTreeMap::new();
+		
+		for (name, address) in labels {
+			names.entry(address).or_default().push(name);
+		}
+		
+		names.values_mut().for_each(|names| names.sort_unstable());
+		
+		let name = |address: u16| match names.get(&address) {
+			Some(names) => names[0].to_owned(),
+			None if address == 0 => "(start)".to_owned(),
+			None => format!("0x{address:03X}"),
+		};
+		
+		let mut dot = String::new();
+		
+		writeln!(dot, "digraph cfg {{").unwrap();
+		writeln!(dot, "\tnode [shape=box, fontname=\"monospace\"];").unwrap();
+		
+		// Blocks shared by several functions are drawn in the first one
+		let mut drawn = vec![false; self.blocks.len()];
+		let draw_block = |dot: &mut String, block: usize, indent: &str| {
+			let mut label = String::new();
+			
+			for (address, instruction) in self.instructions(block) {
+				for name in names.get(&address).into_iter().flatten() {
+					write!(label, "{name}\\l").unwrap();
+				}
+				
+				let instruction = match instruction {
+					Instruction::JMP { addr } => format!("JMP {}", name(addr)),
+					Instruction::BRH { cond, addr } => format!("BRH {cond} {}", name(addr)),
+					Instruction::CAL { addr } => format!("CAL {}", name(addr)),
+					instruction => instruction.to_string(),
+				};
+				
+				write!(label, "{address:03X}  {}\\l", escape(&instruction)).unwrap();
+			}
+			
+			writeln!(dot, "{indent}b{block} [label=\"{label}\"];").unwrap();
+		};
+		
+		for (index, function) in self.functions.iter().enumerate() {
+			writeln!(dot, "\tsubgraph cluster_{index} {{").unwrap();
+			writeln!(dot, "\t\tlabel=\"{}\";", escape(&name(function.entry))).unwrap();
+			
+			for &block in &function.blocks {
+				if !std::mem::replace(&mut drawn[block], true) {
+					draw_block(&mut dot, block, "\t\t");
+				}
+			}
+			
+			writeln!(dot, "\t}}").unwrap();
+		}
+		
+		for (block, _) in drawn.iter().enumerate().filter(|(_, &drawn)| !drawn) {
+			draw_block(&mut dot, block, "\t");
+		}
+		
+		for edge in &self.edges {
+			let style = match edge.kind {
+				EdgeKind::Branch => match self.code[self.blocks[edge.from].addresses.end as usize - 1] {
+					Instruction::BRH { cond, .. } => format!(" [label=\"{cond}\"]"),
+					_ => String::new(),
+				},
+				EdgeKind::Fallthrough => String::new(),
+				EdgeKind::Call => " [style=dashed, color=blue]".to_owned(),
+				EdgeKind::Return => " [style=dotted, color=gray]".to_owned(),
+			};
+			
+			writeln!(dot, "\tb{} -> b{}{style};", edge.from, edge.to).unwrap();
+		}
+		
+		writeln!(dot, "}}").unwrap();
+		
+		dot
+	}
+	
+	/// Blocks reachable from the given one without following calls.
+	fn body(&self, start: usize) -> Vec<usize> {
+		// Blocks end at every jump, so the body always covers whole blocks
+		let body = walk_body(self.code, self.blocks[start].addresses.start, |_, _| {});
+		
+		(0..self.blocks.len()).filter(|&block| body[self.blocks[block].addresses.start as usize]).collect()
+	}
+	
+	fn ends_with_ret(&self, block: usize) -> bool {
+		self.code[self.blocks[block].addresses.end as usize - 1] == Instruction::RET
+	}
+}
+
+/// Escapes a string for use inside a quoted DOT identifier.
+fn escape(text: &str) -> String {
+	text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,223 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use crate::asm::{AsmError, Dialect, Line, Token};
+use crate::isa::MAX_ARGS;
+use crate::asm::parser::{find_comment, tokenize};
+
+/// Lossless concrete syntax tree of an assembly source.
+///
+/// Every byte of the source belongs to exactly one [`SyntaxToken`], including whitespace, comments and
+/// line terminators, so the tree prints back to the exact original text. Lines can be converted into
+/// [`Line`]s used by the assembler.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::{Dialect, SyntaxKind, SyntaxTree};
+///
+/// let code = ".loop  JMP .loop ; forever\r\n\n";
+/// let tree = SyntaxTree::parse(code, Dialect::Extended);
+///
+/// assert_eq!(tree.to_string(), code);
+/// assert_eq!(tree.lines().len(), 2);
+///
+/// let comment = tree.lines()[0].comment().unwrap();
+/// assert_eq!(comment.kind, SyntaxKind::Comment);
+/// assert_eq!(comment.range(), 17..26);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyntaxTree<'a> {
+	source: &'a str,
+	lines: Vec<SyntaxLine<'a>>,
+}
+
+/// Single source line, including its line terminator.
+#[derive(Debug, Clone)]
+pub struct SyntaxLine<'a> {
+	pub line_number: usize,
+	/// Byte offset of the start of the line in the source
+	pub offset: usize,
+	pub tokens: Vec<SyntaxToken<'a>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SyntaxToken<'a> {
+	pub kind: SyntaxKind,
+	/// Byte offset of the token in the source
+	pub offset: usize,
+	pub text: &'a str,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SyntaxKind {
+	Whitespace,
+	Newline,
+	Comment,
+	Label,
+	Mnemonic,
+	Operand,
+}
+
+impl<'a> SyntaxTree<'a> {
+	pub fn parse(source: &'a str, dialect: Dialect) -> Self {
+		Self {
+			source,
+			lines: parse_syntax_lines(source, dialect).collect(),
+		}
+	}
+	
+	pub fn source(&self) -> &'a str {
+		self.source
+	}
+	
+	pub fn lines(&self) -> &[SyntaxLine<'a>] {
+		&self.lines
+	}
+	
+	pub fn tokens(&self) -> impl Iterator<Item=&SyntaxToken<'a>> {
+		self.lines.iter().flat_map(|line| &line.tokens)
+	}
+	
+	/// Returns the token containing given byte offset.
+	pub fn token_at(&self, offset: usize) -> Option<&SyntaxToken<'a>> {
+		self.tokens().find(|token| token.range().contains(&offset))
+	}
+}
+
+/// Lazily splits the source into [`SyntaxLine`]s.
+pub fn parse_syntax_lines(source: &str, dialect: Dialect) -> impl Iterator<Item=SyntaxLine<'_>> {
+	source.split_inclusive('\n')
+	      .scan(0, |offset, line| {
+		      let start = *offset;
+		      *offset += line.len();
+		      Some((start, line))
+	      })
+	      .enumerate()
+	      .map(move |(id, (offset, line))| SyntaxLine::parse(id + 1, offset, line, dialect))
+}
+
+impl<'a> SyntaxLine<'a> {
+	/// Parses a single line, `text` may end with a line terminator.
+	pub fn parse(line_number: usize, offset: usize, text: &'a str, dialect: Dialect) -> Self {
+		let content = text.strip_suffix('\n').map_or(text, |text| text.strip_suffix('\r').unwrap_or(text));
+		let (code, comment) = match find_comment(content, dialect) {
+			Some(pos) => (&content[..pos], Some(pos)),
+			None => (content, None),
+		};
+		
+		let mut tokens = Vec::new();
+		let mut pos = 0;
+		let mut push = |kind, start: usize, end: usize| tokens.push(SyntaxToken { kind, offset: offset + start, text: &text[start..end] });
+		
+		let mut mnemonic = false;
+		
+		for (id, token) in tokenize(code).enumerate() {
+			let start = token.char_number - 1;
+			
+			if start > pos {
+				push(SyntaxKind::Whitespace, pos, start);
+			}
+			
+			let kind = if id == 0 && token.starts_with('.') {
+				SyntaxKind::Label
+			} else if !mnemonic {
+				mnemonic = true;
+				SyntaxKind::Mnemonic
+			} else {
+				SyntaxKind::Operand
+			};
+			
+			push(kind, start, start + token.len());
+			pos = start + token.len();
+		}
+		
+		if code.len() > pos {
+			push(SyntaxKind::Whitespace, pos, code.len());
+		}
+		
+		if let Some(start) = comment {
+			push(SyntaxKind::Comment, start, content.len());
+		}
+		
+		if text.len() > content.len() {
+			push(SyntaxKind::Newline, content.len(), text.len());
+		}
+		
+		Self { line_number, offset, tokens }
+	}
+	
+	pub fn label(&self) -> Option<&SyntaxToken<'a>> {
+		self.find(SyntaxKind::Label)
+	}
+	
+	pub fn mnemonic(&self) -> Option<&SyntaxToken<'a>> {
+		self.find(SyntaxKind::Mnemonic)
+	}
+	
+	pub fn operands(&self) -> impl Iterator<Item=&SyntaxToken<'a>> {
+		self.tokens.iter().filter(|token| token.kind == SyntaxKind::Operand)
+	}
+	
+	pub fn comment(&self) -> Option<&SyntaxToken<'a>> {
+		self.find(SyntaxKind::Comment)
+	}
+	
+	/// Returns `true` if the line contains only whitespace.
+	pub fn is_blank(&self) -> bool {
+		self.tokens.iter().all(|token| token.is_trivia() && token.kind != SyntaxKind::Comment)
+	}
+	
+	/// Byte range of the line in the source, including the line terminator.
+	pub fn range(&self) -> Range<usize> {
+		self.offset..self.offset + self.tokens.iter().map(|token| token.text.len()).sum::<usize>()
+	}
+	
+	/// Builds the [`Line`] used by the assembler.
+	pub fn to_line(&self) -> Result<Line<'a>, AsmError<'a>> {
+		let token = |token: &SyntaxToken<'a>| Token::new(token.offset - self.offset + 1, token.text);
+		
+		let mut operands = self.operands().map(token);
+		let args = (&mut operands).take(MAX_ARGS).collect();
+		
+		if let Some(token) = operands.next() {
+			return Err(AsmError::TooManyTokens { line_number: self.line_number, token });
+		}
+		
+		Ok(Line {
+			line_number: self.line_number,
+			label: self.label().map(token),
+			mnemonic: self.mnemonic().map(token),
+			args,
+			comment: self.comment().map(|comment| Token::new(comment.offset - self.offset, comment.text)),
+		})
+	}
+	
+	fn find(&self, kind: SyntaxKind) -> Option<&SyntaxToken<'a>> {
+		self.tokens.iter().find(|token| token.kind == kind)
+	}
+}
+
+impl SyntaxToken<'_> {
+	/// Byte range of the token in the source.
+	pub fn range(&self) -> Range<usize> {
+		self.offset..self.offset + self.text.len()
+	}
+	
+	/// Returns `true` for tokens without meaning to the assembler: whitespace, comments and line terminators.
+	pub fn is_trivia(&self) -> bool {
+		matches!(self.kind, SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+	}
+}
+
+impl Display for SyntaxTree<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		self.lines.iter().try_for_each(|line| line.fmt(f))
+	}
+}
+
+impl Display for SyntaxLine<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		self.tokens.iter().try_for_each(|token| f.write_str(token.text))
+	}
+}
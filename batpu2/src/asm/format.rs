@@ -0,0 +1,129 @@
+use std::fmt::Write;
+
+use crate::asm::{AsmError, Dialect, Line, parse_syntax_lines};
+use crate::isa::{Mnemonic, MAX_ARGS};
+
+/// Layout settings used by [`format`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FormatOptions {
+	/// Column at which mnemonics start, labels always start at column 0
+	pub indent: usize,
+	pub dialect: Dialect,
+}
+
+impl Default for FormatOptions {
+	fn default() -> Self {
+		Self {
+			indent: 8,
+			dialect: Dialect::default(),
+		}
+	}
+}
+
+/// Rewrites assembly source into a canonical layout.
+///
+/// Labels are placed at column 0 and mnemonics at [`FormatOptions::indent`]. Mnemonics are upper-cased,
+/// while operands are kept as written. Within each group of lines separated by blank lines, operands
+/// and trailing comments are aligned into columns. Blank lines and comments are preserved.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::{format, FormatOptions};
+///
+/// let code = "
+/// .loop ldi r1 0x0F ; load
+///   adi r1 -1  # decrement
+///     brh nz .loop
+/// ";
+///
+/// assert_eq!(format(code, &FormatOptions::default()).unwrap(), "
+/// .loop   LDI r1 0x0F  ; load
+///         ADI r1 -1    # decrement
+///         BRH nz .loop
+/// ");
+/// ```
+pub fn format<'c>(code: &'c str, options: &FormatOptions) -> Result<String, AsmError<'c>> {
+	let lines = parse_syntax_lines(code, options.dialect).map(|line| line.to_line())
+	                                                     .collect::<Result<Vec<_>, _>>()?;
+	
+	let mut output = String::with_capacity(code.len());
+	
+	for (id, block) in lines.split(is_blank).enumerate() {
+		if id > 0 {
+			output.push('\n');
+		}
+		
+		format_block(&mut output, block, options);
+	}
+	
+	Ok(output)
+}
+
+fn is_blank(line: &Line) -> bool {
+	line.label.is_none() && line.mnemonic.is_none() && line.comment.is_none()
+}
+
+fn format_block(output: &mut String, block: &[Line], options: &FormatOptions) {
+	let mnemonic_width = block.iter()
+	                          .filter_map(|line| line.mnemonic)
+	                          .map(|mnemonic| mnemonic.len())
+	                          .max()
+	                          .unwrap_or(0);
+	
+	// The last operand of a line is not padded, so it does not widen its column
+	let mut arg_widths = [0; MAX_ARGS];
+	for line in block {
+		let padded = line.args.len().saturating_sub(1);
+		for (width, arg) in arg_widths.iter_mut().zip(&line.args[..padded]) {
+			*width = (*width).max(arg.len());
+		}
+	}
+	
+	let code: Vec<String> = block.iter()
+	                             .map(|line| format_code(line, mnemonic_width, &arg_widths, options))
+	                             .collect();
+	
+	let comment_column = code.iter()
+	                         .map(String::len)
+	                         .max()
+	                         .unwrap_or(0) + 1;
+	
+	for (line, code) in block.iter().zip(code) {
+		match line.comment {
+			Some(comment) if code.is_empty() => {
+				let indent = if comment.char_number == 0 { 0 } else { options.indent };
+				write!(output, "{:indent$}{}", "", comment.trim_end()).unwrap();
+			}
+			Some(comment) => write!(output, "{code:comment_column$}{}", comment.trim_end()).unwrap(),
+			None => output.push_str(&code),
+		}
+		
+		output.push('\n');
+	}
+}
+
+fn format_code(line: &Line, mnemonic_width: usize, arg_widths: &[usize], options: &FormatOptions) -> String {
+	let mut code = String::new();
+	
+	if let Some(label) = line.label {
+		code.push_str(&label);
+	}
+	
+	if let Some(mnemonic) = line.mnemonic {
+		let padding = options.indent.saturating_sub(code.len()).max(if code.is_empty() { 0 } else { 1 });
+		let mnemonic = match Mnemonic::try_from(&*mnemonic) {
+			Ok(_) => mnemonic.to_ascii_uppercase(),
+			Err(_) => mnemonic.to_string(),
+		};
+		
+		write!(code, "{:padding$}{mnemonic:mnemonic_width$}", "").unwrap();
+		
+		for (arg, width) in line.args.iter().zip(arg_widths) {
+			write!(code, " {arg:width$}").unwrap();
+		}
+	}
+	
+	code.truncate(code.trim_end().len());
+	code
+}
@@ -4,15 +4,45 @@ use arrayvec::ArrayVec;
 use thiserror::Error;
 
 mod ast;
+mod cst;
 mod parser;
 mod assembler;
+mod format;
+mod builder;
+mod stdlib;
+mod xref;
+mod cfg;
 
 pub use ast::*;
+pub use cst::*;
 pub use parser::*;
 pub use assembler::*;
-use crate::isa::{MAX_ARGS, MAX_CODE_LEN};
+pub use format::*;
+pub use builder::*;
+pub use stdlib::*;
+pub use xref::*;
+pub use cfg::*;
+use crate::isa::{InstructionError, Mnemonic, MAX_ARGS, MAX_CODE_LEN};
 use crate::utils::PrettyRange;
 
+/// Flavour of the assembly language accepted by the parser and the assembler.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Dialect {
+	/// Matches the original BatPU-2 Python assembler:
+	/// - `/`, `;` and `#` start a comment,
+	/// - `define` values are decimal integers,
+	/// - only the `define` directive is available.
+	Upstream,
+	/// Extends the upstream dialect:
+	/// - `/`, `;` and `#` start a comment, except for the `/` of `<std/...>` paths,
+	/// - `define` values accept the same literals as operands (hex, octal, binary and characters),
+	/// - `redefine`, `undef` and `export` directives are available,
+	/// - `ASSERT` annotations are collected into [`Assertions`](crate::vm::Assertions) without taking up ROM space,
+	/// - `include <std/...>` pulls in a file of the bundled [standard library](STD_FILES).
+	#[default]
+	Extended,
+}
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum AsmError<'a> {
@@ -44,7 +74,7 @@ pub enum AsmError<'a> {
 		line_number: usize,
 		token: Token<'a>,
 	},
-	#[error("Unexpected token `{token}`, expected a mnemonic or `define`")]
+	#[error("Unexpected token `{token}`, expected a mnemonic or a directive")]
 	UnknownMnemonic {
 		line_number: usize,
 		token: Token<'a>,
@@ -61,18 +91,58 @@ pub enum AsmError<'a> {
 		token: Token<'a>,
 		#[source] source: ParseIntError,
 	},
+	#[error("Symbol `{token}` is already defined on line {previous_line_number}")]
+	DuplicateSymbol {
+		line_number: usize,
+		token: Token<'a>,
+		previous_line_number: usize,
+		previous: Token<'a>,
+	},
+	#[error("Symbol `{token}` is reserved, use `redefine` to override it")]
+	ReservedSymbol {
+		line_number: usize,
+		token: Token<'a>,
+	},
+	#[error("Label `{token}` is defined on line {label_line_number}, labels cannot be redefined or undefined")]
+	RedefinedLabel {
+		line_number: usize,
+		token: Token<'a>,
+		label_line_number: usize,
+		label: Token<'a>,
+	},
+	#[error("Unknown include `{token}`, expected one of the bundled `<std/...>` files")]
+	UnknownInclude {
+		line_number: usize,
+		token: Token<'a>,
+	},
+	#[error("Unexpected token `{token}`, expected an assertion like `ASSERT r1 == 42`, `ASSERT mem[0x10] < 8` or `ASSERT flags.carry`")]
+	InvalidAssertion {
+		line_number: usize,
+		token: Token<'a>,
+	},
+	#[error("Label `{token}` cannot be relocated, only address and immediate operands can refer to labels in object files")]
+	NotRelocatable {
+		line_number: usize,
+		token: Token<'a>,
+	},
 }
 
 impl AsmError<'_> {
 	pub fn line_num(&self) -> usize {
-		match self {
-			&AsmError::TooManyTokens { line_number, .. } => line_number,
-			&AsmError::WrongOperandCount { line_number, .. } => line_number,
-			&AsmError::OperandOutOfRange { line_number, .. } => line_number,
-			&AsmError::TooManyInstructions { line_number, .. } => line_number,
-			&AsmError::UnknownMnemonic { line_number, .. } => line_number,
-			&AsmError::UnknownSymbol { line_number, .. } => line_number,
-			&AsmError::IntParseError { line_number, .. } => line_number,
+		match *self {
+			AsmError::TooManyTokens { line_number, .. } => line_number,
+			AsmError::WrongOperandCount { line_number, .. } => line_number,
+			AsmError::OperandOutOfRange { line_number, .. } => line_number,
+			AsmError::TooManyInstructions { line_number, .. } => line_number,
+			AsmError::UnknownMnemonic { line_number, .. } => line_number,
+			AsmError::UnknownSymbol { line_number, .. } => line_number,
+			AsmError::IntParseError { line_number, .. } => line_number,
+			AsmError::DuplicateSymbol { line_number, .. } => line_number,
+			AsmError::ReservedSymbol { line_number, .. } => line_number,
+			AsmError::RedefinedLabel { line_number, .. } => line_number,
+			AsmError::UnknownInclude { line_number, .. } => line_number,
+			AsmError::InvalidAssertion { line_number, .. } => line_number,
+			AsmError::NotRelocatable { line_number, .. } => line_number,
 		}
 	}
 	
@@ -80,34 +150,87 @@ impl AsmError<'_> {
 		self.token().char_number
 	}
 	
-	pub fn token(&self) -> Token {
-		match self {
-			&AsmError::TooManyTokens { token, .. } => token,
-			&AsmError::WrongOperandCount { mnemonic, .. } => mnemonic,
-			&AsmError::OperandOutOfRange { token, .. } => token,
-			&AsmError::TooManyInstructions { token, .. } => token,
-			&AsmError::UnknownMnemonic { token, .. } => token,
-			&AsmError::UnknownSymbol { token, .. } => token,
-			&AsmError::IntParseError { token, .. } => token,
+	pub fn token(&self) -> Token<'_> {
+		match *self {
+			AsmError::TooManyTokens { token, .. } => token,
+			AsmError::WrongOperandCount { mnemonic, .. } => mnemonic,
+			AsmError::OperandOutOfRange { token, .. } => token,
+			AsmError::TooManyInstructions { token, .. } => token,
+			AsmError::UnknownMnemonic { token, .. } => token,
+			AsmError::UnknownSymbol { token, .. } => token,
+			AsmError::IntParseError { token, .. } => token,
+			AsmError::DuplicateSymbol { token, .. } => token,
+			AsmError::ReservedSymbol { token, .. } => token,
+			AsmError::RedefinedLabel { token, .. } => token,
+			AsmError::UnknownInclude { token, .. } => token,
+			AsmError::InvalidAssertion { token, .. } => token,
+			AsmError::NotRelocatable { token, .. } => token,
+		}
+	}
+	
+	pub fn tokens(&self) -> ArrayVec<Token<'_>, { MAX_ARGS + 1 }> {
+		match *self {
+			AsmError::TooManyTokens { token, .. } => Some(token).into_iter().collect(),
+			AsmError::WrongOperandCount { mnemonic, ref args, .. } => Some(mnemonic).into_iter().chain(args.iter().cloned()).collect(),
+			AsmError::OperandOutOfRange { token, .. } => Some(token).into_iter().collect(),
+			AsmError::TooManyInstructions { token, .. } => Some(token).into_iter().collect(),
+			AsmError::UnknownMnemonic { token, .. } => Some(token).into_iter().collect(),
+			AsmError::UnknownSymbol { token, .. } => Some(token).into_iter().collect(),
+			AsmError::IntParseError { token, .. } => Some(token).into_iter().collect(),
+			AsmError::DuplicateSymbol { token, .. } => Some(token).into_iter().collect(),
+			AsmError::ReservedSymbol { token, .. } => Some(token).into_iter().collect(),
+			AsmError::RedefinedLabel { token, .. } => Some(token).into_iter().collect(),
+			AsmError::UnknownInclude { token, .. } => Some(token).into_iter().collect(),
+			AsmError::InvalidAssertion { token, .. } => Some(token).into_iter().collect(),
+			AsmError::NotRelocatable { token, .. } => Some(token).into_iter().collect(),
 		}
 	}
 	
-	pub fn tokens(&self) -> ArrayVec<Token, { MAX_ARGS + 1 }> {
-		match self {
-			&AsmError::TooManyTokens { token, .. } => Some(token).into_iter().collect(),
-			&AsmError::WrongOperandCount { mnemonic, ref args, .. } => Some(mnemonic).into_iter().chain(args.iter().cloned()).collect(),
-			&AsmError::OperandOutOfRange { token, .. } => Some(token).into_iter().collect(),
-			&AsmError::TooManyInstructions { token, .. } => Some(token).into_iter().collect(),
-			&AsmError::UnknownMnemonic { token, .. } => Some(token).into_iter().collect(),
-			&AsmError::UnknownSymbol { token, .. } => Some(token).into_iter().collect(),
-			&AsmError::IntParseError { token, .. } => Some(token).into_iter().collect(),
+	/// Returns the line number and token of an earlier definition this error conflicts with, if any.
+	pub fn previous_definition(&self) -> Option<(usize, Token<'_>)> {
+		match *self {
+			AsmError::DuplicateSymbol { previous_line_number, previous, .. } => Some((previous_line_number, previous)),
+			AsmError::RedefinedLabel { label_line_number, label, .. } => Some((label_line_number, label)),
+			_ => None,
 		}
 	}
 }
 
+/// An error returned by [`ProgramBuilder`].
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BuildError {
+	#[error("Label `{label}` is already defined")]
+	DuplicateLabel {
+		label: String,
+	},
+	#[error("Label `{label}` used at address {address} is not defined")]
+	UndefinedLabel {
+		label: String,
+		address: u16,
+	},
+	#[error("Too many operands for {mnemonic} at address {address}")]
+	TooManyOperands {
+		address: u16,
+		mnemonic: Mnemonic,
+	},
+	#[error("Invalid {mnemonic} at address {address}: {source}")]
+	InvalidInstruction {
+		address: u16,
+		mnemonic: Mnemonic,
+		source: InstructionError,
+	},
+	#[error("Program is too long: {len} instructions (max {MAX_CODE_LEN})")]
+	TooManyInstructions {
+		len: usize,
+	},
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::isa::Instruction;
+	use crate::utils;
 	
 	#[test]
 	fn ast_parses() {
@@ -142,5 +265,354 @@ mod tests {
 		
 		let _ast: Vec<_> = parse_lines(code).collect();
 	}
-}
+	
+	fn assemble_errors(code: &str) -> Vec<AsmError<'_>> {
+		let lines: Vec<_> = parse_lines(code).collect::<Result<_, _>>().unwrap();
+		assemble(&lines).filter_map(Result::err).collect()
+	}
+	
+	#[test]
+	fn duplicate_symbols() {
+		let errors = assemble_errors(r"
+		.loop
+		  NOP
+		.loop
+		  JMP .loop");
+		
+		assert!(matches!(errors[..], [AsmError::DuplicateSymbol { line_number: 4, previous_line_number: 2, .. }]));
+		
+		let errors = assemble_errors(r"
+		define speed 3
+		define speed 4
+		.speed
+		  NOP
+		define .speed 1");
+		
+		assert!(matches!(errors[..], [
+			AsmError::DuplicateSymbol { line_number: 3, previous_line_number: 2, .. },
+			AsmError::DuplicateSymbol { line_number: 6, previous_line_number: 4, .. },
+		]));
+	}
+	
+	#[test]
+	fn reserved_symbols() {
+		let errors = assemble_errors(r"
+		define r1 5
+		define rng 3
+		define ADD 1");
+		
+		assert!(matches!(errors[..], [
+			AsmError::ReservedSymbol { line_number: 2, .. },
+			AsmError::ReservedSymbol { line_number: 3, .. },
+			AsmError::ReservedSymbol { line_number: 4, .. },
+		]));
+		
+		for &(name, value) in DEFAULT_SYMBOLS {
+			assert_eq!(default_symbols(name), Some(value), "{name}");
+		}
+	}
+	
+	#[test]
+	fn redefine_and_undef() {
+		let code = r"
+		define speed 3
+		LDI r1 speed
+		redefine speed 4
+		LDI r1 speed
+		redefine rng 7
+		LDI r1 rng
+		undef rng
+		LDI r1 rng
+		undef speed
+		LDI r1 rng";
+		
+		let lines: Vec<_> = parse_lines(code).collect::<Result<_, _>>().unwrap();
+		let program: Vec<_> = assemble(&lines).collect::<Result<_, _>>().unwrap();
+		
+		assert_eq!(program, [
+			Instruction::LDI { a: 1, imm: 3 },
+			Instruction::LDI { a: 1, imm: 4 },
+			Instruction::LDI { a: 1, imm: 7 },
+			Instruction::LDI { a: 1, imm: 254 },
+			Instruction::LDI { a: 1, imm: 254 },
+		]);
+		
+		let errors = assemble_errors(r"
+		.start
+		  redefine .start 2
+		  undef .start");
+		
+		assert!(matches!(errors[..], [
+			AsmError::RedefinedLabel { line_number: 3, label_line_number: 2, .. },
+			AsmError::RedefinedLabel { line_number: 4, label_line_number: 2, .. },
+		]));
+		
+		// Errors of directives count towards the limit which stops the assembler
+		let code = format!(".start\n{}", "undef .start\n".repeat(150));
+		assert_eq!(assemble_errors(&code).len(), 101);
+	}
+	
+	#[test]
+	fn assertions() {
+		use crate::vm::{Assertion, Comparison, Subject};
+		
+		let code = r"
+		define buffer 0x10
+		.start
+		  ASSERT r3 == 42
+		  LDI r1 3
+		  ASSERT mem[buffer] < 8
+		  ASSERT flags.carry
+		  ASSERT r1 >= 0x03
+		  HLT";
+		
+		let lines: Vec<_> = parse_lines(code).collect::<Result<_, _>>().unwrap();
+		let mut assembler = Assembler::new(&lines);
+		let program: Vec<_> = assembler.by_ref().collect::<Result<_, _>>().unwrap();
+		
+		assert_eq!(program, [Instruction::LDI { a: 1, imm: 3 }, Instruction::HLT]);
+		assert_eq!(assembler.assertions().iter().collect::<Vec<_>>(), [
+			(0, &Assertion { line_number: 4, subject: Subject::Reg(3), check: Some((Comparison::Eq, 42)) }),
+			(1, &Assertion { line_number: 6, subject: Subject::Mem(0x10), check: Some((Comparison::Lt, 8)) }),
+			(1, &Assertion { line_number: 7, subject: Subject::Carry, check: None }),
+			(1, &Assertion { line_number: 8, subject: Subject::Reg(1), check: Some((Comparison::Ge, 3)) }),
+		]);
+		assert_eq!(assembler.assertions().get(1)[0].to_string(), "mem[0x10] < 8");
+		
+		let errors = assemble_errors(r"
+		ASSERT rng
+		ASSERT r1 =~ 3
+		ASSERT mem[0xF0] == 1
+		ASSERT r1 == 300
+		ASSERT r1 ==
+		ASSERT r1 >= -1
+		ASSERT mem[missing]");
+		
+		assert!(matches!(errors[..], [
+			AsmError::InvalidAssertion { line_number: 2, .. },
+			AsmError::InvalidAssertion { line_number: 3, .. },
+			AsmError::InvalidAssertion { line_number: 4, .. },
+			AsmError::InvalidAssertion { line_number: 5, .. },
+			AsmError::InvalidAssertion { line_number: 6, .. },
+			AsmError::InvalidAssertion { line_number: 7, .. },
+			AsmError::UnknownSymbol { line_number: 8, .. },
+		]));
+		
+		assert!(matches!(utils::from_asm_with("ASSERT r1", Dialect::Upstream), Err(AsmError::UnknownMnemonic { .. })));
+	}
+	
+	#[test]
+	fn cross_references() {
+		let code = r"
+		define limit 3
+		.start
+		  LDI r1 limit
+		  LDI r2 pixel_x
+		  CAL .sub
+		  BRH nz .start
+		  HLT
+		redefine limit 4
+		.sub
+		  ASSERT r1 < limit
+		  STR r2 r1
+		  RET
+		export .sub";
+		
+		let xref = xref(code, Dialect::Extended).unwrap();
+		let names: Vec<_> = xref.symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+		
+		assert_eq!(names, ["limit", ".start", ".sub", "nz", "pixel_x", "r1", "r2"]);
+		assert_eq!(xref.symbols[0].definitions, [2, 9]);
+		
+		let usages = |name: &str| -> Vec<_> {
+			let symbol = xref.symbols.iter().find(|symbol| symbol.name == name).unwrap();
+			symbol.references.iter().map(|reference| (reference.line_number, reference.address, reference.usage)).collect()
+		};
+		
+		assert_eq!(usages("limit"), [(4, Some(0), Usage::Immediate), (11, None, Usage::Assertion)]);
+		assert_eq!(usages(".start"), [(7, Some(3), Usage::Address)]);
+		assert_eq!(usages(".sub"), [(6, Some(2), Usage::Address), (14, None, Usage::Export)]);
+		assert_eq!(usages("nz"), [(7, Some(3), Usage::Condition)]);
+		assert_eq!(usages("pixel_x"), [(5, Some(1), Usage::IoPort)]);
+		assert_eq!(usages("r2").len(), 2);
+		
+		assert!(xref.to_string().starts_with("limit (define 3, defined on line 2, 9), 2 references\n    4:12"));
+		assert!(matches!(super::xref("JMP .missing", Dialect::Extended), Err(AsmError::UnknownSymbol { .. })));
+	}
+	
+	#[test]
+	fn dialects() {
+		let code = r"
+		define big 0x10 ; comment
+		LDI r1 big // both dialects";
+		
+		assert_eq!(utils::from_asm_with(code, Dialect::Extended).unwrap(), [Instruction::LDI { a: 1, imm: 16 }]);
+		assert!(matches!(utils::from_asm_with(code, Dialect::Upstream), Err(AsmError::IntParseError { line_number: 2, .. })));
+		
+		let lines: Vec<_> = parse_lines_with("  JMP .x / y", Dialect::Upstream).collect::<Result<_, _>>().unwrap();
+		assert_eq!(lines[0].args.len(), 1);
+		assert_eq!(lines[0].comment.as_deref(), Some("/ y"));
+		
+		let lines: Vec<_> = parse_lines_with("  JMP .x / y", Dialect::Extended).collect::<Result<_, _>>().unwrap();
+		assert_eq!(lines[0].args.len(), 1);
+		assert_eq!(lines[0].comment.as_deref(), Some("/ y"));
+		
+		// Programs written for the baseline assembler use single `/` comments
+		assert_eq!(utils::from_asm("LDI r1 5 / load the counter\n/ stop\nHLT").unwrap(), [Instruction::LDI { a: 1, imm: 5 }, Instruction::HLT]);
+		assert!(utils::from_asm("include <std/math.asm> / multiplication\nCAL .math_mul").is_ok());
+		
+		assert!(matches!(utils::from_asm_with("undef r1", Dialect::Upstream), Err(AsmError::UnknownMnemonic { .. })));
+	}
+	
+	#[test]
+	fn formats() {
+		let code = "# header\n\n.start ldi r1 'a' ; char\n  define   limit 0b101\n\n\n   ; body\n.long_label_name add r1 r2 r3\n  Cmp r1 r2 // compare\n";
+		let formatted = format(code, &FormatOptions::default()).unwrap();
+		
+		assert_eq!(formatted, "\
+# header
 
+.start  LDI    r1    'a'   ; char
+        define limit 0b101
+
+
+        ; body
+.long_label_name ADD r1 r2 r3
+        CMP r1 r2             // compare
+");
+		assert_eq!(format(&formatted, &FormatOptions::default()).unwrap(), formatted);
+	}
+	
+	#[test]
+	fn syntax_tree_round_trips() {
+		let code = "; header\r\n\n.loop  ldi r1 ' ' // char\r\n\t  JMP .loop # back  \n   \nHLT";
+		let tree = SyntaxTree::parse(code, Dialect::Extended);
+		
+		assert_eq!(tree.to_string(), code);
+		assert_eq!(tree.lines().len(), 6);
+		assert!(tree.lines()[1].is_blank());
+		assert!(!tree.lines()[0].is_blank());
+		
+		let loop_line = &tree.lines()[2];
+		assert_eq!(loop_line.range(), 11..38);
+		assert_eq!(loop_line.label().unwrap().range(), 11..16);
+		assert_eq!(loop_line.mnemonic().unwrap().text, "ldi");
+		assert_eq!(loop_line.operands().map(|token| token.text).collect::<Vec<_>>(), ["r1", "' '"]);
+		assert_eq!(loop_line.comment().unwrap().text, "// char");
+		assert_eq!(tree.token_at(27).unwrap().kind, SyntaxKind::Operand);
+		assert_eq!(tree.token_at(37).unwrap().kind, SyntaxKind::Newline);
+		
+		for (line, syntax) in parse_lines(code).zip(tree.lines().iter().filter(|line| !line.is_blank())) {
+			let line = line.unwrap();
+			let syntax = syntax.to_line().unwrap();
+			assert_eq!(format!("{syntax:?}"), format!("{line:?}"));
+			assert_eq!(line.line_number, syntax.line_number);
+		}
+		
+		let errors = SyntaxTree::parse("ADD r1 r2 r3 r4", Dialect::Extended).lines()[0].to_line().unwrap_err();
+		assert!(matches!(errors, AsmError::TooManyTokens { .. }));
+	}
+	
+	#[test]
+	fn program_builder() {
+		use crate::isa::{Cond, Mnemonic, InstructionError, R1, R2, R3};
+		
+		let mut b = ProgramBuilder::new();
+		let end = b.fresh_label("end");
+		let skip = b.fresh_label("end");
+		assert_ne!(end, skip);
+		
+		b.ldi(R1, 3)
+		 .ldi(R3, "data")
+		 .label("loop")
+		 .add(R2, R1, R2)
+		 .dec(R1)
+		 .brh(Cond::NotZero, "loop")
+		 .cal(&end)
+		 .hlt()
+		 .label(&end)
+		 .label("data")
+		 .str(R3, R2, -1)
+		 .ret();
+		
+		let program = b.build_program().unwrap();
+		let expected: Vec<_> = utils::from_asm(r"
+			LDI r1 3
+			LDI r3 7
+			.loop ADD r2 r1 r2
+			DEC r1
+			BRH nz .loop
+			CAL 7
+			HLT
+			STR r3 r2 -1
+			RET").unwrap();
+		
+		assert_eq!(program.code, expected);
+		assert_eq!(program.symbols[&end], 7);
+		
+		let mut vm = crate::BatPU2::new(program.code);
+		vm.step_multiple(100);
+		assert_eq!(vm.memory[6], 6);
+		
+		let mut b = ProgramBuilder::new();
+		b.jmp("nowhere");
+		assert_eq!(b.build(), Err(BuildError::UndefinedLabel { label: "nowhere".to_owned(), address: 0 }));
+		
+		let mut b = ProgramBuilder::new();
+		b.nop().adi(R1, 300);
+		assert!(matches!(b.build(), Err(BuildError::InvalidInstruction {
+			address: 1,
+			mnemonic: Mnemonic::ADI,
+			source: InstructionError::OperandOutOfRange { got: 300, .. },
+		})));
+		
+		let mut b = ProgramBuilder::new();
+		b.label("a").nop().label("a");
+		assert_eq!(b.build(), Err(BuildError::DuplicateLabel { label: "a".to_owned() }));
+	}
+	
+	#[test]
+	fn control_flow_graph() {
+		let code = utils::from_asm(r"
+		  CAL .f
+		  CAL .f
+		  HLT
+		.f
+		  LDI r1 1
+		  BRH zero .done
+		  INC r1
+		.done
+		  RET
+		").unwrap();
+		
+		let cfg = Cfg::new(&code);
+		let blocks: Vec<_> = cfg.blocks.iter().map(|block| block.addresses.clone()).collect();
+		
+		assert_eq!(blocks, [0..1, 1..2, 2..3, 3..5, 5..6, 6..7]);
+		assert_eq!(cfg.edges, [
+			Edge { from: 0, to: 3, kind: EdgeKind::Call },
+			Edge { from: 0, to: 1, kind: EdgeKind::Fallthrough },
+			Edge { from: 1, to: 3, kind: EdgeKind::Call },
+			Edge { from: 1, to: 2, kind: EdgeKind::Fallthrough },
+			Edge { from: 3, to: 5, kind: EdgeKind::Branch },
+			Edge { from: 3, to: 4, kind: EdgeKind::Fallthrough },
+			Edge { from: 4, to: 5, kind: EdgeKind::Fallthrough },
+			Edge { from: 5, to: 1, kind: EdgeKind::Return },
+			Edge { from: 5, to: 2, kind: EdgeKind::Return },
+		]);
+		assert_eq!(cfg.functions, [
+			Function { entry: 0, blocks: vec![0, 1, 2] },
+			Function { entry: 3, blocks: vec![3, 4, 5] },
+		]);
+		assert_eq!(cfg.block_at(4), Some(3));
+		assert_eq!(cfg.block_at(7), None);
+		
+		let dot = cfg.to_dot([(".f", 3), (".done", 6)]);
+		
+		assert!(dot.starts_with("digraph cfg {\n"));
+		assert!(dot.contains("\t\tlabel=\".f\";\n"));
+		assert!(dot.contains("\t\tb3 [label=\".f\\l003  LDI r1 1\\l004  BRH zero .done\\l\"];\n"));
+		assert!(dot.contains("\tb3 -> b5 [label=\"zero\"];\n"));
+		assert!(dot.contains("\tb5 -> b1 [style=dotted, color=gray];\n"));
+	}
+}
@@ -1,38 +1,131 @@
-use crate::asm::AsmError;
+use crate::asm::{AsmError, Dialect, SyntaxLine, parse_syntax_lines, std_source};
+use crate::asm::assembler::expect_directive_args;
 use crate::asm::ast::{Line, Token};
 
-pub fn parse_lines(code: &str) -> impl Iterator<Item=Result<Line, AsmError>> + '_ {
-	code.lines()
-	    .enumerate()
-	    .filter(|(_, line)| !line.trim().is_empty())
-	    .map(|(line_number, line)| parse_line(line_number + 1, line))
+pub fn parse_lines(code: &str) -> impl Iterator<Item=Result<Line<'_>, AsmError<'_>>> + '_ {
+	parse_lines_with(code, Dialect::default())
 }
 
-pub fn parse_line(line_number: usize, line: &str) -> Result<Line, AsmError> {
-	let (line, comment) = line.find([';', '/', '#'])
-	                          .map(|pos| (&line[..pos], Some(Token::new(pos, &line[pos..]))))
-	                          .unwrap_or((line, None));
-	
-	let mut tokens = tokenize(line).peekable();
-	
-	let label = tokens.next_if(|token| token.starts_with('.'));
-	let mnemonic = tokens.next();
-	let args = (&mut tokens).take(3).collect();
-	
-	if let Some(token) = tokens.next() {
-		return Err(AsmError::TooManyTokens { line_number, token } )
+/// Parses lines of a program, `include` directives of the extended dialect are replaced by the lines of
+/// the included file.
+///
+/// Every file is included at most once, included lines take the line number of the top level `include`.
+pub fn parse_lines_with(code: &str, dialect: Dialect) -> impl Iterator<Item=Result<Line<'_>, AsmError<'_>>> + '_ {
+	Includes {
+		dialect,
+		files: vec![Box::new(parse_syntax_lines(code, dialect))],
+		included: Vec::new(),
+		line_number: 0,
+	}
+}
+
+pub fn parse_line(line_number: usize, line: &str) -> Result<Line<'_>, AsmError<'_>> {
+	parse_line_with(line_number, line, Dialect::default())
+}
+
+pub fn parse_line_with(line_number: usize, line: &str, dialect: Dialect) -> Result<Line<'_>, AsmError<'_>> {
+	SyntaxLine::parse(line_number, 0, line, dialect).to_line()
+}
+
+struct Includes<'c> {
+	dialect: Dialect,
+	files: Vec<Box<dyn Iterator<Item=SyntaxLine<'c>> + 'c>>,
+	included: Vec<&'c str>,
+	/// Line number of the `include` in the top level file which is being expanded
+	line_number: usize,
+}
+
+impl<'c> Includes<'c> {
+	fn include(&mut self, line: &Line<'c>) -> Result<(), AsmError<'c>> {
+		let [path] = expect_directive_args(line)?;
+		let name = path.span
+		               .strip_prefix('<')
+		               .and_then(|path| path.strip_suffix('>'))
+		               .unwrap_or(path.span);
+		
+		let Some(source) = std_source(name) else {
+			return Err(AsmError::UnknownInclude { line_number: line.line_number, token: path });
+		};
+		
+		if !self.included.contains(&name) {
+			self.included.push(name);
+			self.files.push(Box::new(parse_syntax_lines(source, self.dialect)));
+		}
+		
+		Ok(())
 	}
+}
+
+impl<'c> Iterator for Includes<'c> {
+	type Item = Result<Line<'c>, AsmError<'c>>;
 	
-	Ok(Line {
-		line_number,
-		label,
-		mnemonic,
-		args,
-		comment,
-	})
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let Some(line) = self.files.last_mut()?.next() else {
+				self.files.pop();
+				continue;
+			};
+			
+			if line.is_blank() {
+				continue;
+			}
+			
+			let mut line = match line.to_line() {
+				Ok(line) => line,
+				err => return Some(err),
+			};
+			
+			if self.files.len() == 1 {
+				self.line_number = line.line_number;
+			} else {
+				line.line_number = self.line_number;
+			}
+			
+			if self.dialect != Dialect::Extended || line.mnemonic.is_none_or(|mnemonic| mnemonic.span != "include") {
+				return Some(Ok(line));
+			}
+			
+			if let Err(err) = self.include(&line) {
+				return Some(Err(err));
+			}
+			
+			// A label in front of `include` points at the first included instruction
+			if line.label.is_some() {
+				line.mnemonic = None;
+				line.args.clear();
+				return Some(Ok(line));
+			}
+		}
+	}
+}
+
+pub(super) fn find_comment(line: &str, dialect: Dialect) -> Option<usize> {
+	match dialect {
+		Dialect::Upstream => line.find([';', '/', '#']),
+		Dialect::Extended => {
+			let mut word_start = 0;
+			
+			for (index, char) in line.char_indices() {
+				match char {
+					';' | '#' => return Some(index),
+					'/' if !is_include_path(&line[word_start..]) => return Some(index),
+					char if char.is_whitespace() => word_start = index + char.len_utf8(),
+					_ => {}
+				}
+			}
+			
+			None
+		}
+	}
+}
+
+/// A `/` inside the `<std/...>` path of an `include` does not start a comment.
+fn is_include_path(word: &str) -> bool {
+	let word = &word[..word.find(char::is_whitespace).unwrap_or(word.len())];
+	word.len() > 1 && word.starts_with('<') && word.ends_with('>')
 }
 
-fn tokenize(mut line: &str) -> impl Iterator<Item=Token> {
+pub(super) fn tokenize(mut line: &str) -> impl Iterator<Item=Token<'_>> {
 	let original = line;
 	
 	std::iter::from_fn(move || {
@@ -0,0 +1,28 @@
+/// Version of the bundled standard library, bumped whenever a routine changes its interface.
+pub const STD_VERSION: u32 = 1;
+
+/// Sources of the standard library by their include path, e.g. `include <std/math.asm>`.
+///
+/// | File            | Routines                                                                  |
+/// |-----------------|---------------------------------------------------------------------------|
+/// | `std/math.asm`  | `.math_mul`, `.math_divmod`                                               |
+/// | `std/u16.asm`   | `.u16_add`, `.u16_sub`, `.u16_inc`, `.u16_shl`, `.u16_shr`, `.u16_cmp`    |
+/// | `std/print.asm` | `.print_u8`, `.print_digit`                                               |
+/// | `std/draw.asm`  | `.draw_pixel`, `.draw_line`, `.draw_rect`                                 |
+/// | `std/input.asm` | `.input_pressed`, `.input_wait`                                           |
+///
+/// Every file starts with a comment describing its calling convention.
+pub const STD_FILES: &[(&str, &str)] = &[
+	("std/math.asm", include_str!("../../std/math.asm")),
+	("std/u16.asm", include_str!("../../std/u16.asm")),
+	("std/print.asm", include_str!("../../std/print.asm")),
+	("std/draw.asm", include_str!("../../std/draw.asm")),
+	("std/input.asm", include_str!("../../std/input.asm")),
+];
+
+/// Returns the source of a standard library file.
+pub fn std_source(path: &str) -> Option<&'static str> {
+	STD_FILES.iter()
+	         .find(|&&(name, _)| name == path)
+	         .map(|&(_, source)| source)
+}
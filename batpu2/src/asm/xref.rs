@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::asm::{self, AsmError, Assembler, Dialect, Token, default_symbols, parse_literal};
+use crate::isa::Mnemonic;
+use crate::vm::Comparison;
+
+/// Cross-reference of all symbols used by a program.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Xref {
+	/// Labels and `define`s in order of definition, followed by used built-in symbols sorted by name
+	pub symbols: Vec<XrefSymbol>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XrefSymbol {
+	pub name: String,
+	pub kind: SymbolKind,
+	/// Value of the first definition, address of labels
+	pub value: i16,
+	/// Lines of the `define`, `redefine`s or the label, empty for built-in symbols
+	pub definitions: Vec<usize>,
+	pub references: Vec<XrefReference>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SymbolKind {
+	Label,
+	Define,
+	/// One of the [`DEFAULT_SYMBOLS`](asm::DEFAULT_SYMBOLS)
+	Builtin,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct XrefReference {
+	pub line_number: usize,
+	pub char_number: usize,
+	/// Address of the referencing instruction, `None` for directives
+	pub address: Option<u16>,
+	/// Mnemonic or directive of the referencing line
+	pub mnemonic: String,
+	/// Index of the referencing operand
+	pub operand: usize,
+	pub usage: Usage,
+}
+
+/// How a symbol is used by an operand.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Usage {
+	/// Target of `JMP`, `BRH` or `CAL`
+	Address,
+	Register,
+	Immediate,
+	/// Immediate in the range of memory mapped IO ports
+	IoPort,
+	Condition,
+	/// Offset of `LOD` or `STR`
+	Offset,
+	Export,
+	Assertion,
+}
+
+/// Lists definitions and references of every symbol in a program, including built-in symbols.
+///
+/// The program has to assemble without errors, the first error is returned otherwise.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::{self, Dialect, SymbolKind, Usage};
+///
+/// let xref = asm::xref("
+/// .loop
+///   LDI r1 pixel_x
+///   JMP .loop
+/// ", Dialect::Extended).unwrap();
+///
+/// let label = &xref.symbols[0];
+/// assert_eq!((label.name.as_str(), label.kind, label.definitions.as_slice()), (".loop", SymbolKind::Label, &[2][..]));
+/// assert_eq!(label.references[0].line_number, 4);
+/// assert_eq!(label.references[0].usage, Usage::Address);
+///
+/// let port = xref.symbols.iter().find(|symbol| symbol.name == "pixel_x").unwrap();
+/// assert_eq!((port.kind, port.value, port.references[0].usage), (SymbolKind::Builtin, 240, Usage::IoPort));
+/// ```
+pub fn xref(source: &str, dialect: Dialect) -> Result<Xref, AsmError<'_>> {
+	let lines = asm::parse_lines_with(source, dialect).collect::<Result<Vec<_>, _>>()?;
+	let mut assembler = Assembler::new(&lines).dialect(dialect);
+	
+	if let Some(err) = assembler.by_ref().find_map(Result::err) {
+		return Err(err);
+	}
+	
+	let mut symbols: HashMap<&str, XrefSymbol> = assembler.symbols()
+	                                                      .iter()
+	                                                      .map(|(&name, symbol)| (name, XrefSymbol {
+		                                                      name: name.to_owned(),
+		                                                      kind: if symbol.label { SymbolKind::Label } else { SymbolKind::Define },
+		                                                      value: symbol.value,
+		                                                      definitions: vec![symbol.line_number],
+		                                                      references: Vec::new(),
+	                                                      }))
+	                                                      .collect();
+	
+	let mut overrides: HashMap<&str, Option<i16>> = HashMap::new();
+	let mut address = 0;
+	
+	for line in &lines {
+		let Some(mnemonic) = line.mnemonic else { continue };
+		
+		let reference = |operand, token: Token, usage, address| XrefReference {
+			line_number: line.line_number,
+			char_number: token.char_number,
+			address,
+			mnemonic: mnemonic.span.to_owned(),
+			operand,
+			usage,
+		};
+		
+		match (mnemonic.span, line.args.as_slice()) {
+			("define", _) => {}
+			("redefine", &[key, value]) if dialect == Dialect::Extended => {
+				let value = parse_literal(value);
+				
+				if let Some(symbol) = symbol(&mut symbols, key.span, value) {
+					if symbol.definitions.last() != Some(&line.line_number) {
+						symbol.definitions.push(line.line_number);
+					}
+				}
+				
+				overrides.insert(key.span, value);
+			}
+			("undef", &[key]) if dialect == Dialect::Extended => {
+				overrides.insert(key.span, None);
+			}
+			("export", &[label]) if dialect == Dialect::Extended => {
+				add_reference(&mut symbols, label.span, reference(0, label, Usage::Export, None));
+			}
+			("ASSERT", args) if dialect == Dialect::Extended => for (operand, &token) in args.iter().enumerate() {
+				let token = match token.span.strip_prefix("mem[").and_then(|inner| inner.strip_suffix(']')) {
+					Some(inner) => Token::new(token.char_number + 4, inner),
+					None => token,
+				};
+				
+				if parse_literal(token).is_none() && Comparison::try_from(token.span).is_err() {
+					add_reference(&mut symbols, token.span, reference(operand, token, Usage::Assertion, None));
+				}
+			},
+			(_, args) => {
+				let Ok(instruction) = Mnemonic::try_from(mnemonic.span) else { continue };
+				
+				for (operand, (&token, &name)) in args.iter().zip(instruction.operand_names()).enumerate() {
+					if parse_literal(token).is_some() {
+						continue;
+					}
+					
+					let label = assembler.symbols().get(token.span).is_some_and(|symbol| symbol.label);
+					let value = match overrides.get(token.span) {
+						Some(&value) => value,
+						None => assembler.symbols().get(token.span).map(|symbol| symbol.value).or_else(|| default_symbols(token.span)),
+					};
+					
+					let usage = match name {
+						"addr" => Usage::Address,
+						"cond" => Usage::Condition,
+						"offset" => Usage::Offset,
+						"imm" if !label && value.is_some_and(|value| (240..=255).contains(&value)) => Usage::IoPort,
+						"imm" => Usage::Immediate,
+						_ => Usage::Register,
+					};
+					
+					add_reference(&mut symbols, token.span, reference(operand, token, usage, Some(address)));
+				}
+				
+				address += 1;
+			}
+		}
+	}
+	
+	let mut symbols: Vec<_> = symbols.into_values().collect();
+	
+	symbols.sort_by(|a, b| {
+		let key = |symbol: &XrefSymbol| match symbol.kind {
+			SymbolKind::Builtin => (true, 0, symbol.kind != SymbolKind::Label),
+			kind => (false, symbol.definitions[0], kind != SymbolKind::Label),
+		};
+		
+		key(a).cmp(&key(b)).then_with(|| a.name.cmp(&b.name))
+	});
+	
+	Ok(Xref { symbols })
+}
+
+/// Looks up a symbol, built-in symbols and symbols introduced by `redefine` are added on first use.
+fn symbol<'m, 'c>(symbols: &'m mut HashMap<&'c str, XrefSymbol>, name: &'c str, value: Option<i16>) -> Option<&'m mut XrefSymbol> {
+	if symbols.contains_key(name) {
+		return symbols.get_mut(name);
+	}
+	
+	let (kind, value) = match default_symbols(name) {
+		Some(builtin) => (SymbolKind::Builtin, builtin),
+		None => (SymbolKind::Define, value?),
+	};
+	
+	Some(symbols.entry(name).or_insert(XrefSymbol { name: name.to_owned(), kind, value, definitions: Vec::new(), references: Vec::new() }))
+}
+
+fn add_reference<'c>(symbols: &mut HashMap<&'c str, XrefSymbol>, name: &'c str, reference: XrefReference) {
+	if let Some(symbol) = symbol(symbols, name, None) {
+		symbol.references.push(reference);
+	}
+}
+
+impl Usage {
+	/// Name used in reports.
+	pub fn name(self) -> &'static str {
+		match self {
+			Usage::Address => "address",
+			Usage::Register => "register",
+			Usage::Immediate => "immediate",
+			Usage::IoPort => "io_port",
+			Usage::Condition => "condition",
+			Usage::Offset => "offset",
+			Usage::Export => "export",
+			Usage::Assertion => "assertion",
+		}
+	}
+}
+
+impl SymbolKind {
+	/// Name used in reports.
+	pub fn name(self) -> &'static str {
+		match self {
+			SymbolKind::Label => "label",
+			SymbolKind::Define => "define",
+			SymbolKind::Builtin => "builtin",
+		}
+	}
+}
+
+impl Display for Xref {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for symbol in &self.symbols {
+			write!(f, "{} ({}", symbol.name, symbol.kind.name())?;
+			
+			match symbol.kind {
+				SymbolKind::Label => write!(f, " 0x{:03X}", symbol.value)?,
+				_ => write!(f, " {}", symbol.value)?,
+			}
+			
+			if let Some((first, rest)) = symbol.definitions.split_first() {
+				write!(f, ", defined on line {first}")?;
+				
+				for line_number in rest {
+					write!(f, ", {line_number}")?;
+				}
+			}
+			
+			writeln!(f, "), {} references", symbol.references.len())?;
+			
+			for reference in &symbol.references {
+				let location = format!("{}:{}", reference.line_number, reference.char_number);
+				let address = reference.address.map_or(String::new(), |address| format!("0x{address:03X}"));
+				
+				writeln!(f, "    {location:<8} {address:<6} {:<8} {}", reference.mnemonic, reference.usage.name())?;
+			}
+		}
+		
+		Ok(())
+	}
+}
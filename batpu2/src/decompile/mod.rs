@@ -0,0 +1,315 @@
+//! Decompiler from machine code to C-like pseudo-code
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+use crate::isa::Instruction;
+use crate::asm::Cfg;
+
+mod structure;
+mod translate;
+mod values;
+
+use structure::Structurer;
+
+/// Decompiled program, [displaying](Display) it gives the pseudo-code.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Program {
+	/// The start of the program, every `CAL` target and code not reached from elsewhere, in order of address
+	pub functions: Vec<Function>,
+}
+
+/// Code from the entry of a function up to the next one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Function {
+	pub name: String,
+	pub entry: u16,
+	pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Stmt {
+	/// Assignment, call, `return`, `break`, `goto` and the like, along with the address of its instruction
+	Simple { address: u16, text: String },
+	/// Target of a `goto`
+	Label(String),
+	If { condition: String, then: Vec<Stmt>, otherwise: Vec<Stmt> },
+	While { condition: String, body: Vec<Stmt> },
+	DoWhile { body: Vec<Stmt>, condition: String },
+}
+
+/// Decompiles a program into functions with structured control flow.
+///
+/// `if` and `while` statements are recovered from `BRH` and `JMP`, jumps which do not fit are kept as
+/// `goto`. Conditions are written in terms of the instruction which set the flags, e.g. `CMP r1 r2` followed
+/// by `BRH lt` becomes `r1 < r2`. IO accesses are named after their ports when the value of the address
+/// register is known, values are propagated through the control-flow graph and calls. Label names are used for functions and `goto` targets.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::{decompile, utils};
+///
+/// let code = utils::from_asm("
+///   LDI r15 pixel_x
+///   LDI r1 0
+/// .loop
+///   CMP r1 r2
+///   BRH ge .done
+///   STR r15 r1
+///   STR r15 r0 2
+///   INC r1
+///   JMP .loop
+/// .done
+///   HLT
+/// ").unwrap();
+///
+/// let program = decompile::decompile(&code, [(".loop", 2), (".done", 8)]);
+///
+/// assert_eq!(program.to_string(), "\
+/// void start() {
+///     r15 = 240;
+///     r1 = 0;
+///     while (r1 < r2) {
+///         screen.pixel_x = r1;
+///         screen.draw_pixel();
+///         r1++;
+///     }
+///     halt();
+/// }
+/// ");
+/// ```
+pub fn decompile<'a>(code: &[Instruction], labels: impl IntoIterator<Item=(&'a str, u16)>) -> Program {
+	let cfg = Cfg::new(code);
+	let mut names: BTreeMap<u16, String> = BTreeMap::new();
+	
+	for (name, address) in labels {
+		let name = name.trim_start_matches('.').to_owned();
+		names.entry(address).and_modify(|first| if name < *first { first.clone_from(&name) }).or_insert(name);
+	}
+	
+	// Code nothing jumps to, like subroutines of a library which are never called, starts a function as well
+	let orphans = (1..cfg.blocks.len()).filter(|&block| {
+		cfg.predecessors(block).next().is_none() && cfg.instructions(block).any(|(_, instruction)| instruction != Instruction::NOP)
+	});
+	let entries: BTreeSet<u16> = cfg.functions
+	                                .iter()
+	                                .map(|function| function.entry)
+	                                .chain(orphans.map(|block| cfg.blocks[block].addresses.start))
+	                                .collect();
+	
+	for &entry in &entries {
+		names.entry(entry).or_insert_with(|| if entry == 0 { "start".to_owned() } else { format!("sub_{entry:03X}") });
+	}
+	
+	let clobbers = values::clobbers(&cfg);
+	let values = values::block_values(&cfg, &clobbers);
+	let mut structurer = Structurer {
+		cfg: &cfg,
+		names: &names,
+		entries: &entries,
+		values: &values,
+		clobbers: &clobbers,
+		gotos: BTreeSet::new(),
+	};
+	let ends = entries.iter().skip(1).copied().chain([code.len() as u16]);
+	let ranges: Vec<_> = entries.iter().copied().zip(ends).collect();
+	
+	// Labels are only known after every `goto` was found, so the first pass is discarded
+	for &(entry, end) in &ranges {
+		structurer.function(entry, end);
+	}
+	
+	let functions = ranges.iter()
+	                      .map(|&(entry, end)| Function { name: names[&entry].clone(), entry, body: structurer.function(entry, end) })
+	                      .collect();
+	
+	Program { functions }
+}
+
+impl Display for Program {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for (index, function) in self.functions.iter().enumerate() {
+			if index > 0 {
+				writeln!(f)?;
+			}
+			
+			write!(f, "{function}")?;
+		}
+		
+		Ok(())
+	}
+}
+
+impl Display for Function {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "void {}() {{", self.name)?;
+		write_block(f, &self.body, 1)?;
+		writeln!(f, "}}")
+	}
+}
+
+const INDENT: &str = "    ";
+
+fn write_block(f: &mut Formatter<'_>, statements: &[Stmt], depth: usize) -> fmt::Result {
+	let indent = INDENT.repeat(depth);
+	
+	for statement in statements {
+		match statement {
+			Stmt::Simple { text, .. } => writeln!(f, "{indent}{text}")?,
+			Stmt::Label(name) => writeln!(f, "{}{name}:", INDENT.repeat(depth - 1))?,
+			Stmt::If { condition, then, otherwise } => {
+				// Conditional jumps fit on one line, e.g. `if (r1 == 0) break;`
+				if let ([Stmt::Simple { text, .. }], []) = (&then[..], &otherwise[..]) {
+					writeln!(f, "{indent}if ({condition}) {text}")?;
+					continue;
+				}
+				
+				writeln!(f, "{indent}if ({condition}) {{")?;
+				write_block(f, then, depth + 1)?;
+				
+				let mut otherwise = otherwise;
+				
+				// Chains of `else { if .. }` are written as `else if`
+				while let [Stmt::If { condition, then, otherwise: next }] = &otherwise[..] {
+					writeln!(f, "{indent}}} else if ({condition}) {{")?;
+					write_block(f, then, depth + 1)?;
+					otherwise = next;
+				}
+				
+				if !otherwise.is_empty() {
+					writeln!(f, "{indent}}} else {{")?;
+					write_block(f, otherwise, depth + 1)?;
+				}
+				
+				writeln!(f, "{indent}}}")?;
+			}
+			Stmt::While { condition, body } => {
+				writeln!(f, "{indent}while ({condition}) {{")?;
+				write_block(f, body, depth + 1)?;
+				writeln!(f, "{indent}}}")?;
+			}
+			Stmt::DoWhile { body, condition } => {
+				writeln!(f, "{indent}do {{")?;
+				write_block(f, body, depth + 1)?;
+				writeln!(f, "{indent}}} while ({condition});")?;
+			}
+		}
+	}
+	
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils;
+	
+	fn check(asm: &str, expected: &str) {
+		let lines = crate::asm::parse_lines(asm).collect::<Result<Vec<_>, _>>().unwrap();
+		let mut assembler = crate::asm::Assembler::new(&lines);
+		let code = assembler.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+		let labels: Vec<_> = assembler.symbols()
+		                              .iter()
+		                              .filter(|(_, symbol)| symbol.label)
+		                              .map(|(&name, symbol)| (name, symbol.value as u16))
+		                              .collect();
+		
+		assert_eq!(decompile(&code, labels).to_string(), expected);
+	}
+	
+	#[test]
+	fn if_else() {
+		check(r"
+		  LDI r1 5
+		  CAL .max
+		  HLT
+		.max
+		  CMP r1 r2
+		  BRH lt .second
+		  MOV r1 r3
+		  JMP .end
+		.second
+		  MOV r2 r3
+		.end
+		  RET
+		", "\
+void start() {
+    r1 = 5;
+    max();
+    halt();
+}
+
+void max() {
+    if (r1 >= r2) {
+        r3 = r1;
+    } else {
+        r3 = r2;
+    }
+    return;
+}
+");
+	}
+	
+	#[test]
+	fn loops() {
+		check(r"
+		  LDI r15 rng
+		.wait
+		  LOD r15 r1
+		  AND r1 r1 r0
+		  BRH zero .wait
+		.count
+		  DEC r1
+		  BRH notzero .count
+		.forever
+		  LDI r14 controller_input
+		  LOD r14 r2
+		  CMP r2 r0
+		  BRH eq .forever
+		  LDI r14 show_number
+		  STR r14 r2
+		  JMP .forever
+		", "\
+void start() {
+    r15 = 254;
+    do {
+        r1 = rng();
+    } while ((r1 & r1) == 0);
+    do {
+        r1--;
+    } while (r1 != 0);
+    while (true) {
+        r14 = 255;
+        r2 = controller_input();
+        if (r2 == 0) continue;
+        r14 = 250;
+        number.show(r2);
+    }
+}
+");
+	}
+	
+	#[test]
+	fn gotos() {
+		let code = utils::from_asm(r"
+		  BRH carry 3
+		  JMP 4
+		  NOP
+		  ADI r1 1
+		  BRH zero 3
+		  HLT
+		").unwrap();
+		
+		assert_eq!(decompile(&code, []).to_string(), "\
+void start() {
+    if (!flags.carry) goto label_004;
+    do {
+        r1++;
+    label_004:
+    } while (flags.zero);
+    halt();
+}
+");
+	}
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::isa::Instruction;
+use crate::asm::Cfg;
+use super::Stmt;
+use super::translate::{translate, Translation};
+use super::values::Values;
+
+/// Innermost loop enclosing a region.
+#[derive(Copy, Clone)]
+struct Loop {
+	header: u16,
+	exit: u16,
+	/// The `BRH` back to the header ends the loop as its condition
+	do_while: bool,
+}
+
+/// Recovers `if` and `while` statements from the jumps of a function, jumps which do not fit are kept as `goto`.
+pub(super) struct Structurer<'a> {
+	pub cfg: &'a Cfg<'a>,
+	/// Names of addresses, used for calls, function names and labels
+	pub names: &'a BTreeMap<u16, String>,
+	/// Entries of all functions, jumps to them are tail calls
+	pub entries: &'a BTreeSet<u16>,
+	/// Known register values at the start of every block
+	pub values: &'a [Values],
+	pub clobbers: &'a BTreeMap<u16, u16>,
+	/// Targets of `goto`, they are given a label
+	pub gotos: BTreeSet<u16>,
+}
+
+impl Structurer<'_> {
+	/// Statements of a function occupying `start..end`.
+	pub fn function(&mut self, start: u16, end: u16) -> Vec<Stmt> {
+		self.region(start, end, None, None)
+	}
+	
+	/// Statements for the code in `start..end`, `follow` is where execution continues after the region, so a jump
+	/// to it at the end of the region is left out.
+	fn region(&mut self, start: u16, end: u16, follow: Option<u16>, context: Option<Loop>) -> Vec<Stmt> {
+		let mut statements = Vec::new();
+		let mut address = start;
+		
+		while address < end {
+			// Labels of loop headers are placed before the loop
+			if self.gotos.contains(&address) && !(address == start && context.is_some_and(|context| context.header == address)) {
+				statements.push(Stmt::Label(self.name(address)));
+			}
+			
+			if context.is_none_or(|context| context.header != address) {
+				if let Some(exit) = self.latch(address, end) {
+					statements.push(self.r#loop(address, exit));
+					address = exit;
+					continue;
+				}
+			}
+			
+			let Some(block) = self.cfg.block_at(address) else { break };
+			let block_end = self.cfg.blocks[block].addresses.end.min(end);
+			let translation = self.translate(block);
+			
+			statements.extend(translation.statements);
+			address = block_end;
+			
+			match self.cfg.code[block_end as usize - 1] {
+				Instruction::JMP { addr } if block_end == end && Some(addr) == follow => {}
+				Instruction::JMP { addr } => statements.push(self.jump(block_end - 1, addr, context)),
+				Instruction::BRH { addr, .. } if block_end == end && context.is_some_and(|context| context.do_while && addr == context.header) => {}
+				Instruction::BRH { addr, .. } => {
+					let (condition, negated) = translation.condition.unwrap();
+					
+					if addr > block_end && addr <= end && !self.is_exit(addr, context) {
+						let (statement, next) = self.r#if(negated, block_end, addr, end, context);
+						statements.extend(statement);
+						address = next;
+					} else {
+						let jump = self.jump(block_end - 1, addr, context);
+						statements.push(Stmt::If { condition, then: vec![jump], otherwise: Vec::new() });
+					}
+				}
+				_ => {}
+			}
+		}
+		
+		statements
+	}
+	
+	/// End of the loop starting at `header`, given by the last jump back to it within the region.
+	fn latch(&self, header: u16, end: u16) -> Option<u16> {
+		(header..end).rev()
+		             .find(|&address| matches!(self.cfg.code[address as usize],
+			             Instruction::JMP { addr } | Instruction::BRH { addr, .. } if addr == header))
+		             .map(|address| address + 1)
+	}
+	
+	fn r#loop(&mut self, header: u16, exit: u16) -> Stmt {
+		let header_block = self.cfg.block_at(header).unwrap();
+		let header_end = self.cfg.blocks[header_block].addresses.end;
+		
+		match self.cfg.code[exit as usize - 1] {
+			Instruction::BRH { .. } => {
+				let condition = self.translate(self.cfg.block_at(exit - 1).unwrap()).condition.unwrap().0;
+				let body = self.region(header, exit, Some(header), Some(Loop { header, exit, do_while: true }));
+				
+				Stmt::DoWhile { body, condition }
+			}
+			// The header only compares and leaves the loop, so it becomes the condition of a `while`
+			_ if header_end < exit && self.is_test(header, header_end, exit) => {
+				let translation = self.translate(header_block);
+				let body = self.region(header_end, exit, Some(header), Some(Loop { header, exit, do_while: false }));
+				
+				Stmt::While { condition: translation.condition.unwrap().1, body }
+			}
+			_ => {
+				let body = self.region(header, exit, Some(header), Some(Loop { header, exit, do_while: false }));
+				
+				Stmt::While { condition: "true".to_owned(), body }
+			}
+		}
+	}
+	
+	/// `if` with an optional `else`, the `BRH` skips `start..target` unless the negated condition holds. Returns
+	/// the statement along with the address after it.
+	fn r#if(&mut self, negated: String, start: u16, target: u16, end: u16, context: Option<Loop>) -> (Option<Stmt>, u16) {
+		// A `JMP` over the code after the target separates the `then` part from the `else` part
+		let otherwise = match self.cfg.code[target as usize - 1] {
+			Instruction::JMP { addr } if target > start && addr > target && addr <= end && !self.is_exit(addr, context) => Some(addr),
+			_ => None,
+		};
+		
+		let (then, otherwise, next) = match otherwise {
+			Some(join) => (self.region(start, target, Some(join), context), self.region(target, join, Some(join), context), join),
+			None => (self.region(start, target, Some(target), context), Vec::new(), target),
+		};
+		
+		if then.is_empty() && otherwise.is_empty() {
+			return (None, next);
+		}
+		
+		(Some(Stmt::If { condition: negated, then, otherwise }), next)
+	}
+	
+	/// `break`, `continue`, a tail call or a `goto` for a jump from `address`.
+	fn jump(&mut self, address: u16, target: u16, context: Option<Loop>) -> Stmt {
+		let text = match context {
+			Some(context) if target == context.exit => "break;".to_owned(),
+			Some(context) if target == context.header => "continue;".to_owned(),
+			_ if self.entries.contains(&target) => format!("return {}();", self.name(target)),
+			_ => {
+				self.gotos.insert(target);
+				format!("goto {};", self.name(target))
+			}
+		};
+		
+		Stmt::Simple { address, text }
+	}
+	
+	fn is_exit(&self, address: u16, context: Option<Loop>) -> bool {
+		context.is_some_and(|context| address == context.exit)
+	}
+	
+	/// Returns `true` if the block only sets the flags and branches to `exit`.
+	fn is_test(&self, start: u16, end: u16, exit: u16) -> bool {
+		let code = &self.cfg.code[start as usize..end as usize];
+		
+		matches!(code.last(), Some(&Instruction::BRH { addr, .. }) if addr == exit) &&
+		code[..code.len() - 1].iter().all(|instruction| match instruction {
+			Instruction::NOP => true,
+			Instruction::ADD { .. } | Instruction::SUB { .. } | Instruction::NOR { .. } |
+			Instruction::AND { .. } | Instruction::XOR { .. } => instruction.writes() == 0,
+			_ => false,
+		})
+	}
+	
+	fn translate(&self, block: usize) -> Translation {
+		let addresses = self.cfg.blocks[block].addresses.clone();
+		translate(self.cfg.code, addresses, self.values[block], self.clobbers, |address| self.name(address))
+	}
+	
+	fn name(&self, address: u16) -> String {
+		self.names.get(&address).cloned().unwrap_or_else(|| format!("label_{address:03X}"))
+	}
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::isa::{Cond, Instruction};
+use super::Stmt;
+use super::values::{self, Values};
+
+/// Statements of a basic block, without the `JMP` or `BRH` ending it.
+pub(super) struct Translation {
+	pub statements: Vec<Stmt>,
+	/// Condition of the `BRH` ending the block and its negation
+	pub condition: Option<(String, String)>,
+}
+
+/// Translates the instructions of a block, `name` gives the name of a called subroutine.
+///
+/// Registers with a known value at the start of the block are used to resolve memory-mapped IO.
+pub(super) fn translate(code: &[Instruction], addresses: Range<u16>, known: Values, clobbers: &BTreeMap<u16, u16>, name: impl Fn(u16) -> String) -> Translation {
+	let mut statements = Vec::new();
+	let mut condition = None;
+	let mut known = known;
+	// Last instruction which set the flags and registers written since
+	let mut flags: Option<(Instruction, u16)> = None;
+	
+	for address in addresses {
+		let instruction = code[address as usize];
+		let text = match instruction {
+			Instruction::NOP | Instruction::JMP { .. } => None,
+			Instruction::BRH { cond, .. } => {
+				condition = Some((self::condition(flags, cond), self::condition(flags, inverse(cond))));
+				None
+			}
+			Instruction::HLT => Some("halt();".to_owned()),
+			Instruction::RET => Some("return;".to_owned()),
+			Instruction::CAL { addr } => Some(format!("{}();", name(addr))),
+			Instruction::LOD { a, b, offset } => Some(match address_of(&known, a, offset) {
+				Some(port @ 240..) => match read_port(port) {
+					Some(value) => format!("{} = {value};", reg(b)),
+					None => format!("{} = io[{port}];", reg(b)),
+				},
+				Some(addr) => format!("{} = mem[{addr}];", reg(b)),
+				None => format!("{} = mem[{}];", reg(b), offset_expr(a, offset)),
+			}),
+			Instruction::STR { a, b, offset } => Some(match address_of(&known, a, offset) {
+				Some(port @ 240..) => write_port(port, &reg(b)),
+				Some(addr) => format!("mem[{addr}] = {};", reg(b)),
+				None => format!("mem[{}] = {};", offset_expr(a, offset), reg(b)),
+			}),
+			instruction => assignment(instruction),
+		};
+		
+		statements.extend(text.map(|text| Stmt::Simple { address, text }));
+		
+		match instruction {
+			Instruction::ADD { .. } | Instruction::SUB { .. } | Instruction::NOR { .. } |
+			Instruction::AND { .. } | Instruction::XOR { .. } | Instruction::ADI { .. } => flags = Some((instruction, 0)),
+			Instruction::CAL { .. } => flags = None,
+			_ => if let Some((_, written)) = &mut flags {
+				*written |= instruction.writes();
+			},
+		}
+		
+		values::step(&mut known, instruction, clobbers);
+	}
+	
+	Translation { statements, condition }
+}
+
+fn reg(reg: u8) -> String {
+	if reg == 0 { "0".to_owned() } else { format!("r{reg}") }
+}
+
+fn offset_expr(reg: u8, offset: i8) -> String {
+	match offset {
+		0 => self::reg(reg),
+		1.. => format!("{} + {offset}", self::reg(reg)),
+		_ => format!("{} - {}", self::reg(reg), offset.unsigned_abs()),
+	}
+}
+
+fn address_of(known: &Values, reg: u8, offset: i8) -> Option<u8> {
+	known[reg as usize].map(|value| value.wrapping_add_signed(offset))
+}
+
+/// Expression read from an IO port, see [`DEFAULT_SYMBOLS`](crate::asm::DEFAULT_SYMBOLS).
+fn read_port(port: u8) -> Option<&'static str> {
+	match port {
+		244 => Some("screen.load_pixel()"),
+		254 => Some("rng()"),
+		255 => Some("controller_input()"),
+		_ => None,
+	}
+}
+
+/// Statement writing a value to an IO port.
+fn write_port(port: u8, value: &str) -> String {
+	match port {
+		240 => format!("screen.pixel_x = {value};"),
+		241 => format!("screen.pixel_y = {value};"),
+		242 => "screen.draw_pixel();".to_owned(),
+		243 => "screen.clear_pixel();".to_owned(),
+		245 => "screen.buffer();".to_owned(),
+		246 => "screen.clear_buffer();".to_owned(),
+		247 => format!("chars.write({value});"),
+		248 => "chars.buffer();".to_owned(),
+		249 => "chars.clear_buffer();".to_owned(),
+		250 => format!("number.show({value});"),
+		251 => "number.clear();".to_owned(),
+		252 => "number.signed_mode();".to_owned(),
+		253 => "number.unsigned_mode();".to_owned(),
+		_ => format!("io[{port}] = {value};"),
+	}
+}
+
+/// Assignment done by an ALU instruction, `None` if it only sets the flags.
+fn assignment(instruction: Instruction) -> Option<String> {
+	if let Instruction::ADI { a, imm } = instruction {
+		let text = match imm {
+			1 => format!("{}++;", reg(a)),
+			255 => format!("{}--;", reg(a)),
+			_ => format!("{} += {imm};", reg(a)),
+		};
+		
+		return (a != 0).then_some(text);
+	}
+	
+	let (target, value) = match instruction {
+		Instruction::ADD { a, b: 0, c } | Instruction::ADD { a: 0, b: a, c } => (c, reg(a)),
+		Instruction::ADD { a, b, c } if a == b => (c, format!("{} << 1", reg(a))),
+		Instruction::ADD { a, b, c } => (c, format!("{} + {}", reg(a), reg(b))),
+		Instruction::SUB { a: 0, b, c } => (c, format!("-{}", reg(b))),
+		Instruction::SUB { a, b, c } => (c, format!("{} - {}", reg(a), reg(b))),
+		Instruction::NOR { a, b: 0, c } | Instruction::NOR { a: 0, b: a, c } => (c, format!("~{}", reg(a))),
+		Instruction::NOR { a, b, c } => (c, format!("~({} | {})", reg(a), reg(b))),
+		Instruction::AND { a, b, c } => (c, format!("{} & {}", reg(a), reg(b))),
+		Instruction::XOR { a, b, c } => (c, format!("{} ^ {}", reg(a), reg(b))),
+		Instruction::RSH { a, c } => (c, format!("{} >> 1", reg(a))),
+		Instruction::LDI { a, imm } => (a, imm.to_string()),
+		_ => return None,
+	};
+	
+	(target != 0).then(|| format!("{} = {value};", reg(target)))
+}
+
+fn inverse(cond: Cond) -> Cond {
+	match cond {
+		Cond::Zero => Cond::NotZero,
+		Cond::NotZero => Cond::Zero,
+		Cond::Carry => Cond::NotCarry,
+		Cond::NotCarry => Cond::Carry,
+	}
+}
+
+/// Readable condition of a branch, taken from the instruction which set the flags if its operands or result
+/// are still in their registers.
+fn condition(flags: Option<(Instruction, u16)>, cond: Cond) -> String {
+	let fallback = match cond {
+		Cond::Zero => "flags.zero",
+		Cond::NotZero => "!flags.zero",
+		Cond::Carry => "flags.carry",
+		Cond::NotCarry => "!flags.carry",
+	};
+	
+	let Some((setter, written)) = flags else { return fallback.to_owned() };
+	
+	// Operands are usable if neither the setter nor anything after it overwrote them
+	let operands = setter.reads() & (written | setter.writes()) == 0;
+	let result = |c: u8| (c != 0 && written & 1 << c == 0).then(|| reg(c));
+	let zero = |value: String| match cond {
+		Cond::Zero => format!("{value} == 0"),
+		_ => format!("{value} != 0"),
+	};
+	let equality = |a: u8, b: u8| match cond {
+		Cond::Zero => format!("{} == {}", reg(a), reg(b)),
+		_ => format!("{} != {}", reg(a), reg(b)),
+	};
+	
+	match (setter, cond) {
+		(Instruction::SUB { a, b, .. } | Instruction::XOR { a, b, .. }, Cond::Zero | Cond::NotZero) if operands => equality(a, b),
+		(Instruction::SUB { a, b, .. }, Cond::Carry) if operands => format!("{} >= {}", reg(a), reg(b)),
+		(Instruction::SUB { a, b, .. }, Cond::NotCarry) if operands => format!("{} < {}", reg(a), reg(b)),
+		(Instruction::ADD { a, b, .. }, Cond::Carry) if operands => format!("{} + {} > 255", reg(a), reg(b)),
+		(Instruction::ADD { a, b, .. }, Cond::NotCarry) if operands => format!("{} + {} <= 255", reg(a), reg(b)),
+		(Instruction::NOR { .. } | Instruction::AND { .. } | Instruction::XOR { .. }, Cond::Carry) => "false".to_owned(),
+		(Instruction::NOR { .. } | Instruction::AND { .. } | Instruction::XOR { .. }, Cond::NotCarry) => "true".to_owned(),
+		(Instruction::ADD { c, .. } | Instruction::SUB { c, .. } | Instruction::NOR { c, .. } |
+		 Instruction::AND { c, .. } | Instruction::XOR { c, .. } | Instruction::ADI { a: c, .. }, Cond::Zero | Cond::NotZero) => {
+			match (result(c), setter) {
+				(Some(value), _) => zero(value),
+				(None, Instruction::ADD { a, b, .. }) if operands => zero(format!("{} + {}", reg(a), reg(b))),
+				(None, Instruction::AND { a, b, .. }) if operands => zero(format!("({} & {})", reg(a), reg(b))),
+				// NOR is zero when any bit of the operands is set
+				(None, Instruction::NOR { a, b, .. }) if operands => match cond {
+					Cond::Zero => format!("({} | {}) != 0", reg(a), reg(b)),
+					_ => format!("({} | {}) == 0", reg(a), reg(b)),
+				},
+				_ => fallback.to_owned(),
+			}
+		}
+		_ => fallback.to_owned(),
+	}
+}
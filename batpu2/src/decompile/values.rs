@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use crate::isa::Instruction;
+use crate::asm::{Cfg, EdgeKind};
+
+/// Known register values, `r0` is always known.
+pub(super) type Values = [Option<u8>; 16];
+
+const UNKNOWN: Values = {
+	let mut values = [None; 16];
+	values[0] = Some(0);
+	values
+};
+
+/// Registers each function may write as a bit mask, including writes of the functions it calls.
+pub(super) fn clobbers(cfg: &Cfg) -> BTreeMap<u16, u16> {
+	let mut clobbers = BTreeMap::new();
+	let mut calls = Vec::new();
+	
+	for function in &cfg.functions {
+		let mut writes = 0;
+		let mut callees = Vec::new();
+		
+		for (_, instruction) in function.blocks.iter().flat_map(|&block| cfg.instructions(block)) {
+			match instruction {
+				Instruction::CAL { addr } => callees.push(addr),
+				instruction => writes |= instruction.writes(),
+			}
+		}
+		
+		clobbers.insert(function.entry, writes);
+		calls.push((function.entry, callees));
+	}
+	
+	let mut changed = true;
+	
+	while changed {
+		changed = false;
+		
+		for (entry, callees) in &calls {
+			let mask = callees.iter().fold(clobbers[entry], |mask, callee| mask | clobbers.get(callee).copied().unwrap_or(!0));
+			
+			if mask != clobbers[entry] {
+				clobbers.insert(*entry, mask);
+				changed = true;
+			}
+		}
+	}
+	
+	clobbers
+}
+
+/// Registers with a known value at the start of every block.
+///
+/// Values are propagated along branches, fallthroughs and calls until every predecessor agrees, a call forgets
+/// the registers clobbered by the called function.
+pub(super) fn block_values(cfg: &Cfg, clobbers: &BTreeMap<u16, u16>) -> Vec<Values> {
+	let mut values: Vec<Option<Values>> = vec![None; cfg.blocks.len()];
+	let mut pending = Vec::new();
+	
+	if let Some(start) = cfg.block_at(0) {
+		values[start] = Some(UNKNOWN);
+		pending.push(start);
+	}
+	
+	while let Some(block) = pending.pop() {
+		let mut before_call = values[block].unwrap();
+		let mut after = before_call;
+		
+		for (_, instruction) in cfg.instructions(block) {
+			before_call = after;
+			step(&mut after, instruction, clobbers);
+		}
+		
+		for edge in cfg.successors(block) {
+			let incoming = match edge.kind {
+				EdgeKind::Call => before_call,
+				EdgeKind::Branch | EdgeKind::Fallthrough => after,
+				EdgeKind::Return => continue,
+			};
+			
+			let merged = match values[edge.to] {
+				Some(current) => std::array::from_fn(|reg| current[reg].filter(|&value| incoming[reg] == Some(value))),
+				None => incoming,
+			};
+			
+			if values[edge.to] != Some(merged) {
+				values[edge.to] = Some(merged);
+				pending.push(edge.to);
+			}
+		}
+	}
+	
+	values.into_iter().map(|values| values.unwrap_or(UNKNOWN)).collect()
+}
+
+/// Updates known values after an instruction.
+pub(super) fn step(values: &mut Values, instruction: Instruction, clobbers: &BTreeMap<u16, u16>) {
+	let value = |reg: u8| values[reg as usize];
+	let binary = |a: u8, b: u8, op: fn(u8, u8) -> u8| value(a).zip(value(b)).map(|(a, b)| op(a, b));
+	
+	let (written, result) = match instruction {
+		Instruction::LDI { a, imm } => (a, Some(imm)),
+		Instruction::ADI { a, imm } => (a, value(a).map(|value| value.wrapping_add(imm))),
+		Instruction::ADD { a, b, c } => (c, binary(a, b, u8::wrapping_add)),
+		Instruction::SUB { a, b, c } => (c, binary(a, b, u8::wrapping_sub)),
+		Instruction::NOR { a, b, c } => (c, binary(a, b, |a, b| !(a | b))),
+		Instruction::AND { a, b, c } => (c, binary(a, b, |a, b| a & b)),
+		Instruction::XOR { a, b, c } => (c, binary(a, b, |a, b| a ^ b)),
+		Instruction::RSH { a, c } => (c, value(a).map(|value| value >> 1)),
+		Instruction::LOD { b, .. } => (b, None),
+		Instruction::CAL { addr } => {
+			let clobbered = clobbers.get(&addr).copied().unwrap_or(!0);
+			
+			for (reg, value) in values.iter_mut().enumerate().skip(1) {
+				if clobbered & 1 << reg != 0 {
+					*value = None;
+				}
+			}
+			
+			return;
+		}
+		_ => return,
+	};
+	
+	if written != 0 {
+		values[written as usize] = result;
+	}
+}
@@ -1,3 +1,4 @@
+use std::fmt::{self, Display, Formatter};
 use std::ops::RangeInclusive;
 use thiserror::Error;
 
@@ -25,12 +26,62 @@ impl From<Operand> for Cond {
 	}
 }
 
-impl Into<Operand> for Cond {
-	fn into(self) -> Operand {
-		self as Operand
+impl From<Cond> for Operand {
+	fn from(value: Cond) -> Self {
+		value as Operand
+	}
+}
+
+impl Display for Cond {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Cond::Zero => "zero".fmt(f),
+			Cond::NotZero => "notzero".fmt(f),
+			Cond::Carry => "carry".fmt(f),
+			Cond::NotCarry => "notcarry".fmt(f),
+		}
+	}
+}
+
+/// General purpose register operand, see [`R0`] to [`R15`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Reg(u8);
+
+impl Reg {
+	/// Returns `None` if there is no register with given index.
+	pub const fn new(index: u8) -> Option<Reg> {
+		if index < 16 { Some(Reg(index)) } else { None }
+	}
+	
+	pub const fn index(self) -> u8 {
+		self.0
+	}
+}
+
+impl From<Reg> for Operand {
+	fn from(value: Reg) -> Self {
+		value.0 as Operand
 	}
 }
 
+/// Always reads as zero, writes are discarded.
+pub const R0: Reg = Reg(0);
+pub const R1: Reg = Reg(1);
+pub const R2: Reg = Reg(2);
+pub const R3: Reg = Reg(3);
+pub const R4: Reg = Reg(4);
+pub const R5: Reg = Reg(5);
+pub const R6: Reg = Reg(6);
+pub const R7: Reg = Reg(7);
+pub const R8: Reg = Reg(8);
+pub const R9: Reg = Reg(9);
+pub const R10: Reg = Reg(10);
+pub const R11: Reg = Reg(11);
+pub const R12: Reg = Reg(12);
+pub const R13: Reg = Reg(13);
+pub const R14: Reg = Reg(14);
+pub const R15: Reg = Reg(15);
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum OperandKind {
@@ -74,7 +125,7 @@ pub(crate) fn write_masked(mut value: Operand, mask: Word) -> Word {
 	if value < 0 {
 		let umax = 1 << mask.count_ones();
 		
-		value = umax + value;
+		value += umax;
 	}
 	
 	((value as Word) << mask.trailing_zeros()) & mask
@@ -104,7 +155,7 @@ pub struct UnknownMnemonicError;
 #[error("Unknown Opcode({0})")]
 pub struct UnknownOpcodeError(pub Operand);
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum InstructionError {
 	#[error("Invalid argument count. Expected {} operands (got {got})", PrettyRange(expected))]
 	WrongOperandCount { expected: RangeInclusive<usize>, got: usize },
@@ -77,6 +77,20 @@ macro_rules! isa {
 			}
 			
 			impl Mnemonic {
+				/// All mnemonics, including aliases
+				pub const ALL: &[Mnemonic] = &[
+					$( Self::$mnemonic, )*
+					$($( Self::$alias, )*)?
+				];
+				
+				/// Names of the operands as written in assembly, e.g. `a`, `imm` or `addr`
+				pub const fn operand_names(self) -> &'static [&'static str] {
+					match self {
+						$( Self::$mnemonic => &[$($( stringify!($operand), )*)?], )*
+						$($( Self::$alias => &[$( stringify!($alias_op), )*], )*)?
+					}
+				}
+				
 				const fn operand_count(self) -> RangeInclusive<usize> {
 					match self {
 						$( Self::$mnemonic => RangeInclusive::new(count!($($( $operand )*)?) - (count!($($($( $operand_def )?)*)?)), count!($($( $operand )*)?)), )*
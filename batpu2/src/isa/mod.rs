@@ -1,6 +1,8 @@
 mod macros;
 mod common;
 
+use std::fmt::{self, Display, Formatter};
+
 pub use common::*;
 
 macros::isa! {
@@ -48,10 +50,93 @@ macros::isa! {
 	}
 }
 
+impl Instruction {
+	/// Registers read by the instruction as a bit mask, bit `n` stands for `rn`. `r0` is never included.
+	pub fn reads(self) -> u16 {
+		let mask = match self {
+			Instruction::ADD { a, b, .. } | Instruction::SUB { a, b, .. } | Instruction::NOR { a, b, .. } |
+			Instruction::AND { a, b, .. } | Instruction::XOR { a, b, .. } | Instruction::STR { a, b, .. } => 1 << a | 1 << b,
+			Instruction::RSH { a, .. } | Instruction::ADI { a, .. } | Instruction::LOD { a, .. } => 1 << a,
+			_ => 0,
+		};
+		
+		mask & !1
+	}
+	
+	/// Registers written by the instruction as a bit mask, bit `n` stands for `rn`. `r0` is never included.
+	pub fn writes(self) -> u16 {
+		let mask = match self {
+			Instruction::ADD { c, .. } | Instruction::SUB { c, .. } | Instruction::NOR { c, .. } |
+			Instruction::AND { c, .. } | Instruction::XOR { c, .. } | Instruction::RSH { c, .. } => 1 << c,
+			Instruction::LDI { a, .. } | Instruction::ADI { a, .. } | Instruction::LOD { b: a, .. } => 1 << a,
+			_ => 0,
+		};
+		
+		mask & !1
+	}
+}
+
+impl Display for Instruction {
+	/// Disassembles the instruction, the output can be assembled back into the same instruction.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::isa::{Cond, Instruction};
+	///
+	/// assert_eq!(Instruction::ADD { a: 1, b: 2, c: 3 }.to_string(), "ADD r1 r2 r3");
+	/// assert_eq!(Instruction::BRH { cond: Cond::NotZero, addr: 12 }.to_string(), "BRH notzero 12");
+	/// assert_eq!(Instruction::LOD { a: 4, b: 5, offset: -1 }.to_string(), "LOD r4 r5 -1");
+	/// ```
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match *self {
+			Instruction::NOP => write!(f, "NOP"),
+			Instruction::HLT => write!(f, "HLT"),
+			Instruction::ADD { a, b, c } => write!(f, "ADD r{a} r{b} r{c}"),
+			Instruction::SUB { a, b, c } => write!(f, "SUB r{a} r{b} r{c}"),
+			Instruction::NOR { a, b, c } => write!(f, "NOR r{a} r{b} r{c}"),
+			Instruction::AND { a, b, c } => write!(f, "AND r{a} r{b} r{c}"),
+			Instruction::XOR { a, b, c } => write!(f, "XOR r{a} r{b} r{c}"),
+			Instruction::RSH { a, c } => write!(f, "RSH r{a} r{c}"),
+			Instruction::LDI { a, imm } => write!(f, "LDI r{a} {imm}"),
+			Instruction::ADI { a, imm } => write!(f, "ADI r{a} {imm}"),
+			Instruction::JMP { addr } => write!(f, "JMP {addr}"),
+			Instruction::BRH { cond, addr } => write!(f, "BRH {cond} {addr}"),
+			Instruction::CAL { addr } => write!(f, "CAL {addr}"),
+			Instruction::RET => write!(f, "RET"),
+			Instruction::LOD { a, b, offset: 0 } => write!(f, "LOD r{a} r{b}"),
+			Instruction::LOD { a, b, offset } => write!(f, "LOD r{a} r{b} {offset}"),
+			Instruction::STR { a, b, offset: 0 } => write!(f, "STR r{a} r{b}"),
+			Instruction::STR { a, b, offset } => write!(f, "STR r{a} r{b} {offset}"),
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 	
+	#[test]
+	fn register_usage() {
+		assert_eq!(Instruction::ADD { a: 1, b: 2, c: 3 }.reads(), 0b110);
+		assert_eq!(Instruction::ADD { a: 1, b: 2, c: 3 }.writes(), 0b1000);
+		assert_eq!(Instruction::SUB { a: 0, b: 4, c: 0 }.writes(), 0);
+		assert_eq!(Instruction::LOD { a: 5, b: 6, offset: 0 }.reads(), 1 << 5);
+		assert_eq!(Instruction::LOD { a: 5, b: 6, offset: 0 }.writes(), 1 << 6);
+		assert_eq!(Instruction::STR { a: 5, b: 6, offset: 0 }.writes(), 0);
+		assert_eq!(Instruction::CAL { addr: 3 }.reads() | Instruction::CAL { addr: 3 }.writes(), 0);
+	}
+	
+	#[test]
+	fn disassembles() {
+		for word in 0..=u16::MAX {
+			let instruction = Instruction::from(word);
+			let code = crate::utils::from_asm(&instruction.to_string()).unwrap();
+			
+			assert_eq!(code, [instruction], "{instruction}");
+		}
+	}
+	
 	#[test]
 	fn to_machine_code() {
 		assert_eq!(Instruction::NOP                                     .as_word(), 0x0000, "NOP");
@@ -4,20 +4,29 @@
 //! ### Features
 //! All features are enabled by default.
 //! - `embedded_io`: Provides an example IO implementation [`EmbeddedIO`](vm::embedded::EmbeddedIO) which depends on [rand].
+//! - `schematic`: Reads and writes Minecraft schematics of the BatPU-2 ROM with [`into_schem`](utils::into_schem) and writes datapacks loading programs with [`into_datapack`](utils::into_datapack), which depends on [flate2].
 
 #![feature(debug_closure_helpers)]
 #![feature(never_type)]
 #![cfg_attr(feature = "doc_cfg", feature(doc_auto_cfg))]
+#![allow(clippy::result_large_err)]
 
 pub mod vm;
 pub mod asm;
 pub mod isa;
+pub mod link;
+pub mod opt;
+pub mod decompile;
+pub mod sprite;
+#[cfg(feature = "embedded_io")]
+pub mod testing;
 pub mod utils;
 
 pub use vm::BatPU2;
 
 #[cfg(test)]
 #[cfg(feature = "embedded_io")]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
 	use crate::BatPU2;
 	
@@ -42,6 +51,35 @@ mod tests {
 		assert_eq!(vm.io.char_display.to_string(), "HELLOWORLD");
 	}
 	
+	#[test]
+	fn checked_mode() {
+		use crate::asm;
+		use crate::vm::RunError;
+		
+		let lines: Vec<_> = asm::parse_lines(r"
+			LDI r1 5
+		.loop
+			ASSERT r1 != 2
+			DEC r1
+			BRH nz .loop
+			ASSERT flags.zero
+			HLT
+		").collect::<Result<_, _>>().unwrap();
+		
+		let mut assembler = asm::Assembler::new(&lines);
+		let code: Vec<_> = assembler.by_ref().collect::<Result<_, _>>().unwrap();
+		
+		let mut vm = BatPU2::new(code.clone());
+		vm.step_multiple(100);
+		assert!(vm.halted);
+		
+		let mut vm = BatPU2::new(code);
+		let Err(RunError::AssertionFailed(failure)) = vm.try_step_multiple_checked(100, assembler.assertions()) else { panic!() };
+		
+		assert_eq!((failure.address, failure.assertion.line_number, failure.actual), (1, 4, 2));
+		assert_eq!((vm.pc, vm.registers[0]), (1, 2));
+	}
+	
 	#[test]
 	fn hello_asm() {
 		let mut vm = BatPU2::from_asm(r"
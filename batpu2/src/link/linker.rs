@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use crate::isa::{Instruction, MAX_CODE_LEN};
+use crate::link::{LinkError, Object, RelocationKind, RelocationTarget};
+
+/// Program produced by the linker, along with the information about where everything was placed.
+#[derive(Debug, Clone)]
+pub struct Linked {
+	pub code: Vec<Instruction>,
+	pub modules: Vec<Placement>,
+	/// Exported symbols with their absolute addresses and the index of the defining module
+	pub symbols: BTreeMap<String, (u16, usize)>,
+}
+
+/// Location of a single module in the linked ROM.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Placement {
+	pub name: String,
+	pub base: u16,
+	pub len: usize,
+}
+
+/// Places objects one after another into the ROM and resolves their relocations.
+///
+/// The first object is placed at address 0, so it should contain the program entry point.
+/// All problems found are reported at once.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+/// use batpu2::link::{self, Object};
+///
+/// let main = Object::from_asm("main", "
+/// CAL .twice
+/// HLT
+/// ").unwrap();
+///
+/// let lib = Object::from_asm("lib", "
+/// export .twice
+/// .twice ADD r1 r1 r1
+///        RET
+/// ").unwrap();
+///
+/// let linked = link::link(&[main, lib]).unwrap();
+///
+/// assert_eq!(linked.code[0], Instruction::CAL { addr: 2 });
+/// ```
+pub fn link(objects: &[Object]) -> Result<Linked, Vec<LinkError>> {
+	let mut errors = Vec::new();
+	let mut modules = Vec::with_capacity(objects.len());
+	let mut symbols: BTreeMap<String, (u16, usize)> = BTreeMap::new();
+	let mut base = 0;
+	
+	for (id, object) in objects.iter().enumerate() {
+		modules.push(Placement {
+			name: object.name.clone(),
+			base: base as u16,
+			len: object.code.len(),
+		});
+		
+		for (name, &address) in &object.exports {
+			if let Some(&(_, previous)) = symbols.get(name) {
+				errors.push(LinkError::DuplicateSymbol {
+					symbol: name.clone(),
+					module: object.name.clone(),
+					previous_module: objects[previous].name.clone(),
+				});
+			} else {
+				symbols.insert(name.clone(), (base as u16 + address, id));
+			}
+		}
+		
+		base += object.code.len();
+	}
+	
+	if base > MAX_CODE_LEN {
+		errors.push(LinkError::TooManyInstructions { len: base });
+		return Err(errors);
+	}
+	
+	let mut code = Vec::with_capacity(base);
+	
+	for (object, placement) in objects.iter().zip(&modules) {
+		let start = code.len();
+		code.extend_from_slice(&object.code);
+		
+		for relocation in &object.relocations {
+			let target = match &relocation.target {
+				RelocationTarget::Local => placement.base,
+				RelocationTarget::Symbol(symbol) => match symbols.get(symbol) {
+					Some(&(address, _)) => address,
+					None => {
+						errors.push(LinkError::MissingSymbol { symbol: symbol.clone(), module: object.name.clone() });
+						continue;
+					}
+				},
+			};
+			
+			let Some(instruction) = code.get_mut(start + relocation.address as usize)
+			                            .filter(|_| (relocation.address as usize) < object.code.len()) else {
+				errors.push(LinkError::InvalidRelocation { module: object.name.clone(), address: relocation.address });
+				continue;
+			};
+			
+			if let Err(err) = apply_relocation(instruction, relocation.kind, target) {
+				errors.push(match err {
+					RelocationError::OutOfRange(value) => LinkError::RelocationOutOfRange {
+						module: object.name.clone(),
+						address: relocation.address,
+						value,
+					},
+					RelocationError::Mismatch => LinkError::InvalidRelocation {
+						module: object.name.clone(),
+						address: relocation.address,
+					},
+				});
+			}
+		}
+	}
+	
+	if errors.is_empty() {
+		Ok(Linked { code, modules, symbols })
+	} else {
+		Err(errors)
+	}
+}
+
+enum RelocationError {
+	OutOfRange(usize),
+	Mismatch,
+}
+
+fn apply_relocation(instruction: &mut Instruction, kind: RelocationKind, target: u16) -> Result<(), RelocationError> {
+	match (kind, instruction) {
+		(RelocationKind::Addr, Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr }) => {
+			let value = *addr as usize + target as usize;
+			if value >= MAX_CODE_LEN { return Err(RelocationError::OutOfRange(value)) }
+			*addr = value as u16;
+		}
+		(RelocationKind::Imm, Instruction::LDI { imm, .. } | Instruction::ADI { imm, .. }) => {
+			let value = *imm as usize + target as usize;
+			*imm = value.try_into().map_err(|_| RelocationError::OutOfRange(value))?;
+		}
+		_ => return Err(RelocationError::Mismatch),
+	}
+	
+	Ok(())
+}
+
+impl Linked {
+	/// Renders a human readable map of module placement and symbol addresses.
+	pub fn map(&self) -> String {
+		let mut output = String::new();
+		self.write_map(&mut output).unwrap();
+		output
+	}
+	
+	fn write_map(&self, f: &mut impl Write) -> fmt::Result {
+		writeln!(f, "Modules:")?;
+		for module in &self.modules {
+			let end = (module.base as usize + module.len).saturating_sub(1);
+			writeln!(f, "  0x{:03X} - 0x{end:03X}  {:>5} words  {}", module.base, module.len, module.name)?;
+		}
+		
+		writeln!(f)?;
+		writeln!(f, "Symbols:")?;
+		
+		let mut symbols: Vec<_> = self.symbols.iter().collect();
+		symbols.sort_by_key(|&(name, &(address, _))| (address, name));
+		
+		for (name, &(address, module)) in symbols {
+			writeln!(f, "  0x{address:03X}  {name}  ({})", self.modules[module].name)?;
+		}
+		
+		writeln!(f)?;
+		writeln!(f, "Total: {} / {MAX_CODE_LEN} words", self.code.len())?;
+		
+		Ok(())
+	}
+}
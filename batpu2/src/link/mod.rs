@@ -0,0 +1,120 @@
+//! Relocatable object files and a linker combining them into a single program
+
+use thiserror::Error;
+
+mod object;
+mod linker;
+
+pub use object::*;
+pub use linker::*;
+use crate::isa::MAX_CODE_LEN;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LinkError {
+	#[error("Symbol `{symbol}` exported by `{module}` is already exported by `{previous_module}`")]
+	DuplicateSymbol {
+		symbol: String,
+		module: String,
+		previous_module: String,
+	},
+	#[error("Symbol `{symbol}` used by `{module}` is not exported by any module")]
+	MissingSymbol {
+		symbol: String,
+		module: String,
+	},
+	#[error("Linked program is too long: {len} instructions (max {MAX_CODE_LEN})")]
+	TooManyInstructions {
+		len: usize,
+	},
+	#[error("Relocated value {value} does not fit in the instruction at {module}:{address}")]
+	RelocationOutOfRange {
+		module: String,
+		address: u16,
+		value: usize,
+	},
+	#[error("Relocation at {module}:{address} does not match the instruction")]
+	InvalidRelocation {
+		module: String,
+		address: u16,
+	},
+}
+
+/// An error which can be returned when loading an object file
+#[derive(Error, Debug)]
+#[error("{line_number}: Cannot parse \"{line}\"")]
+pub struct ParseObjectError {
+	line_number: usize,
+	line: String,
+}
+
+impl ParseObjectError {
+	pub fn line_number(&self) -> usize {
+		self.line_number
+	}
+	
+	pub fn line(&self) -> &str {
+		self.line.as_str()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::isa::{Cond, Instruction};
+	
+	#[test]
+	fn links_modules() {
+		let main = Object::from_asm("main", r"
+		.loop
+		  LDI r1 7
+		  CAL .double
+		  BRH nz .loop
+		  LDI r2 .table
+		  HLT").unwrap();
+		
+		let lib = Object::from_asm("lib", r"
+		export .double
+		export .table
+		.table
+		  NOP
+		.double
+		  ADD r1 r1 r1
+		  JMP .ret
+		.ret
+		  RET").unwrap();
+		
+		assert_eq!(main.imports(), [".double", ".table"]);
+		assert_eq!(lib.to_string().parse::<Object>().unwrap(), lib);
+		
+		let linked = link(&[main, lib]).unwrap();
+		
+		assert_eq!(linked.code, [
+			Instruction::LDI { a: 1, imm: 7 },
+			Instruction::CAL { addr: 6 },
+			Instruction::BRH { cond: Cond::NotZero, addr: 0 },
+			Instruction::LDI { a: 2, imm: 5 },
+			Instruction::HLT,
+			Instruction::NOP,
+			Instruction::ADD { a: 1, b: 1, c: 1 },
+			Instruction::JMP { addr: 8 },
+			Instruction::RET,
+		]);
+		assert_eq!(linked.symbols[".double"], (6, 1));
+		assert_eq!(linked.modules[1], Placement { name: "lib".to_owned(), base: 5, len: 4 });
+	}
+	
+	#[test]
+	fn reports_symbol_errors() {
+		let main = Object::from_asm("main", "CAL .missing").unwrap();
+		let a = Object::from_asm("a", "export .f\n.f RET").unwrap();
+		let b = Object::from_asm("b", "export .f\n.f RET").unwrap();
+		
+		let errors = link(&[main, a, b]).unwrap_err();
+		
+		assert_eq!(errors, [
+			LinkError::DuplicateSymbol { symbol: ".f".to_owned(), module: "b".to_owned(), previous_module: "a".to_owned() },
+			LinkError::MissingSymbol { symbol: ".missing".to_owned(), module: "main".to_owned() },
+		]);
+	}
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::asm::{self, AsmError, Assembler};
+use crate::isa::Instruction;
+use crate::link::ParseObjectError;
+
+const HEADER: &str = "batpu2-object 1";
+
+/// Relocatable module produced by assembling a single source file.
+///
+/// Addresses stored in the object are relative to the start of the module. Objects are serialized
+/// using a line based text format:
+///
+/// ```text
+/// batpu2-object 1
+/// module math
+/// export .mul 0
+/// reloc 3 addr
+/// reloc 5 addr .print
+/// code
+/// 1000000100000000
+/// ...
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Object {
+	pub name: String,
+	pub code: Vec<Instruction>,
+	pub exports: BTreeMap<String, u16>,
+	pub relocations: Vec<Relocation>,
+}
+
+/// Instruction field which has to be patched once the final address of a symbol is known.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Relocation {
+	/// Module relative address of the patched instruction
+	pub address: u16,
+	pub kind: RelocationKind,
+	pub target: RelocationTarget,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RelocationKind {
+	/// `addr` operand of `JMP`, `BRH` and `CAL`
+	Addr,
+	/// `imm` operand of `LDI` and `ADI`
+	Imm,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RelocationTarget {
+	/// Field holds a module relative address, the module base is added to it.
+	Local,
+	/// Field holds an offset, the address of an imported symbol is added to it.
+	Symbol(String),
+}
+
+impl Object {
+	/// Parses and assembles a module from a source code written in BatPU2 assembly
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::link::Object;
+	///
+	/// let object = Object::from_asm("lib", "
+	/// export .twice
+	/// .twice ADD r1 r1 r1
+	///        JMP .done
+	/// ").unwrap();
+	///
+	/// assert_eq!(object.exports[".twice"], 0);
+	/// assert_eq!(object.imports(), [".done"]);
+	/// ```
+	pub fn from_asm<'c>(name: &str, code: &'c str) -> Result<Object, AsmError<'c>> {
+		let lines = asm::parse_lines(code).collect::<Result<Vec<_>, _>>()?;
+		let mut assembler = Assembler::new(&lines).relocatable();
+		let code = assembler.by_ref().collect::<Result<Vec<_>, _>>()?;
+		
+		Ok(Object::new(name, code, &assembler))
+	}
+	
+	/// Creates an object from instructions and symbols collected by a relocatable [`Assembler`].
+	pub fn new(name: &str, code: Vec<Instruction>, assembler: &Assembler) -> Object {
+		Object {
+			name: name.to_owned(),
+			code,
+			exports: assembler.exports()
+			                  .iter()
+			                  .map(|&(name, address)| (name.to_owned(), address))
+			                  .collect(),
+			relocations: assembler.relocations().to_vec(),
+		}
+	}
+	
+	/// Names of symbols this module expects other modules to export.
+	pub fn imports(&self) -> Vec<&str> {
+		let mut imports: Vec<&str> = self.relocations
+		                                 .iter()
+		                                 .filter_map(|relocation| match &relocation.target {
+			                                 RelocationTarget::Symbol(symbol) => Some(symbol.as_str()),
+			                                 RelocationTarget::Local => None,
+		                                 })
+		                                 .collect();
+		
+		imports.sort_unstable();
+		imports.dedup();
+		imports
+	}
+}
+
+impl Display for Object {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{HEADER}")?;
+		writeln!(f, "module {}", self.name)?;
+		
+		for (name, address) in &self.exports {
+			writeln!(f, "export {name} {address}")?;
+		}
+		
+		for relocation in &self.relocations {
+			write!(f, "reloc {} {}", relocation.address, relocation.kind)?;
+			if let RelocationTarget::Symbol(symbol) = &relocation.target {
+				write!(f, " {symbol}")?;
+			}
+			writeln!(f)?;
+		}
+		
+		writeln!(f, "code")?;
+		
+		for instruction in &self.code {
+			writeln!(f, "{:016b}", instruction.as_word())?;
+		}
+		
+		Ok(())
+	}
+}
+
+impl FromStr for Object {
+	type Err = ParseObjectError;
+	
+	/// Loads an object from its text representation
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::link::Object;
+	///
+	/// let object = Object::from_asm("main", "CAL .print").unwrap();
+	///
+	/// assert_eq!(object.to_string().parse::<Object>().unwrap(), object);
+	/// ```
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let mut lines = text.lines()
+		                    .enumerate()
+		                    .map(|(line_number, line)| (line_number + 1, line.trim()))
+		                    .filter(|(_, line)| !line.is_empty());
+		
+		let error = |line_number: usize, line: &str| ParseObjectError { line_number, line: line.to_owned() };
+		
+		match lines.next() {
+			Some((_, HEADER)) => {}
+			Some((line_number, line)) => return Err(error(line_number, line)),
+			None => return Err(error(1, "")),
+		}
+		
+		let mut object = Object {
+			name: String::new(),
+			code: Vec::new(),
+			exports: BTreeMap::new(),
+			relocations: Vec::new(),
+		};
+		
+		for (line_number, line) in lines.by_ref() {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			
+			match words[..] {
+				["module", name] => object.name = name.to_owned(),
+				["export", name, address] => {
+					let address = address.parse().map_err(|_| error(line_number, line))?;
+					object.exports.insert(name.to_owned(), address);
+				}
+				["reloc", address, kind, ref target @ ..] => {
+					let address = address.parse().map_err(|_| error(line_number, line))?;
+					let kind = match kind {
+						"addr" => RelocationKind::Addr,
+						"imm" => RelocationKind::Imm,
+						_ => return Err(error(line_number, line)),
+					};
+					let target = match *target {
+						[] => RelocationTarget::Local,
+						[symbol] => RelocationTarget::Symbol(symbol.to_owned()),
+						_ => return Err(error(line_number, line)),
+					};
+					
+					object.relocations.push(Relocation { address, kind, target });
+				}
+				["code"] => break,
+				_ => return Err(error(line_number, line)),
+			}
+		}
+		
+		for (line_number, line) in lines {
+			let word = u16::from_str_radix(line, 2).map_err(|_| error(line_number, line))?;
+			object.code.push(word.into());
+		}
+		
+		Ok(object)
+	}
+}
+
+impl Display for RelocationKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			RelocationKind::Addr => "addr".fmt(f),
+			RelocationKind::Imm => "imm".fmt(f),
+		}
+	}
+}
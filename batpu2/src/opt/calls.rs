@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+use crate::isa::Instruction;
+use crate::opt::walk_body;
+
+/// Number of return addresses held by [`BatPU2::call_stack`](crate::BatPU2::call_stack), a deeper call
+/// overwrites the oldest one.
+pub const CALL_STACK_LEN: usize = 16;
+
+/// Call graph of a program along with the maximum call depth of every subroutine.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CallReport {
+	/// The start of the program followed by every `CAL` target, in order of address
+	pub functions: Vec<CallInfo>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CallInfo {
+	pub address: u16,
+	/// First label at the address in alphabetical order, `(start)` or the address if there is none
+	pub name: String,
+	/// Addresses of subroutines called directly
+	pub callees: BTreeSet<u16>,
+	pub depth: CallDepth,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CallDepth {
+	/// Maximum number of return addresses pushed, along with the deepest chain of calls starting at the subroutine
+	Bounded { depth: usize, chain: Vec<String> },
+	/// The subroutine can reach a cycle of calls, the chain ends by repeating a subroutine
+	Recursive { chain: Vec<String> },
+}
+
+impl CallReport {
+	/// Builds the call graph from `CAL` targets, the body of a subroutine is everything reachable from
+	/// its address without following calls, up to `RET` or `HLT`.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::opt::{CallDepth, CallReport};
+	/// use batpu2::utils;
+	///
+	/// let code = utils::from_asm("
+	///   CAL .a
+	///   HLT
+	/// .a
+	///   CAL .b
+	///   RET
+	/// .b
+	///   RET
+	/// ").unwrap();
+	///
+	/// let report = CallReport::new([(".a", 2), (".b", 4)], &code);
+	///
+	/// assert_eq!(report.start().depth, CallDepth::Bounded { depth: 2, chain: vec!["(start)".into(), ".a".into(), ".b".into()] });
+	/// assert!(!report.overflows());
+	/// ```
+	pub fn new<'a>(labels: impl IntoIterator<Item=(&'a str, u16)>, code: &[Instruction]) -> Self {
+		let mut names: BTreeMap<u16, &str> = BTreeMap::new();
+		
+		for (name, address) in labels {
+			names.entry(address).and_modify(|first| *first = name.min(first)).or_insert(name);
+		}
+		
+		let mut graph: BTreeMap<u16, BTreeSet<u16>> = BTreeMap::new();
+		let mut pending = vec![0];
+		
+		while let Some(function) = pending.pop() {
+			if graph.contains_key(&function) {
+				continue;
+			}
+			
+			let callees = callees(code, function);
+			pending.extend(&callees);
+			graph.insert(function, callees);
+		}
+		
+		let name = |address: u16| match names.get(&address) {
+			Some(name) => name.to_string(),
+			None if address == 0 => "(start)".to_owned(),
+			None => format!("0x{address:03X}"),
+		};
+		
+		let mut depths = BTreeMap::new();
+		
+		for &function in graph.keys() {
+			depth(&graph, function, &mut Vec::new(), &mut depths);
+		}
+		
+		let functions = graph.iter()
+		                     .map(|(&address, callees)| {
+			                     let (depth, chain) = &depths[&address];
+			                     // Chains reused from another subroutine of a cycle may go around it more than once
+			                     let len = (1..chain.len()).find(|&i| chain[..i].contains(&chain[i])).map_or(chain.len(), |i| i + 1);
+			                     let chain = chain[..len].iter().map(|&address| name(address)).collect();
+			
+			                     CallInfo {
+				                     address,
+				                     name: name(address),
+				                     callees: callees.clone(),
+				                     depth: match depth {
+					                     Some(depth) => CallDepth::Bounded { depth: *depth, chain },
+					                     None => CallDepth::Recursive { chain },
+				                     },
+			                     }
+		                     })
+		                     .collect();
+		
+		CallReport { functions }
+	}
+	
+	/// Information about the start of the program.
+	pub fn start(&self) -> &CallInfo {
+		&self.functions[0]
+	}
+	
+	/// Returns `true` if the program may call deeper than [`CALL_STACK_LEN`] or recursively.
+	pub fn overflows(&self) -> bool {
+		match self.start().depth {
+			CallDepth::Bounded { depth, .. } => depth > CALL_STACK_LEN,
+			CallDepth::Recursive { .. } => true,
+		}
+	}
+}
+
+/// Subroutines called from the body starting at given address.
+fn callees(code: &[Instruction], entry: u16) -> BTreeSet<u16> {
+	let mut callees = BTreeSet::new();
+	
+	walk_body(code, entry, |_, instruction| {
+		if let Instruction::CAL { addr } = instruction {
+			callees.insert(addr);
+		}
+	});
+	
+	callees
+}
+
+/// Depth and deepest chain of a subroutine, the depth is `None` if it can reach recursion.
+type Depth = (Option<usize>, Vec<u16>);
+
+fn depth(graph: &BTreeMap<u16, BTreeSet<u16>>, function: u16, path: &mut Vec<u16>, depths: &mut BTreeMap<u16, Depth>) -> Depth {
+	if let Some(depth) = depths.get(&function) {
+		return depth.clone();
+	}
+	
+	// Callers on the path prepend themselves, which completes the cycle
+	if path.contains(&function) {
+		return (None, vec![function]);
+	}
+	
+	path.push(function);
+	
+	let mut result: Depth = (Some(0), vec![function]);
+	
+	for &callee in &graph[&function] {
+		let (depth, chain) = depth(graph, callee, path, depths);
+		let chain = [function].into_iter().chain(chain).collect();
+		
+		match (depth, &result.0) {
+			(None, Some(_)) => result = (None, chain),
+			(Some(depth), Some(max)) if depth + 1 > *max => result = (Some(depth + 1), chain),
+			_ => {}
+		}
+	}
+	
+	path.pop();
+	
+	// Depths within a cycle depend on where it was entered, only the first subroutine is final
+	if result.0.is_some() || path.is_empty() {
+		depths.insert(function, result.clone());
+	}
+	
+	result
+}
+
+impl Display for CallReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let width = self.functions.iter().map(|function| function.name.len()).max().unwrap_or(0);
+		
+		for function in &self.functions {
+			match &function.depth {
+				CallDepth::Bounded { depth, chain } => writeln!(f, "{:width$}  depth {depth:<3} {}", function.name, chain.join(" -> "))?,
+				CallDepth::Recursive { chain } => writeln!(f, "{:width$}  recursive {}", function.name, chain.join(" -> "))?,
+			}
+		}
+		
+		match &self.start().depth {
+			CallDepth::Bounded { depth, .. } => write!(f, "maximum call depth {depth} of {CALL_STACK_LEN}"),
+			CallDepth::Recursive { .. } => write!(f, "unbounded call depth, the program is recursive"),
+		}
+	}
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use thiserror::Error;
+
+use crate::asm::{self, AsmError, Dialect, Line};
+use crate::isa::Instruction;
+use crate::opt::walk;
+
+/// Number of instructions examined after a call, beyond it the clobbered value is assumed to be unused.
+const READ_LIMIT: usize = 256;
+
+/// Calling convention of a subroutine, declared by a `; @func` comment.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Convention {
+	/// Label of the subroutine
+	pub name: String,
+	pub line_number: usize,
+	/// Registers as bit masks, bit `n` stands for `rn`
+	pub inputs: u16,
+	pub outputs: u16,
+	pub clobbers: u16,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConventionError {
+	#[error("Invalid annotation on line {line_number}: {message}")]
+	InvalidAnnotation {
+		line_number: usize,
+		message: String,
+	},
+	#[error("Function `{function}` annotated on line {line_number} has no label")]
+	UnknownFunction {
+		line_number: usize,
+		function: String,
+	},
+	#[error("`{function}` writes r{register} on line {line_number}, which is not declared in `out` or `clobbers`")]
+	UndeclaredWrite {
+		line_number: usize,
+		function: String,
+		register: u8,
+	},
+	#[error("r{register} is read on line {line_number} after `CAL {function}` on line {call_line_number}, which clobbers it")]
+	ClobberedRead {
+		line_number: usize,
+		function: String,
+		register: u8,
+		call_line_number: usize,
+	},
+}
+
+impl ConventionError {
+	pub fn line_num(&self) -> usize {
+		match *self {
+			ConventionError::InvalidAnnotation { line_number, .. } => line_number,
+			ConventionError::UnknownFunction { line_number, .. } => line_number,
+			ConventionError::UndeclaredWrite { line_number, .. } => line_number,
+			ConventionError::ClobberedRead { line_number, .. } => line_number,
+		}
+	}
+}
+
+/// Checks subroutines against their calling conventions.
+///
+/// Subroutines are annotated by a comment `@func name in=r1,r2 out=r3 clobbers=r4-r6`, the name refers to
+/// the label of the subroutine with or without the leading dot. Registers not listed in `out` or
+/// `clobbers` have to be preserved. The checker reports:
+/// - writes to registers which are not declared, including writes done by called subroutines,
+/// - reads of registers clobbered by a `CAL`, before they are written again by the caller.
+///
+/// Annotations in [included](crate::asm::STD_FILES) files are taken into account.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::Dialect;
+/// use batpu2::opt;
+///
+/// let errors = opt::check_conventions("
+///   LDI r4 1
+///   CAL .double
+///   ADD r3 r4 r3
+///   HLT
+/// ; @func double in=r1 out=r3 clobbers=r4
+/// .double
+///   MOV r1 r4
+///   ADD r4 r1 r3
+///   RET
+/// ", Dialect::Extended).unwrap();
+///
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].to_string(), "r4 is read on line 4 after `CAL .double` on line 3, which clobbers it");
+/// ```
+pub fn check_conventions(source: &str, dialect: Dialect) -> Result<Vec<ConventionError>, AsmError<'_>> {
+	let lines = asm::parse_lines_with(source, dialect).collect::<Result<Vec<_>, _>>()?;
+	let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+	let mut line_numbers = Vec::new();
+	let mut code = Vec::new();
+	
+	while let Some(result) = assembler.next() {
+		code.push(result?);
+		line_numbers.extend(assembler.current_line().map(|line| line.line_number));
+	}
+	
+	let (conventions, mut errors) = parse_conventions(&lines);
+	let mut functions = HashMap::new();
+	
+	for convention in conventions {
+		match assembler.symbols().get(convention.name.as_str()).filter(|symbol| symbol.label) {
+			Some(symbol) => {
+				functions.insert(symbol.value as usize, convention);
+			}
+			None => errors.push(ConventionError::UnknownFunction { line_number: convention.line_number, function: convention.name }),
+		}
+	}
+	
+	let checker = Checker { code: &code, line_numbers: &line_numbers, functions: &functions };
+	
+	for (&address, convention) in &functions {
+		checker.check_writes(address, convention, &mut errors);
+	}
+	
+	for (address, instruction) in code.iter().enumerate() {
+		if let Instruction::CAL { addr } = instruction {
+			if let Some(convention) = functions.get(&(*addr as usize)) {
+				checker.check_reads(address, convention, &mut errors);
+			}
+		}
+	}
+	
+	errors.sort_by_key(|err| (err.line_num(), err.to_string()));
+	errors.dedup();
+	
+	Ok(errors)
+}
+
+/// Collects `@func` annotations from comments.
+fn parse_conventions(lines: &[Line]) -> (Vec<Convention>, Vec<ConventionError>) {
+	let mut conventions = Vec::new();
+	let mut errors = Vec::new();
+	
+	for line in lines {
+		let Some(comment) = line.comment else { continue };
+		let mut words = comment.trim_start_matches([';', '#', '/']).split_whitespace();
+		
+		if words.next() != Some("@func") {
+			continue;
+		}
+		
+		let error = |message: String| ConventionError::InvalidAnnotation { line_number: line.line_number, message };
+		
+		let Some(name) = words.next() else {
+			errors.push(error("expected a function name after `@func`".into()));
+			continue;
+		};
+		
+		let mut convention = Convention {
+			name: if name.starts_with('.') { name.to_owned() } else { format!(".{name}") },
+			line_number: line.line_number,
+			inputs: 0,
+			outputs: 0,
+			clobbers: 0,
+		};
+		
+		let result = words.try_for_each(|word| {
+			let (key, list) = word.split_once('=').ok_or_else(|| error(format!("expected `in=`, `out=` or `clobbers=`, found `{word}`")))?;
+			let registers = parse_registers(list).ok_or_else(|| error(format!("invalid register list `{list}`")))?;
+			
+			match key {
+				"in" => convention.inputs |= registers,
+				"out" => convention.outputs |= registers,
+				"clobbers" => convention.clobbers |= registers,
+				_ => return Err(error(format!("unknown key `{key}`, expected `in`, `out` or `clobbers`"))),
+			}
+			
+			Ok(())
+		});
+		
+		match result {
+			Ok(()) => conventions.push(convention),
+			Err(err) => errors.push(err),
+		}
+	}
+	
+	(conventions, errors)
+}
+
+/// Parses registers separated by commas, `r3-r6` stands for a range.
+fn parse_registers(list: &str) -> Option<u16> {
+	let register = |name: &str| name.strip_prefix('r')?.parse::<u8>().ok().filter(|&reg| reg < 16);
+	let mut mask = 0;
+	
+	for item in list.split(',').filter(|item| !item.is_empty()) {
+		let (first, last) = match item.split_once('-') {
+			Some((first, last)) => (register(first)?, register(last)?),
+			None => (register(item)?, register(item)?),
+		};
+		
+		for reg in first..=last {
+			mask |= 1 << reg;
+		}
+	}
+	
+	Some(mask & !1)
+}
+
+struct Checker<'a> {
+	code: &'a [Instruction],
+	line_numbers: &'a [usize],
+	functions: &'a HashMap<usize, Convention>,
+}
+
+impl Checker<'_> {
+	/// Registers a call to given address may change, `None` for subroutines without an annotation.
+	fn effect(&self, address: usize) -> Option<u16> {
+		self.functions.get(&address).map(|convention| convention.outputs | convention.clobbers)
+	}
+	
+	/// Walks the body of a subroutine, unannotated subroutines it calls are treated as part of the body.
+	fn check_writes(&self, entry: usize, convention: &Convention, errors: &mut Vec<ConventionError>) {
+		let allowed = convention.outputs | convention.clobbers;
+		let mut writes: BTreeMap<u8, usize> = BTreeMap::new();
+		
+		walk(self.code, [entry as u16], |address, instruction| {
+			let (written, target) = match instruction {
+				Instruction::HLT | Instruction::RET => (0, None),
+				Instruction::JMP { addr } if addr as usize != entry => match self.effect(addr as usize) {
+					// Tail call of another subroutine
+					Some(effect) => (effect, None),
+					None => (0, Some(addr as usize)),
+				},
+				Instruction::JMP { addr } | Instruction::BRH { addr, .. } => (0, Some(addr as usize)),
+				Instruction::CAL { addr } => match self.effect(addr as usize) {
+					Some(effect) => (effect, None),
+					None => (0, Some(addr as usize)),
+				},
+				instruction => (instruction.writes(), None),
+			};
+			
+			for reg in registers(written & !allowed) {
+				let line_number = self.line_numbers[address];
+				writes.entry(reg).and_modify(|line| *line = line_number.min(*line)).or_insert(line_number);
+			}
+			
+			target
+		});
+		
+		errors.extend(writes.into_iter().map(|(register, line_number)| ConventionError::UndeclaredWrite {
+			line_number,
+			function: convention.name.clone(),
+			register,
+		}));
+	}
+	
+	/// Looks for reads of registers clobbered by a call, before the caller writes them.
+	fn check_reads(&self, call: usize, convention: &Convention, errors: &mut Vec<ConventionError>) {
+		for register in registers(convention.clobbers) {
+			let mask = 1 << register;
+			let mut stack = vec![call + 1];
+			let mut visited = BTreeSet::new();
+			
+			while let Some(address) = stack.pop() {
+				if visited.len() > READ_LIMIT {
+					break;
+				}
+				
+				let Some(&instruction) = self.code.get(address).filter(|_| visited.insert(address)) else { continue };
+				
+				let read = match instruction {
+					Instruction::CAL { addr } => self.functions
+					                                 .get(&(addr as usize))
+					                                 .is_some_and(|callee| callee.inputs & mask != 0),
+					instruction => instruction.reads() & mask != 0,
+				};
+				
+				if read {
+					errors.push(ConventionError::ClobberedRead {
+						line_number: self.line_numbers[address],
+						function: convention.name.clone(),
+						register,
+						call_line_number: self.line_numbers[call],
+					});
+					break;
+				}
+				
+				match instruction {
+					_ if instruction.writes() & mask != 0 => {}
+					Instruction::HLT | Instruction::RET => {}
+					Instruction::JMP { addr } => stack.push(addr as usize),
+					Instruction::BRH { addr, .. } => stack.extend([addr as usize, address + 1]),
+					// Calls of unannotated subroutines may do anything with the register
+					Instruction::CAL { addr } => if self.effect(addr as usize).is_some_and(|effect| effect & mask == 0) {
+						stack.push(address + 1);
+					},
+					_ => stack.push(address + 1),
+				}
+			}
+		}
+	}
+}
+
+/// Register numbers set in a mask.
+fn registers(mask: u16) -> impl Iterator<Item=u8> {
+	(0..16).filter(move |reg| mask & 1 << reg != 0)
+}
@@ -0,0 +1,349 @@
+//! Optimization passes and analyses of assembled programs
+
+use std::fmt::{self, Display, Formatter};
+
+mod program;
+mod peephole;
+mod reach;
+mod size;
+mod convention;
+mod calls;
+
+pub use peephole::*;
+pub use reach::*;
+pub use size::*;
+pub use convention::*;
+pub use calls::*;
+
+/// What the optimizer did, along with the program size before and after.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Report {
+	pub changes: Vec<Change>,
+	pub old_len: usize,
+	pub new_len: usize,
+}
+
+/// A single rewrite performed by the optimizer.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+	/// Address of the rewritten instruction in the original program
+	pub address: u16,
+	/// Number of instructions saved
+	pub removed: usize,
+	pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ChangeKind {
+	/// `JMP` or `BRH` to the next instruction was removed
+	JumpToNext,
+	/// Jump to a `JMP` now goes directly to its target, given as an address in the original program
+	ThreadedJump { to: u16 },
+	/// Write to `r0` which sets no flags read later was removed
+	DeadWrite,
+	/// `LDI` and `ADD` were folded into `ADI`
+	FoldedAdd,
+	/// `LDI rX 0` was removed, the next instruction reads `r0` instead
+	FoldedZero,
+	/// Consecutive `ADI`s were merged
+	MergedAdi,
+}
+
+impl Report {
+	/// Number of instructions saved.
+	pub fn removed(&self) -> usize {
+		self.old_len - self.new_len
+	}
+}
+
+impl Display for Report {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for change in &self.changes {
+			writeln!(f, "{change}")?;
+		}
+		
+		write!(f, "{} -> {} instructions, {} removed", self.old_len, self.new_len, self.removed())
+	}
+}
+
+impl Display for Change {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "0x{:03X}: ", self.address)?;
+		
+		match self.kind {
+			ChangeKind::JumpToNext => write!(f, "removed jump to the next instruction"),
+			ChangeKind::ThreadedJump { to } => write!(f, "jump threaded to 0x{to:03X}"),
+			ChangeKind::DeadWrite => write!(f, "removed unused write to r0"),
+			ChangeKind::FoldedAdd => write!(f, "folded LDI and ADD into ADI"),
+			ChangeKind::FoldedZero => write!(f, "replaced LDI of zero with r0"),
+			ChangeKind::MergedAdi => write!(f, "merged consecutive ADIs"),
+		}
+	}
+}
+
+#[cfg(test)]
+#[cfg(feature = "embedded_io")]
+mod tests {
+	use super::*;
+	use crate::BatPU2;
+	use crate::isa::{Cond, Instruction};
+	use crate::link::{self, Object};
+	
+	/// Optimizes a program and checks that it still computes the same registers.
+	fn check(code: &str) -> (Vec<Instruction>, Report) {
+		let mut object = Object::from_asm("main", code).unwrap();
+		let original = link::link(&[object.clone()]).unwrap().code;
+		let report = optimize(&mut object);
+		let optimized = link::link(&[object]).unwrap().code;
+		
+		let mut expected = BatPU2::new(original);
+		let mut actual = BatPU2::new(optimized.clone());
+		expected.step_multiple(10_000);
+		actual.step_multiple(10_000);
+		
+		assert!(expected.halted && actual.halted);
+		assert_eq!(expected.registers, actual.registers);
+		assert_eq!(report.new_len, optimized.len());
+		
+		(optimized, report)
+	}
+	
+	#[test]
+	fn removes_jumps_to_next() {
+		let (code, report) = check("
+		  LDI r1 3
+		.loop
+		  DEC r1
+		  BRH nz .next
+		.next
+		  BRH nz .loop
+		  JMP .end
+		.end
+		  LDI r2 .loop
+		  HLT");
+		
+		assert_eq!(code, [
+			Instruction::LDI { a: 1, imm: 3 },
+			Instruction::ADI { a: 1, imm: 0xFF },
+			Instruction::BRH { cond: Cond::NotZero, addr: 1 },
+			Instruction::LDI { a: 2, imm: 1 },
+			Instruction::HLT,
+		]);
+		assert_eq!(report.removed(), 2);
+		assert_eq!(report.changes.iter().map(|change| change.address).collect::<Vec<_>>(), [2, 4]);
+	}
+	
+	#[test]
+	fn threads_jumps() {
+		let (code, report) = check("
+		  CAL .first
+		  HLT
+		.first
+		  JMP .second
+		.second
+		  JMP .third
+		  NOP
+		.third
+		  LDI r1 1
+		  RET");
+		
+		assert_eq!(code[0], Instruction::CAL { addr: 5 });
+		assert_eq!(report.changes[0], Change { address: 0, removed: 0, kind: ChangeKind::ThreadedJump { to: 5 } });
+	}
+	
+	#[test]
+	fn respects_flag_liveness() {
+		let (code, _) = check("
+		  LDI r1 3
+		  CMP r1 r0
+		  CMP r1 r1
+		  BRH eq .equal
+		  LDI r2 1
+		.equal
+		  LDI r3 2
+		  HLT");
+		
+		assert_eq!(code.len(), 6);
+		assert_eq!(code[1], Instruction::SUB { a: 1, b: 1, c: 0 });
+		
+		let (_, report) = check("
+		  ADI r1 200
+		  ADI r1 100
+		  BRH c .carry
+		  ADI r2 1
+		  ADI r2 1
+		  BRH z .carry
+		.carry
+		  HLT");
+		
+		assert_eq!(report.changes.iter().map(|change| &change.kind).collect::<Vec<_>>(), [&ChangeKind::MergedAdi, &ChangeKind::JumpToNext]);
+	}
+	
+	#[test]
+	fn folds_immediates() {
+		let (code, report) = check("
+		  LDI r1 7
+		  LDI r2 5
+		  ADD r1 r2 r1
+		  LDI r3 0
+		  SUB r3 r1 r4
+		  LDI r5 9
+		  ADD r5 r1 r1
+		  MOV r5 r6
+		  LDI r2 1
+		  LDI r3 1
+		  HLT");
+		
+		assert_eq!(code[..4], [
+			Instruction::LDI { a: 1, imm: 7 },
+			Instruction::ADI { a: 1, imm: 5 },
+			Instruction::SUB { a: 0, b: 1, c: 4 },
+			Instruction::LDI { a: 5, imm: 9 },
+		]);
+		assert_eq!(report.removed(), 2);
+	}
+	
+	#[test]
+	fn keeps_merged_instructions_apart_from_targets() {
+		let mut object = Object::from_asm("main", "
+		export .entry
+		  ADI r1 1
+		.entry
+		  ADI r1 1
+		  RET").unwrap();
+		
+		let report = optimize(&mut object);
+		
+		assert!(report.changes.is_empty());
+		assert_eq!(object.exports[".entry"], 1);
+	}
+	
+	#[test]
+	fn strips_unreachable_code() {
+		let mut object = Object::from_asm("main", "
+		export .api
+		  CAL .used
+		  LDI r1 .used
+		  HLT
+		.unused
+		  LDI r2 1
+		  RET
+		.used
+		  RET
+		.api
+		  JMP .external
+		  NOP").unwrap();
+		
+		assert_eq!(strip_unreachable(&mut object), [3..5, 7..8]);
+		assert_eq!(object.code, [
+			Instruction::CAL { addr: 3 },
+			Instruction::LDI { a: 1, imm: 3 },
+			Instruction::HLT,
+			Instruction::RET,
+			Instruction::JMP { addr: 0 },
+		]);
+		assert_eq!(object.exports[".api"], 4);
+		assert_eq!(object.relocations.iter().map(|relocation| relocation.address).collect::<Vec<_>>(), [0, 1, 4]);
+	}
+	
+	#[test]
+	fn size_report() {
+		let reachable = [true, true, true, false, false, true];
+		let report = SizeReport::new([(".b", 2), (".a", 2), (".c", 5)], &reachable);
+		
+		let sizes: Vec<_> = report.entries.iter().map(|entry| (entry.labels.join(" "), entry.size, entry.unreachable)).collect();
+		assert_eq!(sizes, [("".to_owned(), 2, 0), (".a .b".to_owned(), 3, 2), (".c".to_owned(), 1, 0)]);
+		assert_eq!(report.unreachable, 2);
+		assert!(report.to_string().ends_with("total 6 of 1024 instructions (0.6%), 2 unreachable"));
+	}
+	
+	#[test]
+	fn calling_conventions() {
+		let errors = check_conventions(r"
+		  CAL .outer
+		  ADD r5 r6 r7
+		  LDI r5 1
+		  CAL .inner
+		  ADD r5 r0 r0
+		  HLT
+		; @func outer in=r1 out=r2 clobbers=r5
+		.outer
+		  CAL .helper
+		  JMP .inner
+		.helper
+		  LDI r6 3
+		  RET
+		; @func inner out=r2 clobbers=r5
+		.inner
+		  LDI r2 1
+		  LDI r5 2
+		  RET
+		; @func missing
+		; @func bad in=r16
+		", crate::asm::Dialect::Extended).unwrap();
+		
+		assert_eq!(errors, [
+			ConventionError::ClobberedRead { line_number: 3, function: ".outer".into(), register: 5, call_line_number: 2 },
+			ConventionError::ClobberedRead { line_number: 6, function: ".inner".into(), register: 5, call_line_number: 5 },
+			ConventionError::UndeclaredWrite { line_number: 13, function: ".outer".into(), register: 6 },
+			ConventionError::UnknownFunction { line_number: 20, function: ".missing".into() },
+			ConventionError::InvalidAnnotation { line_number: 21, message: "invalid register list `r16`".into() },
+		]);
+	}
+	
+	#[test]
+	fn call_depth() {
+		let code = crate::utils::from_asm(r"
+		  CAL .a
+		  CAL .loop
+		  HLT
+		.a
+		  BRH zero .skip
+		  CAL .b
+		.skip
+		  JMP .b
+		.b
+		  RET
+		.loop
+		  CAL .even
+		  RET
+		.even
+		  CAL .odd
+		  RET
+		.odd
+		  CAL .even
+		  RET
+		").unwrap();
+		
+		let report = CallReport::new([(".a", 3), (".skip", 5), (".b", 6), (".loop", 7), (".even", 9), (".odd", 11)], &code);
+		let depths: Vec<_> = report.functions.iter().map(|function| (function.name.as_str(), &function.depth)).collect();
+		let chain = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+		
+		assert_eq!(depths, [
+			("(start)", &CallDepth::Recursive { chain: chain(&["(start)", ".loop", ".even", ".odd", ".even"]) }),
+			(".a", &CallDepth::Bounded { depth: 1, chain: chain(&[".a", ".b"]) }),
+			(".b", &CallDepth::Bounded { depth: 0, chain: chain(&[".b"]) }),
+			(".loop", &CallDepth::Recursive { chain: chain(&[".loop", ".even", ".odd", ".even"]) }),
+			(".even", &CallDepth::Recursive { chain: chain(&[".even", ".odd", ".even"]) }),
+			(".odd", &CallDepth::Recursive { chain: chain(&[".odd", ".even", ".odd"]) }),
+		]);
+		assert!(report.overflows());
+		
+		let mut asm = String::new();
+		
+		for i in 0..17 {
+			asm += &format!(".f{i}\n  CAL .f{}\n  RET\n", i + 1);
+		}
+		
+		asm += ".f17\n  RET\n";
+		
+		let code = crate::utils::from_asm(&asm).unwrap();
+		let labels: Vec<_> = (0..=17).map(|i| (format!(".f{i}"), i as u16 * 2)).collect();
+		let report = CallReport::new(labels.iter().map(|(name, address)| (name.as_str(), *address)), &code);
+		
+		assert_eq!(report.start().depth, CallDepth::Bounded { depth: 17, chain: labels.iter().map(|(name, _)| name.clone()).collect() });
+		assert!(report.overflows());
+		assert!(report.to_string().ends_with("maximum call depth 17 of 16"));
+	}
+}
@@ -0,0 +1,280 @@
+use std::collections::BTreeSet;
+
+use crate::isa::{Cond, Instruction};
+use crate::link::{Object, RelocationKind, RelocationTarget};
+use crate::opt::{Change, ChangeKind, Report};
+use crate::opt::program::Program;
+
+/// Number of instructions examined when deciding if a value is still needed, beyond it the value is assumed live.
+const LIVENESS_LIMIT: usize = 256;
+/// Length of the longest chain of jumps followed when threading jumps.
+const THREAD_LIMIT: usize = 16;
+
+const ZERO: u8 = 0b01;
+const CARRY: u8 = 0b10;
+
+/// Applies peephole optimizations to an object until none of them applies, returns what was changed.
+///
+/// Working on an [`Object`] instead of plain instructions lets the optimizer know which operands refer to
+/// labels. All of them, including `LDI`s of label addresses and exported symbols, are adjusted when
+/// instructions are removed. Jumps to imported symbols are left untouched.
+///
+/// The following rewrites are performed:
+/// - `JMP` and `BRH` to the next instruction are removed,
+/// - jumps to an unconditional `JMP` go directly to its target,
+/// - writes to `r0` are removed unless they set flags which are read later,
+/// - `LDI rX imm` followed by `ADD rX rY rY` becomes `ADI rY imm` if `rX` is overwritten before it is read,
+/// - `LDI rX 0` is removed and the next instruction reads `r0` instead, if `rX` is overwritten before it is read,
+/// - consecutive `ADI`s of the same register are merged, unless the carry flag is read later.
+///
+/// Instructions which are jumped to are never merged with the preceding instruction.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+/// use batpu2::link::Object;
+/// use batpu2::opt;
+///
+/// let mut object = Object::from_asm("main", "
+///   LDI r2 5
+///   ADD r1 r2 r1
+///   JMP .end
+/// .end
+///   LDI r2 1
+///   HLT
+/// ").unwrap();
+///
+/// let report = opt::optimize(&mut object);
+///
+/// assert_eq!(object.code, [Instruction::ADI { a: 1, imm: 5 }, Instruction::LDI { a: 2, imm: 1 }, Instruction::HLT]);
+/// assert_eq!(report.removed(), 2);
+/// ```
+pub fn optimize(object: &mut Object) -> Report {
+	let mut program = Program::new(object);
+	
+	let mut report = Report { changes: Vec::new(), old_len: program.code.len(), new_len: 0 };
+	
+	loop {
+		let count = report.changes.len();
+		
+		program.thread_jumps(&mut report.changes);
+		program.rewrite(&mut report.changes);
+		
+		if report.changes.len() == count {
+			break;
+		}
+	}
+	
+	report.changes.sort_by_key(|change| change.address);
+	report.new_len = program.code.len();
+	
+	program.store(object);
+	
+	report
+}
+
+#[derive(Copy, Clone)]
+enum Resource {
+	Flags(u8),
+	Reg(u8),
+}
+
+impl Program {
+	/// Addresses which can be reached other than by falling through from the previous instruction.
+	fn targets(&self) -> BTreeSet<usize> {
+		let jumps = (0..self.code.len()).filter_map(|address| self.local_target(address));
+		let values = self.relocations
+		                 .iter()
+		                 .filter(|relocation| relocation.kind == RelocationKind::Imm && relocation.target == RelocationTarget::Local)
+		                 .filter_map(|relocation| match self.code.get(relocation.address as usize)? {
+			                 Instruction::LDI { imm, .. } | Instruction::ADI { imm, .. } => Some(*imm as usize),
+			                 _ => None,
+		                 });
+		
+		jumps.chain(values)
+		     .chain(self.exports.values().map(|&address| address as usize))
+		     .chain([0])
+		     .collect()
+	}
+	
+	/// Returns `true` if the resource may be read, starting at given address, before it is overwritten.
+	fn is_live(&self, start: usize, resource: Resource) -> bool {
+		let mut stack = vec![start];
+		let mut visited = BTreeSet::new();
+		
+		while let Some(address) = stack.pop() {
+			if !visited.insert(address) {
+				continue;
+			}
+			
+			let Some(&instruction) = self.code.get(address).filter(|_| visited.len() <= LIVENESS_LIMIT) else {
+				return true;
+			};
+			
+			if reads(instruction, resource) {
+				return true;
+			}
+			
+			if writes(instruction, resource) {
+				continue;
+			}
+			
+			match instruction {
+				// Registers can be inspected once the program halts
+				Instruction::HLT => if matches!(resource, Resource::Reg(_)) {
+					return true;
+				},
+				// The callee or the caller may read it
+				Instruction::CAL { .. } | Instruction::RET => return true,
+				Instruction::JMP { .. } | Instruction::BRH { .. } => {
+					let Some(target) = self.local_target(address) else { return true };
+					stack.push(target);
+					
+					if matches!(instruction, Instruction::BRH { .. }) {
+						stack.push(address + 1);
+					}
+				}
+				_ => stack.push(address + 1),
+			}
+		}
+		
+		false
+	}
+	
+	fn change(&self, address: usize, removed: usize, kind: ChangeKind) -> Change {
+		Change { address: self.origin[address], removed, kind }
+	}
+	
+	fn thread_jumps(&mut self, changes: &mut Vec<Change>) {
+		for address in 0..self.code.len() {
+			let Some(first) = self.local_target(address) else { continue };
+			let mut target = first;
+			let mut visited = vec![address];
+			
+			while let Some(next) = self.code
+			                           .get(target)
+			                           .filter(|instruction| matches!(instruction, Instruction::JMP { .. }))
+			                           .and_then(|_| self.local_target(target)) {
+				if visited.contains(&target) || visited.len() > THREAD_LIMIT {
+					break;
+				}
+				
+				visited.push(target);
+				target = next;
+			}
+			
+			// A cycle of jumps is left alone, it has no target to go to
+			if target == first || visited.contains(&target) {
+				continue;
+			}
+			
+			if let Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr } = &mut self.code[address] {
+				*addr = target as u16;
+			}
+			
+			let to = self.origin.get(target).copied().unwrap_or(target as u16);
+			changes.push(self.change(address, 0, ChangeKind::ThreadedJump { to }));
+		}
+	}
+	
+	fn rewrite(&mut self, changes: &mut Vec<Change>) {
+		let targets = self.targets();
+		let mut removed = vec![false; self.code.len()];
+		let mut address = 0;
+		
+		while address < self.code.len() {
+			let next = address + 1;
+			let pair = self.code.get(next).filter(|_| !targets.contains(&next) && self.relocation(next).is_none());
+			
+			match (self.code[address], pair) {
+				(Instruction::JMP { .. } | Instruction::BRH { .. }, _) if self.local_target(address) == Some(next) => {
+					removed[address] = true;
+					changes.push(self.change(address, 1, ChangeKind::JumpToNext));
+				}
+				(Instruction::LDI { a: 0, .. } | Instruction::RSH { c: 0, .. }, _) => {
+					removed[address] = true;
+					changes.push(self.change(address, 1, ChangeKind::DeadWrite));
+				}
+				(Instruction::ADD { c: 0, .. } | Instruction::SUB { c: 0, .. } | Instruction::NOR { c: 0, .. } |
+				 Instruction::AND { c: 0, .. } | Instruction::XOR { c: 0, .. } | Instruction::ADI { a: 0, .. }, _)
+				if !self.is_live(next, Resource::Flags(ZERO | CARRY)) => {
+					removed[address] = true;
+					changes.push(self.change(address, 1, ChangeKind::DeadWrite));
+				}
+				(Instruction::ADI { a, imm }, Some(&Instruction::ADI { a: a2, imm: imm2 }))
+				if a == a2 && a != 0 && self.relocation(address).is_none() && !self.is_live(next + 1, Resource::Flags(CARRY)) => {
+					let imm = imm.wrapping_add(imm2);
+					
+					if imm == 0 && !self.is_live(next + 1, Resource::Flags(ZERO)) {
+						removed[address] = true;
+						changes.push(self.change(address, 2, ChangeKind::MergedAdi));
+					} else {
+						self.code[address] = Instruction::ADI { a, imm };
+						changes.push(self.change(address, 1, ChangeKind::MergedAdi));
+					}
+					
+					removed[next] = true;
+					address += 1;
+				}
+				(Instruction::LDI { a: x, imm }, Some(&Instruction::ADD { a, b, c }))
+				if x != c && c != 0 && (a == x && b == c || b == x && a == c) && !self.is_live(next + 1, Resource::Reg(x)) => {
+					self.code[address] = Instruction::ADI { a: c, imm };
+					removed[next] = true;
+					changes.push(self.change(address, 1, ChangeKind::FoldedAdd));
+					address += 1;
+				}
+				(Instruction::LDI { a: x, imm: 0 }, Some(&instruction))
+				if self.relocation(address).is_none() && reads(instruction, Resource::Reg(x))
+					&& (writes(instruction, Resource::Reg(x)) || !self.is_live(next + 1, Resource::Reg(x))) => {
+					if let Some(instruction) = replace_reads(instruction, x) {
+						self.code[next] = instruction;
+						removed[address] = true;
+						changes.push(self.change(address, 1, ChangeKind::FoldedZero));
+						address += 1;
+					}
+				}
+				_ => {}
+			}
+			
+			address += 1;
+		}
+		
+		if removed.contains(&true) {
+			self.remove(&removed);
+		}
+	}
+}
+
+fn reads(instruction: Instruction, resource: Resource) -> bool {
+	match (instruction, resource) {
+		(Instruction::BRH { cond: Cond::Zero | Cond::NotZero, .. }, Resource::Flags(flags)) => flags & ZERO != 0,
+		(Instruction::BRH { cond: Cond::Carry | Cond::NotCarry, .. }, Resource::Flags(flags)) => flags & CARRY != 0,
+		(_, Resource::Flags(_)) => false,
+		(_, Resource::Reg(reg)) => instruction.reads() & 1 << reg != 0,
+	}
+}
+
+fn writes(instruction: Instruction, resource: Resource) -> bool {
+	match (instruction, resource) {
+		(Instruction::ADD { .. } | Instruction::SUB { .. } | Instruction::NOR { .. } |
+		 Instruction::AND { .. } | Instruction::XOR { .. } | Instruction::ADI { .. }, Resource::Flags(_)) => true,
+		(_, Resource::Flags(_)) => false,
+		(_, Resource::Reg(reg)) => instruction.writes() & 1 << reg != 0,
+	}
+}
+
+/// Makes an ALU instruction read `r0` instead of given register.
+fn replace_reads(instruction: Instruction, reg: u8) -> Option<Instruction> {
+	let replace = |operand: u8| if operand == reg { 0 } else { operand };
+	
+	Some(match instruction {
+		Instruction::ADD { a, b, c } => Instruction::ADD { a: replace(a), b: replace(b), c },
+		Instruction::SUB { a, b, c } => Instruction::SUB { a: replace(a), b: replace(b), c },
+		Instruction::NOR { a, b, c } => Instruction::NOR { a: replace(a), b: replace(b), c },
+		Instruction::AND { a, b, c } => Instruction::AND { a: replace(a), b: replace(b), c },
+		Instruction::XOR { a, b, c } => Instruction::XOR { a: replace(a), b: replace(b), c },
+		Instruction::RSH { a, c } => Instruction::RSH { a: replace(a), c },
+		_ => return None,
+	})
+}
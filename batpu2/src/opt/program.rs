@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use crate::isa::Instruction;
+use crate::link::{Object, Relocation, RelocationKind, RelocationTarget};
+
+/// Contents of an [`Object`] being rewritten, remembers where every instruction came from.
+pub(super) struct Program {
+	pub code: Vec<Instruction>,
+	/// Original address of every instruction
+	pub origin: Vec<u16>,
+	pub relocations: Vec<Relocation>,
+	pub exports: BTreeMap<String, u16>,
+}
+
+impl Program {
+	pub fn new(object: &mut Object) -> Self {
+		Program {
+			origin: (0..object.code.len() as u16).collect(),
+			code: std::mem::take(&mut object.code),
+			relocations: std::mem::take(&mut object.relocations),
+			exports: std::mem::take(&mut object.exports),
+		}
+	}
+	
+	pub fn store(self, object: &mut Object) {
+		object.code = self.code;
+		object.relocations = self.relocations;
+		object.exports = self.exports;
+	}
+	
+	pub fn relocation(&self, address: usize) -> Option<&Relocation> {
+		self.relocations.iter().find(|relocation| relocation.address as usize == address)
+	}
+	
+	/// Target of a jump, unless it refers to an imported symbol.
+	pub fn local_target(&self, address: usize) -> Option<usize> {
+		let (Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr }) = self.code[address] else {
+			return None;
+		};
+		
+		match self.relocation(address) {
+			Some(Relocation { target: RelocationTarget::Symbol(_), .. }) => None,
+			_ => Some(addr as usize),
+		}
+	}
+	
+	/// Removes instructions and moves everything referring to them to the next remaining instruction.
+	pub fn remove(&mut self, removed: &[bool]) {
+		let relocate = |address: usize| address - removed.iter().take(address).filter(|&&removed| removed).count();
+		
+		for address in 0..self.code.len() {
+			let Some(target) = self.local_target(address) else { continue };
+			
+			if let Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr } = &mut self.code[address] {
+				*addr = relocate(target) as u16;
+			}
+		}
+		
+		for relocation in &self.relocations {
+			if relocation.kind == RelocationKind::Imm && relocation.target == RelocationTarget::Local {
+				if let Instruction::LDI { imm, .. } | Instruction::ADI { imm, .. } = &mut self.code[relocation.address as usize] {
+					*imm = relocate(*imm as usize) as u8;
+				}
+			}
+		}
+		
+		for address in self.exports.values_mut() {
+			*address = relocate(*address as usize) as u16;
+		}
+		
+		self.relocations.retain(|relocation| !removed[relocation.address as usize]);
+		
+		for relocation in &mut self.relocations {
+			relocation.address = relocate(relocation.address as usize) as u16;
+		}
+		
+		let mut keep = removed.iter().map(|removed| !removed);
+		self.code.retain(|_| keep.next().unwrap());
+		
+		let mut keep = removed.iter().map(|removed| !removed);
+		self.origin.retain(|_| keep.next().unwrap());
+	}
+}
@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+use crate::isa::Instruction;
+use crate::link::Object;
+use crate::opt::program::Program;
+
+/// Marks instructions which can be executed when starting at any of the entry points.
+///
+/// Control flow is followed through `JMP`, `BRH`, `CAL` and `RET`, every call is assumed to return to the
+/// instruction after it. `HLT` stops the flow.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::{opt, utils};
+///
+/// let code = utils::from_asm("
+///   CAL .f
+///   HLT
+///   NOP
+/// .f
+///   RET
+/// ").unwrap();
+///
+/// let reachable = opt::reachable(&code, [0]);
+///
+/// assert_eq!(reachable, [true, true, false, true]);
+/// assert_eq!(opt::unreachable_ranges(&reachable), [2..3]);
+/// ```
+pub fn reachable(code: &[Instruction], entries: impl IntoIterator<Item=u16>) -> Vec<bool> {
+	walk(code, entries, |_, instruction| match instruction {
+		Instruction::JMP { addr } | Instruction::BRH { addr, .. } | Instruction::CAL { addr } => Some(addr as usize),
+		_ => None,
+	})
+}
+
+/// Visits every instruction reachable from the entry points once, returns which addresses were visited.
+///
+/// `visit` returns the address the jump of an instruction continues at, `None` if it is not followed.
+/// Execution continues with the next instruction after anything but `JMP`, `RET` and `HLT`, so every
+/// call is assumed to return.
+pub(crate) fn walk(code: &[Instruction], entries: impl IntoIterator<Item=u16>, mut visit: impl FnMut(usize, Instruction) -> Option<usize>) -> Vec<bool> {
+	let mut visited = vec![false; code.len()];
+	let mut stack: Vec<usize> = entries.into_iter().map(usize::from).collect();
+	
+	while let Some(address) = stack.pop() {
+		let Some(seen) = visited.get_mut(address) else { continue };
+		
+		if std::mem::replace(seen, true) {
+			continue;
+		}
+		
+		let instruction = code[address];
+		stack.extend(visit(address, instruction));
+		
+		if !matches!(instruction, Instruction::HLT | Instruction::RET | Instruction::JMP { .. }) {
+			stack.push(address + 1);
+		}
+	}
+	
+	visited
+}
+
+/// Walks the body of a subroutine, everything reachable from its entry without following calls, up to
+/// `RET` or `HLT`.
+pub(crate) fn walk_body(code: &[Instruction], entry: u16, mut visit: impl FnMut(usize, Instruction)) -> Vec<bool> {
+	walk(code, [entry], |address, instruction| {
+		visit(address, instruction);
+		
+		match instruction {
+			Instruction::JMP { addr } | Instruction::BRH { addr, .. } => Some(addr as usize),
+			_ => None,
+		}
+	})
+}
+
+/// Groups addresses which are not reachable into ranges.
+pub fn unreachable_ranges(reachable: &[bool]) -> Vec<Range<u16>> {
+	let mut ranges: Vec<Range<u16>> = Vec::new();
+	
+	for (address, _) in reachable.iter().enumerate().filter(|(_, &reachable)| !reachable) {
+		let address = address as u16;
+		
+		match ranges.last_mut() {
+			Some(range) if range.end == address => range.end += 1,
+			_ => ranges.push(address..address + 1),
+		}
+	}
+	
+	ranges
+}
+
+/// Removes instructions not reachable from address 0 or any exported label, returns the removed ranges.
+///
+/// Labels referring to removed instructions are moved to the next remaining instruction, jumps to
+/// imported symbols are not followed.
+pub fn strip_unreachable(object: &mut Object) -> Vec<Range<u16>> {
+	let mut program = Program::new(object);
+	let entries: Vec<u16> = [0].into_iter().chain(program.exports.values().copied()).collect();
+	let reachable = walk(&program.code, entries, |address, _| program.local_target(address));
+	let removed: Vec<bool> = reachable.iter().map(|reachable| !reachable).collect();
+	
+	program.remove(&removed);
+	program.store(object);
+	
+	unreachable_ranges(&reachable)
+}
@@ -0,0 +1,99 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::isa::MAX_CODE_LEN;
+
+/// ROM space used by every label, each label spans the instructions up to the next one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SizeReport {
+	pub entries: Vec<SizeEntry>,
+	/// Length of the whole program
+	pub len: usize,
+	pub unreachable: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SizeEntry {
+	/// Labels placed at the address, empty for instructions before the first label
+	pub labels: Vec<String>,
+	pub address: u16,
+	pub size: usize,
+	pub unreachable: usize,
+}
+
+impl SizeReport {
+	/// Creates a report from label addresses and the [`reachable`](crate::opt::reachable) instructions.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::opt::{self, SizeReport};
+	/// use batpu2::utils;
+	///
+	/// let code = utils::from_asm("
+	///   CAL .f
+	///   HLT
+	/// .f
+	///   ADD r1 r1 r1
+	///   RET
+	/// .unused
+	///   RET
+	/// ").unwrap();
+	///
+	/// let report = SizeReport::new([(".f", 2), (".unused", 4)], &opt::reachable(&code, [0]));
+	///
+	/// assert_eq!(report.entries[1].size, 2);
+	/// assert_eq!(report.entries[2].unreachable, 1);
+	/// ```
+	pub fn new<'a>(labels: impl IntoIterator<Item=(&'a str, u16)>, reachable: &[bool]) -> Self {
+		let mut labels: Vec<_> = labels.into_iter().collect();
+		labels.sort_by_key(|&(name, address)| (address, name));
+		
+		let mut entries: Vec<SizeEntry> = Vec::new();
+		
+		for (name, address) in labels {
+			match entries.last_mut() {
+				Some(entry) if entry.address == address => entry.labels.push(name.to_owned()),
+				_ => entries.push(SizeEntry { labels: vec![name.to_owned()], address, size: 0, unreachable: 0 }),
+			}
+		}
+		
+		if entries.first().is_none_or(|entry| entry.address > 0) && !reachable.is_empty() {
+			entries.insert(0, SizeEntry { labels: Vec::new(), address: 0, size: 0, unreachable: 0 });
+		}
+		
+		let ends: Vec<usize> = entries.iter()
+		                              .skip(1)
+		                              .map(|entry| entry.address as usize)
+		                              .chain([reachable.len()])
+		                              .collect();
+		
+		for (entry, end) in entries.iter_mut().zip(ends) {
+			let range = reachable.get(entry.address as usize..end).unwrap_or_default();
+			
+			entry.size = range.len();
+			entry.unreachable = range.iter().filter(|&&reachable| !reachable).count();
+		}
+		
+		SizeReport {
+			entries,
+			len: reachable.len(),
+			unreachable: reachable.iter().filter(|&&reachable| !reachable).count(),
+		}
+	}
+}
+
+impl Display for SizeReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let percent = |size: usize| size as f32 * 100.0 / MAX_CODE_LEN as f32;
+		
+		writeln!(f, "address   size    rom  unreachable  label")?;
+		
+		for entry in &self.entries {
+			let labels = if entry.labels.is_empty() { "(start)".to_owned() } else { entry.labels.join(", ") };
+			
+			writeln!(f, "  0x{:03X}  {:5}  {:4.1}%  {:11}  {labels}", entry.address, entry.size, percent(entry.size), entry.unreachable)?;
+		}
+		
+		write!(f, "total {} of {MAX_CODE_LEN} instructions ({:.1}%), {} unreachable", self.len, percent(self.len), self.unreachable)
+	}
+}
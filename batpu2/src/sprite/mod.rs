@@ -0,0 +1,370 @@
+//! Converter from images to code drawing them on the 32×32 screen
+
+use std::fmt::{self, Display, Formatter, Write};
+use thiserror::Error;
+
+mod parse;
+
+pub use parse::*;
+
+/// Width and height of the screen.
+pub const SCREEN_SIZE: u8 = 32;
+
+/// Monochrome image of at most 32×32 pixels.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sprite {
+	width: u8,
+	height: u8,
+	/// Rows from top to bottom, bit `x` is the pixel in column `x` like in [`Screen`](crate::vm::embedded::Screen)
+	rows: Vec<u32>,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SpriteError {
+	#[error("Invalid image header, expected a PBM or PGM header like `P1 32 32`")]
+	InvalidHeader,
+	#[error("Image data ends after {pixels} of {expected} pixels")]
+	UnexpectedEnd {
+		pixels: usize,
+		expected: usize,
+	},
+	#[error("Invalid pixel value `{value}` (max {max})")]
+	InvalidValue {
+		value: String,
+		max: u32,
+	},
+	#[error("Unexpected character `{char}` on line {line_number}, expected `#` for lit or `.` for unlit pixels")]
+	InvalidCharacter {
+		line_number: usize,
+		char: char,
+	},
+	#[error("Image of {width}×{height} pixels is larger than the 32×32 screen")]
+	TooLarge {
+		width: usize,
+		height: usize,
+	},
+	#[error("Sprite of {width}×{height} pixels at ({x}, {y}) does not fit on the screen")]
+	OutOfScreen {
+		x: u8,
+		y: u8,
+		width: u8,
+		height: u8,
+	},
+}
+
+/// How the generated code draws a sprite.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Strategy {
+	/// Every lit pixel is drawn by its own instructions, `pixel_x` and `pixel_y` are only set when they change
+	PerPixel,
+	/// Horizontal runs of two or more pixels are drawn by a loop, which is a subroutine named after the sprite
+	/// with a `_run` suffix
+	RunLength,
+}
+
+impl Display for Strategy {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Strategy::PerPixel => write!(f, "per-pixel"),
+			Strategy::RunLength => write!(f, "run-length"),
+		}
+	}
+}
+
+/// Assembly drawing a sprite, see [`Sprite::to_asm`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SpriteCode {
+	pub strategy: Strategy,
+	pub asm: String,
+	/// Number of instructions, the ROM space taken by the code
+	pub instructions: usize,
+}
+
+impl Sprite {
+	/// Creates a sprite with every pixel unlit.
+	///
+	/// # Panics
+	///
+	/// Panics if the sprite is larger than the screen.
+	pub fn new(width: u8, height: u8) -> Self {
+		assert!(width <= SCREEN_SIZE && height <= SCREEN_SIZE, "sprite of {width}×{height} pixels is larger than the screen");
+		
+		Self { width, height, rows: vec![0; height as usize] }
+	}
+	
+	pub fn width(&self) -> u8 {
+		self.width
+	}
+	
+	pub fn height(&self) -> u8 {
+		self.height
+	}
+	
+	/// Rows from top to bottom, bit `x` of a row is the pixel in column `x`.
+	pub fn rows(&self) -> &[u32] {
+		&self.rows
+	}
+	
+	/// Returns `true` if the pixel in column `x` of row `y`, counted from the top, is lit. Pixels outside of the
+	/// sprite are unlit.
+	pub fn get(&self, x: u8, y: u8) -> bool {
+		x < self.width && self.rows.get(y as usize).is_some_and(|row| row & 1 << x != 0)
+	}
+	
+	/// Sets a pixel, `y` is counted from the top.
+	///
+	/// # Panics
+	///
+	/// Panics if the pixel lies outside of the sprite.
+	pub fn set(&mut self, x: u8, y: u8, lit: bool) {
+		assert!(x < self.width && y < self.height, "pixel ({x}, {y}) outside of a {}×{} sprite", self.width, self.height);
+		
+		if lit {
+			self.rows[y as usize] |= 1 << x;
+		} else {
+			self.rows[y as usize] &= !(1 << x);
+		}
+	}
+	
+	/// Swaps lit and unlit pixels.
+	pub fn invert(&mut self) {
+		let mask = u32::MAX.checked_shr(SCREEN_SIZE as u32 - self.width as u32).unwrap_or(0);
+		
+		for row in &mut self.rows {
+			*row ^= mask;
+		}
+	}
+	
+	/// Generates a subroutine drawing the sprite into the screen buffer with the top left corner at `x`, `y`.
+	/// The screen's `y` grows upwards, so the top left corner of the screen is at 0, 31.
+	///
+	/// Code is generated with every [`Strategy`] and the one with the fewest instructions is returned. Only
+	/// lit pixels are drawn, call `buffer_screen` afterwards to show them.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::BatPU2;
+	/// use batpu2::sprite::{Sprite, Strategy};
+	///
+	/// let sprite = Sprite::from_text("\
+	/// ########
+	/// #......#
+	/// ########
+	/// ").unwrap();
+	///
+	/// let code = sprite.to_asm("box", 0, 31).unwrap();
+	/// assert_eq!(code.strategy, Strategy::RunLength);
+	///
+	/// let mut vm = BatPU2::from_asm(&format!("CAL .box\nHLT\n{}", code.asm)).unwrap();
+	/// vm.step_multiple(1000);
+	///
+	/// assert!(vm.io.screen.get_buffer_pixel(7, 30));
+	/// assert!(!vm.io.screen.get_buffer_pixel(6, 30));
+	/// ```
+	pub fn to_asm(&self, name: &str, x: u8, y: u8) -> Result<SpriteCode, SpriteError> {
+		let per_pixel = self.to_asm_with(name, x, y, Strategy::PerPixel)?;
+		let run_length = self.to_asm_with(name, x, y, Strategy::RunLength)?;
+		
+		Ok(if run_length.instructions < per_pixel.instructions { run_length } else { per_pixel })
+	}
+	
+	/// Generates a subroutine drawing the sprite using the given strategy, see [`to_asm`](Sprite::to_asm).
+	///
+	/// The subroutine is annotated with `@func`, it clobbers `r1`, `r2`, `r15` and `r3` for runs.
+	pub fn to_asm_with(&self, name: &str, x: u8, y: u8, strategy: Strategy) -> Result<SpriteCode, SpriteError> {
+		if x as usize + self.width as usize > SCREEN_SIZE as usize || y >= SCREEN_SIZE || y as usize + 1 < self.height as usize {
+			return Err(SpriteError::OutOfScreen { x, y, width: self.width, height: self.height });
+		}
+		
+		let run = format!("{name}_run");
+		let runs = strategy == Strategy::RunLength;
+		let mut code = Code::default();
+		// Values written to `pixel_x` and `pixel_y` so far
+		let mut pixel_x = None;
+		let mut pixel_y = None;
+		
+		code.instruction(format_args!("LDI r15 pixel_x"));
+		
+		for (row, &bits) in self.rows.iter().enumerate() {
+			let screen_y = y - row as u8;
+			
+			for (start, len) in row_runs(bits) {
+				if pixel_y != Some(screen_y) {
+					code.instruction(format_args!("LDI r2 {screen_y}"));
+					code.instruction(format_args!("STR r15 r2 1"));
+					pixel_y = Some(screen_y);
+				}
+				
+				let start = x + start;
+				
+				if runs && len > 1 {
+					code.instruction(format_args!("LDI r1 {start}"));
+					code.instruction(format_args!("LDI r3 {}", start + len));
+					code.instruction(format_args!("CAL .{run}"));
+					pixel_x = Some(start + len - 1);
+					code.calls = true;
+					continue;
+				}
+				
+				for screen_x in start..start + len {
+					if pixel_x != Some(screen_x) {
+						code.instruction(format_args!("LDI r1 {screen_x}"));
+						code.instruction(format_args!("STR r15 r1"));
+						pixel_x = Some(screen_x);
+					}
+					
+					code.instruction(format_args!("STR r15 r0 2"));
+				}
+			}
+		}
+		
+		code.instruction(format_args!("RET"));
+		
+		let mut asm = String::new();
+		let clobbers = if code.calls { "r1-r3,r15" } else { "r1,r2,r15" };
+		
+		writeln!(asm, "; Draws a {}×{} sprite at {x}, {y} into the screen buffer.", self.width, self.height).unwrap();
+		writeln!(asm, "; @func {name} clobbers={clobbers}").unwrap();
+		writeln!(asm, ".{name}").unwrap();
+		asm.push_str(&code.text);
+		
+		if code.calls {
+			writeln!(asm).unwrap();
+			writeln!(asm, "; Draws pixels from r1 up to r3 excluding it, r15 holds the address of pixel_x.").unwrap();
+			writeln!(asm, "; @func {run} in=r1,r3,r15 clobbers=r1").unwrap();
+			writeln!(asm, ".{run}").unwrap();
+			
+			for instruction in ["STR r15 r1", "STR r15 r0 2", "INC r1", "CMP r1 r3", &format!("BRH ne .{run}"), "RET"] {
+				writeln!(asm, "\t{instruction}").unwrap();
+				code.instructions += 1;
+			}
+		}
+		
+		Ok(SpriteCode { strategy, asm, instructions: code.instructions })
+	}
+}
+
+/// Instructions of the subroutine drawing a sprite.
+#[derive(Default)]
+struct Code {
+	text: String,
+	instructions: usize,
+	/// The subroutine drawing runs is used
+	calls: bool,
+}
+
+impl Code {
+	fn instruction(&mut self, instruction: fmt::Arguments) {
+		writeln!(self.text, "\t{instruction}").unwrap();
+		self.instructions += 1;
+	}
+}
+
+/// Start and length of every run of set bits.
+fn row_runs(mut bits: u32) -> impl Iterator<Item=(u8, u8)> {
+	let mut start = 0;
+	
+	std::iter::from_fn(move || {
+		if bits == 0 {
+			return None;
+		}
+		
+		let skip = bits.trailing_zeros();
+		bits >>= skip;
+		let len = bits.trailing_ones();
+		bits = bits.checked_shr(len).unwrap_or(0);
+		
+		let run = (start + skip as u8, len as u8);
+		start += (skip + len) as u8;
+		
+		Some(run)
+	})
+}
+
+#[cfg(test)]
+#[cfg(feature = "embedded_io")]
+mod tests {
+	use super::*;
+	use crate::BatPU2;
+	
+	/// Draws the sprite with the generated code and compares every pixel of the screen buffer.
+	fn check_rendering(sprite: &Sprite, x: u8, y: u8, strategy: Strategy) -> usize {
+		let code = sprite.to_asm_with("sprite", x, y, strategy).unwrap();
+		let mut vm = BatPU2::from_asm(&format!("CAL .sprite\nHLT\n{}", code.asm)).unwrap();
+		
+		vm.step_multiple(100_000);
+		assert!(vm.halted);
+		
+		for screen_y in 0..SCREEN_SIZE {
+			for screen_x in 0..SCREEN_SIZE {
+				let expected = screen_x >= x && y >= screen_y && sprite.get(screen_x - x, y - screen_y);
+				assert_eq!(vm.io.screen.get_buffer_pixel(screen_x, screen_y), expected, "{strategy} at {screen_x}, {screen_y}");
+			}
+		}
+		
+		code.instructions
+	}
+	
+	#[test]
+	fn renders() {
+		let mut checkerboard = Sprite::new(20, 20);
+		let mut noise = Sprite::new(20, 13);
+		let mut state = 0x2545_F491_u32;
+		
+		for y in 0..20 {
+			for x in 0..20 {
+				checkerboard.set(x, y, (x + y).is_multiple_of(2));
+			}
+		}
+		
+		for y in 0..13 {
+			for x in 0..20 {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				noise.set(x, y, !state.is_multiple_of(3));
+			}
+		}
+		
+		let mut full = Sprite::new(32, 32);
+		full.invert();
+		
+		for (sprite, x, y) in [(&checkerboard, 4, 27), (&noise, 12, 20), (&Sprite::new(0, 0), 0, 0)] {
+			let per_pixel = check_rendering(sprite, x, y, Strategy::PerPixel);
+			let run_length = check_rendering(sprite, x, y, Strategy::RunLength);
+			let best = sprite.to_asm("sprite", x, y).unwrap();
+			
+			assert_eq!(best.instructions, per_pixel.min(run_length));
+		}
+		
+		assert_eq!(checkerboard.to_asm("sprite", 4, 27).unwrap().strategy, Strategy::PerPixel);
+		// Drawing every pixel on its own would not fit into the ROM
+		assert_eq!(full.to_asm("sprite", 0, 31).unwrap().strategy, Strategy::RunLength);
+		assert_eq!(check_rendering(&full, 0, 31, Strategy::RunLength), 32 * 5 + 8);
+	}
+	
+	#[test]
+	fn formats() {
+		let text = Sprite::from_text("\
+#..#.........
+.##...#######
+").unwrap();
+
+		assert_eq!((text.width(), text.height()), (13, 2));
+		assert_eq!(Sprite::parse(b"P1 13 2 1001000000000 0110001111111").unwrap(), text);
+		assert_eq!(Sprite::parse(b"P4\n13 2\n\x90\x00\x63\xF8").unwrap(), text);
+		assert_eq!(Sprite::parse(b"P5 13 2 1\n\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x01\x00\x00\x00\x01\x01\x01\x01\x01\x01\x01").unwrap(), text);
+		
+		let mut inverted = text.clone();
+		inverted.invert();
+		assert_eq!(inverted.rows()[0], 0b1_1111_1111_0110);
+		
+		assert_eq!(Sprite::parse(b"P1 2 2 0 1 1"), Err(SpriteError::UnexpectedEnd { pixels: 3, expected: 4 }));
+		assert_eq!(Sprite::parse(b"P2 1 1 15 16"), Err(SpriteError::InvalidValue { value: "16".to_owned(), max: 15 }));
+		assert_eq!(Sprite::parse(b"P1 33 1"), Err(SpriteError::TooLarge { width: 33, height: 1 }));
+		assert_eq!(Sprite::parse(b"..\n.o"), Err(SpriteError::InvalidCharacter { line_number: 2, char: 'o' }));
+		assert_eq!(text.to_asm("title", 20, 31), Err(SpriteError::OutOfScreen { x: 20, y: 31, width: 13, height: 2 }));
+	}
+}
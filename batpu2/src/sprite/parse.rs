@@ -0,0 +1,188 @@
+use super::{Sprite, SpriteError, SCREEN_SIZE};
+
+/// Characters of plain-text bitmaps standing for lit pixels.
+pub const LIT: [char; 6] = ['#', 'X', 'x', '1', '@', '*'];
+/// Characters of plain-text bitmaps standing for unlit pixels.
+pub const UNLIT: [char; 5] = ['.', ' ', '0', '_', '-'];
+
+impl Sprite {
+	/// Parses a PBM (`P1`, `P4`) or PGM (`P2`, `P5`) image, anything else is read as a plain-text bitmap.
+	///
+	/// Set bits of a PBM and pixels brighter than half of the maximum value of a PGM are lit.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::sprite::Sprite;
+	///
+	/// let pbm = Sprite::parse(b"P1\n# arrow\n3 2\n010\n111\n").unwrap();
+	/// let pgm = Sprite::parse(b"P2 3 2 255\n0 200 0\n255 128 255\n").unwrap();
+	///
+	/// assert_eq!(pbm, Sprite::from_text(".#.\n###").unwrap());
+	/// assert_eq!(pbm, pgm);
+	/// ```
+	pub fn parse(input: &[u8]) -> Result<Self, SpriteError> {
+		match input {
+			[b'P', magic @ b'1'..=b'5', next, ..] if next.is_ascii_whitespace() && *magic != b'3' => Netpbm { input, position: 2 }.parse(*magic),
+			_ => Self::from_text(std::str::from_utf8(input).map_err(|_| SpriteError::InvalidHeader)?),
+		}
+	}
+	
+	/// Parses a bitmap with a line per row, see [`LIT`] and [`UNLIT`] for the accepted characters. Shorter
+	/// lines are padded with unlit pixels and trailing empty lines are ignored.
+	pub fn from_text(text: &str) -> Result<Self, SpriteError> {
+		let lines: Vec<&str> = text.trim_end_matches(['\n', '\r']).lines().collect();
+		let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+		let mut sprite = Self::with_size(width, lines.len())?;
+		
+		for (y, line) in lines.iter().enumerate() {
+			for (x, char) in line.chars().enumerate() {
+				if LIT.contains(&char) {
+					sprite.set(x as u8, y as u8, true);
+				} else if !UNLIT.contains(&char) {
+					return Err(SpriteError::InvalidCharacter { line_number: y + 1, char });
+				}
+			}
+		}
+		
+		Ok(sprite)
+	}
+	
+	fn with_size(width: usize, height: usize) -> Result<Self, SpriteError> {
+		if width > SCREEN_SIZE as usize || height > SCREEN_SIZE as usize {
+			return Err(SpriteError::TooLarge { width, height });
+		}
+		
+		Ok(Self::new(width as u8, height as u8))
+	}
+}
+
+/// Reader of the Netpbm formats.
+struct Netpbm<'a> {
+	input: &'a [u8],
+	position: usize,
+}
+
+impl Netpbm<'_> {
+	fn parse(mut self, magic: u8) -> Result<Sprite, SpriteError> {
+		let width = self.header()? as usize;
+		let height = self.header()? as usize;
+		let max = match magic {
+			b'2' | b'5' => self.header()?.clamp(1, u16::MAX as u32),
+			_ => 1,
+		};
+		let mut sprite = Sprite::with_size(width, height)?;
+		let expected = width * height;
+		
+		// A single whitespace character separates the header from binary data
+		self.position += 1;
+		
+		let pixels: Vec<bool> = match magic {
+			b'1' | b'2' => {
+				let mut pixels = Vec::with_capacity(expected);
+				
+				for _ in 0..expected {
+					self.skip();
+					
+					if self.position >= self.input.len() {
+						return Err(SpriteError::UnexpectedEnd { pixels: pixels.len(), expected });
+					}
+					
+					pixels.push(if magic == b'1' { self.plain_bit()? } else { self.plain_value(max)? * 2 > max });
+				}
+				
+				pixels
+			}
+			// Rows of PBM are padded to whole bytes, the first pixel is the most significant bit
+			b'4' => {
+				let row_len = width.div_ceil(8);
+				let available = self.input.len().saturating_sub(self.position);
+				
+				if available < row_len * height {
+					return Err(SpriteError::UnexpectedEnd { pixels: available / row_len * width, expected });
+				}
+				
+				let data = &self.input[self.position..];
+				
+				(0..expected).map(|index| data[index / width * row_len + index % width / 8] & 0x80 >> (index % width % 8) != 0).collect()
+			}
+			_ => {
+				let size = if max > 255 { 2 } else { 1 };
+				let data = self.input.get(self.position..).unwrap_or_default();
+				
+				if data.len() < expected * size {
+					return Err(SpriteError::UnexpectedEnd { pixels: data.len() / size, expected });
+				}
+				
+				data.chunks(size)
+				    .take(expected)
+				    .map(|value| {
+					    let value = value.iter().fold(0, |value, &byte| value << 8 | byte as u32);
+					    if value > max { Err(SpriteError::InvalidValue { value: value.to_string(), max }) } else { Ok(value * 2 > max) }
+				    })
+				    .collect::<Result<_, _>>()?
+			}
+		};
+		
+		for (index, lit) in pixels.into_iter().enumerate() {
+			sprite.set((index % width) as u8, (index / width) as u8, lit);
+		}
+		
+		Ok(sprite)
+	}
+	
+	/// Skips whitespace and comments starting with `#`.
+	fn skip(&mut self) {
+		while let Some(&byte) = self.input.get(self.position) {
+			if byte == b'#' {
+				while self.input.get(self.position).is_some_and(|&byte| byte != b'\n') {
+					self.position += 1;
+				}
+			} else if byte.is_ascii_whitespace() {
+				self.position += 1;
+			} else {
+				break;
+			}
+		}
+	}
+	
+	fn number(&mut self) -> Option<u32> {
+		self.skip();
+		
+		let start = self.position;
+		
+		while self.input.get(self.position).is_some_and(u8::is_ascii_digit) {
+			self.position += 1;
+		}
+		
+		std::str::from_utf8(&self.input[start..self.position]).ok()?.parse().ok()
+	}
+	
+	fn header(&mut self) -> Result<u32, SpriteError> {
+		self.number().ok_or(SpriteError::InvalidHeader)
+	}
+	
+	/// Bit of a plain PBM, digits need not be separated.
+	fn plain_bit(&mut self) -> Result<bool, SpriteError> {
+		let byte = self.input[self.position];
+		self.position += 1;
+		
+		match byte {
+			b'0' => Ok(false),
+			b'1' => Ok(true),
+			byte => Err(SpriteError::InvalidValue { value: (byte as char).to_string(), max: 1 }),
+		}
+	}
+	
+	fn plain_value(&mut self, max: u32) -> Result<u32, SpriteError> {
+		let start = self.position;
+		
+		match self.number() {
+			Some(value) if value <= max => Ok(value),
+			_ => {
+				let end = self.input[start..].iter().position(u8::is_ascii_whitespace).map_or(self.input.len(), |len| start + len);
+				Err(SpriteError::InvalidValue { value: String::from_utf8_lossy(&self.input[start..end]).into_owned(), max })
+			}
+		}
+	}
+}
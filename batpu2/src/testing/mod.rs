@@ -0,0 +1,288 @@
+//! Unit tests written in comments of assembly files
+//!
+//! Tests are declared in comment blocks next to the code they test and run on [`BatPU2`](crate::BatPU2)
+//! by [`TestSuite`]. The same files can be tested by `batpu2-cli test` and from Rust tests:
+//!
+//! ```
+//! use batpu2::asm::Dialect;
+//! use batpu2::testing::TestSuite;
+//!
+//! let source = r#"
+//! ; test "multiply 7x6" setup: r1=7 r2=6 call .math_mul expect: r1=42 r2=0
+//! HLT
+//! include <std/math.asm>
+//! "#;
+//!
+//! for result in TestSuite::from_asm(source, Dialect::Extended).unwrap().run() {
+//!     assert!(result.passed(), "{result}");
+//! }
+//! ```
+
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+use crate::asm::AsmError;
+use crate::vm::AssertionFailure;
+
+mod parse;
+mod run;
+
+pub use parse::*;
+pub use run::*;
+
+/// Single test block.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TestCase {
+	pub name: String,
+	/// Line of the `test` comment
+	pub line_number: usize,
+	pub setup: Vec<(Location, Value)>,
+	/// Label or address of the tested routine, the program starts at address 0 if there is none
+	pub call: Option<String>,
+	pub expect: Vec<(Location, Value)>,
+	/// Maximum number of executed instructions
+	pub limit: usize,
+}
+
+/// Part of the machine state which can be set up or checked by a test.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Location {
+	Reg(u8),
+	Mem(u8),
+	Zero,
+	Carry,
+	/// Text shown on the character display
+	Chars,
+	/// Value shown on the number display
+	Number,
+	/// Pixel shown on the screen
+	Pixel(u8, u8),
+	/// Buttons held on the controller
+	Controller,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Value {
+	Int(u8),
+	Text(String),
+}
+
+/// Outcome of a single test.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TestResult {
+	pub name: String,
+	pub line_number: usize,
+	/// Number of executed instructions
+	pub steps: usize,
+	pub failure: Option<Failure>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Failure {
+	/// Some of the expected values differ
+	Mismatch(Vec<Mismatch>),
+	/// The program did not halt or return within the step limit
+	Timeout { limit: usize },
+	/// The called label is not defined
+	UnknownLabel(String),
+	/// An `ASSERT` did not hold
+	Assertion(AssertionFailure),
+	/// The location cannot be set up, or was given a text
+	InvalidSetup(Location),
+	/// The program fills the whole ROM, leaving no room for the `HLT` called routines return to
+	NoReturnAddress { len: usize },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Mismatch {
+	pub location: Location,
+	pub expected: Value,
+	pub actual: Value,
+}
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TestError<'a> {
+	#[error("{0}")]
+	Asm(AsmError<'a>),
+	#[error("Invalid test on line {line_number}: {message}")]
+	Syntax {
+		line_number: usize,
+		message: String,
+	},
+}
+
+impl<'a> From<AsmError<'a>> for TestError<'a> {
+	fn from(err: AsmError<'a>) -> Self {
+		TestError::Asm(err)
+	}
+}
+
+impl TestResult {
+	pub fn passed(&self) -> bool {
+		self.failure.is_none()
+	}
+}
+
+impl Display for TestResult {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match &self.failure {
+			None => write!(f, "PASS {} ({} steps)", self.name, self.steps),
+			Some(failure) => {
+				write!(f, "FAIL {} (line {})", self.name, self.line_number)?;
+				
+				match failure {
+					Failure::Mismatch(mismatches) => for mismatch in mismatches {
+						write!(f, "\n    {}: expected {}, got {}", mismatch.location, mismatch.expected, mismatch.actual)?;
+					},
+					Failure::Timeout { limit } => write!(f, "\n    did not finish within {limit} steps")?,
+					Failure::UnknownLabel(label) => write!(f, "\n    unknown label `{label}`")?,
+					Failure::Assertion(failure) => write!(f, "\n    {failure}")?,
+					Failure::InvalidSetup(location) => write!(f, "\n    `{location}` cannot be set up to this value")?,
+					Failure::NoReturnAddress { len } => write!(f, "\n    program has {len} instructions, no room is left for the `HLT` routines return to")?,
+				}
+				
+				Ok(())
+			}
+		}
+	}
+}
+
+impl Display for Location {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Location::Reg(reg) => write!(f, "r{reg}"),
+			Location::Mem(address) => write!(f, "mem[0x{address:02X}]"),
+			Location::Zero => write!(f, "flags.zero"),
+			Location::Carry => write!(f, "flags.carry"),
+			Location::Chars => write!(f, "chars"),
+			Location::Number => write!(f, "number"),
+			Location::Pixel(x, y) => write!(f, "pixel[{x},{y}]"),
+			Location::Controller => write!(f, "controller"),
+		}
+	}
+}
+
+impl Display for Value {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Value::Int(value) => write!(f, "{value}"),
+			Value::Text(text) => write!(f, "\"{text}\""),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asm::Dialect;
+	use crate::isa::Instruction;
+	
+	#[test]
+	fn parses_test_blocks() {
+		let tests = parse_tests(r#"
+		; test "first" setup: r1=7 mem[0x10]=0xFF
+		;   flags.carry=1 controller=0b101
+		;   call .f
+		;   expect: chars="HI THERE" number=-1 pixel[3,4]=1
+		;   limit: 1_000
+		; Unrelated comment
+		.f RET
+		# test "second"
+		"#, Dialect::Extended).unwrap();
+		
+		assert_eq!(tests, [
+			TestCase {
+				name: "first".into(),
+				line_number: 2,
+				setup: vec![
+					(Location::Reg(1), Value::Int(7)),
+					(Location::Mem(0x10), Value::Int(0xFF)),
+					(Location::Carry, Value::Int(1)),
+					(Location::Controller, Value::Int(5)),
+				],
+				call: Some(".f".into()),
+				expect: vec![
+					(Location::Chars, Value::Text("HI THERE".into())),
+					(Location::Number, Value::Int(255)),
+					(Location::Pixel(3, 4), Value::Int(1)),
+				],
+				limit: 1000,
+			},
+			TestCase {
+				name: "second".into(),
+				line_number: 9,
+				setup: Vec::new(),
+				call: None,
+				expect: Vec::new(),
+				limit: DEFAULT_LIMIT,
+			},
+		]);
+		
+		for invalid in [r#"; test first"#, r#"; test "x" r1=1"#, r#"; test "x" setup: chars="A""#, r#"; test "x" expect: r16=1"#] {
+			assert!(matches!(parse_tests(invalid, Dialect::Extended), Err(TestError::Syntax { line_number: 1, .. })), "{invalid}");
+		}
+	}
+	
+	#[test]
+	fn reports_failures() {
+		let suite = TestSuite::from_asm(r#"
+		; test "program" expect: r1=3 chars="HI" number=3
+		; test "wrong" setup: r1=1 call .inc expect: r1=3 mem[0]=1
+		; test "loop" call .loop limit: 100
+		; test "missing" call .missing
+		; test "assertion" setup: r1=255 call .inc
+		  LDI r1 3
+		  LDI r2 write_char
+		  LDI r3 'H'
+		  STR r2 r3
+		  LDI r3 'I'
+		  STR r2 r3
+		  LDI r2 buffer_chars
+		  STR r2 r0
+		  LDI r2 show_number
+		  STR r2 r1
+		  HLT
+		.inc
+		  ASSERT r1 < 255
+		  INC r1
+		  RET
+		.loop
+		  JMP .loop
+		"#, Dialect::Extended).unwrap();
+		
+		let results = suite.run();
+		
+		assert!(results[0].passed(), "{}", results[0]);
+		assert_eq!(results[1].failure, Some(Failure::Mismatch(vec![
+			Mismatch { location: Location::Reg(1), expected: Value::Int(3), actual: Value::Int(2) },
+			Mismatch { location: Location::Mem(0), expected: Value::Int(1), actual: Value::Int(0) },
+		])));
+		assert_eq!(results[1].to_string(), "FAIL wrong (line 3)\n    r1: expected 3, got 2\n    mem[0x00]: expected 1, got 0");
+		assert_eq!(results[2].failure, Some(Failure::Timeout { limit: 100 }));
+		assert_eq!(results[3].failure, Some(Failure::UnknownLabel(".missing".into())));
+		assert_eq!(results[4].to_string(), "FAIL assertion (line 6)\n    Assertion `r1 < 255` on line 19 failed at 0x00B: r1 is 255");
+	}
+	
+	#[test]
+	fn rejects_invalid_runs() {
+		let mut suite = TestSuite::from_asm(r#"
+		; test "fill" call .f
+		.f RET
+		"#, Dialect::Extended).unwrap();
+		
+		let mut test = suite.tests[0].clone();
+		test.setup.push((Location::Chars, Value::Text("A".into())));
+		assert_eq!(suite.run_test(&test).failure, Some(Failure::InvalidSetup(Location::Chars)));
+		
+		test.setup = vec![(Location::Reg(1), Value::Text("A".into()))];
+		assert_eq!(suite.run_test(&test).failure, Some(Failure::InvalidSetup(Location::Reg(1))));
+		
+		test.setup = vec![(Location::Reg(0), Value::Int(5))];
+		assert_eq!(suite.run_test(&test).failure, Some(Failure::InvalidSetup(Location::Reg(0))));
+		
+		suite.code.resize(crate::isa::MAX_CODE_LEN, Instruction::RET);
+		assert_eq!(suite.run()[0].failure, Some(Failure::NoReturnAddress { len: 1024 }));
+	}
+}
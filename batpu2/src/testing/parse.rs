@@ -0,0 +1,193 @@
+use crate::asm::{self, Dialect, Token, parse_syntax_lines};
+use crate::testing::{Location, TestCase, TestError, Value};
+
+/// Step limit of tests which do not set `limit:`.
+pub const DEFAULT_LIMIT: usize = 100_000;
+
+/// Collects test blocks from comments of an assembly source.
+///
+/// A block starts with a comment `test "name"`, following comment lines belong to the block as long as
+/// their text is indented deeper than `test`. Blocks consist of clauses:
+/// - `setup:` followed by assignments of registers (`r1=7`), memory (`mem[0x10]=3`), flags
+///   (`flags.carry=1`) and buttons (`controller=0x20`),
+/// - `call .label` calls a routine which ends the test when it returns, otherwise the program runs
+///   from address 0 until it halts,
+/// - `expect:` followed by assignments of registers, memory, flags, the character display
+///   (`chars="HELLO"`), the number display (`number=42`) and pixels (`pixel[3,4]=1`),
+/// - `limit:` followed by the maximum number of executed instructions.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::Dialect;
+/// use batpu2::testing::{self, Location, Value};
+///
+/// let tests = testing::parse_tests(r#"
+/// ; test "add" setup: r1=7 r2=6
+/// ;   call .add
+/// ;   expect: r3=13
+/// .add ADD r1 r2 r3
+///      RET
+/// "#, Dialect::Extended).unwrap();
+///
+/// assert_eq!(tests[0].name, "add");
+/// assert_eq!(tests[0].call.as_deref(), Some(".add"));
+/// assert_eq!(tests[0].expect, [(Location::Reg(3), Value::Int(13))]);
+/// ```
+pub fn parse_tests(source: &str, dialect: Dialect) -> Result<Vec<TestCase>, TestError<'_>> {
+	let mut tests = Vec::new();
+	// Line number, indentation of `test` and text of the current block
+	let mut block: Option<(usize, usize, String)> = None;
+	
+	for line in parse_syntax_lines(source, dialect) {
+		let text = line.comment()
+		               .filter(|_| line.label().is_none() && line.mnemonic().is_none())
+		               .map(|comment| comment.text.trim_start_matches([';', '#', '/']))
+		               .filter(|text| !text.trim().is_empty());
+		
+		let indent = |text: &str| text.len() - text.trim_start().len();
+		
+		match (text, &mut block) {
+			(Some(text), Some((_, test_indent, block))) if indent(text) > *test_indent => {
+				block.push(' ');
+				block.push_str(text.trim());
+			}
+			(text, _) => {
+				if let Some((line_number, _, block)) = block.take() {
+					tests.push(parse_test(line_number, &block)?);
+				}
+				
+				if let Some(text) = text.filter(|text| text.trim_start().starts_with("test ")) {
+					block = Some((line.line_number, indent(text), text.trim().to_owned()));
+				}
+			}
+		}
+	}
+	
+	if let Some((line_number, _, block)) = block {
+		tests.push(parse_test(line_number, &block)?);
+	}
+	
+	Ok(tests)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Clause {
+	Setup,
+	Expect,
+}
+
+fn parse_test(line_number: usize, block: &str) -> Result<TestCase, TestError<'static>> {
+	let error = |message: String| TestError::Syntax { line_number, message };
+	let mut words = split_words(block).into_iter().skip(1);
+	
+	let name = words.next()
+	                .and_then(|name| name.strip_prefix('"')?.strip_suffix('"'))
+	                .ok_or_else(|| error("expected a quoted test name after `test`".into()))?;
+	
+	let mut test = TestCase {
+		name: name.to_owned(),
+		line_number,
+		setup: Vec::new(),
+		call: None,
+		expect: Vec::new(),
+		limit: DEFAULT_LIMIT,
+	};
+	
+	let mut clause = None;
+	
+	while let Some(word) = words.next() {
+		match word {
+			"setup:" => clause = Some(Clause::Setup),
+			"expect:" => clause = Some(Clause::Expect),
+			"call" => {
+				let label = words.next().ok_or_else(|| error("expected a label after `call`".into()))?;
+				test.call = Some(label.to_owned());
+			}
+			"limit:" => {
+				test.limit = words.next()
+				                  .and_then(|limit| limit.replace('_', "").parse().ok())
+				                  .ok_or_else(|| error("expected a number of steps after `limit:`".into()))?;
+			}
+			assignment => {
+				let (location, value) = assignment.split_once('=')
+				                                  .ok_or_else(|| error(format!("expected a clause or an assignment, found `{assignment}`")))?;
+				
+				let location = parse_location(location).ok_or_else(|| error(format!("unknown location `{location}`")))?;
+				let value = parse_value(location, value).ok_or_else(|| error(format!("invalid value `{value}` for `{location}`")))?;
+				
+				match clause {
+					Some(Clause::Setup) if matches!(location, Location::Chars | Location::Number | Location::Pixel(..)) => {
+						return Err(error(format!("`{location}` cannot be set up")));
+					}
+					Some(Clause::Setup) => test.setup.push((location, value)),
+					Some(Clause::Expect) => test.expect.push((location, value)),
+					None => return Err(error(format!("`{assignment}` has to follow `setup:` or `expect:`"))),
+				}
+			}
+		}
+	}
+	
+	Ok(test)
+}
+
+/// Splits at whitespace outside of double quotes.
+fn split_words(text: &str) -> Vec<&str> {
+	let mut words = Vec::new();
+	let mut start = None;
+	let mut quoted = false;
+	
+	for (index, char) in text.char_indices() {
+		if char == '"' {
+			quoted = !quoted;
+		}
+		
+		match (char.is_whitespace() && !quoted, start) {
+			(true, Some(begin)) => {
+				words.push(&text[begin..index]);
+				start = None;
+			}
+			(false, None) => start = Some(index),
+			_ => {}
+		}
+	}
+	
+	words.extend(start.map(|begin| &text[begin..]));
+	words
+}
+
+fn parse_location(text: &str) -> Option<Location> {
+	let indexed = |prefix: &str| text.strip_prefix(prefix)?.strip_prefix('[')?.strip_suffix(']');
+	
+	Some(match text {
+		"flags.zero" => Location::Zero,
+		"flags.carry" => Location::Carry,
+		"chars" => Location::Chars,
+		"number" => Location::Number,
+		"controller" => Location::Controller,
+		_ if indexed("mem").is_some() => Location::Mem(parse_byte(indexed("mem")?).filter(|&address| address < 240)?),
+		_ if indexed("pixel").is_some() => {
+			let (x, y) = indexed("pixel")?.split_once(',')?;
+			Location::Pixel(parse_byte(x.trim()).filter(|&x| x < 32)?, parse_byte(y.trim()).filter(|&y| y < 32)?)
+		}
+		_ => Location::Reg(text.strip_prefix('r')?.parse().ok().filter(|&reg| reg < 16)?),
+	})
+}
+
+fn parse_value(location: Location, text: &str) -> Option<Value> {
+	match location {
+		Location::Chars => Some(Value::Text(text.strip_prefix('"')?.strip_suffix('"')?.to_owned())),
+		Location::Zero | Location::Carry | Location::Pixel(..) => match text {
+			"true" | "1" => Some(Value::Int(1)),
+			"false" | "0" => Some(Value::Int(0)),
+			_ => None,
+		},
+		_ => parse_byte(text).map(Value::Int),
+	}
+}
+
+fn parse_byte(text: &str) -> Option<u8> {
+	let value = asm::parse_literal(Token::new(0, text))?;
+	
+	(-128..=255).contains(&value).then_some(value as u8)
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::BatPU2;
+use crate::asm::{self, Dialect};
+use crate::isa::{Instruction, MAX_CODE_LEN};
+use crate::testing::{Failure, Location, Mismatch, TestCase, TestError, TestResult, Value, parse_tests};
+use crate::vm::{Assertions, RunError};
+use crate::vm::embedded::EmbeddedIO;
+
+/// Assembled program along with the tests found in its source.
+#[derive(Debug, Clone)]
+pub struct TestSuite {
+	pub code: Vec<Instruction>,
+	/// Addresses of labels, used to resolve `call`s
+	pub labels: BTreeMap<String, u16>,
+	/// `ASSERT`s checked while the tests run
+	pub assertions: Assertions,
+	pub tests: Vec<TestCase>,
+}
+
+impl TestSuite {
+	/// Assembles a source and collects its tests, see [`parse_tests`] for the syntax.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::asm::Dialect;
+	/// use batpu2::testing::TestSuite;
+	///
+	/// let suite = TestSuite::from_asm(r#"
+	/// ; test "double" setup: r1=21 call .double expect: r1=42
+	/// ; test "overflow" setup: r1=200 call .double expect: r1=144 flags.carry=1
+	/// .double ADD r1 r1 r1
+	///         RET
+	/// "#, Dialect::Extended).unwrap();
+	///
+	/// assert!(suite.run().iter().all(|result| result.passed()));
+	/// ```
+	pub fn from_asm(source: &str, dialect: Dialect) -> Result<TestSuite, TestError<'_>> {
+		let lines = asm::parse_lines_with(source, dialect).collect::<Result<Vec<_>, _>>()?;
+		let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+		let code = assembler.by_ref().collect::<Result<Vec<_>, _>>()?;
+		
+		let labels = assembler.symbols()
+		                      .iter()
+		                      .filter(|(_, symbol)| symbol.label)
+		                      .map(|(&name, symbol)| (name.to_owned(), symbol.value as u16))
+		                      .collect();
+		
+		Ok(TestSuite { code, labels, assertions: assembler.assertions().clone(), tests: parse_tests(source, dialect)? })
+	}
+	
+	/// Runs all tests, each one in a fresh [`BatPU2`] in checked mode.
+	pub fn run(&self) -> Vec<TestResult> {
+		self.tests.iter().map(|test| self.run_test(test)).collect()
+	}
+	
+	pub fn run_test(&self, test: &TestCase) -> TestResult {
+		let result = |steps, failure| TestResult { name: test.name.clone(), line_number: test.line_number, steps, failure };
+		
+		// Routines return to a `HLT` placed after the program
+		let mut code = self.code.clone();
+		let halt = code.len() as u16;
+		code.push(Instruction::HLT);
+		
+		let mut vm = BatPU2::new(code);
+		
+		for (location, value) in &test.setup {
+			let &Value::Int(value) = value else {
+				return result(0, Some(Failure::InvalidSetup(*location)));
+			};
+			
+			match *location {
+				Location::Reg(reg @ 1..) => vm.registers[reg as usize - 1] = value,
+				Location::Mem(address) => vm.memory[address as usize] = value,
+				Location::Zero => vm.flags.zero = value != 0,
+				Location::Carry => vm.flags.carry = value != 0,
+				Location::Controller => vm.io.controller.state = value,
+				Location::Reg(0) | Location::Chars | Location::Number | Location::Pixel(..) => return result(0, Some(Failure::InvalidSetup(*location))),
+			}
+		}
+		
+		if let Some(label) = &test.call {
+			if self.code.len() >= MAX_CODE_LEN {
+				return result(0, Some(Failure::NoReturnAddress { len: self.code.len() }));
+			}
+			
+			let address = self.labels
+			                  .get(label)
+			                  .copied()
+			                  .or_else(|| asm::parse_literal(asm::Token::new(0, label)).map(|address| address as u16));
+			
+			let Some(address) = address else {
+				return result(0, Some(Failure::UnknownLabel(label.clone())));
+			};
+			
+			vm.pc = address;
+			vm.call_stack[0] = halt;
+		}
+		
+		let steps = match vm.try_step_multiple_checked(test.limit, &self.assertions) {
+			Ok(steps) => steps,
+			Err(RunError::AssertionFailed(failure)) => return result(0, Some(Failure::Assertion(failure))),
+			Err(RunError::IOError(never) | RunError::CodeError(never)) => never,
+		};
+		
+		if !vm.halted {
+			return result(steps, Some(Failure::Timeout { limit: test.limit }));
+		}
+		
+		let mismatches: Vec<_> = test.expect
+		                             .iter()
+		                             .filter_map(|(location, expected)| compare(&vm, *location, expected))
+		                             .collect();
+		
+		result(steps, (!mismatches.is_empty()).then_some(Failure::Mismatch(mismatches)))
+	}
+}
+
+fn compare(vm: &BatPU2<Vec<Instruction>, EmbeddedIO>, location: Location, expected: &Value) -> Option<Mismatch> {
+	let actual = match location {
+		Location::Reg(0) => Value::Int(0),
+		Location::Reg(reg) => Value::Int(vm.registers[reg as usize - 1]),
+		Location::Mem(address) => Value::Int(vm.memory[address as usize]),
+		Location::Zero => Value::Int(vm.flags.zero as u8),
+		Location::Carry => Value::Int(vm.flags.carry as u8),
+		Location::Controller => Value::Int(vm.io.controller.state),
+		Location::Chars => Value::Text(vm.io.char_display.to_string().trim_end().to_owned()),
+		Location::Number => match vm.io.number_display.value {
+			Some(value) => Value::Int(value),
+			None => Value::Text("nothing".to_owned()),
+		},
+		Location::Pixel(x, y) => Value::Int(vm.io.screen.get_pixel(x, y) as u8),
+	};
+	
+	let matches = match (expected, &actual) {
+		(Value::Text(expected), Value::Text(actual)) => expected.trim_end() == actual,
+		(expected, actual) => expected == actual,
+	};
+	
+	(!matches).then(|| Mismatch { location, expected: expected.clone(), actual })
+}
@@ -0,0 +1,404 @@
+use std::fmt::{self, Write, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::isa::{Instruction, MAX_CODE_LEN};
+
+const HEADER: &str = "batpu2-cartridge 1";
+
+/// Names of the controller buttons, in the order of their bits.
+const BUTTON_NAMES: [&str; 8] = ["LEFT", "DOWN", "RIGHT", "UP", "B", "A", "SELECT", "START"];
+
+/// Size of the data memory, which [`Cartridge::memory`] is loaded into from address 0.
+pub const MEMORY_LEN: usize = 240;
+
+/// A program bundled with its initial memory and everything needed to play it, stored in `.bpc` files.
+///
+/// Cartridges are serialized using a line based text format ending with a CRC-32 of all the lines
+/// before it, so a damaged or hand edited file is rejected instead of running with wrong settings:
+///
+/// ```text
+/// batpu2-cartridge 1
+/// title Snake
+/// author mattbatwings
+/// version 1.2
+/// speed 250
+/// clear-mask A,B,SELECT,START
+/// key space A
+/// seed 42
+/// memory 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f
+/// code
+/// 1000000100000101
+/// ...
+/// checksum 1a2b3c4d
+/// ```
+///
+/// Buttons are written as comma separated names of [`Controller`](crate::vm::embedded::Controller)
+/// buttons or `NONE`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cartridge {
+	/// Title, author and version of the game, each must fit on a single line.
+	pub title: String,
+	pub author: String,
+	pub version: String,
+	pub code: Vec<Instruction>,
+	/// Initial data memory, at most [`MEMORY_LEN`] bytes.
+	pub memory: Vec<u8>,
+	/// Buttons cleared after the controller is read, `None` keeps the choice of the emulator.
+	pub clear_mask: Option<u8>,
+	/// Keyboard keys, as named by the emulator, and the buttons they press.
+	pub keys: Vec<(String, u8)>,
+	/// Recommended clock speed in instructions per second.
+	pub speed: Option<f32>,
+	pub seed: SeedPolicy,
+}
+
+/// How the random number generator is seeded when a [`Cartridge`] starts.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum SeedPolicy {
+	/// Seeded differently on every run by the emulator.
+	#[default]
+	Random,
+	/// Same numbers on every run, useful for puzzles and replays.
+	Fixed(u64),
+}
+
+impl Cartridge {
+	/// Creates a cartridge holding only a program.
+	pub fn new(code: Vec<Instruction>) -> Cartridge {
+		Cartridge { code, ..Cartridge::default() }
+	}
+	
+	/// Parses button names separated by commas, case insensitive, `NONE` is no button.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::utils::Cartridge;
+	///
+	/// assert_eq!(Cartridge::parse_buttons("a,start"), Some(0xA0));
+	/// assert_eq!(Cartridge::parse_buttons("none"), Some(0));
+	/// assert_eq!(Cartridge::parse_buttons("a,x"), None);
+	/// ```
+	pub fn parse_buttons(names: &str) -> Option<u8> {
+		if names.eq_ignore_ascii_case("NONE") {
+			return Some(0);
+		}
+		
+		names.split(',').try_fold(0, |buttons, name| {
+			let bit = BUTTON_NAMES.iter().position(|button| button.eq_ignore_ascii_case(name.trim()))?;
+			Some(buttons | 1 << bit)
+		})
+	}
+	
+	/// Checks that every field can be written in the text format and read back, titles, authors and versions
+	/// must not contain line breaks or start or end with whitespace, the speed must be finite and above 0 and
+	/// key names must be non-empty without whitespace.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::utils::Cartridge;
+	///
+	/// let mut cartridge = Cartridge::new(Vec::new());
+	/// cartridge.keys.push(("space".to_owned(), 0x20));
+	/// assert!(cartridge.validate().is_ok());
+	///
+	/// cartridge.keys.push((" ".to_owned(), 0x20));
+	/// assert!(cartridge.validate().is_err());
+	/// ```
+	pub fn validate(&self) -> Result<(), CartridgeError> {
+		for (field, value) in [("title", &self.title), ("author", &self.author), ("version", &self.version)] {
+			if value.contains(['\n', '\r']) {
+				return Err(CartridgeError::MultilineText { field });
+			}
+			
+			if value.trim() != value {
+				return Err(CartridgeError::PaddedText { field });
+			}
+		}
+		
+		if let Some(speed) = self.speed.filter(|speed| !speed.is_finite() || *speed <= 0.0) {
+			return Err(CartridgeError::InvalidSpeed { speed });
+		}
+		
+		for (key, _) in &self.keys {
+			if key.is_empty() || key.contains(char::is_whitespace) {
+				return Err(CartridgeError::InvalidKey { key: key.clone() });
+			}
+		}
+		
+		if self.memory.len() > MEMORY_LEN {
+			return Err(CartridgeError::MemoryTooLong { len: self.memory.len() });
+		}
+		
+		Ok(())
+	}
+	
+	/// Creates a virtual machine running the program with the initial memory, the clear mask and a fixed
+	/// seed applied. A random seed is left to the caller, since the library has no source of entropy.
+	///
+	/// # Panics
+	///
+	/// Panics if the memory is longer than [`MEMORY_LEN`].
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::utils::{Cartridge, SeedPolicy};
+	///
+	/// let mut cartridge = Cartridge::new(batpu2::utils::from_asm("HLT").unwrap());
+	/// cartridge.memory = vec![0, 0, 0, 42];
+	/// cartridge.clear_mask = Some(0);
+	/// cartridge.seed = SeedPolicy::Fixed(7);
+	///
+	/// let vm = cartridge.to_vm();
+	///
+	/// assert_eq!(vm.memory[3], 42);
+	/// assert_eq!(vm.io.controller.clear_mask(), 0);
+	/// ```
+	#[cfg(feature = "embedded_io")]
+	pub fn to_vm(&self) -> crate::BatPU2 {
+		let mut vm = crate::BatPU2::new(self.code.clone());
+		
+		vm.memory[..self.memory.len()].copy_from_slice(&self.memory);
+		
+		if let Some(clear_mask) = self.clear_mask {
+			vm.io.controller.set_clear_mask(clear_mask);
+		}
+		
+		if let Some(seed) = self.seed.to_seed() {
+			vm.io.set_seed(seed);
+		}
+		
+		vm
+	}
+}
+
+impl SeedPolicy {
+	/// Returns the seed of the random number generator, `None` if it should be random.
+	pub fn to_seed(self) -> Option<[u8; 32]> {
+		match self {
+			SeedPolicy::Random => None,
+			SeedPolicy::Fixed(value) => {
+				let mut seed = [0; 32];
+				seed[..8].copy_from_slice(&value.to_le_bytes());
+				Some(seed)
+			}
+		}
+	}
+}
+
+struct Buttons(u8);
+
+impl Display for Buttons {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.0 == 0 {
+			return "NONE".fmt(f);
+		}
+		
+		let names: Vec<&str> = (0..8).filter(|bit| self.0 & 1 << bit != 0)
+		                             .map(|bit| BUTTON_NAMES[bit])
+		                             .collect();
+		
+		names.join(",").fmt(f)
+	}
+}
+
+/// CRC-32 as used by zip and PNG.
+pub(super) fn crc32(bytes: &[u8]) -> u32 {
+	let mut crc = !0_u32;
+	
+	for &byte in bytes {
+		crc ^= byte as u32;
+		
+		for _ in 0..8 {
+			crc = crc >> 1 ^ 0xEDB88320 & (crc & 1).wrapping_neg();
+		}
+	}
+	
+	!crc
+}
+
+/// Writes the text format, fields rejected by [`Cartridge::validate`] are written as they are and cannot be
+/// read back.
+impl Display for Cartridge {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let mut body = String::new();
+		
+		writeln!(body, "{HEADER}")?;
+		
+		for (keyword, value) in [("title", &self.title), ("author", &self.author), ("version", &self.version)] {
+			if !value.is_empty() {
+				writeln!(body, "{keyword} {value}")?;
+			}
+		}
+		
+		if let Some(speed) = self.speed {
+			writeln!(body, "speed {speed}")?;
+		}
+		
+		if let Some(clear_mask) = self.clear_mask {
+			writeln!(body, "clear-mask {}", Buttons(clear_mask))?;
+		}
+		
+		for (key, buttons) in &self.keys {
+			writeln!(body, "key {key} {}", Buttons(*buttons))?;
+		}
+		
+		if let SeedPolicy::Fixed(seed) = self.seed {
+			writeln!(body, "seed {seed}")?;
+		}
+		
+		for chunk in self.memory.chunks(16) {
+			let bytes: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+			writeln!(body, "memory {}", bytes.join(" "))?;
+		}
+		
+		writeln!(body, "code")?;
+		
+		for instruction in &self.code {
+			writeln!(body, "{:016b}", instruction.as_word())?;
+		}
+		
+		writeln!(f, "{body}checksum {:08x}", crc32(body.as_bytes()))
+	}
+}
+
+impl FromStr for Cartridge {
+	type Err = CartridgeError;
+	
+	/// Loads a cartridge from its text representation, verifying its checksum
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::utils::Cartridge;
+	///
+	/// let mut cartridge = Cartridge::new(batpu2::utils::from_asm("HLT").unwrap());
+	/// cartridge.title = "Halt".to_owned();
+	/// cartridge.speed = Some(250.0);
+	///
+	/// let text = cartridge.to_string();
+	///
+	/// assert_eq!(text.parse::<Cartridge>().unwrap(), cartridge);
+	/// assert!(text.replace("Halt", "Stop").parse::<Cartridge>().is_err());
+	/// ```
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let trimmed = text.trim_end();
+		let (body, checksum) = match trimmed.rfind('\n') {
+			Some(end) => (&trimmed[..end + 1], &trimmed[end + 1..]),
+			None => ("", trimmed),
+		};
+		
+		let found = checksum.trim()
+		                    .strip_prefix("checksum ")
+		                    .and_then(|checksum| u32::from_str_radix(checksum.trim(), 16).ok())
+		                    .ok_or(CartridgeError::MissingChecksum)?;
+		let expected = crc32(body.as_bytes());
+		
+		if found != expected {
+			return Err(CartridgeError::Checksum { expected, found });
+		}
+		
+		let mut lines = body.lines()
+		                    .enumerate()
+		                    .map(|(line_number, line)| (line_number + 1, line.trim()))
+		                    .filter(|(_, line)| !line.is_empty());
+		
+		let error = |line_number: usize, line: &str| CartridgeError::Syntax { line_number, line: line.to_owned() };
+		
+		match lines.next() {
+			Some((_, HEADER)) => {}
+			_ => return Err(CartridgeError::MissingHeader),
+		}
+		
+		let mut cartridge = Cartridge::default();
+		
+		for (line_number, line) in lines.by_ref() {
+			let (keyword, value) = line.split_once(' ').map_or((line, ""), |(keyword, value)| (keyword, value.trim()));
+			
+			match keyword {
+				"title" => cartridge.title = value.to_owned(),
+				"author" => cartridge.author = value.to_owned(),
+				"version" => cartridge.version = value.to_owned(),
+				"speed" => {
+					let speed: f32 = value.parse().map_err(|_| error(line_number, line))?;
+					
+					if !speed.is_finite() || speed <= 0.0 {
+						return Err(error(line_number, line));
+					}
+					
+					cartridge.speed = Some(speed);
+				}
+				"clear-mask" => cartridge.clear_mask = Some(Cartridge::parse_buttons(value).ok_or_else(|| error(line_number, line))?),
+				"key" => {
+					let Some((key, buttons)) = value.split_once(' ') else { return Err(error(line_number, line)) };
+					let buttons = Cartridge::parse_buttons(buttons.trim()).ok_or_else(|| error(line_number, line))?;
+					
+					cartridge.keys.push((key.to_owned(), buttons));
+				}
+				"seed" => cartridge.seed = SeedPolicy::Fixed(value.parse().map_err(|_| error(line_number, line))?),
+				"memory" => {
+					for byte in value.split_whitespace() {
+						cartridge.memory.push(u8::from_str_radix(byte, 16).map_err(|_| error(line_number, line))?);
+					}
+					
+					if cartridge.memory.len() > MEMORY_LEN {
+						return Err(error(line_number, line));
+					}
+				}
+				"code" if value.is_empty() => break,
+				_ => return Err(error(line_number, line)),
+			}
+		}
+		
+		for (line_number, line) in lines {
+			let word = u16::from_str_radix(line, 2).map_err(|_| error(line_number, line))?;
+			
+			if cartridge.code.len() >= MAX_CODE_LEN {
+				return Err(error(line_number, line));
+			}
+			
+			cartridge.code.push(word.into());
+		}
+		
+		Ok(cartridge)
+	}
+}
+
+/// An error which can be returned when parsing a [`Cartridge`]
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum CartridgeError {
+	#[error("Missing \"{HEADER}\" header")]
+	MissingHeader,
+	#[error("Missing checksum on the last line")]
+	MissingChecksum,
+	#[error("Checksum is {found:08x}, expected {expected:08x}, the cartridge is damaged")]
+	Checksum { expected: u32, found: u32 },
+	#[error("{line_number}: Cannot parse \"{line}\"")]
+	Syntax { line_number: usize, line: String },
+	#[error("The {field} must fit on a single line")]
+	MultilineText { field: &'static str },
+	#[error("The {field} must not start or end with whitespace")]
+	PaddedText { field: &'static str },
+	#[error("Speed {speed} is not a positive number")]
+	InvalidSpeed { speed: f32 },
+	#[error("Key name \"{key}\" is empty or contains whitespace")]
+	InvalidKey { key: String },
+	#[error("Memory has {len} bytes (max {MEMORY_LEN})")]
+	MemoryTooLong { len: usize },
+}
+
+impl CartridgeError {
+	/// Returns the line the error was found at.
+	pub fn line_number(&self) -> Option<usize> {
+		match self {
+			CartridgeError::Syntax { line_number, .. } => Some(*line_number),
+			CartridgeError::MissingHeader | CartridgeError::MissingChecksum | CartridgeError::Checksum { .. } => None,
+			CartridgeError::MultilineText { .. } | CartridgeError::PaddedText { .. } | CartridgeError::InvalidSpeed { .. } => None,
+			CartridgeError::InvalidKey { .. } | CartridgeError::MemoryTooLong { .. } => None,
+		}
+	}
+}
@@ -0,0 +1,138 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use flate2::{Compression, Crc};
+use flate2::write::DeflateEncoder;
+
+use crate::isa::{Instruction, MAX_CODE_LEN};
+use super::schem::{cell_block, rom_position};
+
+/// Pack format of Minecraft 1.18.2, which the BatPU-2 world is saved in.
+const PACK_FORMAT: u32 = 9;
+
+/// Path of the function writing the program, which is run with `/function batpu2:load`.
+pub const LOAD_FUNCTION: &str = "data/batpu2/functions/load.mcfunction";
+
+/// Files of a datapack writing a program into the ROM of the BatPU-2 world, see [`into_datapack`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Datapack {
+	/// Paths relative to the root of the datapack and their contents.
+	pub files: Vec<(String, String)>,
+}
+
+/// Builds a datapack whose `batpu2:load` function writes a program into the ROM of the BatPU-2 world.
+///
+/// `origin` is the position in the world which [`rom_position`] is relative to, the point schematics of
+/// [`into_schem`](super::into_schem) are pasted from. Every cell gets its own `setblock`, since cells are
+/// never next to each other and a `fill` would overwrite the wiring between them. Addresses after the end
+/// of the program are cleared.
+///
+/// The positions come from [`rom_position`], whose layout has not been verified against the world. If it is
+/// wrong, the 16384 `setblock`s overwrite the wiring of the computer, so run the function on a copy of the
+/// world first.
+///
+/// # Panics
+///
+/// Panics if the program is longer than [`MAX_CODE_LEN`].
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+/// use batpu2::utils::LOAD_FUNCTION;
+///
+/// let datapack = batpu2::utils::into_datapack(&[Instruction::JMP { addr: 1 }], [100, 64, -20]);
+/// let (_, load) = datapack.files.iter().find(|(path, _)| path == LOAD_FUNCTION).unwrap();
+///
+/// assert!(load.contains("setblock 96 63 -18 minecraft:repeater[facing=east]\n"));
+/// assert!(load.contains("setblock 96 61 -18 minecraft:purple_wool\n"));
+/// assert_eq!(load.lines().filter(|line| line.starts_with("setblock")).count(), 1024 * 16);
+/// ```
+pub fn into_datapack(instructions: &[Instruction], origin: [i32; 3]) -> Datapack {
+	assert!(instructions.len() <= MAX_CODE_LEN, "program has {} instructions (max {MAX_CODE_LEN})", instructions.len());
+	
+	let [x, y, z] = origin;
+	let mut load = format!("# Writes a program of {} instructions into the ROM, relative to {x} {y} {z}\n", instructions.len());
+	
+	for address in 0..MAX_CODE_LEN {
+		let word = instructions.get(address).map_or(0, |instruction| instruction.as_word());
+		
+		if address == instructions.len() {
+			load.push_str("# Unused addresses\n");
+		}
+		
+		for bit in 0..16 {
+			let position = rom_position(address, bit);
+			let [x, y, z] = std::array::from_fn(|axis| origin[axis] + position[axis]);
+			
+			writeln!(load, "setblock {x} {y} {z} {}", cell_block(address, word << bit & 0x8000 != 0)).unwrap();
+		}
+	}
+	
+	writeln!(load, r#"tellraw @s {{"text":"Loaded {} instructions into the ROM"}}"#, instructions.len()).unwrap();
+	
+	let mcmeta = format!(r#"{{"pack":{{"pack_format":{PACK_FORMAT},"description":"BatPU-2 program, run /function batpu2:load"}}}}"#);
+	
+	Datapack {
+		files: vec![
+			("pack.mcmeta".to_owned(), mcmeta + "\n"),
+			(LOAD_FUNCTION.to_owned(), load),
+		],
+	}
+}
+
+impl Datapack {
+	/// Packs the files into a zip archive, which Minecraft loads like a directory.
+	pub fn to_zip(&self) -> Vec<u8> {
+		let mut output = Vec::new();
+		let mut directory = Vec::new();
+		
+		for (path, content) in &self.files {
+			let mut crc = Crc::new();
+			crc.update(content.as_bytes());
+			
+			let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+			encoder.write_all(content.as_bytes()).unwrap();
+			let data = encoder.finish().unwrap();
+			
+			// Version 2.0, deflated, modified at 1980-01-01 00:00
+			let header = [
+				&20_u16.to_le_bytes()[..],
+				&0_u16.to_le_bytes(),
+				&8_u16.to_le_bytes(),
+				&0_u16.to_le_bytes(),
+				&0x21_u16.to_le_bytes(),
+				&crc.sum().to_le_bytes(),
+				&(data.len() as u32).to_le_bytes(),
+				&(content.len() as u32).to_le_bytes(),
+				&(path.len() as u16).to_le_bytes(),
+				&0_u16.to_le_bytes(),
+			].concat();
+			
+			directory.extend(0x02014B50_u32.to_le_bytes());
+			directory.extend(20_u16.to_le_bytes());
+			directory.extend(&header);
+			directory.extend([0; 10]);
+			directory.extend((output.len() as u32).to_le_bytes());
+			directory.extend(path.as_bytes());
+			
+			output.extend(0x04034B50_u32.to_le_bytes());
+			output.extend(&header);
+			output.extend(path.as_bytes());
+			output.extend(data);
+		}
+		
+		let offset = output.len() as u32;
+		let entries = (self.files.len() as u16).to_le_bytes();
+		
+		output.extend(&directory);
+		output.extend(0x06054B50_u32.to_le_bytes());
+		output.extend([0; 4]);
+		output.extend(entries);
+		output.extend(entries);
+		output.extend((directory.len() as u32).to_le_bytes());
+		output.extend(offset.to_le_bytes());
+		output.extend([0; 2]);
+		
+		output
+	}
+}
@@ -0,0 +1,529 @@
+//! Utility functions for loading and assembling program files, and the machine code formats of [`RomFormat`] and [`Cartridge`]s
+
+use std::fmt::{self, Write, Display, Formatter};
+use std::ops::RangeInclusive;
+use std::num::ParseIntError;
+use thiserror::Error;
+
+use crate::asm::{self, AsmError, Dialect};
+use crate::isa::Instruction;
+
+mod rom;
+mod cartridge;
+#[cfg(feature = "schematic")]
+mod nbt;
+#[cfg(feature = "schematic")]
+mod schem;
+#[cfg(feature = "schematic")]
+mod datapack;
+
+pub use rom::*;
+pub use cartridge::*;
+#[cfg(feature = "schematic")]
+pub use schem::*;
+#[cfg(feature = "schematic")]
+pub use datapack::*;
+
+/// Parses and assembles a program from a source code written in BatPU2 assembly
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let code = "
+/// JMP .label
+/// .label ADD r1 r2 r3
+/// ";
+///
+/// let program = batpu2::utils::from_asm(code).unwrap();
+///
+/// assert_eq!(&program, &[
+///     Instruction::JMP { addr: 1 },
+///     Instruction::ADD{ a: 1, b: 2, c: 3 },
+/// ]);
+/// ```
+pub fn from_asm(code: &str) -> Result<Vec<Instruction>, AsmError<'_>> {
+	from_asm_with(code, Dialect::default())
+}
+
+/// Parses and assembles a program using the given assembly [`Dialect`]
+///
+/// # Example
+///
+/// ```
+/// use batpu2::asm::Dialect;
+/// use batpu2::isa::Instruction;
+///
+/// let code = "
+/// define twelve 0xC
+/// LDI r1 twelve // comment
+/// ";
+///
+/// assert_eq!(batpu2::utils::from_asm_with(code, Dialect::Extended).unwrap(), [Instruction::LDI { a: 1, imm: 12 }]);
+/// assert!(batpu2::utils::from_asm_with(code, Dialect::Upstream).is_err());
+/// ```
+pub fn from_asm_with(code: &str, dialect: Dialect) -> Result<Vec<Instruction>, AsmError<'_>> {
+	let lines = asm::parse_lines_with(code, dialect).collect::<Result<Vec<_>, _>>()?;
+	let instructions = asm::Assembler::new(&lines).dialect(dialect).collect::<Result<Vec<_>, _>>()?;
+	
+	Ok(instructions)
+}
+
+/// Loads a compiled program from a compiled code in .mc format
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let code = "
+/// 1010000000000001
+/// 0010000100100011
+/// ";
+///
+/// let program = batpu2::utils::from_mc(code).unwrap();
+///
+/// assert_eq!(&program, &[
+///     Instruction::JMP { addr: 1 },
+///     Instruction::ADD{ a: 1, b: 2, c: 3 },
+/// ]);
+/// ```
+pub fn from_mc(code: &str) -> Result<Vec<Instruction>, FromMcError> {
+	code.lines()
+		.enumerate()
+		.filter(|(_, line)| !line.is_empty())
+	    .map(|(line_number, line)|
+		    u16::from_str_radix(line, 2)
+		        .map(Into::into)
+		        .map_err(|source| FromMcError { line_number, line: line.to_owned(), source }))
+	    .collect()
+}
+
+/// Serializes a program into a string in .mc format
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let program = [
+///     Instruction::JMP { addr: 1 },
+///     Instruction::ADD{ a: 1, b: 2, c: 3 },
+/// ];
+///
+/// let code = batpu2::utils::into_mc(&program);
+///
+/// assert_eq!(&code, "1010000000000001\n0010000100100011\n");
+/// ```
+pub fn into_mc(instructions: &[Instruction]) -> String {
+	let mut output = String::with_capacity(instructions.len() * 17);
+	
+	for instruction in instructions.iter() {
+		writeln!(output, "{:016b}", instruction.as_word()).unwrap();
+	}
+	
+	output
+}
+
+pub(crate) struct PrettyRange<'a>(pub &'a RangeInclusive<usize>);
+
+impl Display for PrettyRange<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if *self.0.start() == 0 && *self.0.end() == 0 {
+			write!(f, "no")
+		} else if self.0.start() == self.0.end() {
+			write!(f, "{}", *self.0.start())
+		} else {
+			write!(f, "from {} to {}", *self.0.start(), *self.0.end())
+		}
+	}
+}
+
+/// Single character encoded using BatPU-2 character encoding:
+///
+/// ```text
+/// 0 - SPACE
+/// 1 - 26 - A - Z
+/// 27 - .
+/// 28 - !
+/// 29 - ?
+/// ```
+///
+/// ### Invalid Characters
+///
+/// `Char` can be constructed using an u8 that does not correspond to any character in the character
+/// table lays outside. Such invalid characters are not printed by redstone text screen(needs checking)
+/// but can still be generated by a program.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Default, Hash)]
+pub struct Char(u8);
+
+impl Char {
+	/// BatPU-2 character table(uppercase).
+	pub const TABLE: [char; 30] = [' ', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '.', '!', '?'];
+	pub const SPACE: Char = Char(0);
+	
+	/// Constructs new character from its code.
+	pub fn new(value: u8) -> Self {
+		Self(value)
+	}
+	
+	/// Converts character to its `char` representation. Returns `None` if the character code lays outside the character table.
+	pub fn to_char(self) -> Option<char> {
+		Char::TABLE.get(self.as_u8() as usize)
+		           .copied()
+	}
+	
+	/// Returns character code
+	pub fn as_u8(self) -> u8 {
+		self.0
+	}
+	
+	/// Checks if the character lays within the character table
+	pub fn is_valid(self) -> bool {
+		(self.as_u8() as usize) < Char::TABLE.len()
+	}
+}
+
+impl TryFrom<char> for Char {
+	type Error = InvalidCharacter;
+	
+	fn try_from(value: char) -> Result<Self, Self::Error> {
+		for (id, char) in Self::TABLE.into_iter().enumerate() {
+			if value == char {
+				return Ok(Char(id as u8))
+			}
+		}
+		
+		Err(InvalidCharacter { char: value })
+	}
+}
+
+impl Display for Char {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if let Some(c) = self.to_char() {
+			c.fmt(f)
+		} else {
+			write!(f, "<{}>", self.0)
+		}
+	}
+}
+
+/// An error which can be returned when loading .mc code
+#[derive(Error, Debug)]
+#[error("{line_number}: Cannot parse \"{line}\"")]
+pub struct FromMcError {
+	line_number: usize,
+	line: String,
+	#[source] source: ParseIntError,
+}
+
+impl FromMcError {
+	pub fn line_number(&self) -> usize {
+		self.line_number
+	}
+	
+	pub fn line(&self) -> &str {
+		self.line.as_str()
+	}
+}
+
+/// An error which can be returned when converting [`char`] to [`Char`]
+#[derive(Error, Debug)]
+#[error("Invalid character \"{char}\"")]
+pub struct InvalidCharacter { char: char }
+
+impl InvalidCharacter {
+	pub fn char(&self) -> char {
+		self.char
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::isa::MAX_CODE_LEN;
+	
+	fn program() -> Vec<Instruction> {
+		from_asm("
+			LDI r1 5
+		.loop
+			DEC r1
+			BRH nz .loop
+			NOP
+			NOP
+			NOP
+			NOP
+			NOP
+			HLT
+		").unwrap()
+	}
+	
+	#[test]
+	fn round_trip() {
+		let program = program();
+		
+		for &format in RomFormat::ALL {
+			let bytes = format.write(&program);
+			
+			assert_eq!(format.read(&bytes).unwrap(), program, "{format}");
+			assert_eq!(format.to_string().parse::<RomFormat>().unwrap(), format);
+			
+			if format != RomFormat::Bin(Endian::Little) {
+				assert_eq!(RomFormat::detect(&bytes), Some(if format == RomFormat::ReadMemB { RomFormat::Mc } else { format }), "{format}");
+			}
+		}
+		
+		assert_eq!(into_logisim(&program), "v2.0 raw\n8105 91ff b401 5*0 1000\n");
+		assert!("hex".parse::<RomFormat>().is_err());
+	}
+	
+	#[test]
+	fn detects() {
+		let guess = |path: &str, bytes: &[u8]| RomFormat::guess(std::path::Path::new(path), bytes);
+		
+		assert_eq!(guess("prog.bin", b"\x81\x05"), Some(RomFormat::Bin(Endian::Big)));
+		assert_eq!(guess("prog.mem", b"@10 0000_0001_0000_0000 // comment"), Some(RomFormat::ReadMemH));
+		assert_eq!(guess("prog", b"@10 0000_0001_0000_0000 // comment"), Some(RomFormat::ReadMemB));
+		assert_eq!(guess("prog", b"/* ROM\n */ 8105 9101"), Some(RomFormat::ReadMemH));
+		assert_eq!(guess("prog.hex", b"v2.0 raw\n8105"), Some(RomFormat::Logisim));
+		assert_eq!(guess("prog.hex", b":00000001FF"), Some(RomFormat::IntelHex));
+		assert_eq!(guess("prog.bin", b"8105\n9101\n"), Some(RomFormat::Bin(Endian::Big)));
+		assert_eq!(guess("prog.mc", b":00000001FF"), Some(RomFormat::Mc));
+		assert_eq!(guess("prog", b"LDI r1 5\nHLT"), None);
+		assert_eq!(guess("prog.s", b"8105"), None);
+	}
+	
+	#[test]
+	fn rejects_long_programs() {
+		let nops = vec![Instruction::NOP; MAX_CODE_LEN + 1];
+		
+		for &format in RomFormat::ALL {
+			#[cfg(feature = "schematic")]
+			if format == RomFormat::Schem {
+				continue;
+			}
+			
+			assert!(format.read(&format.write(&nops[1..])).is_ok(), "{format}");
+			assert!(format.read(&format.write(&nops)).is_err(), "{format}");
+		}
+		
+		assert!(matches!(from_readmemh("@3ff 0 0"), Err(RomError::OutOfRange { line_number: 1, address: 0x400 })));
+		assert!(matches!(from_logisim("v2.0 raw\n1024*0 1"), Err(RomError::OutOfRange { line_number: 2, address: 0x400 })));
+		assert!(matches!(from_logisim("v2.0 raw\na001 18446744073709551615*0"), Err(RomError::OutOfRange { line_number: 2, address: usize::MAX })));
+	}
+	
+	#[test]
+	#[cfg(feature = "schematic")]
+	fn schematic() {
+		use std::collections::HashSet;
+		use std::io::Read;
+		use nbt::Tag;
+		
+		let positions: HashSet<_> = (0..MAX_CODE_LEN).flat_map(|address| (0..16).map(move |bit| rom_position(address, bit))).collect();
+		assert_eq!(positions.len(), MAX_CODE_LEN * 16);
+		
+		let program = program();
+		let mut nbt = Vec::new();
+		flate2::read::GzDecoder::new(&into_schem(&program)[..]).read_to_end(&mut nbt).unwrap();
+		
+		assert!(nbt.starts_with(b"\x0a\x00\x09Schematic\x03\x00\x07Version\x00\x00\x00\x02"));
+		
+		let (_, root) = Tag::read_root(&nbt).unwrap();
+		
+		assert_eq!(root.get("Offset"), Some(&Tag::IntArray(vec![-111, -33, 1])));
+		assert_eq!(["Width", "Height", "Length"].map(|name| root.get(name).and_then(Tag::int)), [Some(108), Some(33), Some(69)]);
+		
+		// The first bit of the program is set, so east facing repeaters are the first block after air
+		let start = nbt.windows(9).position(|name| name == b"BlockData").unwrap() + 9;
+		let len = i32::from_be_bytes(nbt[start..start + 4].try_into().unwrap()) as usize;
+		let ones: u32 = program.iter().map(|instruction| instruction.as_word().count_ones()).sum();
+		
+		assert_eq!(len, 108 * 33 * 69);
+		assert_eq!(nbt[start + 4..][..len].iter().filter(|&&id| id == 1).count(), ones as usize);
+		
+		// Replacing the second bit of the first instruction at -4 -3 2 by air, blocks go along x, then z, then y
+		// from the offset
+		let index = |[x, y, z]: [i32; 3]| ((x + 111) + (z - 1) * 108 + (y + 33) * 108 * 69) as usize;
+		nbt[start + 4 + index([-4, -3, 2])] = 0;
+		
+		let rom = read_schem(&nbt).unwrap();
+		
+		assert_eq!(rom.instructions(), program);
+		assert_eq!(rom.corrupted, [CorruptedCell { address: 0, bit: 1, position: [-4, -3, 2], block: Some("minecraft:air".into()) }]);
+		assert_eq!(from_schem(&nbt).unwrap_err().to_string(), "1 corrupted ROM cells, the first is address 0x000 bit 1 at -4 -3 2: minecraft:air");
+	}
+	
+	#[test]
+	#[cfg(feature = "schematic")]
+	fn litematic() {
+		use nbt::Tag;
+		
+		// Column of the first instruction 0x8105 at x -4 z 2, from y -33 up to its first bit at y -1. Bits are 2
+		// blocks apart with 2 more between both bytes, `R` is a repeater, `W` purple wool and `.` air.
+		let column = "R.W.R.W.W.W.W.W...R.W.W.W.W.W.W.R";
+		let mut longs = [0_u64; 2];
+		
+		for (index, block) in column.chars().enumerate() {
+			let state = ".WR".find(block).unwrap() as u64;
+			longs[index * 2 / 64] |= state << (index * 2 % 64);
+		}
+		
+		let vector = |x, y, z| Tag::compound([("x", Tag::Int(x)), ("y", Tag::Int(y)), ("z", Tag::Int(z))]);
+		let state = |name: &str, properties: Vec<(String, Tag)>| Tag::compound([("Name", Tag::String(name.into())), ("Properties", Tag::Compound(properties))]);
+		let region = Tag::compound([
+			("Position", vector(-4, -1, 2)),
+			("Size", vector(1, -33, 1)),
+			("BlockStatePalette", Tag::List(vec![
+				state("minecraft:air", vec![]),
+				state("minecraft:purple_wool", vec![]),
+				state("minecraft:repeater", vec![("delay".into(), Tag::String("1".into())), ("facing".into(), Tag::String("east".into()))]),
+			])),
+			("BlockStates", Tag::LongArray(longs.map(|long| long as i64).to_vec())),
+		]);
+		let litematic = Tag::compound([("Version", Tag::Int(6)), ("Regions", Tag::compound([("rom", region)]))]);
+		
+		let mut nbt = Vec::new();
+		litematic.write_root("", &mut nbt);
+		
+		let rom = read_schem(&nbt).unwrap();
+		
+		assert_eq!(rom.words[0], 0x8105);
+		assert_eq!(rom.corrupted.len(), (MAX_CODE_LEN - 1) * 16);
+		assert_eq!(rom.corrupted[0], CorruptedCell { address: 1, bit: 0, position: [-11, -1, 3], block: None });
+	}
+	
+	#[test]
+	#[cfg(feature = "schematic")]
+	fn rejects_overflowing_regions() {
+		use nbt::Tag;
+		
+		let sponge = |offset: [i32; 3], width: i16| {
+			let mut nbt = Vec::new();
+			Tag::compound([
+				("Width", Tag::Short(width)),
+				("Height", Tag::Short(1)),
+				("Length", Tag::Short(1)),
+				("Offset", Tag::IntArray(offset.to_vec())),
+				("Palette", Tag::compound([("minecraft:air", Tag::Int(0))])),
+				("BlockData", Tag::ByteArray(vec![0; width as usize])),
+			]).write_root("Schematic", &mut nbt);
+			read_schem(&nbt)
+		};
+		
+		assert!(matches!(sponge([i32::MAX, 0, 0], 2), Err(SchemError::InvalidTag { name: "Offset" })));
+		assert_eq!(sponge([i32::MAX, 0, 0], 1).unwrap().corrupted.len(), MAX_CODE_LEN * 16);
+		assert_eq!(sponge([i32::MIN, 0, 0], 1).unwrap().corrupted.len(), MAX_CODE_LEN * 16);
+		
+		let litematic = |position: [i32; 3], size: [i32; 3]| {
+			let vector = |[x, y, z]: [i32; 3]| Tag::compound([("x", Tag::Int(x)), ("y", Tag::Int(y)), ("z", Tag::Int(z))]);
+			let region = Tag::compound([
+				("Position", vector(position)),
+				("Size", vector(size)),
+				("BlockStatePalette", Tag::List(vec![Tag::compound([("Name", Tag::String("minecraft:air".into()))])])),
+				("BlockStates", Tag::LongArray(vec![0])),
+			]);
+			let mut nbt = Vec::new();
+			Tag::compound([("Regions", Tag::compound([("rom", region)]))]).write_root("", &mut nbt);
+			read_schem(&nbt)
+		};
+		
+		assert!(matches!(litematic([i32::MIN, 0, 0], [-2, 1, 1]), Err(SchemError::InvalidTag { name: "Position" })));
+		assert!(matches!(litematic([i32::MAX, 0, 0], [2, 1, 1]), Err(SchemError::InvalidTag { name: "Size" })));
+		assert!(matches!(litematic([0, 0, 0], [i32::MAX; 3]), Err(SchemError::InvalidTag { name: "Size" })));
+		assert!(matches!(litematic([0, 0, 0], [33, 1, 1]), Err(SchemError::InvalidTag { name: "BlockStates" })));
+	}
+	
+	#[test]
+	#[cfg(feature = "schematic")]
+	fn datapack() {
+		use std::io::Read;
+		
+		let program = program();
+		let datapack = into_datapack(&program, [0, 0, 0]);
+		let zip = datapack.to_zip();
+		
+		// Every file is listed by the end of central directory record and its local header is followed by its data
+		assert_eq!(zip[zip.len() - 12..zip.len() - 10], [2, 0]);
+		
+		let (path, content) = &datapack.files[0];
+		let data_len = u32::from_le_bytes(zip[18..22].try_into().unwrap()) as usize;
+		let mut inflated = String::new();
+		
+		assert_eq!(&zip[..4], b"PK\x03\x04");
+		assert_eq!(&zip[30..30 + path.len()], path.as_bytes());
+		
+		flate2::read::DeflateDecoder::new(&zip[30 + path.len()..][..data_len]).read_to_string(&mut inflated).unwrap();
+		assert_eq!(&inflated, content);
+	}
+	
+	#[test]
+	#[cfg(feature = "embedded_io")]
+	fn cartridge() {
+		use crate::vm::embedded::Controller;
+		
+		let original = Cartridge {
+			title: "Count down".to_owned(),
+			author: "batpu2-rs".to_owned(),
+			version: "1.0".to_owned(),
+			code: program(),
+			memory: (0..20).collect(),
+			clear_mask: Some(Controller::B_A | Controller::B_START),
+			keys: vec![("space".to_owned(), Controller::B_A), ("q".to_owned(), Controller::B_NONE)],
+			speed: Some(12.5),
+			seed: SeedPolicy::Fixed(u64::MAX),
+		};
+		let text = original.to_string();
+		
+		assert_eq!(text.parse::<Cartridge>().unwrap(), original);
+		assert!(text.contains("\nclear-mask A,START\nkey space A\nkey q NONE\n"));
+		assert!(text.contains("\nmemory 10 11 12 13\ncode\n"));
+		assert_eq!(cartridge::crc32(b"123456789"), 0xCBF43926);
+		
+		// Damaging any line is detected by the checksum, syntax errors only once the checksum matches
+		assert!(matches!(text.replace("12.5", "125").parse::<Cartridge>(), Err(CartridgeError::Checksum { .. })));
+		assert!(matches!(text.trim_end().rsplit_once('\n').unwrap().0.parse::<Cartridge>(), Err(CartridgeError::MissingChecksum)));
+		
+		let body = "batpu2-cartridge 1\nkey space X\ncode\n";
+		let damaged = format!("{body}checksum {:08x}\n", cartridge::crc32(body.as_bytes()));
+		
+		assert_eq!(damaged.parse::<Cartridge>().unwrap_err().line_number(), Some(2));
+		
+		// Fields which would be written as other lines or split differently are rejected before writing
+		let mut invalid = original.clone();
+		invalid.keys.push((" ".to_owned(), Controller::B_A));
+		
+		assert!(original.validate().is_ok());
+		assert!(matches!(invalid.validate(), Err(CartridgeError::InvalidKey { .. })));
+		
+		invalid.author = "batpu2-rs\nkey q A".to_owned();
+		
+		assert!(matches!(invalid.validate(), Err(CartridgeError::MultilineText { field: "author" })));
+		
+		invalid.author = "batpu2-rs ".to_owned();
+		
+		assert!(matches!(invalid.validate(), Err(CartridgeError::PaddedText { field: "author" })));
+		
+		invalid = original.clone();
+		invalid.speed = Some(0.0);
+		
+		assert!(matches!(invalid.validate(), Err(CartridgeError::InvalidSpeed { .. })));
+		
+		let mut long = original.clone();
+		long.code = vec![Instruction::NOP; MAX_CODE_LEN + 1];
+		
+		assert!(matches!(long.to_string().parse::<Cartridge>(), Err(CartridgeError::Syntax { .. })));
+	}
+	
+	#[test]
+	fn errors() {
+		assert!(matches!(from_ihex(":0400000081059101D5\n"), Err(RomError::Checksum { line_number: 1, expected: 0xE4, found: 0xD5 })));
+		assert!(matches!(from_ihex("\n:0200000081057"), Err(RomError::InvalidRecord { line_number: 2, .. })));
+		assert!(matches!(from_ihex(":02080000810570"), Err(RomError::OutOfRange { line_number: 1, address: 0x800 })));
+		assert!(matches!(from_ihex(":00000006FA"), Err(RomError::UnsupportedRecord { line_number: 1, kind: 6 })));
+		assert!(matches!(from_readmemh("8105\n@400"), Err(RomError::OutOfRange { line_number: 2, address: 0x400 })));
+		assert!(matches!(from_readmemb("0102"), Err(RomError::InvalidWord { line_number: 1, .. })));
+		assert!(matches!(from_logisim("8105"), Err(RomError::MissingHeader)));
+		assert!(matches!(from_logisim("v2.0 raw\n3*x"), Err(RomError::InvalidWord { line_number: 2, .. })));
+		assert_eq!(RomFormat::Logisim.read(b"v2.0 raw\nq").unwrap_err().line_number(), Some(2));
+		assert!(matches!(RomFormat::IntelHex.read(&[0xFF]), Err(RomError::NotText)));
+	}
+}
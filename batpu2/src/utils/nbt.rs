@@ -0,0 +1,213 @@
+//! Minimal reader and writer of Minecraft's Named Binary Tag format, enough for schematics
+
+/// Deepest nesting of lists and compounds accepted by the reader.
+const MAX_DEPTH: usize = 512;
+
+/// A value of an NBT tree.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tag {
+	Byte(i8),
+	Short(i16),
+	Int(i32),
+	Long(i64),
+	Float(f32),
+	Double(f64),
+	ByteArray(Vec<u8>),
+	String(String),
+	/// Elements must all be of the same type, an empty list is written as a list of `End` tags.
+	List(Vec<Tag>),
+	Compound(Vec<(String, Tag)>),
+	IntArray(Vec<i32>),
+	LongArray(Vec<i64>),
+}
+
+impl Tag {
+	/// Builds a compound from its entries.
+	pub fn compound<const N: usize>(entries: [(&str, Tag); N]) -> Tag {
+		Tag::Compound(entries.into_iter().map(|(name, tag)| (name.to_owned(), tag)).collect())
+	}
+	
+	pub fn id(&self) -> u8 {
+		match self {
+			Tag::Byte(_) => 1,
+			Tag::Short(_) => 2,
+			Tag::Int(_) => 3,
+			Tag::Long(_) => 4,
+			Tag::Float(_) => 5,
+			Tag::Double(_) => 6,
+			Tag::ByteArray(_) => 7,
+			Tag::String(_) => 8,
+			Tag::List(_) => 9,
+			Tag::Compound(_) => 10,
+			Tag::IntArray(_) => 11,
+			Tag::LongArray(_) => 12,
+		}
+	}
+	
+	/// Returns the entry of a compound with the given name.
+	pub fn get(&self, name: &str) -> Option<&Tag> {
+		match self {
+			Tag::Compound(entries) => entries.iter().find(|(entry, _)| entry == name).map(|(_, tag)| tag),
+			_ => None,
+		}
+	}
+	
+	/// Returns the value of an integer tag no larger than `Int`.
+	pub fn int(&self) -> Option<i32> {
+		match *self {
+			Tag::Byte(value) => Some(value as i32),
+			Tag::Short(value) => Some(value as i32),
+			Tag::Int(value) => Some(value),
+			_ => None,
+		}
+	}
+	
+	/// Writes the tag as the root of a file, which is a named tag.
+	pub fn write_root(&self, name: &str, output: &mut Vec<u8>) {
+		output.push(self.id());
+		write_string(name, output);
+		self.write_payload(output);
+	}
+	
+	fn write_payload(&self, output: &mut Vec<u8>) {
+		match self {
+			Tag::Byte(value) => output.push(*value as u8),
+			Tag::Short(value) => output.extend(value.to_be_bytes()),
+			Tag::Int(value) => output.extend(value.to_be_bytes()),
+			Tag::Long(value) => output.extend(value.to_be_bytes()),
+			Tag::Float(value) => output.extend(value.to_be_bytes()),
+			Tag::Double(value) => output.extend(value.to_be_bytes()),
+			Tag::ByteArray(bytes) => {
+				output.extend((bytes.len() as i32).to_be_bytes());
+				output.extend(bytes);
+			}
+			Tag::String(string) => write_string(string, output),
+			Tag::List(tags) => {
+				output.push(tags.first().map_or(0, Tag::id));
+				output.extend((tags.len() as i32).to_be_bytes());
+				
+				for tag in tags {
+					tag.write_payload(output);
+				}
+			}
+			Tag::Compound(entries) => {
+				for (name, tag) in entries {
+					tag.write_root(name, output);
+				}
+				
+				output.push(0);
+			}
+			Tag::IntArray(values) => {
+				output.extend((values.len() as i32).to_be_bytes());
+				
+				for value in values {
+					output.extend(value.to_be_bytes());
+				}
+			}
+			Tag::LongArray(values) => {
+				output.extend((values.len() as i32).to_be_bytes());
+				
+				for value in values {
+					output.extend(value.to_be_bytes());
+				}
+			}
+		}
+	}
+	
+	/// Reads the root tag of a file along with its name, the error is the offset of the invalid data.
+	pub fn read_root(input: &[u8]) -> Result<(String, Tag), usize> {
+		let mut reader = Reader { input, position: 0 };
+		
+		reader.named(0)
+		      .and_then(|(id, name)| Some((name, reader.payload(id, 0)?)))
+		      .ok_or(reader.position)
+	}
+}
+
+/// Strings are prefixed by their length, they are only valid for characters of the BMP without NUL, where
+/// the modified UTF-8 of Java is the same as UTF-8.
+fn write_string(string: &str, output: &mut Vec<u8>) {
+	output.extend((string.len() as u16).to_be_bytes());
+	output.extend(string.as_bytes());
+}
+
+struct Reader<'a> {
+	input: &'a [u8],
+	position: usize,
+}
+
+impl Reader<'_> {
+	fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+		let bytes = self.input.get(self.position..self.position + N)?.try_into().ok()?;
+		self.position += N;
+		Some(bytes)
+	}
+	
+	/// Length of an array or list, which must fit in the rest of the input given the size of its elements.
+	fn len(&mut self, size: usize) -> Option<usize> {
+		let len = usize::try_from(i32::from_be_bytes(self.take()?)).ok()?;
+		(len * size <= self.input.len() - self.position).then_some(len)
+	}
+	
+	/// Reads the id and the name of a tag, returns `None` for `End` tags.
+	fn named(&mut self, depth: usize) -> Option<(u8, String)> {
+		let [id] = self.take()?;
+		
+		if id == 0 || depth > MAX_DEPTH {
+			return None;
+		}
+		
+		Some((id, self.string()?))
+	}
+	
+	fn string(&mut self) -> Option<String> {
+		let len = u16::from_be_bytes(self.take()?) as usize;
+		let bytes = self.input.get(self.position..self.position + len)?;
+		self.position += len;
+		
+		// Modified UTF-8 only differs in NUL and characters outside of the BMP, which never appear in block states
+		Some(String::from_utf8_lossy(bytes).into_owned())
+	}
+	
+	fn payload(&mut self, id: u8, depth: usize) -> Option<Tag> {
+		Some(match id {
+			1 => Tag::Byte(i8::from_be_bytes(self.take()?)),
+			2 => Tag::Short(i16::from_be_bytes(self.take()?)),
+			3 => Tag::Int(i32::from_be_bytes(self.take()?)),
+			4 => Tag::Long(i64::from_be_bytes(self.take()?)),
+			5 => Tag::Float(f32::from_be_bytes(self.take()?)),
+			6 => Tag::Double(f64::from_be_bytes(self.take()?)),
+			7 => {
+				let len = self.len(1)?;
+				let bytes = self.input[self.position..self.position + len].to_vec();
+				self.position += len;
+				Tag::ByteArray(bytes)
+			}
+			8 => Tag::String(self.string()?),
+			9 if depth < MAX_DEPTH => {
+				let [element] = self.take()?;
+				let len = self.len(1)?;
+				
+				if element == 0 && len > 0 {
+					return None;
+				}
+				
+				Tag::List((0..len).map(|_| self.payload(element, depth + 1)).collect::<Option<_>>()?)
+			}
+			10 => {
+				let mut entries = Vec::new();
+				
+				while self.input.get(self.position) != Some(&0) {
+					let (id, name) = self.named(depth + 1)?;
+					entries.push((name, self.payload(id, depth + 1)?));
+				}
+				
+				self.position += 1;
+				Tag::Compound(entries)
+			}
+			11 => Tag::IntArray((0..self.len(4)?).map(|_| self.take().map(i32::from_be_bytes)).collect::<Option<_>>()?),
+			12 => Tag::LongArray((0..self.len(8)?).map(|_| self.take().map(i64::from_be_bytes)).collect::<Option<_>>()?),
+			_ => return None,
+		})
+	}
+}
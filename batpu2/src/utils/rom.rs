@@ -0,0 +1,727 @@
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{fs, io};
+use thiserror::Error;
+
+use crate::asm::{self, Dialect};
+use crate::isa::{Instruction, MAX_CODE_LEN};
+use crate::vm::Assertions;
+use super::{from_mc, into_mc, FromMcError};
+
+/// Byte order of words in a raw binary image
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub enum Endian {
+	#[default]
+	Big,
+	Little,
+}
+
+/// Loads a program from a raw binary image, two bytes per instruction, at most [`MAX_CODE_LEN`] of them
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+/// use batpu2::utils::Endian;
+///
+/// let program = [Instruction::JMP { addr: 1 }, Instruction::ADD { a: 1, b: 2, c: 3 }];
+///
+/// assert_eq!(batpu2::utils::from_bin(&[0xA0, 0x01, 0x21, 0x23], Endian::Big).unwrap(), program);
+/// assert_eq!(batpu2::utils::from_bin(&[0x01, 0xA0, 0x23, 0x21], Endian::Little).unwrap(), program);
+/// assert!(batpu2::utils::from_bin(&[0xA0], Endian::Big).is_err());
+/// assert!(batpu2::utils::from_bin(&[0; 2050], Endian::Big).is_err());
+/// ```
+pub fn from_bin(bytes: &[u8], endian: Endian) -> Result<Vec<Instruction>, RomError> {
+	if !bytes.len().is_multiple_of(2) {
+		return Err(RomError::OddLength { len: bytes.len() });
+	}
+	
+	if bytes.len() / 2 > MAX_CODE_LEN {
+		return Err(RomError::TooLong { len: bytes.len() / 2 });
+	}
+	
+	Ok(bytes.chunks_exact(2)
+	        .map(|word| match endian {
+		        Endian::Big => u16::from_be_bytes([word[0], word[1]]),
+		        Endian::Little => u16::from_le_bytes([word[0], word[1]]),
+	        })
+	        .map(Into::into)
+	        .collect())
+}
+
+/// Serializes a program into a raw binary image
+pub fn into_bin(instructions: &[Instruction], endian: Endian) -> Vec<u8> {
+	instructions.iter()
+	            .flat_map(|instruction| match endian {
+		            Endian::Big => instruction.as_word().to_be_bytes(),
+		            Endian::Little => instruction.as_word().to_le_bytes(),
+	            })
+	            .collect()
+}
+
+/// Loads a program from Intel HEX records
+///
+/// Addresses count bytes and every instruction is stored big endian, gaps between data records are filled
+/// with `NOP`s. Extended segment and linear address records are supported, start address records are ignored.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let code = "
+/// :04000000A001212317
+/// :00000001FF
+/// ";
+///
+/// assert_eq!(batpu2::utils::from_ihex(code).unwrap(), [
+///     Instruction::JMP { addr: 1 },
+///     Instruction::ADD { a: 1, b: 2, c: 3 },
+/// ]);
+/// ```
+pub fn from_ihex(code: &str) -> Result<Vec<Instruction>, RomError> {
+	let mut bytes = Vec::new();
+	let mut base = 0;
+	
+	for (index, line) in code.lines().enumerate() {
+		let line_number = index + 1;
+		let line = line.trim();
+		
+		if line.is_empty() {
+			continue;
+		}
+		
+		let invalid = || RomError::InvalidRecord { line_number, line: line.to_owned() };
+		let record = line.strip_prefix(':')
+		                 .filter(|record| record.is_ascii() && record.len() >= 10 && record.len().is_multiple_of(2))
+		                 .ok_or_else(invalid)?;
+		let data = (0..record.len()).step_by(2)
+		                            .map(|index| u8::from_str_radix(&record[index..index + 2], 16))
+		                            .collect::<Result<Vec<_>, _>>()
+		                            .map_err(|_| invalid())?;
+		
+		let [len, high, low, kind, ..] = data[..] else { unreachable!() };
+		let (&found, record) = data.split_last().unwrap();
+		let payload = &record[4..];
+		
+		if payload.len() != len as usize {
+			return Err(invalid());
+		}
+		
+		let expected = record.iter().fold(0_u8, |sum, &byte| sum.wrapping_add(byte)).wrapping_neg();
+		
+		if found != expected {
+			return Err(RomError::Checksum { line_number, expected, found });
+		}
+		
+		match (kind, payload) {
+			(0x00, _) => {
+				let address = base + (u16::from_be_bytes([high, low]) as usize);
+				let end = address + payload.len();
+				
+				if end > MAX_CODE_LEN * 2 {
+					return Err(RomError::OutOfRange { line_number, address });
+				}
+				
+				if bytes.len() < end {
+					bytes.resize(end, 0);
+				}
+				
+				bytes[address..end].copy_from_slice(payload);
+			}
+			(0x01, _) => break,
+			(0x02, &[high, low]) => base = (u16::from_be_bytes([high, low]) as usize) << 4,
+			(0x04, &[high, low]) => base = (u16::from_be_bytes([high, low]) as usize) << 16,
+			(0x03 | 0x05, _) => {},
+			(0x02 | 0x04, _) => return Err(invalid()),
+			(kind, _) => return Err(RomError::UnsupportedRecord { line_number, kind }),
+		}
+	}
+	
+	if !bytes.len().is_multiple_of(2) {
+		bytes.push(0);
+	}
+	
+	from_bin(&bytes, Endian::Big)
+}
+
+/// Serializes a program into Intel HEX data records of 16 bytes, followed by an end of file record
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let program = [
+///     Instruction::JMP { addr: 1 },
+///     Instruction::ADD { a: 1, b: 2, c: 3 },
+/// ];
+///
+/// assert_eq!(batpu2::utils::into_ihex(&program), ":04000000A001212317\n:00000001FF\n");
+/// ```
+pub fn into_ihex(instructions: &[Instruction]) -> String {
+	let bytes = into_bin(instructions, Endian::Big);
+	let mut output = String::with_capacity(bytes.len() * 2 + bytes.len() / 16 * 12 + 12);
+	
+	for (index, chunk) in bytes.chunks(16).enumerate() {
+		let address = index * 16;
+		
+		if address > 0 && address.is_multiple_of(0x10000) {
+			write_record(&mut output, 0x04, 0, &((address >> 16) as u16).to_be_bytes());
+		}
+		
+		write_record(&mut output, 0x00, address as u16, chunk);
+	}
+	
+	write_record(&mut output, 0x01, 0, &[]);
+	
+	output
+}
+
+fn write_record(output: &mut String, kind: u8, address: u16, data: &[u8]) {
+	let [high, low] = address.to_be_bytes();
+	let mut sum = (data.len() as u8).wrapping_add(high).wrapping_add(low).wrapping_add(kind);
+	
+	write!(output, ":{:02X}{address:04X}{kind:02X}", data.len()).unwrap();
+	
+	for &byte in data {
+		write!(output, "{byte:02X}").unwrap();
+		sum = sum.wrapping_add(byte);
+	}
+	
+	writeln!(output, "{:02X}", sum.wrapping_neg()).unwrap();
+}
+
+/// Loads a program from a memory file of Verilog's `$readmemh`
+///
+/// Words are separated by whitespace and may contain `_`, `@` followed by a hexadecimal address moves to
+/// another word. `//` and `/* */` comments are skipped, skipped addresses are filled with `NOP`s.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let code = "
+/// // entry
+/// a001
+/// @2 2123 /* add */
+/// ";
+///
+/// assert_eq!(batpu2::utils::from_readmemh(code).unwrap(), [
+///     Instruction::JMP { addr: 1 },
+///     Instruction::NOP,
+///     Instruction::ADD { a: 1, b: 2, c: 3 },
+/// ]);
+/// ```
+pub fn from_readmemh(code: &str) -> Result<Vec<Instruction>, RomError> {
+	from_readmem(code, 16)
+}
+
+/// Loads a program from a memory file of Verilog's `$readmemb`, see [`from_readmemh`] for the syntax
+pub fn from_readmemb(code: &str) -> Result<Vec<Instruction>, RomError> {
+	from_readmem(code, 2)
+}
+
+fn from_readmem(code: &str, radix: u32) -> Result<Vec<Instruction>, RomError> {
+	let mut words = Vec::new();
+	let mut address = 0;
+	
+	for (line_number, token) in readmem_tokens(code) {
+		if let Some(target) = token.strip_prefix('@') {
+			address = usize::from_str_radix(&target.replace('_', ""), 16)
+				.map_err(|_| RomError::InvalidWord { line_number, word: token.to_owned() })?;
+			
+			if address >= MAX_CODE_LEN {
+				return Err(RomError::OutOfRange { line_number, address });
+			}
+			
+			continue;
+		}
+		
+		let word = u16::from_str_radix(&token.replace('_', ""), radix)
+			.map_err(|_| RomError::InvalidWord { line_number, word: token.to_owned() })?;
+		
+		if address >= MAX_CODE_LEN {
+			return Err(RomError::OutOfRange { line_number, address });
+		}
+		
+		if words.len() <= address {
+			words.resize(address + 1, 0);
+		}
+		
+		words[address] = word;
+		address += 1;
+	}
+	
+	Ok(words.into_iter().map(Into::into).collect())
+}
+
+/// Words of a readmem file with their line numbers, comments are left out.
+fn readmem_tokens(code: &str) -> Vec<(usize, &str)> {
+	let mut tokens = Vec::new();
+	let mut in_comment = false;
+	
+	for (index, mut line) in code.lines().enumerate() {
+		loop {
+			if in_comment {
+				let Some(end) = line.find("*/") else { break };
+				line = &line[end + 2..];
+				in_comment = false;
+			}
+			
+			let end = line.find("//").unwrap_or(line.len());
+			let (code, rest) = match line[..end].find("/*") {
+				Some(start) => (&line[..start], Some(&line[start + 2..])),
+				None => (&line[..end], None),
+			};
+			
+			tokens.extend(code.split_whitespace().map(|token| (index + 1, token)));
+			
+			let Some(rest) = rest else { break };
+			line = rest;
+			in_comment = true;
+		}
+	}
+	
+	tokens
+}
+
+/// Serializes a program into a `$readmemh` file with a word per line
+pub fn into_readmemh(instructions: &[Instruction]) -> String {
+	let mut output = String::with_capacity(instructions.len() * 5);
+	
+	for instruction in instructions.iter() {
+		writeln!(output, "{:04x}", instruction.as_word()).unwrap();
+	}
+	
+	output
+}
+
+/// Serializes a program into a `$readmemb` file with a word per line, which is the same as .mc format
+pub fn into_readmemb(instructions: &[Instruction]) -> String {
+	into_mc(instructions)
+}
+
+/// Header of Logisim memory images
+const LOGISIM_HEADER: &str = "v2.0 raw";
+
+/// Loads a program from a Logisim v2.0 raw memory image
+///
+/// Words are hexadecimal and separated by whitespace, `N*word` repeats a word `N` times and `#` starts a comment.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let code = "v2.0 raw\na001 2*0 # padding\n2123\n";
+///
+/// assert_eq!(batpu2::utils::from_logisim(code).unwrap(), [
+///     Instruction::JMP { addr: 1 },
+///     Instruction::NOP,
+///     Instruction::NOP,
+///     Instruction::ADD { a: 1, b: 2, c: 3 },
+/// ]);
+/// ```
+pub fn from_logisim(code: &str) -> Result<Vec<Instruction>, RomError> {
+	let mut lines = code.lines()
+	                    .enumerate()
+	                    .map(|(index, line)| (index + 1, line.split('#').next().unwrap().trim()))
+	                    .filter(|(_, line)| !line.is_empty());
+	
+	if lines.next().is_none_or(|(_, header)| header != LOGISIM_HEADER) {
+		return Err(RomError::MissingHeader);
+	}
+	
+	let mut words = Vec::new();
+	
+	for (line_number, line) in lines {
+		for token in line.split_whitespace() {
+			let invalid = || RomError::InvalidWord { line_number, word: token.to_owned() };
+			let (count, word) = match token.split_once('*') {
+				Some((count, word)) => (count.parse::<usize>().map_err(|_| invalid())?, word),
+				None => (1, token),
+			};
+			let word = u16::from_str_radix(word, 16).map_err(|_| invalid())?;
+			
+			if count > MAX_CODE_LEN - words.len() {
+				return Err(RomError::OutOfRange { line_number, address: words.len().saturating_add(count - 1) });
+			}
+			
+			words.resize(words.len() + count, word);
+		}
+	}
+	
+	Ok(words.into_iter().map(Into::into).collect())
+}
+
+/// Serializes a program into a Logisim v2.0 raw memory image, repeated words are written as `N*word`
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let program = [Instruction::JMP { addr: 1 }, Instruction::NOP, Instruction::NOP, Instruction::NOP, Instruction::NOP];
+///
+/// assert_eq!(batpu2::utils::into_logisim(&program), "v2.0 raw\na001 4*0\n");
+/// ```
+pub fn into_logisim(instructions: &[Instruction]) -> String {
+	let mut output = String::from(LOGISIM_HEADER);
+	let mut items = 0;
+	let mut index = 0;
+	
+	while index < instructions.len() {
+		let word = instructions[index].as_word();
+		let count = instructions[index..].iter().take_while(|instruction| instruction.as_word() == word).count();
+		
+		output.push(if items % 8 == 0 { '\n' } else { ' ' });
+		
+		// Logisim itself starts writing runs from 4 repeated words
+		if count >= 4 {
+			write!(output, "{count}*{word:x}").unwrap();
+			index += count;
+		} else {
+			write!(output, "{word:x}").unwrap();
+			index += 1;
+		}
+		
+		items += 1;
+	}
+	
+	output.push('\n');
+	output
+}
+
+/// File formats of machine code
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum RomFormat {
+	/// Text with a 16 digit binary word per line, see [`from_mc`]
+	Mc,
+	/// Raw binary image, see [`from_bin`]
+	Bin(Endian),
+	/// Intel HEX records, see [`from_ihex`]
+	IntelHex,
+	/// Verilog `$readmemh` file, see [`from_readmemh`]
+	ReadMemH,
+	/// Verilog `$readmemb` file, see [`from_readmemb`]
+	ReadMemB,
+	/// Logisim v2.0 raw memory image, see [`from_logisim`]
+	Logisim,
+	/// Minecraft schematic of the BatPU-2 ROM, see [`into_schem`](super::into_schem). Litematica schematics can
+	/// also be read.
+	#[cfg(feature = "schematic")]
+	Schem,
+}
+
+impl RomFormat {
+	/// Every format, in the order of their names in [`Display`].
+	pub const ALL: &'static [RomFormat] = &[
+		RomFormat::Mc,
+		RomFormat::Bin(Endian::Big),
+		RomFormat::Bin(Endian::Little),
+		RomFormat::IntelHex,
+		RomFormat::ReadMemH,
+		RomFormat::ReadMemB,
+		RomFormat::Logisim,
+		#[cfg(feature = "schematic")]
+		RomFormat::Schem,
+	];
+	
+	/// Returns the format usually stored in files with the given extension. `.hex` files are assumed to be
+	/// `$readmemh` files, since Intel HEX files are recognized by their content.
+	pub fn from_extension(extension: &str) -> Option<Self> {
+		match extension.to_ascii_lowercase().as_str() {
+			"mc" => Some(RomFormat::Mc),
+			"bin" | "rom" => Some(RomFormat::Bin(Endian::Big)),
+			"ihex" | "ihx" => Some(RomFormat::IntelHex),
+			"hex" | "mem" | "memh" => Some(RomFormat::ReadMemH),
+			"memb" => Some(RomFormat::ReadMemB),
+			#[cfg(feature = "schematic")]
+			"schem" => Some(RomFormat::Schem),
+			_ => None,
+		}
+	}
+	
+	/// Detects the format from the content of a file, returns `None` if it looks like anything else, such as
+	/// assembly.
+	///
+	/// Gzipped files are schematics and other files which are not valid UTF-8 are raw big endian images. Text made of binary words is .mc if every
+	/// line contains a single 16 digit word, otherwise words longer than 4 digits make it a `$readmemb` file.
+	pub fn detect(bytes: &[u8]) -> Option<Self> {
+		#[cfg(feature = "schematic")]
+		if bytes.starts_with(&[0x1F, 0x8B]) {
+			return Some(RomFormat::Schem);
+		}
+		
+		let Ok(text) = std::str::from_utf8(bytes) else { return Some(RomFormat::Bin(Endian::Big)) };
+		let trimmed = text.trim_start();
+		
+		if trimmed.starts_with(LOGISIM_HEADER) {
+			return Some(RomFormat::Logisim);
+		}
+		
+		if trimmed.starts_with(':') {
+			return Some(RomFormat::IntelHex);
+		}
+		
+		if text.lines().all(|line| line.is_empty() || line.len() == 16 && line.bytes().all(|byte| byte == b'0' || byte == b'1')) {
+			return Some(RomFormat::Mc);
+		}
+		
+		let tokens = readmem_tokens(text);
+		let is_word = |radix: u32| tokens.iter().all(|(_, token)| {
+			let token = token.strip_prefix('@').unwrap_or(token);
+			!token.is_empty() && token.chars().all(|char| char == '_' || char.is_digit(radix))
+		});
+		
+		if tokens.is_empty() {
+			None
+		} else if is_word(2) && tokens.iter().any(|(_, token)| token.len() > 4) {
+			Some(RomFormat::ReadMemB)
+		} else if is_word(16) {
+			Some(RomFormat::ReadMemH)
+		} else {
+			None
+		}
+	}
+	
+	/// Guesses the format of a file from its extension, falling back to [`RomFormat::detect`] if the
+	/// extension names no format. `.hex` is also used by Intel HEX and Logisim images, which are told apart
+	/// by their content. Assembly files (`.asm` or `.s`) always return `None`.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use std::path::Path;
+	/// use batpu2::utils::{Endian, RomFormat};
+	///
+	/// assert_eq!(RomFormat::guess(Path::new("prog.hex"), b":00000001FF"), Some(RomFormat::IntelHex));
+	/// assert_eq!(RomFormat::guess(Path::new("prog.hex"), b"0001"), Some(RomFormat::ReadMemH));
+	/// assert_eq!(RomFormat::guess(Path::new("prog.bin"), b"0001"), Some(RomFormat::Bin(Endian::Big)));
+	/// assert_eq!(RomFormat::guess(Path::new("prog"), b"1010000000000001\n"), Some(RomFormat::Mc));
+	/// assert_eq!(RomFormat::guess(Path::new("prog.asm"), b"ADD r1 r2 r3"), None);
+	/// ```
+	pub fn guess(path: &Path, bytes: &[u8]) -> Option<Self> {
+		let extension = path.extension().and_then(OsStr::to_str).map(str::to_ascii_lowercase);
+		
+		match extension.as_deref() {
+			Some("asm" | "s") => None,
+			Some("hex") => match Self::detect(bytes) {
+				Some(format @ (RomFormat::Logisim | RomFormat::IntelHex)) => Some(format),
+				_ => Some(RomFormat::ReadMemH),
+			},
+			extension => extension.and_then(Self::from_extension).or_else(|| Self::detect(bytes)),
+		}
+	}
+	
+	/// Loads a program stored in this format, at most [`MAX_CODE_LEN`] instructions long.
+	pub fn read(self, bytes: &[u8]) -> Result<Vec<Instruction>, RomError> {
+		match self {
+			RomFormat::Bin(endian) => return from_bin(bytes, endian),
+			#[cfg(feature = "schematic")]
+			RomFormat::Schem => return Ok(super::from_schem(bytes)?),
+			_ => {},
+		}
+		
+		let code = std::str::from_utf8(bytes).map_err(|_| RomError::NotText)?;
+		
+		match self {
+			RomFormat::Mc => {
+				let instructions = from_mc(code)?;
+				
+				if instructions.len() > MAX_CODE_LEN {
+					return Err(RomError::TooLong { len: instructions.len() });
+				}
+				
+				Ok(instructions)
+			}
+			RomFormat::IntelHex => from_ihex(code),
+			RomFormat::ReadMemH => from_readmemh(code),
+			RomFormat::ReadMemB => from_readmemb(code),
+			RomFormat::Logisim => from_logisim(code),
+			#[cfg(feature = "schematic")]
+			RomFormat::Schem => unreachable!(),
+			RomFormat::Bin(_) => unreachable!(),
+		}
+	}
+	
+	/// Serializes a program into this format.
+	///
+	/// # Panics
+	///
+	/// Panics if a program longer than [`MAX_CODE_LEN`] is written as [`RomFormat::Schem`].
+	pub fn write(self, instructions: &[Instruction]) -> Vec<u8> {
+		match self {
+			RomFormat::Mc => into_mc(instructions).into_bytes(),
+			RomFormat::Bin(endian) => into_bin(instructions, endian),
+			RomFormat::IntelHex => into_ihex(instructions).into_bytes(),
+			RomFormat::ReadMemH => into_readmemh(instructions).into_bytes(),
+			RomFormat::ReadMemB => into_readmemb(instructions).into_bytes(),
+			RomFormat::Logisim => into_logisim(instructions).into_bytes(),
+			#[cfg(feature = "schematic")]
+			RomFormat::Schem => super::into_schem(instructions),
+		}
+	}
+}
+
+impl Display for RomFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			RomFormat::Mc => "mc",
+			RomFormat::Bin(Endian::Big) => "bin",
+			RomFormat::Bin(Endian::Little) => "bin-le",
+			RomFormat::IntelHex => "ihex",
+			RomFormat::ReadMemH => "memh",
+			RomFormat::ReadMemB => "memb",
+			RomFormat::Logisim => "logisim",
+			#[cfg(feature = "schematic")]
+			RomFormat::Schem => "schem",
+		})
+	}
+}
+
+impl FromStr for RomFormat {
+	type Err = UnknownFormat;
+	
+	/// Parses a format from its name, as written by [`Display`].
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL.iter()
+		         .copied()
+		         .find(|format| format.to_string() == s)
+		         .ok_or_else(|| UnknownFormat { name: s.to_owned() })
+	}
+}
+
+/// Loads a program from a file in any [`RomFormat`], files which are not recognized by [`RomFormat::guess`]
+/// are assembled.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Instruction>, LoadError> {
+	let program = load_with(path, None, Dialect::default())?;
+	
+	#[cfg(feature = "schematic")]
+	if !program.corrupted.is_empty() {
+		return Err(RomError::Schem(super::SchemError::Corrupted { cells: program.corrupted }).into());
+	}
+	
+	Ok(program.code)
+}
+
+/// A program loaded by [`load_with`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LoadedProgram {
+	pub code: Vec<Instruction>,
+	/// Format the file was read in, `None` if it was assembled
+	pub format: Option<RomFormat>,
+	/// `ASSERT`s of an assembled program
+	pub assertions: Assertions,
+	/// Corrupted cells of a schematic, which are read as 0
+	#[cfg(feature = "schematic")]
+	pub corrupted: Vec<super::CorruptedCell>,
+}
+
+/// Loads a program like [`load`], in the given format instead of the guessed one and assembling with the
+/// given [`Dialect`].
+///
+/// Corrupted cells of schematics are read as 0 instead of failing, so they can be looked for in the world.
+pub fn load_with(path: impl AsRef<Path>, format: Option<RomFormat>, dialect: Dialect) -> Result<LoadedProgram, LoadError> {
+	let path = path.as_ref();
+	let bytes = fs::read(path).map_err(|source| LoadError::Io { path: path.to_owned(), source })?;
+	let mut program = LoadedProgram {
+		code: Vec::new(),
+		format: format.or_else(|| RomFormat::guess(path, &bytes)),
+		assertions: Assertions::new(),
+		#[cfg(feature = "schematic")]
+		corrupted: Vec::new(),
+	};
+	
+	match program.format {
+		#[cfg(feature = "schematic")]
+		Some(RomFormat::Schem) => {
+			let rom = super::read_schem(&bytes).map_err(RomError::from)?;
+			program.code = rom.instructions();
+			program.corrupted = rom.corrupted;
+		}
+		Some(format) => program.code = format.read(&bytes)?,
+		None => {
+			let code = std::str::from_utf8(&bytes).map_err(|_| RomError::NotText)?;
+			let error = |err: asm::AsmError| LoadError::Asm { line_number: err.line_num(), column: err.col_num(), message: err.to_string() };
+			let lines = asm::parse_lines_with(code, dialect).collect::<Result<Vec<_>, _>>().map_err(error)?;
+			let mut assembler = asm::Assembler::new(&lines).dialect(dialect);
+			
+			program.code = assembler.by_ref().collect::<Result<Vec<_>, _>>().map_err(error)?;
+			program.assertions = assembler.assertions().clone();
+		}
+	}
+	
+	Ok(program)
+}
+
+/// An error which can be returned when loading machine code in one of the [`RomFormat`]s
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum RomError {
+	#[error(transparent)]
+	Mc(#[from] FromMcError),
+	#[error("Binary image has an odd length of {len} bytes")]
+	OddLength { len: usize },
+	#[error("File is not valid UTF-8 text")]
+	NotText,
+	#[error("{line_number}: Invalid record \"{line}\"")]
+	InvalidRecord { line_number: usize, line: String },
+	#[error("{line_number}: Checksum is 0x{found:02X}, expected 0x{expected:02X}")]
+	Checksum { line_number: usize, expected: u8, found: u8 },
+	#[error("{line_number}: Unsupported record type 0x{kind:02X}")]
+	UnsupportedRecord { line_number: usize, kind: u8 },
+	#[error("{line_number}: Cannot parse \"{word}\"")]
+	InvalidWord { line_number: usize, word: String },
+	#[error("{line_number}: Address 0x{address:X} is out of range")]
+	OutOfRange { line_number: usize, address: usize },
+	#[error("Missing \"{LOGISIM_HEADER}\" header")]
+	MissingHeader,
+	#[error("Program has {len} instructions (max {MAX_CODE_LEN})")]
+	TooLong { len: usize },
+	#[cfg(feature = "schematic")]
+	#[error(transparent)]
+	Schem(#[from] super::SchemError),
+}
+
+impl RomError {
+	/// Returns the line the error was found at, if the format is a text.
+	pub fn line_number(&self) -> Option<usize> {
+		match self {
+			RomError::Mc(err) => Some(err.line_number()),
+			RomError::InvalidRecord { line_number, .. } |
+			RomError::Checksum { line_number, .. } |
+			RomError::UnsupportedRecord { line_number, .. } |
+			RomError::InvalidWord { line_number, .. } |
+			RomError::OutOfRange { line_number, .. } => Some(*line_number),
+			RomError::OddLength { .. } | RomError::NotText | RomError::MissingHeader | RomError::TooLong { .. } => None,
+			#[cfg(feature = "schematic")]
+			RomError::Schem(_) => None,
+		}
+	}
+}
+
+/// An error which can be returned by [`load`]
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+	#[error("Cannot read \"{}\"", .path.display())]
+	Io { path: PathBuf, #[source] source: io::Error },
+	#[error(transparent)]
+	Rom(#[from] RomError),
+	#[error("{line_number}:{column}: {message}")]
+	Asm { line_number: usize, column: usize, message: String },
+}
+
+/// An error which can be returned when parsing a [`RomFormat`] from its name
+#[derive(Error, Debug)]
+#[error("Unknown format \"{name}\"")]
+pub struct UnknownFormat { name: String }
+
+impl UnknownFormat {
+	pub fn name(&self) -> &str {
+		self.name.as_str()
+	}
+}
@@ -0,0 +1,422 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use thiserror::Error;
+
+use crate::isa::{Instruction, MAX_CODE_LEN};
+use super::nbt::Tag;
+
+/// Data version of Minecraft 1.18.2, which the BatPU-2 world is saved in.
+const DATA_VERSION: i32 = 2975;
+
+/// Block of a bit set to 0.
+pub const ZERO_BLOCK: &str = "minecraft:purple_wool";
+/// Block of a bit set to 1 in the first half of the ROM, the second half uses repeaters facing west.
+pub const ONE_BLOCK: &str = "minecraft:repeater[facing=east]";
+
+const REPEATER: &str = "minecraft:repeater";
+
+/// Position of a bit of an instruction in the ROM of the BatPU-2 world, relative to the point the schematic
+/// is pasted at. Bits are counted from the most significant one, like in .mc files.
+///
+/// The two halves of 512 instructions are 2 blocks apart on the x axis and hold 32 rows of 16 instructions
+/// along the z axis, with a gap of 4 blocks after the 16th row. Instructions of a row are 7 blocks apart and
+/// alternate between two lines, bits go down every 2 blocks with a gap between both bytes.
+///
+/// This layout has not been verified against the BatPU-2 world or the upstream `schematic.py`, no capture of
+/// the world has been added to `tests/schematic` yet. Try schematics on a copy of the world first.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::utils::rom_position;
+///
+/// assert_eq!(rom_position(0, 0), [-4, -1, 2]);
+/// assert_eq!(rom_position(1, 15), [-11, -33, 3]);
+/// assert_eq!(rom_position(512 + 16, 8), [-6, -19, 4]);
+/// ```
+pub fn rom_position(address: usize, bit: usize) -> [i32; 3] {
+	let half = (address / 512) as i32;
+	let row = (address % 512 / 16) as i32;
+	let column = (address % 16) as i32;
+	let bit = bit as i32;
+	
+	let x = -4 - 2 * half - 7 * column;
+	let y = -1 - 2 * bit - if bit >= 8 { 2 } else { 0 };
+	let z = 2 + 2 * row + if row >= 16 { 4 } else { 0 } + (column % 2) * (1 - 2 * half);
+	
+	[x, y, z]
+}
+
+/// Block of a cell of the ROM holding a bit.
+pub(super) fn cell_block(address: usize, one: bool) -> &'static str {
+	match (one, address < 512) {
+		(false, _) => ZERO_BLOCK,
+		(true, true) => ONE_BLOCK,
+		(true, false) => "minecraft:repeater[facing=west]",
+	}
+}
+
+/// Serializes a program into a gzipped Sponge schematic (version 2) of the ROM of the BatPU-2 world.
+///
+/// Every one of the 1024 addresses is written, so pasting the schematic also clears the rest of the ROM. The
+/// schematic's origin is the point positions of [`rom_position`] are relative to, whose layout has not been
+/// verified against the world.
+///
+/// # Panics
+///
+/// Panics if the program is longer than [`MAX_CODE_LEN`].
+pub fn into_schem(instructions: &[Instruction]) -> Vec<u8> {
+	assert!(instructions.len() <= MAX_CODE_LEN, "program has {} instructions (max {MAX_CODE_LEN})", instructions.len());
+	
+	let positions = (0..MAX_CODE_LEN).flat_map(|address| (0..16).map(move |bit| rom_position(address, bit)));
+	let (min, max) = positions.fold(([i32::MAX; 3], [i32::MIN; 3]), |(min, max), position| {
+		(std::array::from_fn(|axis| min[axis].min(position[axis])), std::array::from_fn(|axis| max[axis].max(position[axis])))
+	});
+	let [width, height, length] = std::array::from_fn(|axis| (max[axis] - min[axis] + 1) as usize);
+	
+	let mut palette = vec!["minecraft:air"];
+	let mut blocks = vec![0; width * height * length];
+	
+	for address in 0..MAX_CODE_LEN {
+		let word = instructions.get(address).map_or(0, |instruction| instruction.as_word());
+		
+		for bit in 0..16 {
+			let block = cell_block(address, word << bit & 0x8000 != 0);
+			let id = palette.iter().position(|&name| name == block).unwrap_or_else(|| {
+				palette.push(block);
+				palette.len() - 1
+			});
+			
+			let [x, y, z] = std::array::from_fn(|axis| (rom_position(address, bit)[axis] - min[axis]) as usize);
+			blocks[x + z * width + y * width * length] = id;
+		}
+	}
+	
+	let mut block_data = Vec::with_capacity(blocks.len());
+	
+	for id in blocks {
+		write_varint(id, &mut block_data);
+	}
+	
+	let offset = |axis: usize, name: &str| (name.to_owned(), Tag::Int(min[axis]));
+	let schematic = Tag::compound([
+		("Version", Tag::Int(2)),
+		("DataVersion", Tag::Int(DATA_VERSION)),
+		("Metadata", Tag::Compound(vec![offset(0, "WEOffsetX"), offset(1, "WEOffsetY"), offset(2, "WEOffsetZ")])),
+		("Width", Tag::Short(width as i16)),
+		("Height", Tag::Short(height as i16)),
+		("Length", Tag::Short(length as i16)),
+		("Offset", Tag::IntArray(min.to_vec())),
+		("PaletteMax", Tag::Int(palette.len() as i32)),
+		("Palette", Tag::Compound(palette.iter().enumerate().map(|(id, &name)| (name.to_owned(), Tag::Int(id as i32))).collect())),
+		("BlockData", Tag::ByteArray(block_data)),
+		("BlockEntities", Tag::List(Vec::new())),
+	]);
+	
+	let mut nbt = Vec::new();
+	schematic.write_root("Schematic", &mut nbt);
+	
+	let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+	encoder.write_all(&nbt).unwrap();
+	encoder.finish().unwrap()
+}
+
+/// Bits of the ROM read from a schematic by [`read_schem`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RomImage {
+	/// Every one of the [`MAX_CODE_LEN`] words, corrupted bits are cleared.
+	pub words: Vec<u16>,
+	pub corrupted: Vec<CorruptedCell>,
+}
+
+impl RomImage {
+	/// Returns the program without the trailing `NOP`s filling the rest of the ROM.
+	pub fn instructions(&self) -> Vec<Instruction> {
+		let len = self.words.iter().rposition(|&word| word != 0).map_or(0, |last| last + 1);
+		self.words[..len].iter().map(|&word| word.into()).collect()
+	}
+}
+
+/// Cell of the ROM holding neither a repeater facing the right way nor purple wool.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CorruptedCell {
+	pub address: usize,
+	/// Bit counted from the most significant one, like in [`rom_position`].
+	pub bit: usize,
+	/// Position relative to the schematic's origin.
+	pub position: [i32; 3],
+	/// Block state found in the cell, `None` if the schematic does not contain it.
+	pub block: Option<String>,
+}
+
+impl Display for CorruptedCell {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let [x, y, z] = self.position;
+		write!(f, "address 0x{:03X} bit {} at {x} {y} {z}: ", self.address, self.bit)?;
+		
+		match &self.block {
+			Some(block) => write!(f, "{block}"),
+			None => write!(f, "outside of the schematic"),
+		}
+	}
+}
+
+/// Reads the ROM from a Sponge schematic (versions 1 to 3) or a Litematica schematic captured from the
+/// BatPU-2 world, gzipped or not.
+///
+/// Cells are looked up at [`rom_position`], so the schematic's origin must be the point [`into_schem`]
+/// uses. Cells which do not hold a bit are reported instead of failing, see [`from_schem`] for a strict
+/// version.
+pub fn read_schem(bytes: &[u8]) -> Result<RomImage, SchemError> {
+	let mut nbt = Vec::new();
+	
+	let nbt = if bytes.starts_with(&[0x1F, 0x8B]) {
+		GzDecoder::new(bytes).read_to_end(&mut nbt).map_err(SchemError::Decompress)?;
+		&nbt[..]
+	} else {
+		bytes
+	};
+	
+	let (_, root) = Tag::read_root(nbt).map_err(|offset| SchemError::Nbt { offset })?;
+	let regions = match root.get("Regions") {
+		Some(Tag::Compound(regions)) => regions.iter().map(|(_, region)| Region::litematic(region)).collect::<Result<_, _>>()?,
+		Some(_) => return Err(SchemError::InvalidTag { name: "Regions" }),
+		None => vec![Region::sponge(&root)?],
+	};
+	
+	let mut words = vec![0; MAX_CODE_LEN];
+	let mut corrupted = Vec::new();
+	
+	for (address, word) in words.iter_mut().enumerate() {
+		let facing = if address < 512 { "facing=east" } else { "facing=west" };
+		
+		for bit in 0..16 {
+			let position = rom_position(address, bit);
+			let block = regions.iter().find_map(|region: &Region| region.get(position));
+			let (name, state) = block.map_or(("", ""), |block| block.split_once('[').unwrap_or((block, "")));
+			
+			if name == REPEATER && (state.is_empty() || state.trim_end_matches(']').split(',').any(|property| property == facing)) {
+				*word |= 0x8000 >> bit;
+			} else if name != ZERO_BLOCK {
+				corrupted.push(CorruptedCell { address, bit, position, block: block.map(str::to_owned) });
+			}
+		}
+	}
+	
+	Ok(RomImage { words, corrupted })
+}
+
+/// Loads a program from a schematic like [`read_schem`], failing if any cell is corrupted.
+///
+/// # Example
+///
+/// ```
+/// use batpu2::isa::Instruction;
+///
+/// let program = [Instruction::JMP { addr: 1 }, Instruction::ADD { a: 1, b: 2, c: 3 }];
+/// let schem = batpu2::utils::into_schem(&program);
+///
+/// assert_eq!(batpu2::utils::from_schem(&schem).unwrap(), program);
+/// ```
+pub fn from_schem(bytes: &[u8]) -> Result<Vec<Instruction>, SchemError> {
+	let rom = read_schem(bytes)?;
+	
+	if !rom.corrupted.is_empty() {
+		return Err(SchemError::Corrupted { cells: rom.corrupted });
+	}
+	
+	Ok(rom.instructions())
+}
+
+/// Box of blocks in a schematic, indexed by `x + z * width + y * width * length`.
+struct Region {
+	min: [i32; 3],
+	size: [usize; 3],
+	palette: Vec<String>,
+	blocks: Vec<usize>,
+}
+
+impl Region {
+	fn get(&self, position: [i32; 3]) -> Option<&str> {
+		let [x, y, z] = std::array::from_fn(|axis| {
+			let offset = position[axis].checked_sub(self.min[axis])?;
+			usize::try_from(offset).ok().filter(|&offset| offset < self.size[axis])
+		});
+		let [width, _, length] = self.size;
+		
+		self.palette.get(self.blocks[x? + z? * width + y? * width * length]).map(String::as_str)
+	}
+	
+	/// Version 3 nests the schematic in a `Schematic` compound and its blocks in `Blocks`. Offsets of older
+	/// versions are relative to the world, the offset of WorldEdit's metadata is used instead if present.
+	fn sponge(root: &Tag) -> Result<Self, SchemError> {
+		let schematic = root.get("Schematic").unwrap_or(root);
+		let blocks = schematic.get("Blocks");
+		let (palette, data) = match blocks {
+			Some(blocks) => (field(blocks, "Palette")?, field(blocks, "Data")?),
+			None => (field(schematic, "Palette")?, field(schematic, "BlockData")?),
+		};
+		
+		// Sizes are unsigned shorts
+		let size = ["Width", "Height", "Length"].map(|name| schematic.get(name).and_then(Tag::int).map(|size| size as u16 as usize));
+		let [Some(width), Some(height), Some(length)] = size else { return Err(SchemError::MissingTag { name: "Width" }) };
+		
+		let min = match (schematic.get("Metadata"), schematic.get("Offset")) {
+			(Some(metadata), _) if blocks.is_none() && metadata.get("WEOffsetX").is_some() => {
+				[int_field(metadata, "WEOffsetX")?, int_field(metadata, "WEOffsetY")?, int_field(metadata, "WEOffsetZ")?]
+			}
+			(_, Some(Tag::IntArray(offset))) if offset.len() == 3 => [offset[0], offset[1], offset[2]],
+			(_, Some(_)) => return Err(SchemError::InvalidTag { name: "Offset" }),
+			(_, None) => [0; 3],
+		};
+		let size = [width, height, length];
+		
+		if !fits(min, size) {
+			return Err(SchemError::InvalidTag { name: "Offset" });
+		}
+		
+		let Tag::Compound(entries) = palette else { return Err(SchemError::InvalidTag { name: "Palette" }) };
+		let mut names = vec![String::from("minecraft:air"); entries.len()];
+		
+		for (name, id) in entries {
+			let id = id.int().and_then(|id| usize::try_from(id).ok()).filter(|&id| id < names.len()).ok_or(SchemError::InvalidTag { name: "Palette" })?;
+			names[id] = name.clone();
+		}
+		
+		let Tag::ByteArray(data) = data else { return Err(SchemError::InvalidTag { name: "BlockData" }) };
+		let mut blocks = Vec::with_capacity(data.len());
+		let mut bytes = data.iter();
+		
+		while bytes.len() > 0 {
+			blocks.push(read_varint(&mut bytes).ok_or(SchemError::InvalidTag { name: "BlockData" })?);
+		}
+		
+		if Some(blocks.len()) != volume(size) {
+			return Err(SchemError::InvalidTag { name: "BlockData" });
+		}
+		
+		Ok(Region { min, size, palette: names, blocks })
+	}
+	
+	/// Sizes of Litematica regions are negative when they extend towards negative coordinates from their
+	/// position. Block states are packed in longs with as few bits as the palette needs, at least 2, and
+	/// may span two longs.
+	fn litematic(region: &Tag) -> Result<Self, SchemError> {
+		let vector = |name| -> Result<[i32; 3], SchemError> {
+			let tag = field(region, name)?;
+			Ok([int_field(tag, "x")?, int_field(tag, "y")?, int_field(tag, "z")?])
+		};
+		
+		let position = vector("Position")?;
+		let signed_size = vector("Size")?;
+		let mut min = position;
+		
+		for axis in (0..3).filter(|&axis| signed_size[axis] < 0) {
+			min[axis] = position[axis].checked_add(signed_size[axis] + 1).ok_or(SchemError::InvalidTag { name: "Position" })?;
+		}
+		
+		let size = signed_size.map(|size| size.unsigned_abs() as usize);
+		let len = volume(size).filter(|_| fits(min, size)).ok_or(SchemError::InvalidTag { name: "Size" })?;
+		
+		let Tag::List(entries) = field(region, "BlockStatePalette")? else { return Err(SchemError::InvalidTag { name: "BlockStatePalette" }) };
+		let palette = entries.iter().map(litematic_state).collect::<Result<Vec<_>, _>>()?;
+		
+		let Tag::LongArray(states) = field(region, "BlockStates")? else { return Err(SchemError::InvalidTag { name: "BlockStates" }) };
+		let bits = (usize::BITS - palette.len().saturating_sub(1).leading_zeros()).max(2) as usize;
+		
+		if len.checked_mul(bits).is_none_or(|needed| states.len().saturating_mul(64) < needed) {
+			return Err(SchemError::InvalidTag { name: "BlockStates" });
+		}
+		
+		let blocks = (0..len).map(|index| {
+			let start = index * bits;
+			let (long, offset) = (start / 64, start % 64);
+			let mut value = states[long] as u64 >> offset;
+			
+			if offset + bits > 64 {
+				value |= (states[long + 1] as u64) << (64 - offset);
+			}
+			
+			(value & ((1 << bits) - 1)) as usize
+		}).collect();
+		
+		Ok(Region { min, size, palette, blocks })
+	}
+}
+
+/// Number of blocks in a box of given size, `None` if it does not fit in `usize`.
+fn volume(size: [usize; 3]) -> Option<usize> {
+	size.iter().try_fold(1_usize, |volume, &size| volume.checked_mul(size))
+}
+
+/// Returns `true` if every coordinate of a box starting at `min` fits in `i32`.
+fn fits(min: [i32; 3], size: [usize; 3]) -> bool {
+	(0..3).all(|axis| i32::try_from(size[axis]).is_ok_and(|size| size == 0 || min[axis].checked_add(size - 1).is_some()))
+}
+
+/// Converts an entry of a Litematica palette to a block state like the ones of Sponge palettes.
+fn litematic_state(entry: &Tag) -> Result<String, SchemError> {
+	let Some(Tag::String(name)) = entry.get("Name") else { return Err(SchemError::InvalidTag { name: "Name" }) };
+	let Some(Tag::Compound(properties)) = entry.get("Properties") else { return Ok(name.clone()) };
+	
+	let properties: Vec<String> = properties.iter()
+	                                        .filter_map(|(key, value)| match value {
+		                                        Tag::String(value) => Some(format!("{key}={value}")),
+		                                        _ => None,
+	                                        })
+	                                        .collect();
+	
+	Ok(format!("{name}[{}]", properties.join(",")))
+}
+
+fn field<'a>(tag: &'a Tag, name: &'static str) -> Result<&'a Tag, SchemError> {
+	tag.get(name).ok_or(SchemError::MissingTag { name })
+}
+
+fn int_field(tag: &Tag, name: &'static str) -> Result<i32, SchemError> {
+	field(tag, name)?.int().ok_or(SchemError::InvalidTag { name })
+}
+
+/// Writes a LEB128 varint, used by the block data of schematics.
+fn write_varint(mut value: usize, output: &mut Vec<u8>) {
+	while value >= 0x80 {
+		output.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	
+	output.push(value as u8);
+}
+
+fn read_varint<'a>(bytes: &mut impl Iterator<Item=&'a u8>) -> Option<usize> {
+	let mut value = 0;
+	
+	for shift in (0..usize::BITS).step_by(7) {
+		let byte = *bytes.next()?;
+		value |= ((byte & 0x7F) as usize) << shift;
+		
+		if byte & 0x80 == 0 {
+			return Some(value);
+		}
+	}
+	
+	None
+}
+
+/// An error which can be returned when reading a schematic
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum SchemError {
+	#[error("Cannot decompress the schematic")]
+	Decompress(#[source] io::Error),
+	#[error("Invalid NBT data at byte {offset}")]
+	Nbt { offset: usize },
+	#[error("Missing \"{name}\" tag")]
+	MissingTag { name: &'static str },
+	#[error("Invalid \"{name}\" tag")]
+	InvalidTag { name: &'static str },
+	#[error("{} corrupted ROM cells, the first is {}", .cells.len(), .cells[0])]
+	Corrupted { cells: Vec<CorruptedCell> },
+}
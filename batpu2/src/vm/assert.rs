@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+/// Condition written as `ASSERT` in assembly, checked in [checked mode](crate::BatPU2::try_step_checked)
+/// before the instruction which follows it is executed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Assertion {
+	pub line_number: usize,
+	pub subject: Subject,
+	/// Comparison with a value, without it the subject has to be non-zero
+	pub check: Option<(Comparison, u8)>,
+}
+
+/// Value inspected by an [`Assertion`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Subject {
+	Reg(u8),
+	Mem(u8),
+	Zero,
+	Carry,
+}
+
+/// Unsigned comparison of an [`Assertion`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+/// Assertions of a program keyed by the address of the instruction they precede.
+///
+/// Assertions take no space in the program itself, they only exist in this side table.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Assertions {
+	map: BTreeMap<u16, Vec<Assertion>>,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("Assertion `{assertion}` on line {} failed at 0x{address:03X}: {} is {actual}", assertion.line_number, assertion.subject)]
+pub struct AssertionFailure {
+	pub address: u16,
+	pub assertion: Assertion,
+	pub actual: u8,
+}
+
+impl Assertion {
+	/// Returns `true` if the assertion holds for given value of its subject.
+	pub fn holds(&self, actual: u8) -> bool {
+		match self.check {
+			None => actual != 0,
+			Some((Comparison::Eq, value)) => actual == value,
+			Some((Comparison::Ne, value)) => actual != value,
+			Some((Comparison::Lt, value)) => actual < value,
+			Some((Comparison::Le, value)) => actual <= value,
+			Some((Comparison::Gt, value)) => actual > value,
+			Some((Comparison::Ge, value)) => actual >= value,
+		}
+	}
+}
+
+impl Assertions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	
+	pub fn insert(&mut self, address: u16, assertion: Assertion) {
+		self.map.entry(address).or_default().push(assertion);
+	}
+	
+	/// Assertions checked before executing the instruction at given address.
+	pub fn get(&self, address: u16) -> &[Assertion] {
+		self.map.get(&address).map_or(&[], Vec::as_slice)
+	}
+	
+	pub fn iter(&self) -> impl Iterator<Item=(u16, &Assertion)> {
+		self.map.iter().flat_map(|(&address, assertions)| assertions.iter().map(move |assertion| (address, assertion)))
+	}
+	
+	pub fn len(&self) -> usize {
+		self.map.values().map(Vec::len).sum()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.map.is_empty()
+	}
+}
+
+impl FromIterator<(u16, Assertion)> for Assertions {
+	fn from_iter<T: IntoIterator<Item=(u16, Assertion)>>(iter: T) -> Self {
+		let mut assertions = Self::new();
+		
+		for (address, assertion) in iter {
+			assertions.insert(address, assertion);
+		}
+		
+		assertions
+	}
+}
+
+impl Display for Assertion {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self.check {
+			None => write!(f, "{}", self.subject),
+			Some((comparison, value)) => write!(f, "{} {comparison} {value}", self.subject),
+		}
+	}
+}
+
+impl Display for Subject {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Subject::Reg(reg) => write!(f, "r{reg}"),
+			Subject::Mem(address) => write!(f, "mem[0x{address:02X}]"),
+			Subject::Zero => write!(f, "flags.zero"),
+			Subject::Carry => write!(f, "flags.carry"),
+		}
+	}
+}
+
+impl Display for Comparison {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Comparison::Eq => "==",
+			Comparison::Ne => "!=",
+			Comparison::Lt => "<",
+			Comparison::Le => "<=",
+			Comparison::Gt => ">",
+			Comparison::Ge => ">=",
+		})
+	}
+}
+
+impl TryFrom<&str> for Comparison {
+	type Error = ();
+	
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		Ok(match value {
+			"==" => Comparison::Eq,
+			"!=" => Comparison::Ne,
+			"<" => Comparison::Lt,
+			"<=" => Comparison::Le,
+			">" => Comparison::Gt,
+			">=" => Comparison::Ge,
+			_ => return Err(()),
+		})
+	}
+}
@@ -7,6 +7,10 @@ pub trait Code {
 	
 	fn instruction(&self, pc: u16) -> Result<Option<Instruction>, Self::Error>;
 	fn len(&self) -> usize;
+	
+	fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
 }
 
 impl<T: Into<Instruction> + Copy> Code for [T] {
@@ -30,6 +30,12 @@ impl EmbeddedIO {
 	}
 }
 
+impl Default for EmbeddedIO {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl Debug for EmbeddedIO {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("EmbeddedIO")
@@ -204,7 +210,7 @@ pub struct CharDisplay {
 
 impl CharDisplay {
 	pub fn write(&mut self, char: Char) {
-		self.head = self.head % 10;
+		self.head %= 10;
 		self.buffer[self.head] = char;
 		self.head += 1;
 	}
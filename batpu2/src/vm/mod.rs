@@ -4,9 +4,11 @@ use thiserror::Error;
 
 mod io;
 mod code;
+mod assert;
 
 use crate::isa::{Cond, Instruction};
 pub use code::Code;
+pub use assert::*;
 pub use io::{RawIO, IO};
 #[cfg(feature = "embedded_io")]
 pub use io::embedded;
@@ -53,7 +55,7 @@ where C: Code {
 impl BatPU2<Vec<Instruction>, embedded::EmbeddedIO> {
 	/// Creates a BatPU2 Instance using program in asm
 	#[cfg(feature = "embedded_io")]
-	pub fn from_asm(code: &str) -> Result<Self, crate::asm::AsmError> {
+	pub fn from_asm(code: &str) -> Result<Self, crate::asm::AsmError<'_>> {
 		Ok(Self::new(crate::utils::from_asm(code)?))
 	}
 	
@@ -162,6 +164,57 @@ where C: Code,
 		Ok(limit)
 	}
 	
+	/// Evaluates assertions placed before the instruction at `pc`.
+	pub fn check_assertions(&self, assertions: &Assertions) -> Result<(), AssertionFailure> {
+		let address = self.pc % (1 << 10);
+		
+		for assertion in assertions.get(address) {
+			let actual = match assertion.subject {
+				Subject::Reg(reg) => self.register(reg),
+				Subject::Mem(addr) => self.memory[addr as usize],
+				Subject::Zero => self.flags.zero as u8,
+				Subject::Carry => self.flags.carry as u8,
+			};
+			
+			if !assertion.holds(actual) {
+				return Err(AssertionFailure { address, assertion: assertion.clone(), actual });
+			}
+		}
+		
+		Ok(())
+	}
+	
+	/// Executes a single instruction in checked mode, stopping before it if any of its assertions fails.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use batpu2::BatPU2;
+	/// use batpu2::asm::{self, Dialect};
+	///
+	/// let lines: Vec<_> = asm::parse_lines("LDI r1 3\nASSERT r1 < 3\nHLT").collect::<Result<_, _>>().unwrap();
+	/// let mut assembler = asm::Assembler::new(&lines);
+	/// let code: Vec<_> = assembler.by_ref().collect::<Result<_, _>>().unwrap();
+	///
+	/// let mut vm = BatPU2::new(code);
+	/// let err = vm.try_step_multiple_checked(10, assembler.assertions()).unwrap_err();
+	///
+	/// assert_eq!(err.to_string(), "Assertion `r1 < 3` on line 2 failed at 0x001: r1 is 3");
+	/// assert_eq!(vm.pc, 1);
+	/// ```
+	pub fn try_step_checked(&mut self, assertions: &Assertions) -> Result<(), RunError<I::Error, C::Error>> {
+		self.check_assertions(assertions).map_err(RunError::AssertionFailed)?;
+		self.try_step()
+	}
+	
+	pub fn try_step_multiple_checked(&mut self, limit: usize, assertions: &Assertions) -> Result<usize, RunError<I::Error, C::Error>> {
+		for count in 0..limit {
+			if self.halted { return Ok(count) }
+			self.try_step_checked(assertions)?;
+		}
+		Ok(limit)
+	}
+	
 	fn register(&self, reg: u8) -> u8 {
 		match reg {
 			0 => 0,
@@ -184,7 +237,8 @@ where C: Code,
 	
 	fn write_memory(&mut self, addr: u8, value: u8) -> Result<(), RunError<I::Error, C::Error>> {
 		if addr < 240 {
-			Ok(self.memory[addr as usize] = value)
+			self.memory[addr as usize] = value;
+			Ok(())
 		} else {
 			self.io.write_addr(addr, value)
 			       .map_err(RunError::IOError)
@@ -224,9 +278,12 @@ where T: Code,
 }
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum RunError<IOError: StdError + 'static, CodeError: StdError + 'static> {
 	#[error("Input/Output error: {}", .0)]
 	IOError(#[source] IOError),
 	#[error("Code error: {}", .0)]
 	CodeError(#[source] CodeError),
+	#[error("{}", .0)]
+	AssertionFailed(#[source] AssertionFailure),
 }
@@ -0,0 +1,82 @@
+; std/draw.asm - line and rectangle drawing
+;
+; Pixels are drawn into the screen buffer, use `buffer_screen` to show them.
+; Coordinates are passed as x in r1 and y in r2, the second point in r3 (x) and r4 (y).
+
+; Draws a single pixel at r1, r2.
+; Clobbers r13.
+; @func draw_pixel in=r1,r2 clobbers=r13
+.draw_pixel
+	LDI r13 pixel_x
+	STR r13 r1
+	LDI r13 pixel_y
+	STR r13 r2
+	LDI r13 draw_pixel
+	STR r13 r0
+	RET
+
+; Draws a line from r1, r2 to r3, r4 including both ends.
+; Clobbers r1, r2, r5-r13.
+; @func draw_line in=r1-r4 clobbers=r1,r2,r5-r13
+.draw_line
+	LDI r7 1
+	SUB r3 r1 r5
+	BRH c .draw_line_dy
+	SUB r1 r3 r5
+	LDI r7 -1
+.draw_line_dy
+	LDI r8 1
+	SUB r4 r2 r6
+	BRH c .draw_line_init
+	SUB r2 r4 r6
+	LDI r8 -1
+.draw_line_init
+	; r9 is the error term biased by 64, comparisons are done on twice its value biased by 128
+	LDI r9 64
+	ADD r9 r5 r9
+	SUB r9 r6 r9
+	LDI r11 128
+	SUB r11 r6 r11
+	LDI r12 128
+	ADD r12 r5 r12
+.draw_line_loop
+	CAL .draw_pixel
+	CMP r1 r3
+	BRH ne .draw_line_step
+	CMP r2 r4
+	BRH eq .draw_line_done
+.draw_line_step
+	LSH r9 r10
+	CMP r10 r11
+	BRH lt .draw_line_step_y
+	SUB r9 r6 r9
+	ADD r1 r7 r1
+.draw_line_step_y
+	CMP r12 r10
+	BRH lt .draw_line_loop
+	ADD r9 r5 r9
+	ADD r2 r8 r2
+	JMP .draw_line_loop
+.draw_line_done
+	RET
+
+; Fills a rectangle with corners r1, r2 and r3, r4, where r1 <= r3 and r2 <= r4.
+; Clobbers r1, r2, r5, r13.
+; @func draw_rect in=r1-r4 clobbers=r1,r2,r5,r13
+.draw_rect
+	MOV r1 r5
+.draw_rect_row
+	MOV r5 r1
+.draw_rect_pixel
+	CAL .draw_pixel
+	CMP r1 r3
+	BRH eq .draw_rect_next_row
+	INC r1
+	JMP .draw_rect_pixel
+.draw_rect_next_row
+	CMP r2 r4
+	BRH eq .draw_rect_done
+	INC r2
+	JMP .draw_rect_row
+.draw_rect_done
+	RET
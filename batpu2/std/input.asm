@@ -0,0 +1,28 @@
+; std/input.asm - controller input debouncing
+;
+; Buttons held down are reported only once, when they are first pressed.
+; The previous controller state is kept in memory at `std_input_state`.
+
+define std_input_state 239
+
+; Returns buttons pressed since the previous call in r1.
+; Clobbers r1-r3.
+; @func input_pressed out=r1 clobbers=r2,r3
+.input_pressed
+	LDI r2 controller_input
+	LOD r2 r1
+	LDI r2 std_input_state
+	LOD r2 r3
+	STR r2 r1
+	NOT r3 r3
+	AND r1 r3 r1
+	RET
+
+; Waits until a button is pressed and returns it in r1.
+; Clobbers r1-r3.
+; @func input_wait out=r1 clobbers=r2,r3
+.input_wait
+	CAL .input_pressed
+	CMP r1 r0
+	BRH eq .input_wait
+	RET
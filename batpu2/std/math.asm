@@ -0,0 +1,69 @@
+; std/math.asm - 8-bit multiplication and division
+;
+; Arguments are passed in r1 and r2, results are returned in r1 and r2.
+; Every routine lists the registers it clobbers.
+
+; Multiplies r1 by r2, the 16-bit product is returned in r1 (low) and r2 (high).
+; Clobbers r1-r6.
+; test "multiply 7x6" setup: r1=7 r2=6 call .math_mul expect: r1=42 r2=0
+; test "multiply 200x200" setup: r1=200 r2=200 call .math_mul expect: r1=0x40 r2=0x9C
+; @func math_mul in=r1,r2 out=r1,r2 clobbers=r3-r6
+.math_mul
+	LDI r3 0
+	LDI r4 0
+	LDI r5 0
+.math_mul_loop
+	CMP r2 r0
+	BRH eq .math_mul_done
+	LDI r6 1
+	AND r2 r6 r6
+	BRH eq .math_mul_shift
+	ADD r3 r1 r3
+	BRH nc .math_mul_high
+	INC r4
+.math_mul_high
+	ADD r4 r5 r4
+.math_mul_shift
+	LSH r5 r5
+	LSH r1 r1
+	BRH nc .math_mul_next
+	INC r5
+.math_mul_next
+	RSH r2 r2
+	JMP .math_mul_loop
+.math_mul_done
+	MOV r3 r1
+	MOV r4 r2
+	RET
+
+; Divides r1 by r2, the quotient is returned in r1 and the remainder in r2.
+; Division by zero returns 255 and the dividend as the remainder.
+; Clobbers r1-r5.
+; test "divide 43 by 5" setup: r1=43 r2=5 call .math_divmod expect: r1=8 r2=3
+; test "divide by zero" setup: r1=42 r2=0 call .math_divmod expect: r1=255 r2=42
+; @func math_divmod in=r1,r2 out=r1,r2 clobbers=r3-r5
+.math_divmod
+	LDI r3 0
+	LDI r4 8
+.math_divmod_loop
+	LDI r5 0
+	LSH r1 r1
+	BRH nc .math_divmod_shift
+	LDI r5 1
+.math_divmod_shift
+	LSH r3 r3
+	BRH c .math_divmod_overflow
+	ADD r3 r5 r3
+	CMP r3 r2
+	BRH lt .math_divmod_next
+	JMP .math_divmod_subtract
+.math_divmod_overflow
+	ADD r3 r5 r3
+.math_divmod_subtract
+	SUB r3 r2 r3
+	INC r1
+.math_divmod_next
+	DEC r4
+	BRH ne .math_divmod_loop
+	MOV r3 r2
+	RET
@@ -0,0 +1,72 @@
+; std/print.asm - decimal printing to the character display
+;
+; The character set has no digits, they are written as look-alike letters:
+; 0 O, 1 I, 2 Z, 3 E, 4 A, 5 S, 6 G, 7 T, 8 B, 9 P.
+; Characters are written into the buffer, use `buffer_chars` to show them.
+
+include <std/math.asm>
+
+; Writes r1 in decimal without leading zeros.
+; Clobbers r1-r7.
+; @func print_u8 in=r1 clobbers=r1-r7
+.print_u8
+	LDI r2 100
+	CAL .math_divmod
+	MOV r2 r7
+	LDI r6 0
+	CMP r1 r0
+	BRH eq .print_u8_tens
+	CAL .print_digit
+	LDI r6 1
+.print_u8_tens
+	MOV r7 r1
+	LDI r2 10
+	CAL .math_divmod
+	MOV r2 r7
+	CMP r1 r0
+	BRH ne .print_u8_tens_digit
+	CMP r6 r0
+	BRH eq .print_u8_ones
+.print_u8_tens_digit
+	CAL .print_digit
+.print_u8_ones
+	MOV r7 r1
+	CAL .print_digit
+	RET
+
+; Writes a single digit in r1.
+; Clobbers r1-r3.
+; @func print_digit in=r1 clobbers=r1-r3
+.print_digit
+	LDI r3 write_char
+	LDI r2 'O'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'I'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'Z'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'E'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'A'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'S'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'G'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'T'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'B'
+	DEC r1
+	BRH nc .print_digit_write
+	LDI r2 'P'
+.print_digit_write
+	STR r3 r2
+	RET
@@ -0,0 +1,78 @@
+; std/u16.asm - 16-bit arithmetic
+;
+; 16-bit values are passed in register pairs, low byte first:
+; the first operand in r1 (low) and r2 (high), the second one in r3 (low) and r4 (high).
+; Results are returned in r1 and r2.
+
+; Adds r3:r4 to r1:r2, the carry flag is not meaningful.
+; Clobbers r1, r2.
+; test "add with carry" setup: r1=0xFF r2=0x12 r3=0x01 r4=0x01
+;   call .u16_add
+;   expect: r1=0x00 r2=0x14
+; @func u16_add in=r1-r4 out=r1,r2
+.u16_add
+	ADD r2 r4 r2
+	ADD r1 r3 r1
+	BRH nc .u16_add_done
+	INC r2
+.u16_add_done
+	RET
+
+; Subtracts r3:r4 from r1:r2, the carry flag is not meaningful.
+; Clobbers r1, r2.
+; @func u16_sub in=r1-r4 out=r1,r2
+.u16_sub
+	SUB r2 r4 r2
+	SUB r1 r3 r1
+	BRH c .u16_sub_done
+	DEC r2
+.u16_sub_done
+	RET
+
+; Increments r1:r2.
+; Clobbers r1, r2.
+; @func u16_inc in=r1,r2 out=r1,r2
+.u16_inc
+	INC r1
+	BRH nc .u16_inc_done
+	INC r2
+.u16_inc_done
+	RET
+
+; Shifts r1:r2 left by one bit.
+; Clobbers r1, r2.
+; @func u16_shl in=r1,r2 out=r1,r2
+.u16_shl
+	LSH r2 r2
+	LSH r1 r1
+	BRH nc .u16_shl_done
+	INC r2
+.u16_shl_done
+	RET
+
+; Shifts r1:r2 right by one bit.
+; Clobbers r1, r2, r5.
+; @func u16_shr in=r1,r2 out=r1,r2 clobbers=r5
+.u16_shr
+	RSH r1 r1
+	LDI r5 1
+	AND r2 r5 r5
+	RSH r2 r2
+	CMP r5 r0
+	BRH eq .u16_shr_done
+	ADI r1 0x80
+.u16_shr_done
+	RET
+
+; Compares r1:r2 with r3:r4 and sets flags the same way as `CMP`:
+; zero if they are equal, carry if r1:r2 is greater or equal.
+; Clobbers nothing.
+; test "compare greater" setup: r1=0x00 r2=0x02 r3=0xFF r4=0x01
+;   call .u16_cmp expect: flags.zero=0 flags.carry=1
+; @func u16_cmp in=r1-r4
+.u16_cmp
+	CMP r2 r4
+	BRH ne .u16_cmp_done
+	CMP r1 r3
+.u16_cmp_done
+	RET
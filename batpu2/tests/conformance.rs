@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+
+use batpu2::asm::Dialect;
+use batpu2::utils;
+
+/// Assembles every `<name>.<extension>` of a directory and compares it against `<name>.mc`, returns the names.
+fn assemble_directory(directory: &str, extension: &str) -> Vec<String> {
+	let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+	let mut checked = Vec::new();
+	
+	for entry in fs::read_dir(&directory).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().is_none_or(|ext| ext != extension) { continue }
+		
+		let source = fs::read_to_string(&path).unwrap();
+		let expected = fs::read_to_string(path.with_extension("mc")).unwrap();
+		
+		let program = utils::from_asm_with(&source, Dialect::Upstream)
+			.unwrap_or_else(|err| panic!("{}:{}: {err}", path.display(), err.line_num()));
+		
+		assert_eq!(utils::into_mc(&program), expected, "{} does not match the reference output", path.display());
+		checked.push(path.file_name().unwrap().to_string_lossy().into_owned());
+	}
+	
+	checked
+}
+
+#[test]
+#[ignore = "the upstream corpus has not been imported yet, see tests/conformance/README.md"]
+fn matches_upstream_assembler() {
+	let sources = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/README.md")).unwrap();
+	let checked = assemble_directory("tests/conformance", "as");
+	
+	assert!(!checked.is_empty(), "tests/conformance is empty");
+	for name in checked {
+		assert!(sources.contains(&format!("| `{name}` |")), "{name} is not listed with its upstream commit in tests/conformance/README.md");
+	}
+}
+
+#[test]
+fn upstream_dialect_regressions() {
+	assert!(!assemble_directory("tests/dialect", "asm").is_empty(), "tests/dialect is empty");
+}
@@ -0,0 +1,138 @@
+/ Bounces a DVD logo around the screen
+
+/ Write "DVD" to the character display
+LDI r15 clear_chars_buffer
+STR r15 r0
+LDI r15 write_char
+LDI r1 'D'
+STR r15 r1
+LDI r1 'V'
+STR r15 r1
+LDI r1 'D'
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r1 ' '
+STR r15 r1
+LDI r15 buffer_chars
+STR r15 r0
+
+/ Store the logo bitmap, one byte per column
+LDI r1 0
+LDI r2 0b01001111
+STR r1 r2
+INC r1
+LDI r2 0b11001001
+STR r1 r2
+INC r1
+LDI r2 0b11100110
+STR r1 r2
+INC r1
+LDI r2 0b11100000
+STR r1 r2
+INC r1
+LDI r2 0b11100111
+STR r1 r2
+INC r1
+LDI r2 0b10101000
+STR r1 r2
+INC r1
+LDI r2 0b11100111
+STR r1 r2
+INC r1
+LDI r2 0b11100000
+STR r1 r2
+INC r1
+LDI r2 0b11101111
+STR r1 r2
+INC r1
+LDI r2 0b01101001
+STR r1 r2
+INC r1
+LDI r2 0b01000110
+STR r1 r2
+INC r1
+
+/ r1, r2 - position, r3, r4 - velocity
+LDI r1 0
+LDI r2 0
+LDI r3 1
+LDI r4 1
+LDI r12 pixel_x
+LDI r13 pixel_y
+LDI r14 draw_pixel
+LDI r15 20
+
+.frame
+  LDI r11 clear_screen_buffer
+  STR r11 r0
+  CAL .draw
+  LDI r11 buffer_screen
+  STR r11 r0
+  CAL .move
+  CAL .bounce
+  SUB r15 r1 r15
+  BRH carry .frame
+  HLT
+
+.draw
+  LDI r8 0
+  LDI r9 11
+  LDI r10 1
+.draw_column
+  LOD r8 r7
+  LDI r6 8
+  ADD r8 r1 r8
+  STR r12 r8
+  SUB r8 r1 r8
+.draw_pixel
+  DEC r6
+  AND r7 r10 r0
+  BRH zero .skip_pixel
+  ADD r6 r2 r6
+  STR r13 r6
+  SUB r6 r2 r6
+  STR r14 r0
+.skip_pixel
+  RSH r7 r7
+  MOV r6 r6
+  BRH notzero .draw_pixel
+  INC r8
+  CMP r8 r9
+  BRH notzero .draw_column
+  RET
+
+.move
+  ADD r1 r3 r1
+  ADD r2 r4 r2
+  RET
+
+.bounce
+  LDI r5 21
+  LDI r6 24
+  CMP r1 r5
+  BRH zero .flip_x
+  CMP r1 r0
+  BRH zero .flip_x
+.bounce_y
+  CMP r2 r6
+  BRH zero .flip_y
+  CMP r2 r0
+  BRH zero .flip_y
+  RET
+.flip_x
+  NEG r3 r3
+  JMP .bounce_y
+.flip_y
+  NEG r4 r4
+  RET
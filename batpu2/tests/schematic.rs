@@ -0,0 +1,33 @@
+#![cfg(feature = "schematic")]
+
+use std::fs;
+use std::path::Path;
+
+use batpu2::utils;
+
+#[test]
+#[ignore = "no capture of the world has been added yet, see tests/schematic/README.md"]
+fn matches_reference_schematics() {
+	let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/schematic");
+	let sources = fs::read_to_string(directory.join("README.md")).unwrap();
+	let mut checked = 0;
+	
+	for entry in fs::read_dir(&directory).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().is_none_or(|ext| ext != "schem" && ext != "litematic") { continue }
+		
+		let name = path.file_name().unwrap().to_string_lossy();
+		assert!(sources.contains(&format!("| `{name}` |")), "{name} is not listed with its source in tests/schematic/README.md");
+		
+		let rom = utils::read_schem(&fs::read(&path).unwrap()).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+		let program = utils::from_mc(&fs::read_to_string(path.with_extension("mc")).unwrap()).unwrap();
+		let mut expected: Vec<u16> = program.iter().map(|instruction| instruction.as_word()).collect();
+		expected.resize(rom.words.len(), 0);
+		
+		assert_eq!(rom.corrupted, [], "{} has corrupted cells", path.display());
+		assert_eq!(rom.words, expected, "{} does not hold its program", path.display());
+		checked += 1;
+	}
+	
+	assert_ne!(checked, 0, "tests/schematic holds no schematic");
+}
@@ -0,0 +1,261 @@
+#![cfg(feature = "embedded_io")]
+
+use std::collections::BTreeSet;
+
+use batpu2::BatPU2;
+use batpu2::asm::{self, Dialect, STD_FILES};
+use batpu2::isa::Instruction;
+use batpu2::testing::TestSuite;
+use batpu2::{opt, utils};
+use batpu2::vm::embedded::EmbeddedIO;
+
+type VM = BatPU2<Vec<Instruction>, EmbeddedIO>;
+
+/// Assembles a program calling `label` once, followed by the standard library file.
+fn routine(file: &str, label: &str) -> Vec<Instruction> {
+	utils::from_asm(&format!("CAL {label}\nHLT\ninclude <std/{file}>"))
+		.unwrap_or_else(|err| panic!("{file}:{}: {err}", err.line_num()))
+}
+
+/// Runs the routine with arguments placed in r1, r2, ...
+fn call(code: &[Instruction], args: &[u8]) -> VM {
+	let mut vm = BatPU2::new(code.to_vec());
+	vm.registers[..args.len()].copy_from_slice(args);
+	rerun(&mut vm);
+	vm
+}
+
+fn rerun(vm: &mut VM) {
+	vm.pc = 0;
+	vm.halted = false;
+	vm.step_multiple(100_000);
+	assert!(vm.halted, "routine did not return");
+}
+
+fn buffer_pixels(vm: &VM) -> BTreeSet<(u8, u8)> {
+	(0..32).flat_map(|y| (0..32).map(move |x| (x, y)))
+	       .filter(|&(x, y)| vm.io.screen.get_buffer_pixel(x, y))
+	       .collect()
+}
+
+#[test]
+fn std_files_assemble() {
+	for (path, _) in STD_FILES {
+		let code = format!("HLT\ninclude <{path}>");
+		let lines: Vec<_> = asm::parse_lines(&code).collect::<Result<_, _>>().unwrap();
+		
+		assert!(lines.len() > 2, "{path} is empty");
+		asm::Assembler::new(&lines).collect::<Result<Vec<_>, _>>().unwrap_or_else(|err| panic!("{path}: {err}"));
+	}
+}
+
+#[test]
+fn std_test_blocks() {
+	let mut count = 0;
+	
+	for (path, source) in STD_FILES {
+		let suite = TestSuite::from_asm(source, Dialect::Extended).unwrap_or_else(|err| panic!("{path}: {err}"));
+		
+		for result in suite.run() {
+			assert!(result.passed(), "{path}: {result}");
+			count += 1;
+		}
+	}
+	
+	assert!(count >= 5);
+}
+
+#[test]
+fn std_calling_conventions() {
+	for (path, source) in STD_FILES {
+		let errors = opt::check_conventions(source, Dialect::Extended).unwrap_or_else(|err| panic!("{path}: {err}"));
+		
+		assert!(errors.is_empty(), "{path}: {errors:#?}");
+	}
+}
+
+#[test]
+fn includes_are_expanded_once() {
+	let code = utils::from_asm("HLT\ninclude <std/print.asm>\ninclude <std/math.asm>").unwrap();
+	let print = utils::from_asm("HLT\ninclude <std/print.asm>").unwrap();
+	
+	assert_eq!(code, print);
+	assert!(utils::from_asm("include <std/missing.asm>").is_err());
+}
+
+#[test]
+fn math_mul() {
+	let code = routine("math.asm", ".math_mul");
+	
+	for a in (0..=255).step_by(3) {
+		for b in 0..=255 {
+			let vm = call(&code, &[a, b]);
+			let product = a as u16 * b as u16;
+			
+			assert_eq!(vm.registers[..2], product.to_le_bytes(), "{a} * {b}");
+		}
+	}
+}
+
+#[test]
+fn math_divmod() {
+	let code = routine("math.asm", ".math_divmod");
+	
+	for a in (0..=255).step_by(3) {
+		for b in 1..=255 {
+			let vm = call(&code, &[a, b]);
+			
+			assert_eq!(vm.registers[..2], [a / b, a % b], "{a} / {b}");
+		}
+	}
+	
+	assert_eq!(call(&code, &[42, 0]).registers[..2], [255, 42]);
+}
+
+#[test]
+fn u16_helpers() {
+	let values = [0u16, 1, 0xFF, 0x100, 0x1234, 0x7FFF, 0x8000, 0xABCD, 0xFFFE, 0xFFFF];
+	type Binary = fn(u16, u16) -> u16;
+	type Unary = fn(u16) -> u16;
+	
+	let binary: [(&str, Binary); 2] = [(".u16_add", u16::wrapping_add), (".u16_sub", u16::wrapping_sub)];
+	let unary: [(&str, Unary); 3] = [(".u16_inc", |a| a.wrapping_add(1)), (".u16_shl", |a| a << 1), (".u16_shr", |a| a >> 1)];
+	
+	for (label, op) in binary {
+		let code = routine("u16.asm", label);
+		
+		for a in values {
+			for b in values {
+				let [al, ah] = a.to_le_bytes();
+				let [bl, bh] = b.to_le_bytes();
+				let vm = call(&code, &[al, ah, bl, bh]);
+				
+				assert_eq!(vm.registers[..2], op(a, b).to_le_bytes(), "{label} {a} {b}");
+			}
+		}
+	}
+	
+	for (label, op) in unary {
+		let code = routine("u16.asm", label);
+		
+		for a in values {
+			let vm = call(&code, &a.to_le_bytes());
+			
+			assert_eq!(vm.registers[..2], op(a).to_le_bytes(), "{label} {a}");
+		}
+	}
+	
+	let code = routine("u16.asm", ".u16_cmp");
+	
+	for a in values {
+		for b in values {
+			let [al, ah] = a.to_le_bytes();
+			let [bl, bh] = b.to_le_bytes();
+			let vm = call(&code, &[al, ah, bl, bh]);
+			
+			assert_eq!((vm.flags.zero, vm.flags.carry), (a == b, a >= b), "{a} cmp {b}");
+		}
+	}
+}
+
+#[test]
+fn print_u8() {
+	let code = routine("print.asm", ".print_u8");
+	
+	for (value, expected) in [(0, "O"), (7, "T"), (10, "IO"), (42, "AZ"), (100, "IOO"), (105, "IOS"), (189, "IBP"), (255, "ZSS")] {
+		let mut vm = call(&code, &[value]);
+		vm.io.char_display.show_buffer();
+		
+		assert_eq!(vm.io.char_display.to_string().trim_end(), expected, "{value}");
+	}
+}
+
+#[test]
+fn draw_line() {
+	let code = routine("draw.asm", ".draw_line");
+	let points = [(0, 0), (31, 31), (31, 0), (0, 31), (5, 3), (3, 5), (16, 16), (20, 2), (1, 30), (16, 7)];
+	
+	for (x0, y0) in points {
+		for (x1, y1) in points {
+			let vm = call(&code, &[x0, y0, x1, y1]);
+			
+			assert_eq!(buffer_pixels(&vm), bresenham(x0, y0, x1, y1), "({x0}, {y0}) - ({x1}, {y1})");
+		}
+	}
+}
+
+#[test]
+fn draw_rect() {
+	let code = routine("draw.asm", ".draw_rect");
+	
+	for [x0, y0, x1, y1] in [[0, 0, 0, 0], [2, 3, 10, 4], [0, 0, 31, 31], [7, 1, 7, 20]] {
+		let vm = call(&code, &[x0, y0, x1, y1]);
+		let expected: BTreeSet<_> = (y0..=y1).flat_map(|y| (x0..=x1).map(move |x| (x, y))).collect();
+		
+		assert_eq!(buffer_pixels(&vm), expected);
+	}
+}
+
+#[test]
+fn input_pressed() {
+	let code = routine("input.asm", ".input_pressed");
+	let mut vm = call(&code, &[]);
+	assert_eq!(vm.registers[0], 0);
+	
+	vm.io.controller.set_clear_mask(0);
+	vm.io.controller.set_button(0x01 | 0x08);
+	rerun(&mut vm);
+	assert_eq!(vm.registers[0], 0x01 | 0x08);
+	
+	rerun(&mut vm);
+	assert_eq!(vm.registers[0], 0);
+	
+	vm.io.controller.clear_button(0x01);
+	vm.io.controller.set_button(0x20);
+	rerun(&mut vm);
+	assert_eq!(vm.registers[0], 0x20);
+	
+	vm.io.controller.set_button(0x01);
+	rerun(&mut vm);
+	assert_eq!(vm.registers[0], 0x01);
+}
+
+#[test]
+fn input_wait() {
+	let mut vm = BatPU2::new(routine("input.asm", ".input_wait"));
+	
+	vm.step_multiple(1000);
+	assert!(!vm.halted);
+	
+	vm.io.controller.set_button(0x80);
+	vm.step_multiple(1000);
+	assert!(vm.halted);
+	assert_eq!(vm.registers[0], 0x80);
+}
+
+fn bresenham(mut x0: u8, mut y0: u8, x1: u8, y1: u8) -> BTreeSet<(u8, u8)> {
+	let dx = x0.abs_diff(x1) as i16;
+	let dy = -(y0.abs_diff(y1) as i16);
+	let mut err = dx + dy;
+	let mut pixels = BTreeSet::new();
+	
+	loop {
+		pixels.insert((x0, y0));
+		
+		if (x0, y0) == (x1, y1) {
+			return pixels;
+		}
+		
+		let e2 = 2 * err;
+		
+		if e2 >= dy {
+			err += dy;
+			x0 = if x0 < x1 { x0 + 1 } else { x0 - 1 };
+		}
+		
+		if e2 <= dx {
+			err += dx;
+			y0 = if y0 < y1 { y0 + 1 } else { y0 - 1 };
+		}
+	}
+}